@@ -1,9 +1,13 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs, io,
     path::{Path, PathBuf},
     process::Stdio,
-    time::Duration,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use color_eyre::eyre::{Result, WrapErr, eyre};
@@ -16,1778 +20,18126 @@ use crossterm::{
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use glob::glob;
+use hmac::{Hmac, Mac};
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Sparkline, Wrap},
 };
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::Command,
-    sync::{mpsc, watch},
+    sync::{Semaphore, mpsc, watch},
 };
 
 const CONFIG_CANDIDATES: [&str; 3] = ["lazyterraform.yaml", "Config.yaml", "config.yaml"];
+const CONFIG_INCLUDE_DIR: &str = "lazyterraform.d";
 const OUTPUT_BUFFER_LIMIT: usize = 4_000;
+const OUTPUT_FOLD_CHARS: usize = 1_024;
+const OUTPUT_FOLD_PREVIEW_CHARS: usize = 120;
+const OUTPUT_WRAP_WIDTH: usize = 160;
+const DEFAULT_APPROVAL_KEY_ENV: &str = "LAZYTF_APPROVAL_KEY";
+const DEFAULT_APPROVAL_TTL_MINUTES: u64 = 60;
+const DEFAULT_STATE_GROWTH_ALERT_PERCENT: u64 = 50;
+const DEFAULT_CLEANUP_STALE_AFTER_DAYS: u64 = 90;
+const DEFAULT_EPHEMERAL_TTL_HOURS: u64 = 72;
+const DEFAULT_REMINDER_WARN_BEFORE_HOURS: u64 = 24;
+const DEFAULT_REMINDER_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_DRIFT_CHECK_INTERVAL_MINUTES: u64 = 360;
+const DEFAULT_PREFETCH_HOVER_DELAY_MS: u64 = 400;
+const DEFAULT_BACKEND_PROBE_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_TEAM_SYNC_INTERVAL_SECS: u64 = 30;
+const TEAM_RECENT_LIMIT: usize = 20;
+const PRECONDITION_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_BACKGROUND_CONCURRENCY: usize = 2;
+const DEFAULT_LOW_MEMORY_WINDOW_LINES: usize = 500;
+const ACTIVITY_HISTORY_LIMIT: usize = 20;
+const MAX_EVENTS_PER_FRAME: usize = 2_000;
+const CHATTY_LINES_PER_SECOND: usize = 10_000;
+const UPDATE_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const RELEASES_API_URL: &str = "https://api.github.com/repos/jaxsonsuth/lazytf/releases/latest";
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
 
 #[derive(Debug, Deserialize)]
 struct Config {
     accounts: BTreeMap<String, AccountConfig>,
-}
-
-#[derive(Debug, Deserialize)]
-struct AccountConfig {
-    aws_profile: String,
-    composition_path: String,
-    region: Option<String>,
+    approval: Option<ApprovalConfig>,
     #[serde(default)]
-    var_files: Vec<String>,
-}
-
-#[derive(Debug, Clone)]
-struct AccountState {
-    name: String,
-    aws_profile: String,
-    region: Option<String>,
-    composition_path: PathBuf,
-    composition_issue: Option<String>,
-    var_files: Vec<PathBuf>,
-    auth: AuthStatus,
-    workspaces: Vec<String>,
-}
-
-#[derive(Debug)]
-struct LoadedConfig {
-    path: PathBuf,
-    base_dir: PathBuf,
-    config: Config,
+    audit: AuditConfig,
+    #[serde(default)]
+    state_tracking: StateTrackingConfig,
+    #[serde(default)]
+    cleanup: CleanupConfig,
+    #[serde(default)]
+    reminders: ReminderConfig,
+    #[serde(default)]
+    ci: CiConfig,
+    #[serde(default)]
+    schedule: ScheduleConfig,
+    #[serde(default)]
+    prefetch: PrefetchConfig,
+    #[serde(default)]
+    backend_probe: BackendProbeConfig,
+    #[serde(default)]
+    low_memory: LowMemoryConfig,
+    #[serde(default)]
+    scroll: ScrollConfig,
+    #[serde(default = "default_mouse")]
+    mouse: bool,
+    #[serde(default)]
+    icons: IconSet,
+    binary: Option<String>,
+    terraform_versions_dir: Option<String>,
+    #[serde(default = "default_stateful_resource_types")]
+    stateful_resource_types: Vec<String>,
+    #[serde(default)]
+    noisy_attributes: Vec<String>,
+    #[serde(default = "default_update_check")]
+    update_check: bool,
+    #[serde(default)]
+    watch_config: bool,
+    team_sync: Option<TeamSyncConfig>,
+    #[serde(default)]
+    defaults: AccountDefaults,
+    #[serde(default)]
+    collapsed_groups: BTreeSet<String>,
 }
 
-#[derive(Debug, Default)]
-struct CliOptions {
-    config_path: Option<PathBuf>,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum IconSet {
+    #[default]
+    Ascii,
+    Unicode,
+    #[serde(alias = "nerd-font")]
+    NerdFont,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum AuthStatus {
-    Unknown,
-    Checking,
-    Authenticated,
-    Failed,
-}
+impl IconSet {
+    fn auth(self, status: AuthStatus) -> &'static str {
+        match (self, status) {
+            (Self::Ascii, AuthStatus::Unknown) => "?",
+            (Self::Ascii, AuthStatus::Checking) => "~",
+            (Self::Ascii, AuthStatus::Authenticated) => "*",
+            (Self::Ascii, AuthStatus::Failed) => "x",
+            (Self::Unicode, AuthStatus::Unknown) => "?",
+            (Self::Unicode, AuthStatus::Checking) => "…",
+            (Self::Unicode, AuthStatus::Authenticated) => "✓",
+            (Self::Unicode, AuthStatus::Failed) => "✗",
+            (Self::NerdFont, AuthStatus::Unknown) => "\u{f059}",
+            (Self::NerdFont, AuthStatus::Checking) => "\u{f110}",
+            (Self::NerdFont, AuthStatus::Authenticated) => "\u{f058}",
+            (Self::NerdFont, AuthStatus::Failed) => "\u{f057}",
+        }
+    }
 
-impl AuthStatus {
-    fn icon(self) -> &'static str {
-        match self {
-            Self::Unknown => "?",
-            Self::Checking => "~",
-            Self::Authenticated => "*",
-            Self::Failed => "x",
+    fn activity(self, activity: AccountActivity) -> &'static str {
+        match (self, activity) {
+            (Self::Ascii, AccountActivity::Running) => "~",
+            (Self::Ascii, AccountActivity::Succeeded) => "ok",
+            (Self::Ascii, AccountActivity::Failed) => "x",
+            (Self::Ascii, AccountActivity::Cancelled) => "-",
+            (Self::Unicode, AccountActivity::Running) => "…",
+            (Self::Unicode, AccountActivity::Succeeded) => "✓",
+            (Self::Unicode, AccountActivity::Failed) => "✗",
+            (Self::Unicode, AccountActivity::Cancelled) => "⊘",
+            (Self::NerdFont, AccountActivity::Running) => "\u{f110}",
+            (Self::NerdFont, AccountActivity::Succeeded) => "\u{f058}",
+            (Self::NerdFont, AccountActivity::Failed) => "\u{f057}",
+            (Self::NerdFont, AccountActivity::Cancelled) => "\u{f05e}",
         }
     }
 
-    fn label(self) -> &'static str {
-        match self {
-            Self::Unknown => "unknown",
-            Self::Checking => "checking",
-            Self::Authenticated => "ready",
-            Self::Failed => "failed",
+    fn group(self, collapsed: bool) -> &'static str {
+        match (self, collapsed) {
+            (Self::Ascii, false) => "[-]",
+            (Self::Ascii, true) => "[+]",
+            (Self::Unicode, false) => "▾",
+            (Self::Unicode, true) => "▸",
+            (Self::NerdFont, false) => "\u{f078}",
+            (Self::NerdFont, true) => "\u{f054}",
         }
     }
 
-    fn color(self) -> Color {
+    fn warning(self) -> &'static str {
         match self {
-            Self::Unknown => Color::DarkGray,
-            Self::Checking => Color::Yellow,
-            Self::Authenticated => Color::Green,
-            Self::Failed => Color::Red,
+            Self::Ascii => "!",
+            Self::Unicode => "⚠",
+            Self::NerdFont => "\u{f071}",
         }
     }
+
+    fn panel_title(self, panel: FocusPanel, title: &str) -> String {
+        let icon = match (self, panel) {
+            (Self::Ascii, _) => return title.to_string(),
+            (Self::Unicode, FocusPanel::Accounts) => "☁",
+            (Self::Unicode, FocusPanel::Workspaces) => "▤",
+            (Self::Unicode, FocusPanel::Output) => "≡",
+            (Self::NerdFont, FocusPanel::Accounts) => "\u{f0c2}",
+            (Self::NerdFont, FocusPanel::Workspaces) => "\u{f07b}",
+            (Self::NerdFont, FocusPanel::Output) => "\u{f120}",
+        };
+        format!("{icon} {title}")
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum FocusPanel {
-    Accounts,
-    Workspaces,
-    Output,
+fn default_mouse() -> bool {
+    true
 }
 
-impl FocusPanel {
-    fn next(self) -> Self {
-        match self {
-            Self::Accounts => Self::Workspaces,
-            Self::Workspaces => Self::Output,
-            Self::Output => Self::Accounts,
-        }
-    }
+fn default_update_check() -> bool {
+    true
+}
 
-    fn previous(self) -> Self {
-        match self {
-            Self::Accounts => Self::Output,
-            Self::Workspaces => Self::Accounts,
-            Self::Output => Self::Workspaces,
-        }
-    }
+fn compile_patterns(
+    setting: &str,
+    patterns: &[String],
+    startup_lines: &mut Vec<String>,
+) -> Vec<glob::Pattern> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match glob::Pattern::new(pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(err) => {
+                startup_lines.push(format!(
+                    "warning: ignoring {setting} pattern `{pattern}`: {err}"
+                ));
+                None
+            }
+        })
+        .collect()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum LayoutMode {
-    Split,
-    OutputOnly,
+fn default_stateful_resource_types() -> Vec<String> {
+    [
+        "aws_db_instance",
+        "aws_rds_cluster",
+        "aws_docdb_cluster",
+        "aws_neptune_cluster",
+        "aws_redshift_cluster",
+        "aws_dynamodb_table",
+        "aws_s3_bucket",
+        "aws_ebs_volume",
+        "aws_efs_file_system",
+        "aws_elasticache_*",
+        "aws_elasticsearch_domain",
+        "aws_opensearch_domain",
+        "aws_kms_key",
+        "google_sql_database_instance",
+        "google_storage_bucket",
+        "google_compute_disk",
+        "azurerm_*_database",
+        "azurerm_*_server",
+        "azurerm_storage_account",
+        "azurerm_managed_disk",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
 }
 
-impl LayoutMode {
-    fn label(self) -> &'static str {
-        match self {
-            Self::Split => "split",
-            Self::OutputOnly => "output",
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct LowMemoryConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_low_memory_window_lines")]
+    window_lines: usize,
+}
+
+impl Default for LowMemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_lines: default_low_memory_window_lines(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum OperationKind {
-    AuthLogin,
-    RefreshWorkspaces,
-    TerraformInit,
-    TerraformPlan,
-    TerraformApply,
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct ScrollConfig {
+    #[serde(default = "default_scroll_wheel_lines")]
+    wheel_lines: usize,
+    #[serde(default = "default_scroll_page_lines")]
+    page_lines: usize,
 }
 
-impl OperationKind {
-    fn label(self) -> &'static str {
-        match self {
-            Self::AuthLogin => "aws sso login",
-            Self::RefreshWorkspaces => "workspace refresh",
-            Self::TerraformInit => "terraform init",
-            Self::TerraformPlan => "terraform plan",
-            Self::TerraformApply => "terraform apply",
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        Self {
+            wheel_lines: default_scroll_wheel_lines(),
+            page_lines: default_scroll_page_lines(),
         }
     }
+}
 
-    fn requires_workspace(self) -> bool {
-        matches!(self, Self::TerraformPlan | Self::TerraformApply)
-    }
+fn default_scroll_wheel_lines() -> usize {
+    3
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum CancelSignal {
-    None,
-    Graceful,
-    Force,
+fn default_scroll_page_lines() -> usize {
+    10
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum CancelStage {
-    None,
-    GracefulRequested,
-    ForceRequested,
+fn default_low_memory_window_lines() -> usize {
+    DEFAULT_LOW_MEMORY_WINDOW_LINES
 }
 
 #[derive(Debug)]
-struct InflightOperation {
-    kind: OperationKind,
-    account_idx: usize,
-    cancel_tx: watch::Sender<CancelSignal>,
-    cancel_stage: CancelStage,
+struct OperationLog {
+    path: PathBuf,
+    writer: io::BufWriter<fs::File>,
+    lines: usize,
+    summary: Vec<String>,
 }
 
 #[derive(Debug)]
-struct AppState {
-    accounts: Vec<AccountState>,
-    selected_account: usize,
-    selected_workspace: usize,
-    focused_panel: FocusPanel,
-    previous_focus_panel: FocusPanel,
-    layout_mode: LayoutMode,
-    output_lines: Vec<String>,
-    output_scroll_from_bottom: usize,
-    status_line: String,
-    inflight: Option<InflightOperation>,
-    pending_apply_confirmation: bool,
-    show_help: bool,
-    quit_requested: bool,
+struct OutputRate {
+    window_start: Instant,
+    lines: usize,
+    suppressed: usize,
+    summarizing: bool,
 }
 
-impl AppState {
-    fn from_config(config: Config, config_base_dir: &Path) -> Result<Self> {
-        if config.accounts.is_empty() {
-            return Err(eyre!(
-                "Config has no accounts. Add at least one account under `accounts:`"
-            ));
-        }
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CiConfig {
+    github: Option<GithubCiConfig>,
+    gitlab: Option<GitlabCiConfig>,
+    #[serde(default)]
+    accounts: BTreeMap<String, CiAccountConfig>,
+}
 
-        let mut accounts = Vec::with_capacity(config.accounts.len());
-        let mut startup_lines =
-            vec!["lazytf ready. Press `a` to authenticate selected account.".to_string()];
+#[derive(Debug, Clone, Deserialize)]
+struct GithubCiConfig {
+    repo: String,
+    #[serde(default = "default_github_api_url")]
+    api_url: String,
+    #[serde(default = "default_github_token_env")]
+    token_env: String,
+}
 
-        for (name, account_cfg) in config.accounts {
-            let (composition_path, composition_issue) = match resolve_composition_path(
-                config_base_dir,
-                &account_cfg.composition_path,
-            ) {
-                Ok(path) => (path, None),
-                Err(err) => {
-                    let fallback =
-                        fallback_composition_path(config_base_dir, &account_cfg.composition_path);
-                    let issue = format!(
-                        "composition_path `{}` invalid: {err}",
-                        account_cfg.composition_path
-                    );
-                    startup_lines.push(format!("warning: account `{name}` {issue}"));
-                    startup_lines.push(format!(
-                            "warning: using fallback path `{}` so UI can start; execution remains blocked until fixed",
-                            fallback.display()
-                        ));
-                    (fallback, Some(issue))
-                }
-            };
+fn default_github_api_url() -> String {
+    "https://api.github.com".to_string()
+}
 
-            accounts.push(AccountState {
-                name,
-                aws_profile: account_cfg.aws_profile,
-                region: account_cfg.region,
-                var_files: resolve_var_file_paths(&account_cfg.var_files, &composition_path),
-                composition_path,
-                composition_issue,
-                auth: AuthStatus::Unknown,
-                workspaces: Vec::new(),
-            });
-        }
+fn default_github_token_env() -> String {
+    "GITHUB_TOKEN".to_string()
+}
 
-        Ok(Self {
-            accounts,
-            selected_account: 0,
-            selected_workspace: 0,
-            focused_panel: FocusPanel::Accounts,
-            previous_focus_panel: FocusPanel::Accounts,
-            layout_mode: LayoutMode::Split,
-            output_lines: startup_lines,
-            output_scroll_from_bottom: 0,
-            status_line: "idle".to_string(),
-            inflight: None,
-            pending_apply_confirmation: false,
-            show_help: false,
-            quit_requested: false,
-        })
-    }
+#[derive(Debug, Clone, Deserialize)]
+struct GitlabCiConfig {
+    project: String,
+    #[serde(default = "default_gitlab_url")]
+    url: String,
+    #[serde(default = "default_gitlab_token_env")]
+    token_env: String,
+}
 
-    fn selected_account(&self) -> Option<&AccountState> {
-        self.accounts.get(self.selected_account)
+impl GitlabCiConfig {
+    fn project_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}",
+            self.url.trim_end_matches('/'),
+            self.project.replace('/', "%2F")
+        )
     }
+}
 
-    fn selected_account_mut(&mut self) -> Option<&mut AccountState> {
-        self.accounts.get_mut(self.selected_account)
-    }
+fn default_gitlab_url() -> String {
+    "https://gitlab.com".to_string()
+}
 
-    fn selected_workspace_name(&self) -> Option<String> {
-        let account = self.selected_account()?;
-        account.workspaces.get(self.selected_workspace).cloned()
-    }
+fn default_gitlab_token_env() -> String {
+    "GITLAB_TOKEN".to_string()
+}
 
-    fn current_operation_label(&self) -> String {
-        match &self.inflight {
-            Some(op) => {
-                let account_name = self
-                    .accounts
-                    .get(op.account_idx)
-                    .map(|a| a.name.as_str())
-                    .unwrap_or("?");
-                format!("running {} on {account_name}", op.kind.label())
-            }
-            None => self.status_line.clone(),
+#[derive(Debug, Clone)]
+enum CiProvider {
+    Github(GithubCiConfig),
+    Gitlab(GitlabCiConfig),
+}
+
+impl CiProvider {
+    fn source(&self) -> String {
+        match self {
+            Self::Github(github) => github.repo.clone(),
+            Self::Gitlab(gitlab) => gitlab.project.clone(),
         }
     }
+}
 
-    fn is_busy(&self) -> bool {
-        self.inflight.is_some()
+impl CiConfig {
+    fn git_host(&self) -> Option<CiProvider> {
+        self.github
+            .clone()
+            .map(CiProvider::Github)
+            .or_else(|| self.gitlab.clone().map(CiProvider::Gitlab))
     }
+}
 
-    fn push_output(&mut self, line: impl Into<String>) {
-        self.output_lines.push(line.into());
-        if self.output_lines.len() > OUTPUT_BUFFER_LIMIT {
-            let to_drop = self.output_lines.len() - OUTPUT_BUFFER_LIMIT;
-            self.output_lines.drain(0..to_drop);
+#[derive(Debug, Clone, Deserialize)]
+struct CiAccountConfig {
+    workflow: Option<String>,
+    branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct PrefetchConfig {
+    #[serde(default = "default_prefetch_enabled")]
+    enabled: bool,
+    #[serde(default = "default_prefetch_hover_delay_ms")]
+    hover_delay_ms: u64,
+    #[serde(default = "default_background_concurrency")]
+    max_concurrent: usize,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_prefetch_enabled(),
+            hover_delay_ms: default_prefetch_hover_delay_ms(),
+            max_concurrent: default_background_concurrency(),
         }
     }
+}
 
-    fn set_status(&mut self, status: impl Into<String>) {
-        self.status_line = status.into();
-    }
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct BackendProbeConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_backend_probe_timeout_seconds")]
+    timeout_seconds: u64,
+}
 
-    fn clear_apply_confirmation(&mut self) {
-        self.pending_apply_confirmation = false;
+impl Default for BackendProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_seconds: default_backend_probe_timeout_seconds(),
+        }
     }
+}
 
-    fn toggle_help(&mut self) {
-        self.show_help = !self.show_help;
-    }
+fn default_backend_probe_timeout_seconds() -> u64 {
+    DEFAULT_BACKEND_PROBE_TIMEOUT_SECS
+}
 
-    fn close_help(&mut self) {
-        self.show_help = false;
-    }
+fn default_prefetch_enabled() -> bool {
+    true
+}
 
-    fn request_cancel(&mut self) {
-        if let Some(op) = self.inflight.as_mut() {
-            match op.cancel_stage {
-                CancelStage::None => {
-                    let _ = op.cancel_tx.send(CancelSignal::Graceful);
-                    op.cancel_stage = CancelStage::GracefulRequested;
-                    self.push_output(
-                        "Graceful cancel requested. Sending SIGINT and waiting for Terraform to clean up state lock...",
-                    );
-                    self.push_output("Press `c` again to force kill if absolutely necessary.");
-                    self.set_status("cancelling (graceful)...");
-                }
-                CancelStage::GracefulRequested => {
-                    let _ = op.cancel_tx.send(CancelSignal::Force);
-                    op.cancel_stage = CancelStage::ForceRequested;
-                    self.push_output(
-                        "Force kill requested. This may leave Terraform state locked.",
-                    );
-                    self.set_status("cancelling (forced)...");
-                }
-                CancelStage::ForceRequested => {
-                    self.push_output(
-                        "Force kill already requested. Waiting for process to exit...",
-                    );
-                }
-            }
+fn default_prefetch_hover_delay_ms() -> u64 {
+    DEFAULT_PREFETCH_HOVER_DELAY_MS
+}
+
+fn default_background_concurrency() -> usize {
+    DEFAULT_BACKGROUND_CONCURRENCY
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReminderConfig {
+    #[serde(default = "default_reminder_warn_before_hours")]
+    warn_before_hours: u64,
+    #[serde(default = "default_reminder_interval_minutes")]
+    interval_minutes: u64,
+    notify_command: Option<String>,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        Self {
+            warn_before_hours: default_reminder_warn_before_hours(),
+            interval_minutes: default_reminder_interval_minutes(),
+            notify_command: None,
         }
     }
+}
 
-    fn is_output_only(&self) -> bool {
-        self.layout_mode == LayoutMode::OutputOnly
-    }
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ScheduleConfig {
+    notify_command: Option<String>,
+}
 
-    fn enter_output_only(&mut self) {
-        if self.layout_mode == LayoutMode::Split {
-            self.previous_focus_panel = self.focused_panel;
+fn default_reminder_warn_before_hours() -> u64 {
+    DEFAULT_REMINDER_WARN_BEFORE_HOURS
+}
+
+fn default_reminder_interval_minutes() -> u64 {
+    DEFAULT_REMINDER_INTERVAL_MINUTES
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct CleanupConfig {
+    #[serde(default = "default_cleanup_stale_after_days")]
+    stale_after_days: u64,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            stale_after_days: default_cleanup_stale_after_days(),
         }
-        self.layout_mode = LayoutMode::OutputOnly;
-        self.focused_panel = FocusPanel::Output;
     }
+}
 
-    fn exit_output_only(&mut self) {
-        if self.layout_mode == LayoutMode::Split {
-            return;
-        }
+fn default_cleanup_stale_after_days() -> u64 {
+    DEFAULT_CLEANUP_STALE_AFTER_DAYS
+}
 
-        self.layout_mode = LayoutMode::Split;
-        self.focused_panel = self.previous_focus_panel;
-    }
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct StateTrackingConfig {
+    #[serde(default = "default_state_growth_alert_percent")]
+    growth_alert_percent: u64,
+}
 
-    fn toggle_output_only(&mut self) {
-        if self.is_output_only() {
-            self.exit_output_only();
-        } else {
-            self.enter_output_only();
+impl Default for StateTrackingConfig {
+    fn default() -> Self {
+        Self {
+            growth_alert_percent: default_state_growth_alert_percent(),
         }
     }
 }
 
-#[derive(Debug)]
-enum WorkerEvent {
-    OutputLine(String),
-    AccountAuthUpdate {
-        account_idx: usize,
-        status: AuthStatus,
-        message: String,
+fn default_state_growth_alert_percent() -> u64 {
+    DEFAULT_STATE_GROWTH_ALERT_PERCENT
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AuditConfig {
+    #[serde(default)]
+    sinks: Vec<AuditSink>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AuditSink {
+    Syslog {
+        address: String,
+        #[serde(default = "default_syslog_facility")]
+        facility: String,
     },
-    WorkspacesLoaded {
-        account_idx: usize,
-        workspaces: Vec<String>,
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
     },
-    OperationFinished {
-        kind: OperationKind,
-        account_idx: usize,
-        success: bool,
-        cancelled: bool,
-        message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TeamSyncConfig {
+    #[serde(flatten)]
+    backend: TeamSyncBackend,
+    #[serde(default = "default_team_sync_interval_seconds")]
+    interval_seconds: u64,
+    operator_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TeamSyncBackend {
+    S3 {
+        prefix: String,
+        aws_profile: Option<String>,
+        region: Option<String>,
+    },
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
     },
 }
 
-#[derive(Debug)]
-struct RunOutcome {
-    success: bool,
-    cancelled: bool,
-    exit_code: Option<i32>,
+fn default_team_sync_interval_seconds() -> u64 {
+    DEFAULT_TEAM_SYNC_INTERVAL_SECS
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
+fn default_syslog_facility() -> String {
+    "local0".to_string()
+}
 
-    let cli_options = parse_cli_options()?;
-    let cwd = std::env::current_dir().wrap_err("Unable to read current working directory")?;
-    let loaded_config = load_config(&cwd, cli_options.config_path.as_deref())?;
-    let mut app = AppState::from_config(loaded_config.config, &loaded_config.base_dir)?;
-    app.push_output(format!(
-        "Loaded config from {}",
-        loaded_config.path.display()
-    ));
+#[derive(Debug, Deserialize)]
+struct AccountConfig {
+    #[serde(default)]
+    aws_profile: String,
+    composition_path: String,
+    group: Option<String>,
+    region: Option<String>,
+    binary: Option<String>,
+    auth_check_cmd: Option<String>,
+    login_cmd: Option<String>,
+    terraform_version: Option<String>,
+    #[serde(default)]
+    var_files: Vec<String>,
+    #[serde(default)]
+    require_approval: bool,
+    change_ticket: Option<ChangeTicketConfig>,
+    #[serde(default)]
+    orphan_checks: Vec<OrphanCheckConfig>,
+    tag_policy: Option<TagPolicyConfig>,
+    ephemeral: Option<EphemeralConfig>,
+    docs_path: Option<String>,
+    #[serde(default)]
+    roles: Vec<RoleConfig>,
+    apply_role: Option<String>,
+    run_options: Option<RunOptionsConfig>,
+    drift_check: Option<DriftCheckConfig>,
+    desired_workspaces: Option<DesiredWorkspacesConfig>,
+    #[serde(default)]
+    preconditions: Vec<PreconditionConfig>,
+    backend_config: Option<BackendConfigSettings>,
+    #[serde(default)]
+    extra_args: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    color: Option<String>,
+    #[serde(default)]
+    variables: BTreeMap<String, serde_yaml::Value>,
+    #[serde(default)]
+    workspace_variables: BTreeMap<String, BTreeMap<String, serde_yaml::Value>>,
+    #[serde(default)]
+    noisy_attributes: Vec<String>,
+}
 
-    let (worker_tx, mut worker_rx) = mpsc::unbounded_channel::<WorkerEvent>();
-    let (ctrlc_tx, mut ctrlc_rx) = mpsc::unbounded_channel::<()>();
+#[derive(Debug, Default, Deserialize)]
+struct AccountDefaults {
+    region: Option<String>,
+    binary: Option<String>,
+    auth_check_cmd: Option<String>,
+    login_cmd: Option<String>,
+    #[serde(default)]
+    var_files: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    extra_args: BTreeMap<String, Vec<String>>,
+}
 
-    tokio::spawn(async move {
-        if tokio::signal::ctrl_c().await.is_ok() {
-            let _ = ctrlc_tx.send(());
+impl AccountDefaults {
+    fn expanded_env(&self) -> BTreeMap<String, String> {
+        self.env
+            .iter()
+            .map(|(key, value)| (key.clone(), expand_env_vars(value)))
+            .collect()
+    }
+
+    fn apply(&self, mut account: AccountConfig) -> AccountConfig {
+        account.region = account.region.or_else(|| self.region.clone());
+        account.binary = account.binary.or_else(|| self.binary.clone());
+        account.auth_check_cmd = account
+            .auth_check_cmd
+            .or_else(|| self.auth_check_cmd.clone());
+        account.login_cmd = account.login_cmd.or_else(|| self.login_cmd.clone());
+        account.var_files = self
+            .var_files
+            .iter()
+            .cloned()
+            .chain(account.var_files)
+            .collect();
+        for (name, value) in &self.env {
+            account
+                .env
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
         }
-    });
+        for (subcommand, args) in &self.extra_args {
+            account
+                .extra_args
+                .entry(subcommand.clone())
+                .or_insert_with(|| args.clone());
+        }
+        account
+    }
+}
 
-    let mut terminal = setup_terminal()?;
+/// Strings are passed as they are; numbers, lists, and maps as JSON, which Terraform
+/// parses like HCL for complex variable types.
+fn tf_var_values(variables: BTreeMap<String, serde_yaml::Value>) -> BTreeMap<String, String> {
+    variables
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_yaml::Value::String(text) => text,
+                other => serde_json::to_string(&other).unwrap_or_default(),
+            };
+            (name, value)
+        })
+        .collect()
+}
 
-    for idx in 0..app.accounts.len() {
-        spawn_auth_check(idx, app.accounts[idx].clone(), worker_tx.clone());
+const EXTRA_ARGS_SUBCOMMANDS: [&str; 5] = ["init", "plan", "apply", "import", "fmt"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum BackendConfigSettings {
+    Map(BTreeMap<String, serde_yaml::Value>),
+    List(Vec<String>),
+}
+
+impl BackendConfigSettings {
+    fn init_args(&self) -> Vec<String> {
+        let entries: Vec<String> = match self {
+            Self::Map(settings) => settings
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_yaml::Value::String(text) => text.clone(),
+                        other => serde_yaml::to_string(other)
+                            .unwrap_or_default()
+                            .trim()
+                            .to_string(),
+                    };
+                    format!("{key}={value}")
+                })
+                .collect(),
+            Self::List(entries) => entries.clone(),
+        };
+        entries
+            .iter()
+            .map(|entry| format!("-backend-config={}", expand_env_vars(entry)))
+            .collect()
     }
+}
 
-    let run_result = run_event_loop(
-        &mut terminal,
-        &mut app,
-        &worker_tx,
-        &mut worker_rx,
-        &mut ctrlc_rx,
-    );
+#[derive(Debug, Clone, Deserialize)]
+struct PreconditionConfig {
+    dns: Option<String>,
+    command: Option<String>,
+    message: Option<String>,
+}
 
-    restore_terminal(&mut terminal)?;
-    run_result
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum DesiredWorkspacesConfig {
+    List(Vec<String>),
+    File { file: String },
 }
 
-fn run_event_loop(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    app: &mut AppState,
-    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
-    worker_rx: &mut mpsc::UnboundedReceiver<WorkerEvent>,
-    ctrlc_rx: &mut mpsc::UnboundedReceiver<()>,
-) -> Result<()> {
-    loop {
-        while let Ok(()) = ctrlc_rx.try_recv() {
-            if app.is_busy() {
-                app.request_cancel();
-                app.quit_requested = true;
-            } else {
-                app.quit_requested = true;
-            }
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct DriftCheckConfig {
+    #[serde(default = "default_drift_check_enabled")]
+    enabled: bool,
+    #[serde(default = "default_drift_check_interval_minutes")]
+    interval_minutes: u64,
+}
+
+fn default_drift_check_enabled() -> bool {
+    true
+}
+
+fn default_drift_check_interval_minutes() -> u64 {
+    DEFAULT_DRIFT_CHECK_INTERVAL_MINUTES
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DriftStatus {
+    Clean,
+    Drifted,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct DriftRecord {
+    status: DriftStatus,
+    checked_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UpdateCheckRecord {
+    checked_at: u64,
+    latest: Option<String>,
+    dismissed: Option<String>,
+}
+
+impl UpdateCheckRecord {
+    fn notice(&self) -> Option<String> {
+        self.latest
+            .clone()
+            .filter(|latest| is_newer_version(latest, env!("CARGO_PKG_VERSION")))
+            .filter(|latest| self.dismissed.as_ref() != Some(latest))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RunOptionsConfig {
+    #[serde(default)]
+    ask: bool,
+    #[serde(flatten)]
+    defaults: RunTuningConfig,
+    #[serde(default)]
+    profiles: BTreeMap<String, RunTuningConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RunTuningConfig {
+    refresh: Option<bool>,
+    targets: Option<Vec<String>>,
+    parallelism: Option<u32>,
+    extra_args: Option<Vec<String>>,
+    vars: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+struct RunTuning {
+    profile: Option<String>,
+    refresh: bool,
+    targets: Vec<String>,
+    parallelism: Option<u32>,
+    extra_args: Vec<String>,
+    vars: BTreeMap<String, String>,
+}
+
+impl Default for RunTuning {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            refresh: true,
+            targets: Vec::new(),
+            parallelism: None,
+            extra_args: Vec::new(),
+            vars: BTreeMap::new(),
         }
+    }
+}
 
-        while let Ok(event) = worker_rx.try_recv() {
-            handle_worker_event(app, event);
+impl RunTuning {
+    fn from_config(config: Option<&RunOptionsConfig>) -> Self {
+        let mut tuning = Self::default();
+        if let Some(config) = config {
+            tuning.overlay(&config.defaults);
         }
+        tuning
+    }
 
-        if app.quit_requested && !app.is_busy() {
-            break;
+    fn overlay(&mut self, config: &RunTuningConfig) {
+        if let Some(refresh) = config.refresh {
+            self.refresh = refresh;
         }
+        if let Some(targets) = &config.targets {
+            self.targets = targets.clone();
+        }
+        if config.parallelism.is_some() {
+            self.parallelism = config.parallelism;
+        }
+        if let Some(extra_args) = &config.extra_args {
+            self.extra_args = extra_args.clone();
+        }
+        if let Some(vars) = &config.vars {
+            self.vars.extend(vars.clone());
+        }
+    }
 
-        terminal.draw(|frame| draw_ui(frame, app))?;
+    fn is_default(&self) -> bool {
+        self.refresh
+            && self.targets.is_empty()
+            && self.parallelism.is_none()
+            && self.extra_args.is_empty()
+            && self.vars.is_empty()
+    }
 
-        if event::poll(Duration::from_millis(100))? {
-            match event::read()? {
-                CEvent::Key(key) if key.kind == KeyEventKind::Press => {
-                    handle_key_event(app, key, worker_tx);
-                }
-                CEvent::Mouse(mouse) => {
-                    handle_mouse_event(app, mouse);
-                }
-                CEvent::Resize(_, _) => {}
-                _ => {}
-            }
+    fn append_args(&self, args: &mut Vec<String>) {
+        if !self.refresh {
+            args.push("-refresh=false".to_string());
+        }
+        if let Some(parallelism) = self.parallelism {
+            args.push(format!("-parallelism={parallelism}"));
+        }
+        for target in &self.targets {
+            args.push(format!("-target={target}"));
+        }
+        for (name, value) in &self.vars {
+            args.push(format!("-var={name}={value}"));
         }
+        args.extend(self.extra_args.iter().cloned());
     }
 
-    Ok(())
+    /// The subset a saved plan still accepts; refresh, targets and vars are part of the plan.
+    fn append_apply_args(&self, args: &mut Vec<String>) {
+        if let Some(parallelism) = self.parallelism {
+            args.push(format!("-parallelism={parallelism}"));
+        }
+        args.extend(self.extra_args.iter().cloned());
+    }
 }
 
-fn handle_worker_event(app: &mut AppState, event: WorkerEvent) {
-    match event {
-        WorkerEvent::OutputLine(line) => {
-            app.push_output(line);
-        }
-        WorkerEvent::AccountAuthUpdate {
-            account_idx,
-            status,
-            message,
-        } => {
-            if let Some(account) = app.accounts.get_mut(account_idx) {
-                account.auth = status;
-            }
-            app.push_output(message);
-        }
-        WorkerEvent::WorkspacesLoaded {
-            account_idx,
-            mut workspaces,
-        } => {
-            workspaces.sort();
-            let mut summary_message: Option<String> = None;
+#[derive(Debug, Clone)]
+struct VarFilePicker {
+    account_idx: usize,
+    files: Vec<VarFileChoice>,
+    selected: usize,
+}
 
-            if let Some(account) = app.accounts.get_mut(account_idx) {
-                account.workspaces = workspaces;
-                if account.workspaces.is_empty() {
-                    summary_message = Some(format!("No workspaces found for `{}`", account.name));
-                } else {
-                    summary_message = Some(format!(
-                        "Loaded {} workspaces for `{}`",
-                        account.workspaces.len(),
-                        account.name
-                    ));
-                }
-            }
+#[derive(Debug, Clone)]
+struct VarFileChoice {
+    path: PathBuf,
+    label: String,
+    configured: bool,
+    checked: bool,
+}
 
-            if let Some(message) = summary_message {
-                app.push_output(message);
-            }
+const RUN_OPTION_FIELDS: [&str; 7] = [
+    "Run profile",
+    "Refresh",
+    "Targets",
+    "Parallelism",
+    "Var overrides",
+    "Extra args",
+    "Don't ask again this session",
+];
 
-            if account_idx == app.selected_account {
-                app.selected_workspace = 0;
-            }
-        }
-        WorkerEvent::OperationFinished {
-            kind,
-            account_idx,
-            success,
-            cancelled,
-            message,
-        } => {
-            app.push_output(message);
-            app.clear_apply_confirmation();
+#[derive(Debug)]
+struct RunOptionsPopup {
+    kind: OperationKind,
+    account_idx: usize,
+    options: RunOptions,
+    tuning: RunTuning,
+    selected: usize,
+    editing: Option<String>,
+    error: Option<String>,
+    dont_ask: bool,
+}
 
-            if let Some(inflight) = &app.inflight {
-                if inflight.kind == kind && inflight.account_idx == account_idx {
-                    app.inflight = None;
-                }
-            }
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RoleConfig {
+    Name(String),
+    Profile { name: String, aws_profile: String },
+}
 
-            if cancelled {
-                app.set_status("cancelled");
-            } else if success {
-                app.set_status("idle");
+#[derive(Debug, Clone)]
+struct AccountRole {
+    name: String,
+    aws_profile: String,
+    derived_profile: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EphemeralConfig {
+    template: String,
+    #[serde(default = "default_ephemeral_output_dir")]
+    output_dir: String,
+    #[serde(default = "default_ephemeral_ttl_hours")]
+    default_ttl_hours: u64,
+}
+
+fn default_ephemeral_output_dir() -> String {
+    "ephemeral".to_string()
+}
+
+fn default_ephemeral_ttl_hours() -> u64 {
+    DEFAULT_EPHEMERAL_TTL_HOURS
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TagPolicyMode {
+    #[default]
+    Warn,
+    Block,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TagPolicyConfig {
+    required_tags: Vec<String>,
+    #[serde(default)]
+    mode: TagPolicyMode,
+    #[serde(default)]
+    resource_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OrphanCheckConfig {
+    resource_type: String,
+    list_command: String,
+    #[serde(default = "default_orphan_id_attribute")]
+    id_attribute: String,
+}
+
+fn default_orphan_id_attribute() -> String {
+    "id".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChangeTicketConfig {
+    #[serde(default)]
+    required: bool,
+    pattern: Option<String>,
+    validate_command: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ChangeTicketPolicy {
+    required: bool,
+    pattern: Option<Regex>,
+    validate_command: Option<String>,
+}
+
+impl ChangeTicketPolicy {
+    fn from_config(account_name: &str, config: ChangeTicketConfig) -> Result<Self> {
+        let pattern = config
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .wrap_err_with(|| {
+                format!("Invalid change_ticket.pattern for account `{account_name}`")
+            })?;
+
+        Ok(Self {
+            required: config.required,
+            pattern,
+            validate_command: config.validate_command,
+        })
+    }
+
+    fn check(&self, raw: &str) -> std::result::Result<Option<String>, String> {
+        let ticket = raw.trim();
+        if ticket.is_empty() {
+            return if self.required {
+                Err("a change ticket is required for this account".to_string())
             } else {
-                app.set_status("failed");
-            }
+                Ok(None)
+            };
+        }
+
+        if let Some(pattern) = &self.pattern
+            && !pattern.is_match(ticket)
+        {
+            return Err(format!("`{ticket}` does not match `{}`", pattern.as_str()));
         }
+
+        Ok(Some(ticket.to_string()))
     }
 }
 
-fn handle_key_event(
-    app: &mut AppState,
-    key: KeyEvent,
-    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
-) {
-    if key.code == KeyCode::Char('?') {
-        app.toggle_help();
-        app.clear_apply_confirmation();
-        return;
-    }
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ApprovalMode {
+    #[default]
+    Hmac,
+    Command,
+}
 
-    if app.show_help {
-        if key.code == KeyCode::Esc {
-            app.close_help();
-            return;
-        }
+#[derive(Debug, Clone, Deserialize)]
+struct ApprovalConfig {
+    #[serde(default)]
+    mode: ApprovalMode,
+    #[serde(default = "default_approval_key_env")]
+    key_env: String,
+    #[serde(default)]
+    approvers: BTreeMap<String, String>,
+    command: Option<String>,
+    #[serde(default = "default_approval_ttl_minutes")]
+    ttl_minutes: u64,
+}
 
-        if key.code != KeyCode::Char('q') && key.code != KeyCode::Char('c') {
-            return;
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            mode: ApprovalMode::default(),
+            key_env: default_approval_key_env(),
+            approvers: BTreeMap::new(),
+            command: None,
+            ttl_minutes: default_approval_ttl_minutes(),
         }
     }
+}
 
-    if key.code == KeyCode::Esc {
-        app.exit_output_only();
-        app.clear_apply_confirmation();
-        return;
+fn default_approval_key_env() -> String {
+    DEFAULT_APPROVAL_KEY_ENV.to_string()
+}
+
+fn default_approval_ttl_minutes() -> u64 {
+    DEFAULT_APPROVAL_TTL_MINUTES
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ApprovalRequest {
+    id: String,
+    account: String,
+    workspace: String,
+    composition_path: String,
+    requested_by: String,
+    requested_at: u64,
+    expires_at: u64,
+    key_env: String,
+    #[serde(default)]
+    approvers: BTreeMap<String, String>,
+    #[serde(default)]
+    subject: String,
+    /// SHA-256 of the saved plan (or of `subject` when there is none); part of the signature.
+    #[serde(default)]
+    plan_sha256: String,
+}
+
+/// The thing an approval is bound to; a different digest needs a new approval.
+struct ApprovalTarget {
+    subject: String,
+    digest: String,
+}
+
+impl ApprovalTarget {
+    fn plan(plan_file: &Path) -> Result<Self> {
+        let contents = fs::read(plan_file)
+            .wrap_err_with(|| format!("Failed to read saved plan {}", plan_file.display()))?;
+        Ok(Self {
+            subject: format!("saved plan {}", plan_file.display()),
+            digest: hex_encode(&<Sha256 as sha2::Digest>::digest(&contents)),
+        })
     }
 
-    if key.code == KeyCode::Char('q') {
-        if app.is_busy() {
-            app.request_cancel();
-            app.quit_requested = true;
-        } else {
-            app.quit_requested = true;
+    fn described(subject: String) -> Self {
+        let digest = hex_encode(&<Sha256 as sha2::Digest>::digest(subject.as_bytes()));
+        Self { subject, digest }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ApprovalSignature {
+    approved_by: String,
+    approved_at: u64,
+    signature: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum CompositionMode {
+    #[default]
+    Cli,
+    Cloud {
+        organization: Option<String>,
+        workspace: Option<String>,
+    },
+    Stack,
+}
+
+impl CompositionMode {
+    fn pinned_workspace(&self) -> Option<&str> {
+        match self {
+            Self::Cloud {
+                workspace: Some(workspace),
+                ..
+            } => Some(workspace),
+            _ => None,
         }
-        return;
     }
+}
 
-    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-        if app.is_busy() {
-            app.request_cancel();
-            app.quit_requested = true;
-        } else {
-            app.quit_requested = true;
+static CLOUD_BLOCK: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*cloud\s*\{").expect("valid cloud block regex")
+});
+static CLOUD_ORGANIZATION: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r#"organization\s*=\s*"([^"]+)""#).expect("valid organization regex")
+});
+static CLOUD_WORKSPACE_NAME: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r#"workspaces\s*\{[^}]*?\bname\s*=\s*"([^"]+)""#)
+        .expect("valid workspace name regex")
+});
+
+fn detect_composition_mode(composition_path: &Path) -> CompositionMode {
+    let Ok(entries) = fs::read_dir(composition_path) else {
+        return CompositionMode::Cli;
+    };
+    let mut tf_files = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if name.ends_with(".tfstack.hcl") || name.ends_with(".tfdeploy.hcl") {
+            return CompositionMode::Stack;
+        }
+        if name.ends_with(".tf") {
+            tf_files.push(path);
         }
-        return;
     }
+    tf_files.sort();
 
-    if key.code == KeyCode::Char('c') {
-        app.request_cancel();
-        return;
+    for path in tf_files {
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(found) = CLOUD_BLOCK.find(&contents) else {
+            continue;
+        };
+        let block = hcl_block_body(&contents[found.end()..]);
+        let capture = |pattern: &Regex| {
+            pattern
+                .captures(block)
+                .and_then(|captures| captures.get(1))
+                .map(|value| value.as_str().to_string())
+        };
+        return CompositionMode::Cloud {
+            organization: capture(&CLOUD_ORGANIZATION),
+            workspace: capture(&CLOUD_WORKSPACE_NAME),
+        };
     }
+    CompositionMode::Cli
+}
 
-    match key.code {
-        KeyCode::Char('z') => {
-            app.toggle_output_only();
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Tab => {
-            if !app.is_output_only() {
-                app.focused_panel = app.focused_panel.next();
+fn hcl_block_body(text: &str) -> &str {
+    let mut depth = 1;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &text[..idx];
+                }
             }
+            _ => {}
         }
-        KeyCode::BackTab => {
-            if !app.is_output_only() {
-                app.focused_panel = app.focused_panel.previous();
-            }
+    }
+    text
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AccountRow {
+    Group(String),
+    Account(usize),
+}
+
+#[derive(Debug, Clone)]
+struct AccountState {
+    name: String,
+    group: Option<String>,
+    aws_profile: String,
+    region: Option<String>,
+    binary: String,
+    auth_check_cmd: Option<String>,
+    login_cmd: Option<String>,
+    pinned_version: Option<String>,
+    composition_path: PathBuf,
+    composition_issue: Option<String>,
+    composition_mode: CompositionMode,
+    var_files: Vec<PathBuf>,
+    approval: Option<ApprovalConfig>,
+    change_ticket: Option<ChangeTicketPolicy>,
+    orphan_checks: Vec<OrphanCheckConfig>,
+    tag_policy: Option<TagPolicyConfig>,
+    tag_violations: BTreeMap<String, usize>,
+    noisy_attributes: Vec<glob::Pattern>,
+    ephemeral: Option<EphemeralConfig>,
+    docs_path: Option<String>,
+    roles: Vec<AccountRole>,
+    active_role: usize,
+    apply_role: Option<String>,
+    run_options: Option<RunOptionsConfig>,
+    drift_check: Option<DriftCheckConfig>,
+    desired_workspaces: Option<DesiredWorkspacesConfig>,
+    preconditions: Vec<PreconditionConfig>,
+    backend_config: Vec<String>,
+    extra_args: BTreeMap<String, Vec<String>>,
+    env: BTreeMap<String, String>,
+    color: Option<Color>,
+    variables: BTreeMap<String, String>,
+    workspace_variables: BTreeMap<String, BTreeMap<String, String>>,
+    profile_missing: bool,
+    backend_issues: Option<usize>,
+    backend_unreachable: Option<String>,
+    terraform_version: Option<(u64, u64, u64)>,
+    version_mismatch: Option<String>,
+    auth: AuthStatus,
+    workspaces: Vec<String>,
+    current_workspace: Option<String>,
+}
+
+impl AccountState {
+    fn binary_name(&self) -> &str {
+        Path::new(&self.binary)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("terraform")
+    }
+
+    fn is_opentofu(&self) -> bool {
+        self.binary_name().starts_with("tofu")
+    }
+
+    /// `-replace` arrived in Terraform 0.15.2; every OpenTofu release has it.
+    fn supports_replace(&self) -> bool {
+        self.is_opentofu()
+            || self
+                .terraform_version
+                .is_some_and(|version| version >= (0, 15, 2))
+    }
+
+    fn role(&self) -> Option<&AccountRole> {
+        self.roles.get(self.active_role)
+    }
+
+    /// Debug dump without the fields lazytf fills in at runtime, so config reloads can
+    /// tell which accounts really changed.
+    fn config_fingerprint(&self) -> String {
+        let config_only = AccountState {
+            // With roles the profile follows the active role, which the roles already cover.
+            aws_profile: if self.roles.is_empty() {
+                self.aws_profile.clone()
+            } else {
+                String::new()
+            },
+            tag_violations: BTreeMap::new(),
+            active_role: 0,
+            profile_missing: false,
+            backend_issues: None,
+            backend_unreachable: None,
+            terraform_version: None,
+            version_mismatch: None,
+            auth: AuthStatus::Unknown,
+            workspaces: Vec::new(),
+            current_workspace: None,
+            ..self.clone()
+        };
+        format!("{config_only:?}")
+    }
+
+    /// Accounts without `roles` are unrestricted; otherwise only the apply role may mutate.
+    fn can_mutate(&self) -> bool {
+        match (self.role(), &self.apply_role) {
+            (None, _) => true,
+            (Some(role), Some(apply_role)) => role.name == *apply_role,
+            (Some(_), None) => false,
         }
-        KeyCode::Left | KeyCode::Char('h') => {
-            if !app.is_output_only() {
-                app.focused_panel = app.focused_panel.previous();
-            }
+    }
+}
+
+#[derive(Debug)]
+struct LoadedConfig {
+    path: PathBuf,
+    base_dir: PathBuf,
+    config: Config,
+    sources: Vec<PathBuf>,
+}
+
+impl LoadedConfig {
+    fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.sources.clone();
+        paths.push(self.base_dir.join(CONFIG_INCLUDE_DIR));
+        paths
+    }
+}
+
+#[derive(Debug, Default)]
+struct CliOptions {
+    config_path: Option<PathBuf>,
+    low_memory: bool,
+    no_mouse: bool,
+    command: CliCommand,
+}
+
+#[derive(Debug, Default)]
+enum CliCommand {
+    #[default]
+    Tui,
+    Approve {
+        request_path: PathBuf,
+    },
+    BenchPlan {
+        account: String,
+        workspace: Option<String>,
+    },
+    SsoGenerate {
+        start_url: String,
+        sso_region: String,
+        region: Option<String>,
+        composition_path: String,
+    },
+    Report {
+        file: Option<PathBuf>,
+    },
+    ValidateConfig,
+    Init,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuthStatus {
+    Unknown,
+    Checking,
+    Authenticated,
+    Failed,
+}
+
+impl AuthStatus {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Checking => "checking",
+            Self::Authenticated => "ready",
+            Self::Failed => "failed",
         }
-        KeyCode::Right | KeyCode::Char('l') => {
-            if !app.is_output_only() {
-                app.focused_panel = app.focused_panel.next();
-            }
-        }
-        KeyCode::Up | KeyCode::Char('k') => {
-            move_selection_up(app);
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Down | KeyCode::Char('j') => {
-            move_selection_down(app);
-            app.clear_apply_confirmation();
-        }
-        KeyCode::PageUp => {
-            if app.focused_panel == FocusPanel::Output {
-                app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_add(10);
-            }
-            app.clear_apply_confirmation();
-        }
-        KeyCode::PageDown => {
-            if app.focused_panel == FocusPanel::Output {
-                app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_sub(10);
-            }
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Home | KeyCode::Char('g') => {
-            if app.focused_panel == FocusPanel::Output {
-                app.output_scroll_from_bottom = usize::MAX;
-            }
-            app.clear_apply_confirmation();
-        }
-        KeyCode::End | KeyCode::Char('G') => {
-            if app.focused_panel == FocusPanel::Output {
-                app.output_scroll_from_bottom = 0;
-            }
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('a') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_auth_login(app, worker_tx.clone());
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('s') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_auth_check_for_selected(app, worker_tx.clone());
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('r') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_workspace_refresh(app, worker_tx.clone());
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('i') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_terraform_operation(app, worker_tx.clone(), OperationKind::TerraformInit);
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('p') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_terraform_operation(app, worker_tx.clone(), OperationKind::TerraformPlan);
-            app.clear_apply_confirmation();
-        }
-        KeyCode::Char('A') => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            app.pending_apply_confirmation = true;
-            app.set_status("apply confirmation pending: press y to confirm");
-            app.push_output("Apply requested. Press `y` to confirm apply, any nav key to cancel.");
-        }
-        KeyCode::Char('y') if app.pending_apply_confirmation => {
-            if app.is_busy() {
-                app.push_output("Another operation is already running. Press `c` to cancel.");
-                return;
-            }
-            start_terraform_operation(app, worker_tx.clone(), OperationKind::TerraformApply);
-        }
-        _ => {
-            app.clear_apply_confirmation();
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Unknown => Color::DarkGray,
+            Self::Checking => Color::Yellow,
+            Self::Authenticated => Color::Green,
+            Self::Failed => Color::Red,
         }
     }
 }
 
-fn move_selection_up(app: &mut AppState) {
-    match app.focused_panel {
-        FocusPanel::Accounts => {
-            if app.selected_account > 0 {
-                app.selected_account -= 1;
-                app.selected_workspace = 0;
-            }
-        }
-        FocusPanel::Workspaces => {
-            if app.selected_workspace > 0 {
-                app.selected_workspace -= 1;
-            }
-        }
-        FocusPanel::Output => {
-            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_add(1);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccountActivity {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl AccountActivity {
+    fn color(self) -> Color {
+        match self {
+            Self::Running => Color::Yellow,
+            Self::Succeeded => Color::Green,
+            Self::Failed => Color::Red,
+            Self::Cancelled => Color::DarkGray,
         }
     }
 }
 
-fn handle_mouse_event(app: &mut AppState, mouse: MouseEvent) {
-    if app.focused_panel != FocusPanel::Output {
-        return;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FocusPanel {
+    Accounts,
+    Workspaces,
+    Output,
+}
+
+impl FocusPanel {
+    fn next(self) -> Self {
+        match self {
+            Self::Accounts => Self::Workspaces,
+            Self::Workspaces => Self::Output,
+            Self::Output => Self::Accounts,
+        }
     }
 
-    match mouse.kind {
-        MouseEventKind::ScrollUp => {
-            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_add(3);
+    fn previous(self) -> Self {
+        match self {
+            Self::Accounts => Self::Output,
+            Self::Workspaces => Self::Accounts,
+            Self::Output => Self::Workspaces,
         }
-        MouseEventKind::ScrollDown => {
-            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_sub(3);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayoutMode {
+    Split,
+    OutputOnly,
+}
+
+impl LayoutMode {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Split => "split",
+            Self::OutputOnly => "output",
         }
-        _ => {}
     }
 }
 
-fn move_selection_down(app: &mut AppState) {
-    match app.focused_panel {
-        FocusPanel::Accounts => {
-            let max_idx = app.accounts.len().saturating_sub(1);
-            if app.selected_account < max_idx {
-                app.selected_account += 1;
-                app.selected_workspace = 0;
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperationKind {
+    AuthLogin,
+    RefreshWorkspaces,
+    TerraformInit,
+    TerraformValidate,
+    TerraformFmtCheck,
+    TerraformFmt,
+    TerraformPlan,
+    TerraformRefreshPlan,
+    TerraformDestroyPlan,
+    TerraformApply,
+    TerraformImport,
+    OrphanScan,
+    CleanupScan,
+    WorkspaceCleanup,
+    WorkspaceNew,
+    WorkspaceBootstrap,
+    WorkspaceDelete,
+    CostBreakdown,
+    EphemeralCreate,
+    EphemeralTeardown,
+    ForceUnlock,
+    StateList,
+    StateShow,
+    StateRemove,
+    StateMove,
+    StateTaint,
+    StateUntaint,
+    BackendReport,
+    LockTableView,
+    AttachLog,
+    CiJobPlay,
+}
+
+impl OperationKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::AuthLogin => "aws sso login",
+            Self::RefreshWorkspaces => "workspace refresh",
+            Self::TerraformInit => "terraform init",
+            Self::TerraformValidate => "terraform validate",
+            Self::TerraformFmtCheck => "terraform fmt check",
+            Self::TerraformFmt => "terraform fmt",
+            Self::TerraformPlan => "terraform plan",
+            Self::TerraformRefreshPlan => "terraform plan -refresh-only",
+            Self::TerraformDestroyPlan => "terraform plan -destroy",
+            Self::TerraformImport => "terraform import",
+            Self::TerraformApply => "terraform apply",
+            Self::OrphanScan => "orphan scan",
+            Self::CleanupScan => "workspace cleanup scan",
+            Self::WorkspaceCleanup => "workspace cleanup",
+            Self::WorkspaceNew => "terraform workspace new",
+            Self::WorkspaceBootstrap => "workspace bootstrap",
+            Self::WorkspaceDelete => "terraform workspace delete",
+            Self::CostBreakdown => "infracost breakdown",
+            Self::EphemeralCreate => "ephemeral environment create",
+            Self::EphemeralTeardown => "ephemeral environment teardown",
+            Self::ForceUnlock => "terraform force-unlock",
+            Self::StateList => "terraform state list",
+            Self::StateShow => "terraform state show",
+            Self::StateRemove => "terraform state rm",
+            Self::StateMove => "terraform state mv",
+            Self::StateTaint => "terraform taint",
+            Self::StateUntaint => "terraform untaint",
+            Self::BackendReport => "backend hygiene report",
+            Self::LockTableView => "lock table scan",
+            Self::AttachLog => "attached log",
+            Self::CiJobPlay => "CI manual job",
         }
-        FocusPanel::Workspaces => {
-            if let Some(account) = app.selected_account() {
-                let max_idx = account.workspaces.len().saturating_sub(1);
-                if app.selected_workspace < max_idx {
-                    app.selected_workspace += 1;
-                }
-            }
+    }
+
+    fn label_for(self, account: &AccountState) -> String {
+        if self == Self::AuthLogin && account.login_cmd.is_some() {
+            return "login_cmd".to_string();
         }
-        FocusPanel::Output => {
-            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_sub(1);
+        match self.label().strip_prefix("terraform") {
+            Some(rest) => format!("{}{rest}", account.binary_name()),
+            None => self.label().to_string(),
         }
     }
-}
 
-fn start_auth_check_for_selected(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
-    if let Some(account) = app.selected_account().cloned() {
-        let idx = app.selected_account;
-        if let Some(account_mut) = app.selected_account_mut() {
-            account_mut.auth = AuthStatus::Checking;
-        }
-        spawn_auth_check(idx, account, event_tx);
+    fn requires_workspace(self) -> bool {
+        matches!(
+            self,
+            Self::TerraformPlan
+                | Self::TerraformRefreshPlan
+                | Self::TerraformDestroyPlan
+                | Self::TerraformApply
+                | Self::TerraformImport
+                | Self::WorkspaceCleanup
+                | Self::WorkspaceNew
+                | Self::WorkspaceDelete
+                | Self::CostBreakdown
+                | Self::EphemeralCreate
+                | Self::EphemeralTeardown
+                | Self::ForceUnlock
+                | Self::StateList
+                | Self::StateShow
+                | Self::StateRemove
+                | Self::StateMove
+                | Self::StateTaint
+                | Self::StateUntaint
+                | Self::CiJobPlay
+        )
     }
-}
 
-fn start_auth_login(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
-    if app.is_busy() {
-        app.push_output("Another operation is already running.");
-        return;
+    fn is_mutating(self) -> bool {
+        matches!(
+            self,
+            Self::TerraformApply
+                | Self::CiJobPlay
+                | Self::TerraformImport
+                | Self::WorkspaceCleanup
+                | Self::WorkspaceNew
+                | Self::WorkspaceBootstrap
+                | Self::WorkspaceDelete
+                | Self::EphemeralCreate
+                | Self::EphemeralTeardown
+                | Self::ForceUnlock
+                | Self::StateRemove
+                | Self::StateMove
+                | Self::StateTaint
+                | Self::StateUntaint
+        )
     }
 
-    let Some(account) = app.selected_account().cloned() else {
-        app.push_output("No account selected.");
-        return;
-    };
+    fn takes_state_lock(self) -> bool {
+        matches!(
+            self,
+            Self::TerraformPlan
+                | Self::TerraformRefreshPlan
+                | Self::TerraformDestroyPlan
+                | Self::TerraformApply
+                | Self::TerraformImport
+                | Self::WorkspaceCleanup
+                | Self::EphemeralCreate
+                | Self::EphemeralTeardown
+                | Self::StateRemove
+                | Self::StateMove
+                | Self::StateTaint
+                | Self::StateUntaint
+        )
+    }
 
-    let account_idx = app.selected_account;
-    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
-    app.inflight = Some(InflightOperation {
-        kind: OperationKind::AuthLogin,
-        account_idx,
-        cancel_tx,
-        cancel_stage: CancelStage::None,
-    });
-    app.set_status(format!("running aws sso login for {}", account.name));
+    fn indexes_resource_changes(self) -> bool {
+        matches!(
+            self,
+            Self::TerraformPlan
+                | Self::TerraformDestroyPlan
+                | Self::TerraformApply
+                | Self::EphemeralCreate
+        )
+    }
+}
 
-    tokio::spawn(async move {
-        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-            "Starting AWS SSO login for `{}` (profile `{}`)",
-            account.name, account.aws_profile
-        )));
+#[derive(Debug, Clone, Default)]
+struct RunOptions {
+    change_ticket: Option<String>,
+    workspace: Option<String>,
+    var_files: Option<Vec<PathBuf>>,
+    vars: BTreeMap<String, String>,
+    extra_var_files: Vec<PathBuf>,
+    ephemeral: Option<EphemeralSpec>,
+    lock_id: Option<String>,
+    import: Option<ImportSpec>,
+    state_address: Option<String>,
+    state_destination: Option<String>,
+    tuning: Option<RunTuning>,
+    run_at: Option<u64>,
+    plan_file: Option<PathBuf>,
+    approval: Option<(ApprovalRequest, ApprovalSignature)>,
+    ci_job: Option<CiJobSpec>,
+    workspace_names: Vec<String>,
+    force: bool,
+}
 
-        let mut login_cmd = Command::new("aws");
-        login_cmd.args(["sso", "login", "--profile", &account.aws_profile]);
+#[derive(Debug, Clone)]
+struct CiJobSpec {
+    provider: CiProvider,
+    run_id: u64,
+    job_id: u64,
+    name: String,
+}
 
-        let login_result = run_streaming_command(login_cmd, cancel_rx, event_tx.clone()).await;
-        match login_result {
-            Ok(outcome) if outcome.success => {
-                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-                    "SSO login complete for `{}`. Checking credentials...",
-                    account.name
-                )));
+#[derive(Debug, Clone)]
+struct ImportSpec {
+    address: String,
+    id: String,
+    generate_config: bool,
+}
 
-                match check_auth(&account).await {
-                    Ok(true) => {
-                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                            account_idx,
-                            status: AuthStatus::Authenticated,
-                            message: format!("Authenticated to `{}`", account.name),
-                        });
+#[derive(Debug, Clone)]
+struct WorkspaceRetry {
+    account_idx: usize,
+    kind: OperationKind,
+    options: RunOptions,
+}
 
-                        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-                            "Loading workspaces for `{}`...",
-                            account.name
-                        )));
+#[derive(Debug, Clone)]
+struct DeployFlow {
+    account_idx: usize,
+    workspace: String,
+    step: DeployStep,
+}
 
-                        match fetch_workspaces(&account).await {
-                            Ok(workspaces) => {
-                                let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
-                                    account_idx,
-                                    workspaces,
-                                });
-                                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                                    kind: OperationKind::AuthLogin,
-                                    account_idx,
-                                    success: true,
-                                    cancelled: false,
-                                    message: format!("Auth/login complete for `{}`", account.name),
-                                });
-                            }
-                            Err(err) => {
-                                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                                    kind: OperationKind::AuthLogin,
-                                    account_idx,
-                                    success: false,
-                                    cancelled: false,
-                                    message: format!(
-                                        "Authenticated, but failed to load workspaces for `{}`: {err}",
-                                        account.name
-                                    ),
-                                });
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeployStep {
+    Init,
+    Plan,
+    PlanRunning,
+}
+
+#[derive(Debug, Clone)]
+struct StuckLock {
+    account_idx: usize,
+    workspace: String,
+    lock_id: String,
+}
+
+#[derive(Debug, Clone)]
+struct EphemeralSpec {
+    owner: String,
+    ttl_hours: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EphemeralEnvironment {
+    account: String,
+    workspace: String,
+    owner: String,
+    created_at: u64,
+    expires_at: u64,
+    var_file: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MaintenanceLock {
+    reason: String,
+    locked_by: String,
+    locked_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TeamSnapshot {
+    #[serde(default)]
+    id: String,
+    operator: String,
+    updated_at: u64,
+    #[serde(default)]
+    running: Option<AuditRecord>,
+    #[serde(default)]
+    recent: Vec<AuditRecord>,
+    #[serde(default)]
+    maintenance_locks: BTreeMap<String, MaintenanceLock>,
+    #[serde(default)]
+    approval_requests: Vec<ApprovalRequest>,
+    #[serde(default)]
+    approval_signatures: BTreeMap<String, ApprovalSignature>,
+}
+
+#[derive(Debug, Default)]
+struct TeamState {
+    config: Option<TeamSyncConfig>,
+    env: BTreeMap<String, String>,
+    snapshots: Vec<TeamSnapshot>,
+    running: Option<AuditRecord>,
+    recent: Vec<AuditRecord>,
+    next_sync: u64,
+    syncing: bool,
+    seeded: bool,
+    seen: BTreeSet<String>,
+    last_error: Option<String>,
+}
+
+impl TeamState {
+    fn interval(&self) -> u64 {
+        self.config
+            .as_ref()
+            .map_or(DEFAULT_TEAM_SYNC_INTERVAL_SECS, |config| {
+                config.interval_seconds.max(5)
+            })
+    }
+
+    fn maintenance_lock(&self, key: &str) -> Option<(&str, &MaintenanceLock)> {
+        self.snapshots.iter().find_map(|snapshot| {
+            snapshot
+                .maintenance_locks
+                .get(key)
+                .map(|lock| (snapshot.operator.as_str(), lock))
+        })
+    }
+
+    fn running_on(&self, account: &str, workspace: &str, now: u64) -> Option<&AuditRecord> {
+        let stale_after = self.interval() * 3;
+        self.snapshots
+            .iter()
+            .filter(|snapshot| snapshot.updated_at + stale_after >= now)
+            .filter_map(|snapshot| snapshot.running.as_ref())
+            .find(|record| record.account == account && record.workspace == workspace)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScheduledApply {
+    id: u64,
+    account: String,
+    workspace: String,
+    run_at: u64,
+    scheduled_at: u64,
+    scheduled_by: String,
+    change_ticket: Option<String>,
+    plan_file: PathBuf,
+    /// Signed approval collected when the apply was scheduled, checked again when it runs.
+    #[serde(default)]
+    approval: Option<(ApprovalRequest, ApprovalSignature)>,
+}
+
+#[derive(Debug, Clone)]
+enum ConfirmAction {
+    ApplyEphemeral {
+        account_idx: usize,
+        workspace: String,
+        var_file: PathBuf,
+    },
+    TeardownEphemeral {
+        account_idx: usize,
+        workspace: String,
+        var_file: PathBuf,
+    },
+    ForceUnlock(StuckLock),
+    FormatFiles {
+        account_idx: usize,
+    },
+    StateRemove {
+        account_idx: usize,
+        workspace: String,
+        address: String,
+    },
+    StateTaint {
+        account_idx: usize,
+        workspace: String,
+        address: String,
+        mode: TaintMode,
+    },
+    CancelScheduledApplies {
+        account: String,
+    },
+    ReleaseMaintenanceLock {
+        account: String,
+        workspace: String,
+    },
+    BootstrapWorkspaces {
+        account_idx: usize,
+        names: Vec<String>,
+    },
+    DeleteWorkspace {
+        account_idx: usize,
+        workspace: String,
+    },
+    CreateMissingWorkspace {
+        account_idx: usize,
+        workspace: String,
+        retry: Box<WorkspaceRetry>,
+    },
+    PlayCiJob {
+        account_idx: usize,
+        run_id: u64,
+        job_id: u64,
+        name: String,
+    },
+    OpenChangeRequest {
+        paths: Vec<PathBuf>,
+        title: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaintMode {
+    Taint,
+    Replace,
+    Untaint,
+}
+
+impl TaintMode {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Taint => "terraform taint",
+            Self::Replace => "terraform plan -replace",
+            Self::Untaint => "terraform untaint",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Confirmation {
+    title: String,
+    lines: Vec<String>,
+    action: ConfirmAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CostEstimate {
+    monthly_cost: f64,
+    currency: String,
+    timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CleanupCandidate {
+    workspace: String,
+    reason: String,
+    resources: usize,
+}
+
+#[derive(Debug)]
+struct CleanupSession {
+    account_idx: usize,
+    candidates: Vec<CleanupCandidate>,
+    current: usize,
+    removed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AuditEvent {
+    Started,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditRecord {
+    timestamp: u64,
+    operator: String,
+    account: String,
+    workspace: String,
+    operation: String,
+    event: AuditEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    change_ticket: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum PromptPurpose {
+    ChangeTicket {
+        account_idx: usize,
+        workspace: String,
+        kind: OperationKind,
+        options: Box<RunOptions>,
+    },
+    HistorySearch,
+    WorkspaceName {
+        account_idx: usize,
+    },
+    AttachLog,
+    WorkspaceDeleteName {
+        account_idx: usize,
+        workspace: String,
+    },
+    EphemeralName {
+        account_idx: usize,
+    },
+    EphemeralOwner {
+        account_idx: usize,
+        name: String,
+    },
+    ScheduleApply {
+        account_idx: usize,
+        workspace: String,
+    },
+    AcknowledgeStatefulChanges {
+        account_idx: usize,
+        workspace: String,
+        run_at: Option<u64>,
+    },
+    MaintenanceReason {
+        account_idx: usize,
+        workspace: String,
+    },
+    MissingVariable {
+        account_idx: usize,
+        kind: OperationKind,
+        options: Box<RunOptions>,
+        name: String,
+        remaining: Vec<String>,
+    },
+    EphemeralTtl {
+        account_idx: usize,
+        name: String,
+        owner: String,
+    },
+    AwsProfile {
+        account_idx: usize,
+        answers: Vec<String>,
+    },
+    ImportAddress {
+        account_idx: usize,
+    },
+    ImportId {
+        account_idx: usize,
+        address: String,
+    },
+    ImportGenerate {
+        account_idx: usize,
+        address: String,
+        id: String,
+    },
+    StateMoveDestination {
+        account_idx: usize,
+        workspace: String,
+        source: String,
+    },
+}
+
+const AWS_PROFILE_FIELDS: [(&str, &str); 4] = [
+    (
+        "sso_start_url",
+        "SSO start URL (https://<org>.awsapps.com/start)",
+    ),
+    ("sso_region", "SSO region (e.g. us-east-1)"),
+    ("sso_account_id", "AWS account ID (12 digits)"),
+    ("sso_role_name", "SSO role name (e.g. AdministratorAccess)"),
+];
+
+#[derive(Debug, Clone)]
+struct WorkspaceFilter {
+    account_idx: usize,
+    query: String,
+    editing: bool,
+}
+
+#[derive(Debug, Clone)]
+struct TextPrompt {
+    purpose: PromptPurpose,
+    title: String,
+    hint: String,
+    input: String,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CancelSignal {
+    None,
+    Graceful,
+    Force,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CancelStage {
+    None,
+    GracefulRequested,
+    ForceRequested,
+}
+
+#[derive(Debug)]
+struct InflightOperation {
+    kind: OperationKind,
+    account_idx: usize,
+    workspace: String,
+    cancel_tx: watch::Sender<CancelSignal>,
+    cancel_stage: CancelStage,
+    resource_changes: Vec<ResourceChange>,
+    output_source: usize,
+    lock_error: bool,
+    lock_id: Option<String>,
+    missing_variables: Vec<String>,
+    options: Option<Box<RunOptions>>,
+}
+
+#[derive(Debug, Clone)]
+struct OutputEntry {
+    source: Option<usize>,
+    text: String,
+    expanded: bool,
+}
+
+impl OutputEntry {
+    fn is_foldable(&self) -> bool {
+        self.text.len() > OUTPUT_FOLD_CHARS
+    }
+
+    fn rows(&self) -> usize {
+        if self.is_foldable() && self.expanded {
+            self.text.chars().count().div_ceil(OUTPUT_WRAP_WIDTH)
+        } else {
+            1
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+    Replace,
+    Read,
+}
+
+impl ChangeAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Replace => "replace",
+            Self::Read => "read",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Create => Color::Green,
+            Self::Update => Color::Yellow,
+            Self::Delete => Color::Red,
+            Self::Replace => Color::Magenta,
+            Self::Read => Color::Blue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResourceChange {
+    address: String,
+    action: ChangeAction,
+    provider: Option<String>,
+    attributes: Vec<AttributeDiff>,
+    replace_reasons: Vec<String>,
+}
+
+impl ResourceChange {
+    fn is_noise_only(&self) -> bool {
+        self.action == ChangeAction::Update
+            && !self.attributes.is_empty()
+            && self.attributes.iter().all(|diff| diff.noisy)
+    }
+}
+
+fn mark_noisy_attributes(changes: &mut [ResourceChange], patterns: &[glob::Pattern]) {
+    if patterns.is_empty() {
+        return;
+    }
+    for change in changes {
+        let resource_type = resource_type_of_address(&change.address);
+        for diff in &mut change.attributes {
+            let typed_key = format!("{resource_type}.{}", diff.key);
+            diff.noisy = patterns
+                .iter()
+                .any(|pattern| pattern.matches(&diff.key) || pattern.matches(&typed_key));
+        }
+    }
+}
+
+/// One changed top-level attribute. Values are rendered JSON (or a placeholder such as
+/// `(sensitive)`); `None` means the attribute is absent on that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AttributeDiff {
+    key: String,
+    old: Option<String>,
+    new: Option<String>,
+    noisy: bool,
+}
+
+impl AttributeDiff {
+    fn line_ops(&self) -> Option<Vec<(char, String)>> {
+        if ![&self.old, &self.new]
+            .into_iter()
+            .flatten()
+            .any(|value| value.contains('\n'))
+        {
+            return None;
+        }
+        let split = |value: &Option<String>| -> Vec<String> {
+            value
+                .as_deref()
+                .map(|value| value.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        };
+        Some(line_diff(&split(&self.old), &split(&self.new)))
+    }
+
+    fn marker(&self) -> char {
+        match (&self.old, &self.new) {
+            (None, _) => '+',
+            (_, None) => '-',
+            _ => '~',
+        }
+    }
+}
+
+const SIDE_BY_SIDE_COLUMN: usize = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum DiffLayout {
+    #[default]
+    Unified,
+    SideBySide,
+}
+
+/// Line-level diff via the longest common subsequence: `' '` kept, `-` removed, `+` added.
+/// Very large inputs fall back to removing everything and adding everything.
+fn line_diff(old: &[String], new: &[String]) -> Vec<(char, String)> {
+    if old.len().saturating_mul(new.len()) > 4_000_000 {
+        return old
+            .iter()
+            .map(|line| ('-', line.clone()))
+            .chain(new.iter().map(|line| ('+', line.clone())))
+            .collect();
+    }
+    // common[i][j]: length of the LCS of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            ops.push(('-', old[i].clone()));
+            i += 1;
+        } else {
+            ops.push(('+', new[j].clone()));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DiffLine {
+    Text(char, String),
+    Columns(char, String, String),
+}
+
+impl DiffLayout {
+    fn toggled(self) -> Self {
+        match self {
+            Self::Unified => Self::SideBySide,
+            Self::SideBySide => Self::Unified,
+        }
+    }
+
+    fn render(self, diff: &AttributeDiff, line_ops: Option<&[(char, String)]>) -> Vec<DiffLine> {
+        let marker = diff.marker();
+        if let Some(ops) = line_ops {
+            let ops = ops.iter().cloned();
+            let mut lines = vec![DiffLine::Text(marker, format!("{} =", diff.key))];
+            match self {
+                Self::Unified => {
+                    lines.extend(ops.map(|(op, line)| DiffLine::Text(op, format!("    {line}"))));
+                }
+                Self::SideBySide => {
+                    // Removed and added runs are paired row by row between unchanged lines.
+                    let (mut removed, mut added) = (Vec::new(), Vec::new());
+                    let flush = |lines: &mut Vec<DiffLine>,
+                                 removed: &mut Vec<String>,
+                                 added: &mut Vec<String>| {
+                        let rows = removed.len().max(added.len());
+                        let (mut old, mut new) = (removed.drain(..), added.drain(..));
+                        for _ in 0..rows {
+                            let (left, right) = (old.next(), new.next());
+                            let op = match (&left, &right) {
+                                (Some(_), Some(_)) => '~',
+                                (Some(_), None) => '-',
+                                _ => '+',
+                            };
+                            lines.push(DiffLine::Columns(
+                                op,
+                                left.unwrap_or_default(),
+                                right.unwrap_or_default(),
+                            ));
+                        }
+                    };
+                    for (op, line) in ops {
+                        match op {
+                            '-' => removed.push(line),
+                            '+' => added.push(line),
+                            _ => {
+                                flush(&mut lines, &mut removed, &mut added);
+                                lines.push(DiffLine::Columns(' ', line.clone(), line));
                             }
                         }
                     }
-                    Ok(false) => {
-                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                            account_idx,
-                            status: AuthStatus::Failed,
-                            message: format!(
-                                "Credentials for `{}` are not usable yet",
-                                account.name
-                            ),
-                        });
-                        let _ = event_tx.send(WorkerEvent::OperationFinished {
-                            kind: OperationKind::AuthLogin,
-                            account_idx,
-                            success: false,
-                            cancelled: false,
-                            message: format!("Auth check failed for `{}`", account.name),
-                        });
-                    }
+                    flush(&mut lines, &mut removed, &mut added);
+                }
+            }
+            return lines;
+        }
+        match self {
+            Self::Unified => {
+                let shorten = |value: &str| {
+                    if value.chars().count() > 80 {
+                        format!("{}...", value.chars().take(77).collect::<String>())
+                    } else {
+                        value.to_string()
+                    }
+                };
+                let text = match (&diff.old, &diff.new) {
+                    (Some(old), Some(new)) => {
+                        format!("{} = {} -> {}", diff.key, shorten(old), shorten(new))
+                    }
+                    (Some(value), None) | (None, Some(value)) => {
+                        format!("{} = {}", diff.key, shorten(value))
+                    }
+                    (None, None) => diff.key.clone(),
+                };
+                vec![DiffLine::Text(marker, text)]
+            }
+            Self::SideBySide => {
+                let wrap = |value: &Option<String>| -> Vec<String> {
+                    let chars: Vec<char> = value.as_deref().unwrap_or_default().chars().collect();
+                    chars
+                        .chunks(SIDE_BY_SIDE_COLUMN)
+                        .map(|chunk| chunk.iter().collect())
+                        .collect()
+                };
+                let (old, new) = (wrap(&diff.old), wrap(&diff.new));
+                let mut lines = vec![DiffLine::Text(marker, diff.key.clone())];
+                lines.extend((0..old.len().max(new.len())).map(|row| {
+                    DiffLine::Columns(
+                        marker,
+                        old.get(row).cloned().unwrap_or_default(),
+                        new.get(row).cloned().unwrap_or_default(),
+                    )
+                }));
+                lines
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResourceIndexEntry {
+    timestamp: u64,
+    operator: String,
+    account: String,
+    workspace: String,
+    operation: String,
+    outcome: String,
+    address: String,
+    action: ChangeAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StateMetricsSample {
+    timestamp: u64,
+    account: String,
+    workspace: String,
+    serial: u64,
+    size_bytes: u64,
+    resources: usize,
+}
+
+#[derive(Debug)]
+struct StateMetricsView {
+    account: String,
+    workspace: String,
+    samples: Vec<StateMetricsSample>,
+}
+
+#[derive(Debug)]
+struct HistorySearch {
+    query: String,
+    results: Vec<ResourceIndexEntry>,
+    scroll: usize,
+}
+
+#[derive(Debug)]
+struct PlanChangesView {
+    account_idx: usize,
+    workspace: String,
+    changes: Vec<ResourceChange>,
+    selected: usize,
+    blame: BTreeMap<String, String>,
+    blame_requested: bool,
+}
+
+#[derive(Debug, Clone)]
+struct PlanSummary {
+    account_idx: usize,
+    workspace: String,
+    destroy_preview: bool,
+    import: usize,
+    add: usize,
+    change: usize,
+    destroy: usize,
+    noisy: usize,
+}
+
+#[derive(Debug, Clone)]
+struct PlanReview {
+    account_idx: usize,
+    workspace: String,
+    changes: Vec<ResourceChange>,
+    collapsed_modules: BTreeSet<String>,
+    expanded: BTreeSet<usize>,
+    layout: DiffLayout,
+    selected: usize,
+    focus: Option<String>,
+    line_diffs: BTreeMap<(usize, usize), Vec<(char, String)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlanReviewRow {
+    Module(String),
+    Resource(usize),
+    Attribute(usize, usize),
+}
+
+impl PlanReview {
+    fn modules(&self) -> BTreeMap<&str, Vec<usize>> {
+        let mut modules: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (idx, change) in self.changes.iter().enumerate() {
+            let module = module_of_address(&change.address);
+            if self
+                .focus
+                .as_deref()
+                .is_none_or(|focus| module_contains(focus, module))
+            {
+                modules.entry(module).or_default().push(idx);
+            }
+        }
+        modules
+    }
+
+    fn expand(&mut self, idx: usize) {
+        self.expanded.insert(idx);
+        for (attribute, diff) in self.changes[idx].attributes.iter().enumerate() {
+            if !self.line_diffs.contains_key(&(idx, attribute))
+                && let Some(ops) = diff.line_ops()
+            {
+                self.line_diffs.insert((idx, attribute), ops);
+            }
+        }
+    }
+
+    fn diff_lines(&self, idx: usize) -> Vec<DiffLine> {
+        let attributes = &self.changes[idx].attributes;
+        let mut lines: Vec<DiffLine> = attributes
+            .iter()
+            .enumerate()
+            .filter(|(_, diff)| !diff.noisy)
+            .flat_map(
+                |(attribute, diff)| match self.line_diffs.get(&(idx, attribute)) {
+                    Some(ops) => self.layout.render(diff, Some(ops)),
+                    None => self.layout.render(diff, diff.line_ops().as_deref()),
+                },
+            )
+            .collect();
+        let noisy: Vec<&str> = attributes
+            .iter()
+            .filter(|diff| diff.noisy)
+            .map(|diff| diff.key.as_str())
+            .collect();
+        if !noisy.is_empty() {
+            lines.push(DiffLine::Text(
+                ' ',
+                format!("({} noisy: {})", noisy.len(), noisy.join(", ")),
+            ));
+        }
+        lines
+    }
+
+    fn rows(&self) -> Vec<PlanReviewRow> {
+        let mut rows = Vec::new();
+        for (module, changes) in self.modules() {
+            rows.push(PlanReviewRow::Module(module.to_string()));
+            if self.collapsed_modules.contains(module) {
+                continue;
+            }
+            for idx in changes {
+                rows.push(PlanReviewRow::Resource(idx));
+                if self.expanded.contains(&idx) {
+                    rows.extend(
+                        (0..self.diff_lines(idx).len())
+                            .map(|line| PlanReviewRow::Attribute(idx, line)),
+                    );
+                }
+            }
+        }
+        rows
+    }
+}
+
+#[derive(Debug)]
+struct BackgroundTask {
+    id: u64,
+    label: String,
+    started: Instant,
+    finished: Option<(Duration, bool)>,
+}
+
+#[derive(Debug)]
+struct StateBrowser {
+    account_idx: usize,
+    workspace: String,
+    resources: Vec<String>,
+    selected: usize,
+    detail: Option<StateDetail>,
+    marked: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+struct LockItem {
+    path: String,
+    id: String,
+    who: String,
+    operation: String,
+    created: String,
+}
+
+#[derive(Debug, Clone)]
+struct CiRun {
+    id: u64,
+    number: u64,
+    status: String,
+    branch: String,
+    sha: String,
+    title: String,
+    created_at: String,
+    jobs: Option<Vec<CiJob>>,
+    expanded: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CiJob {
+    id: u64,
+    name: String,
+    status: String,
+}
+
+#[derive(Debug)]
+struct CiRunsView {
+    account_idx: usize,
+    provider: CiProvider,
+    source: String,
+    runs: Vec<CiRun>,
+    loading: bool,
+    selected: usize,
+}
+
+impl CiRunsView {
+    fn rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = Vec::new();
+        for (run_idx, run) in self.runs.iter().enumerate() {
+            rows.push((run_idx, None));
+            if run.expanded
+                && let Some(jobs) = &run.jobs
+            {
+                rows.extend((0..jobs.len()).map(|job_idx| (run_idx, Some(job_idx))));
+            }
+        }
+        rows
+    }
+}
+
+#[derive(Debug)]
+struct LockTableView {
+    account_idx: usize,
+    table: String,
+    locks: Vec<LockItem>,
+    selected: usize,
+}
+
+#[derive(Debug)]
+struct StateDetail {
+    address: String,
+    lines: Vec<Line<'static>>,
+    scroll: usize,
+}
+
+#[derive(Debug)]
+struct FileBrowser {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    selected: usize,
+    viewer: Option<FileView>,
+}
+
+#[derive(Debug)]
+struct FileView {
+    path: PathBuf,
+    lines: Vec<Line<'static>>,
+    scroll: usize,
+}
+
+#[derive(Debug)]
+struct AppState {
+    accounts: Vec<AccountState>,
+    selected_account: usize,
+    selected_workspace: usize,
+    focused_panel: FocusPanel,
+    previous_focus_panel: FocusPanel,
+    layout_mode: LayoutMode,
+    output_lines: Vec<OutputEntry>,
+    output_sources: Vec<String>,
+    output_filter: Option<usize>,
+    output_limit: usize,
+    low_memory: bool,
+    mouse_enabled: bool,
+    mouse_released: bool,
+    icons: IconSet,
+    operation_log: Option<OperationLog>,
+    output_rate: OutputRate,
+    output_scroll_from_bottom: usize,
+    scroll: ScrollConfig,
+    output_page_rows: std::cell::Cell<usize>,
+    accounts_offset: std::cell::Cell<usize>,
+    collapsed_groups: BTreeSet<String>,
+    selected_group: Option<String>,
+    account_filter: Option<String>,
+    account_filter_origin: Option<(usize, usize, Option<String>)>,
+    workspace_filter: Option<WorkspaceFilter>,
+    workspaces_offset: std::cell::Cell<usize>,
+    status_line: String,
+    account_activity: BTreeMap<String, AccountActivity>,
+    inflight: Option<InflightOperation>,
+    pending_apply_confirmation: bool,
+    prompt: Option<TextPrompt>,
+    run_options_popup: Option<RunOptionsPopup>,
+    var_file_picker: Option<VarFilePicker>,
+    session_tuning: BTreeMap<usize, RunTuning>,
+    skip_run_options: BTreeSet<usize>,
+    history_search: Option<HistorySearch>,
+    file_browser: Option<FileBrowser>,
+    notes: Option<FileView>,
+    plan_changes: Option<PlanChangesView>,
+    /// Stateful deletions in each saved plan, by `cost_key`; what `A` would apply.
+    saved_plan_stateful: BTreeMap<String, Vec<String>>,
+    plan_changes_open: bool,
+    /// The last `plan -destroy` result, kept apart so it never stands in for the saved plan.
+    destroy_preview: Option<PlanChangesView>,
+    destroy_preview_open: bool,
+    plan_review: Option<PlanReview>,
+    diff_layout: DiffLayout,
+    plan_summary: Option<PlanSummary>,
+    state_browser: Option<StateBrowser>,
+    lock_table: Option<LockTableView>,
+    ci_runs: Option<CiRunsView>,
+    pending_editor: Option<(PathBuf, usize)>,
+    state_metrics_view: Option<StateMetricsView>,
+    cleanup_session: Option<CleanupSession>,
+    costs: BTreeMap<String, CostEstimate>,
+    drift: BTreeMap<String, DriftRecord>,
+    next_drift_check: BTreeMap<usize, u64>,
+    update_check: bool,
+    update_record: UpdateCheckRecord,
+    stateful_resource_types: Vec<glob::Pattern>,
+    environments: Vec<EphemeralEnvironment>,
+    scheduled_applies: Vec<ScheduledApply>,
+    maintenance_locks: BTreeMap<String, MaintenanceLock>,
+    team: TeamState,
+    scheduled_running: Option<ScheduledApply>,
+    schedule_outcomes: Vec<(ScheduledApply, &'static str, String)>,
+    schedule: ScheduleConfig,
+    confirmation: Option<Confirmation>,
+    stuck_lock: Option<StuckLock>,
+    deploy: Option<DeployFlow>,
+    workspace_retry: Option<WorkspaceRetry>,
+    proposed_changes: Vec<(Vec<PathBuf>, String)>,
+    leftover_children: Vec<ChildRecord>,
+    audit_sinks: Vec<AuditSink>,
+    state_tracking: StateTrackingConfig,
+    cleanup: CleanupConfig,
+    reminders: ReminderConfig,
+    ci: CiConfig,
+    next_reminder_at: u64,
+    prefetch: PrefetchConfig,
+    backend_probe: BackendProbeConfig,
+    background_limiter: Arc<Semaphore>,
+    hovered_account: Option<(usize, Instant)>,
+    prefetched_accounts: BTreeSet<usize>,
+    tasks: Vec<BackgroundTask>,
+    activity_open: bool,
+    show_help: bool,
+    quit_requested: bool,
+    config_path: Option<PathBuf>,
+    config_watch: Vec<PathBuf>,
+    config_mtime: Option<SystemTime>,
+    watch_config: bool,
+}
+
+impl AppState {
+    fn from_config(config: Config, config_base_dir: &Path) -> Result<Self> {
+        if config.accounts.is_empty() {
+            return Err(eyre!(
+                "Config has no accounts. Add at least one account under `accounts:`"
+            ));
+        }
+
+        let mut accounts = Vec::with_capacity(config.accounts.len());
+        let mut startup_lines =
+            vec!["lazytf ready. Press `a` to authenticate selected account.".to_string()];
+        let known_profiles = load_aws_profiles();
+        let versions_dir = config
+            .terraform_versions_dir
+            .as_deref()
+            .map(|dir| config_base_dir.join(expand_env_vars(dir)));
+
+        let stateful_resource_types = compile_patterns(
+            "stateful_resource_types",
+            &config.stateful_resource_types,
+            &mut startup_lines,
+        );
+        let noisy_attributes = compile_patterns(
+            "noisy_attributes",
+            &config.noisy_attributes,
+            &mut startup_lines,
+        );
+        let defaults_env = config.defaults.expanded_env();
+
+        for (name, account_cfg) in config.accounts {
+            let account_cfg = config.defaults.apply(account_cfg);
+            let approval = account_cfg
+                .require_approval
+                .then(|| config.approval.clone().unwrap_or_default());
+            if let Some(approval) = &approval
+                && approval.mode == ApprovalMode::Command
+                && approval.command.is_none()
+            {
+                startup_lines.push(format!(
+                    "warning: account `{name}` requires approval but `approval.command` is not set; apply will be refused"
+                ));
+            }
+
+            let roles: Vec<AccountRole> = account_cfg
+                .roles
+                .iter()
+                .map(|role| match role {
+                    RoleConfig::Name(role) => AccountRole {
+                        name: role.clone(),
+                        aws_profile: format!("{name}-{}", role.to_ascii_lowercase()),
+                        derived_profile: true,
+                    },
+                    RoleConfig::Profile { name, aws_profile } => AccountRole {
+                        name: name.clone(),
+                        aws_profile: aws_profile.clone(),
+                        derived_profile: false,
+                    },
+                })
+                .collect();
+            let mut derived_missing = false;
+            for role in roles.iter().filter(|role| role.derived_profile) {
+                if account_cfg.login_cmd.is_none()
+                    && known_profiles
+                        .as_ref()
+                        .is_some_and(|profiles| !profiles.contains(&role.aws_profile))
+                {
+                    derived_missing |= role.name == roles[0].name;
+                    startup_lines.push(format!(
+                        "warning: account `{name}` role `{}` uses the derived profile `{}` (`<account>-<role lowercase>`), which is not in {} or {}; write the role as `{{ name: {}, aws_profile: ... }}` to use another profile",
+                        role.name,
+                        role.aws_profile,
+                        aws_config_path().display(),
+                        aws_credentials_path().display(),
+                        role.name
+                    ));
+                }
+            }
+            let apply_role = account_cfg.apply_role.clone();
+            if roles.is_empty() {
+                if account_cfg.aws_profile.is_empty() {
+                    return Err(eyre!(
+                        "Account `{name}`: set `aws_profile`, or `roles` with an `apply_role`"
+                    ));
+                }
+            } else {
+                let Some(apply_role) = &apply_role else {
+                    return Err(eyre!(
+                        "Account `{name}` has `roles` but no `apply_role`; name the role allowed to apply"
+                    ));
+                };
+                if !roles.iter().any(|role| role.name == *apply_role) {
+                    startup_lines.push(format!(
+                        "warning: account `{name}` apply_role `{apply_role}` is not one of its roles; apply will be refused"
+                    ));
+                }
+                if !account_cfg.aws_profile.is_empty() {
+                    startup_lines.push(format!(
+                        "warning: account `{name}` aws_profile `{}` is ignored because `roles` is set; each role's profile is used instead",
+                        account_cfg.aws_profile
+                    ));
+                }
+            }
+            let aws_profile = roles
+                .first()
+                .map(|role| role.aws_profile.clone())
+                .unwrap_or(account_cfg.aws_profile);
+
+            let profile_missing = account_cfg.login_cmd.is_none()
+                && known_profiles
+                    .as_ref()
+                    .is_some_and(|profiles| !profiles.contains(&aws_profile));
+            if profile_missing && !derived_missing {
+                startup_lines.push(format!(
+                    "warning: account `{name}` uses AWS profile `{aws_profile}` which is not in {} or {}; press `P` to create it",
+                    aws_config_path().display(),
+                    aws_credentials_path().display()
+                ));
+            }
+
+            let (composition_path, composition_issue) = match resolve_composition_path(
+                config_base_dir,
+                &account_cfg.composition_path,
+            ) {
+                Ok(path) => (path, None),
+                Err(err) => {
+                    let fallback =
+                        fallback_composition_path(config_base_dir, &account_cfg.composition_path);
+                    let issue = format!(
+                        "composition_path `{}` invalid: {err}",
+                        account_cfg.composition_path
+                    );
+                    startup_lines.push(format!("warning: account `{name}` {issue}"));
+                    startup_lines.push(format!(
+                            "warning: using fallback path `{}` so UI can start; execution remains blocked until fixed",
+                            fallback.display()
+                        ));
+                    (fallback, Some(issue))
+                }
+            };
+
+            if let Some(index) = account_cfg
+                .preconditions
+                .iter()
+                .position(|check| check.dns.is_some() == check.command.is_some())
+            {
+                return Err(eyre!(
+                    "Account `{name}` precondition #{}: set exactly one of `dns` or `command`",
+                    index + 1
+                ));
+            }
+
+            let change_ticket = account_cfg
+                .change_ticket
+                .map(|cfg| ChangeTicketPolicy::from_config(&name, cfg))
+                .transpose()?;
+
+            if let Some(subcommand) = account_cfg
+                .extra_args
+                .keys()
+                .find(|subcommand| !EXTRA_ARGS_SUBCOMMANDS.contains(&subcommand.as_str()))
+            {
+                return Err(eyre!(
+                    "Account `{name}` extra_args: unknown subcommand `{subcommand}` (expected one of {})",
+                    EXTRA_ARGS_SUBCOMMANDS.join(", ")
+                ));
+            }
+
+            let color = account_cfg
+                .color
+                .as_deref()
+                .map(|color| {
+                    color
+                        .parse::<Color>()
+                        .map_err(|_| eyre!("Account `{name}` color `{color}` is not a color"))
+                })
+                .transpose()?;
+
+            let configured_binary = account_cfg
+                .binary
+                .or_else(|| config.binary.clone())
+                .unwrap_or_else(|| "terraform".to_string());
+            let binary = match &account_cfg.terraform_version {
+                Some(version) => {
+                    match resolve_pinned_binary(
+                        &configured_binary,
+                        version,
+                        versions_dir.as_deref(),
+                    ) {
+                        Some(path) => path.display().to_string(),
+                        None => {
+                            startup_lines.push(format!(
+                                "warning: account `{name}` terraform_version `{version}` is not installed under terraform_versions_dir, tfenv, mise, or asdf; running `{configured_binary}` with the version exported for shims"
+                            ));
+                            configured_binary
+                        }
+                    }
+                }
+                None => configured_binary,
+            };
+
+            let mut account_noisy_attributes = noisy_attributes.clone();
+            account_noisy_attributes.extend(compile_patterns(
+                &format!("account `{name}` noisy_attributes"),
+                &account_cfg.noisy_attributes,
+                &mut startup_lines,
+            ));
+
+            accounts.push(AccountState {
+                name,
+                group: account_cfg.group,
+                aws_profile,
+                region: account_cfg.region,
+                binary,
+                auth_check_cmd: account_cfg.auth_check_cmd,
+                login_cmd: account_cfg.login_cmd,
+                pinned_version: account_cfg.terraform_version,
+                var_files: resolve_var_file_paths(&account_cfg.var_files, &composition_path),
+                composition_mode: detect_composition_mode(&composition_path),
+                composition_path,
+                composition_issue,
+                approval,
+                change_ticket,
+                orphan_checks: account_cfg.orphan_checks,
+                tag_policy: account_cfg.tag_policy,
+                tag_violations: BTreeMap::new(),
+                noisy_attributes: account_noisy_attributes,
+                ephemeral: account_cfg.ephemeral,
+                docs_path: account_cfg.docs_path,
+                roles,
+                active_role: 0,
+                apply_role,
+                run_options: account_cfg.run_options,
+                drift_check: account_cfg.drift_check,
+                desired_workspaces: account_cfg.desired_workspaces,
+                preconditions: account_cfg.preconditions,
+                backend_config: account_cfg
+                    .backend_config
+                    .as_ref()
+                    .map(BackendConfigSettings::init_args)
+                    .unwrap_or_default(),
+                extra_args: account_cfg.extra_args,
+                env: account_cfg
+                    .env
+                    .into_iter()
+                    .map(|(key, value)| (key, expand_env_vars(&value)))
+                    .collect(),
+                color,
+                variables: tf_var_values(account_cfg.variables),
+                workspace_variables: account_cfg
+                    .workspace_variables
+                    .into_iter()
+                    .map(|(workspace, variables)| (workspace, tf_var_values(variables)))
+                    .collect(),
+                profile_missing,
+                backend_issues: None,
+                backend_unreachable: None,
+                terraform_version: None,
+                version_mismatch: None,
+                auth: AuthStatus::Unknown,
+                workspaces: Vec::new(),
+                current_workspace: None,
+            });
+        }
+        // Keep each group together: ungrouped accounts first, then groups by name.
+        accounts.sort_by(|a, b| a.group.cmp(&b.group));
+
+        Ok(Self {
+            accounts,
+            selected_account: 0,
+            selected_workspace: 0,
+            focused_panel: FocusPanel::Accounts,
+            previous_focus_panel: FocusPanel::Accounts,
+            layout_mode: LayoutMode::Split,
+            output_lines: startup_lines
+                .into_iter()
+                .map(|text| OutputEntry {
+                    source: None,
+                    text,
+                    expanded: false,
+                })
+                .collect(),
+            output_sources: Vec::new(),
+            output_filter: None,
+            output_limit: if config.low_memory.enabled {
+                config.low_memory.window_lines.max(1)
+            } else {
+                OUTPUT_BUFFER_LIMIT
+            },
+            low_memory: config.low_memory.enabled,
+            output_rate: OutputRate {
+                window_start: Instant::now(),
+                lines: 0,
+                suppressed: 0,
+                summarizing: false,
+            },
+            mouse_enabled: config.mouse,
+            mouse_released: false,
+            icons: config.icons,
+            operation_log: None,
+            output_scroll_from_bottom: 0,
+            scroll: config.scroll,
+            output_page_rows: std::cell::Cell::new(0),
+            accounts_offset: std::cell::Cell::new(0),
+            collapsed_groups: config.collapsed_groups,
+            selected_group: None,
+            account_filter: None,
+            account_filter_origin: None,
+            workspace_filter: None,
+            workspaces_offset: std::cell::Cell::new(0),
+            status_line: "idle".to_string(),
+            account_activity: BTreeMap::new(),
+            inflight: None,
+            pending_apply_confirmation: false,
+            prompt: None,
+            run_options_popup: None,
+            var_file_picker: None,
+            session_tuning: BTreeMap::new(),
+            skip_run_options: BTreeSet::new(),
+            history_search: None,
+            file_browser: None,
+            notes: None,
+            plan_changes: None,
+            saved_plan_stateful: BTreeMap::new(),
+            plan_changes_open: false,
+            destroy_preview: None,
+            destroy_preview_open: false,
+            plan_review: None,
+            diff_layout: DiffLayout::default(),
+            plan_summary: None,
+            state_browser: None,
+            lock_table: None,
+            ci_runs: None,
+            pending_editor: None,
+            state_metrics_view: None,
+            cleanup_session: None,
+            costs: load_cost_cache(),
+            drift: load_drift_cache(),
+            next_drift_check: BTreeMap::new(),
+            update_check: config.update_check,
+            update_record: load_update_check(),
+            stateful_resource_types,
+            environments: load_environments(),
+            scheduled_applies: load_scheduled_applies(),
+            maintenance_locks: load_maintenance_locks(),
+            team: TeamState {
+                config: config.team_sync,
+                env: defaults_env.clone(),
+                ..TeamState::default()
+            },
+            scheduled_running: None,
+            schedule_outcomes: Vec::new(),
+            schedule: config.schedule,
+            confirmation: None,
+            stuck_lock: None,
+            deploy: None,
+            workspace_retry: None,
+            proposed_changes: Vec::new(),
+            leftover_children: Vec::new(),
+            audit_sinks: config.audit.sinks,
+            state_tracking: config.state_tracking,
+            cleanup: config.cleanup,
+            reminders: config.reminders,
+            ci: config.ci,
+            next_reminder_at: 0,
+            prefetch: config.prefetch,
+            backend_probe: config.backend_probe,
+            background_limiter: Arc::new(Semaphore::new(config.prefetch.max_concurrent.max(1))),
+            hovered_account: None,
+            prefetched_accounts: BTreeSet::new(),
+            tasks: Vec::new(),
+            activity_open: false,
+            show_help: false,
+            quit_requested: false,
+            config_path: None,
+            config_watch: Vec::new(),
+            config_mtime: None,
+            watch_config: config.watch_config,
+        })
+    }
+
+    /// `None` while the cursor is on a group header or the account is folded away, so
+    /// actions never hit an account the panel doesn't show as selected.
+    fn selected_account(&self) -> Option<&AccountState> {
+        if !self.selected_account_visible() {
+            return None;
+        }
+        self.accounts.get(self.selected_account)
+    }
+
+    fn selected_account_mut(&mut self) -> Option<&mut AccountState> {
+        if !self.selected_account_visible() {
+            return None;
+        }
+        self.accounts.get_mut(self.selected_account)
+    }
+
+    fn selected_account_visible(&self) -> bool {
+        let filtering = self
+            .account_filter
+            .as_ref()
+            .is_some_and(|query| !query.is_empty());
+        self.selected_group.is_none()
+            && (filtering
+                || self
+                    .accounts
+                    .get(self.selected_account)
+                    .and_then(|account| account.group.as_ref())
+                    .is_none_or(|group| !self.collapsed_groups.contains(group)))
+    }
+
+    fn maintenance_lock(&self, account: &str, workspace: &str) -> Option<&MaintenanceLock> {
+        let key = cost_key(account, workspace);
+        self.maintenance_locks
+            .get(&key)
+            .or_else(|| self.team.maintenance_lock(&key).map(|(_, lock)| lock))
+    }
+
+    fn account_rows(&self) -> Vec<AccountRow> {
+        if let Some(query) = self
+            .account_filter
+            .as_ref()
+            .filter(|query| !query.is_empty())
+        {
+            return (0..self.accounts.len())
+                .filter(|idx| fuzzy_score(query, &self.accounts[*idx].name).is_some())
+                .map(AccountRow::Account)
+                .collect();
+        }
+        let mut rows = Vec::new();
+        let mut current_group = None;
+        for (idx, account) in self.accounts.iter().enumerate() {
+            if let Some(group) = &account.group
+                && current_group != Some(group)
+            {
+                current_group = Some(group);
+                rows.push(AccountRow::Group(group.clone()));
+            }
+            if account
+                .group
+                .as_ref()
+                .is_none_or(|group| !self.collapsed_groups.contains(group))
+            {
+                rows.push(AccountRow::Account(idx));
+            }
+        }
+        rows
+    }
+
+    fn selected_account_row(&self, rows: &[AccountRow]) -> usize {
+        let folded_group = self
+            .accounts
+            .get(self.selected_account)
+            .and_then(|account| account.group.clone())
+            .filter(|group| self.collapsed_groups.contains(group))
+            .filter(|_| self.account_filter.as_ref().is_none_or(String::is_empty));
+        let group = self.selected_group.clone().or(folded_group);
+        rows.iter()
+            .position(|row| match (row, &group) {
+                (AccountRow::Group(name), Some(group)) => name == group,
+                (AccountRow::Account(idx), None) => *idx == self.selected_account,
+                _ => false,
+            })
+            .unwrap_or(0)
+    }
+
+    fn workspace_filter_query(&self) -> Option<&str> {
+        self.workspace_filter
+            .as_ref()
+            .filter(|filter| filter.account_idx == self.selected_account)
+            .map(|filter| filter.query.as_str())
+    }
+
+    fn visible_workspaces(&self) -> Vec<usize> {
+        let Some(account) = self.selected_account() else {
+            return Vec::new();
+        };
+        let query = self.workspace_filter_query().unwrap_or_default();
+        (0..account.workspaces.len())
+            .filter(|idx| fuzzy_score(query, &account.workspaces[*idx]).is_some())
+            .collect()
+    }
+
+    fn selected_workspace_name(&self) -> Option<String> {
+        let account = self.selected_account()?;
+        account
+            .workspaces
+            .get(self.selected_workspace)
+            .filter(|workspace| {
+                fuzzy_score(self.workspace_filter_query().unwrap_or_default(), workspace).is_some()
+            })
+            .cloned()
+    }
+
+    fn current_operation_label(&self) -> String {
+        match &self.inflight {
+            Some(op) => match self.accounts.get(op.account_idx) {
+                Some(account) => {
+                    format!("running {} on {}", op.kind.label_for(account), account.name)
+                }
+                None => format!("running {} on ?", op.kind.label()),
+            },
+            None => self.status_line.clone(),
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.inflight.is_some()
+    }
+
+    fn has_running_tasks(&self) -> bool {
+        self.tasks.iter().any(|task| task.finished.is_none())
+    }
+
+    fn run_tuning(&self, account_idx: usize) -> RunTuning {
+        self.session_tuning
+            .get(&account_idx)
+            .cloned()
+            .unwrap_or_else(|| {
+                RunTuning::from_config(
+                    self.accounts
+                        .get(account_idx)
+                        .and_then(|account| account.run_options.as_ref()),
+                )
+            })
+    }
+
+    fn push_output(&mut self, line: impl Into<String>) {
+        self.push_sourced_output(None, line);
+    }
+
+    fn push_sourced_output(&mut self, source: Option<usize>, line: impl Into<String>) {
+        self.output_lines.push(OutputEntry {
+            source,
+            text: line.into(),
+            expanded: false,
+        });
+        if self.output_lines.len() > self.output_limit {
+            let to_drop = self.output_lines.len() - self.output_limit;
+            self.output_lines.drain(0..to_drop);
+        }
+    }
+
+    fn roll_output_rate(&mut self, force: bool) {
+        let rate = &mut self.output_rate;
+        let elapsed = rate.window_start.elapsed();
+        if !force && elapsed < Duration::from_secs(1) {
+            return;
+        }
+        let per_second = (rate.lines as f64 / elapsed.as_secs_f64().max(0.001)) as usize;
+        let suppressed = std::mem::take(&mut rate.suppressed);
+        let was_summarizing = rate.summarizing;
+        rate.summarizing = !force && rate.lines > CHATTY_LINES_PER_SECOND;
+        rate.lines = 0;
+        rate.window_start = Instant::now();
+
+        if suppressed > 0 {
+            self.push_output(format!(
+                "... {suppressed} lines suppressed ({per_second} lines/sec); errors, warnings and summaries are still shown"
+            ));
+        }
+        if was_summarizing && !self.output_rate.summarizing && !force {
+            self.push_output("Output rate back to normal; showing every line again.");
+        }
+    }
+
+    fn admit_output_line(&mut self, line: &str) -> bool {
+        self.roll_output_rate(false);
+        let rate = &mut self.output_rate;
+        rate.lines += 1;
+        if rate.lines > CHATTY_LINES_PER_SECOND {
+            rate.summarizing = true;
+        }
+        let important =
+            is_summary_line(line) || line.contains("Error:") || line.contains("Warning:");
+        if rate.summarizing && !important {
+            rate.suppressed += 1;
+            return false;
+        }
+        true
+    }
+
+    fn register_output_source(&mut self, operation: &str, account: &str, workspace: &str) -> usize {
+        let label = if workspace.is_empty() {
+            format!("{operation} {account}")
+        } else {
+            format!("{operation} {account}/{workspace}")
+        };
+        self.output_sources.push(label);
+        self.output_sources.len() - 1
+    }
+
+    fn toggle_output_fold(&mut self) {
+        let filter = self.output_filter;
+        let mut rows_below = self.output_scroll_from_bottom;
+        for entry in self
+            .output_lines
+            .iter_mut()
+            .rev()
+            .filter(|entry| filter.is_none() || entry.source == filter)
+        {
+            let rows = entry.rows();
+            if rows_below < rows && entry.is_foldable() {
+                entry.expanded = !entry.expanded;
+                return;
+            }
+            rows_below = rows_below.saturating_sub(rows);
+        }
+        self.push_output("No folded line in view.");
+    }
+
+    fn cycle_output_filter(&mut self) {
+        let mut present: Vec<usize> = Vec::new();
+        for source in self
+            .output_lines
+            .iter()
+            .rev()
+            .filter_map(|entry| entry.source)
+        {
+            if !present.contains(&source) {
+                present.push(source);
+            }
+        }
+
+        self.output_filter = match self.output_filter {
+            None => present.first().copied(),
+            Some(current) => present
+                .iter()
+                .position(|source| *source == current)
+                .and_then(|idx| present.get(idx + 1).copied()),
+        };
+        self.output_scroll_from_bottom = 0;
+    }
+
+    fn begin_operation_log(&mut self, kind: OperationKind, account: &str, workspace: &str) {
+        if !self.low_memory {
+            return;
+        }
+
+        let dir = lazytf_state_dir().join("logs");
+        let name = [account, workspace, kind.label()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| part.replace(|ch: char| !ch.is_ascii_alphanumeric(), "-"))
+            .collect::<Vec<_>>()
+            .join("_");
+        let path = dir.join(format!("{}_{name}.log", unix_now()));
+        let file = fs::create_dir_all(&dir).and_then(|()| fs::File::create(&path));
+        match file {
+            Ok(file) => {
+                self.push_output(format!("Streaming output to {}", path.display()));
+                self.operation_log = Some(OperationLog {
+                    path,
+                    writer: io::BufWriter::new(file),
+                    lines: 0,
+                    summary: Vec::new(),
+                });
+            }
+            Err(err) => self.push_output(format!(
+                "warning: failed to open operation log {}: {err}",
+                path.display()
+            )),
+        }
+    }
+
+    fn finish_operation_log(&mut self) {
+        let Some(mut log) = self.operation_log.take() else {
+            return;
+        };
+        if let Err(err) = io::Write::flush(&mut log.writer) {
+            self.push_output(format!("warning: failed to flush operation log: {err}"));
+        }
+        for line in log.summary {
+            self.push_output(format!("summary: {line}"));
+        }
+        self.push_output(format!(
+            "Full output ({} lines) written to {}",
+            log.lines,
+            log.path.display()
+        ));
+    }
+
+    fn set_status(&mut self, status: impl Into<String>) {
+        self.status_line = status.into();
+    }
+
+    fn clear_apply_confirmation(&mut self) {
+        self.pending_apply_confirmation = false;
+    }
+
+    fn stateful_changes(&self, changes: &[ResourceChange]) -> Vec<String> {
+        changes
+            .iter()
+            .filter(|change| matches!(change.action, ChangeAction::Delete | ChangeAction::Replace))
+            .filter(|change| {
+                let resource_type = resource_type_of_address(&change.address);
+                self.stateful_resource_types
+                    .iter()
+                    .any(|pattern| pattern.matches(&resource_type))
+            })
+            .map(|change| format!("  - {} {}", change.action.label(), change.address))
+            .collect()
+    }
+
+    fn pending_stateful_changes(&self) -> Vec<String> {
+        let workspace = self.selected_workspace_name().unwrap_or_default();
+        self.saved_plan_stateful_changes(self.selected_account, &workspace)
+    }
+
+    fn saved_plan_stateful_changes(&self, account_idx: usize, workspace: &str) -> Vec<String> {
+        self.accounts
+            .get(account_idx)
+            .and_then(|account| {
+                self.saved_plan_stateful
+                    .get(&cost_key(&account.name, workspace))
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+    }
+
+    fn close_help(&mut self) {
+        self.show_help = false;
+    }
+
+    fn request_cancel(&mut self) {
+        if let Some(op) = self.inflight.as_mut() {
+            match op.cancel_stage {
+                CancelStage::None => {
+                    let _ = op.cancel_tx.send(CancelSignal::Graceful);
+                    op.cancel_stage = CancelStage::GracefulRequested;
+                    self.push_output(
+                        "Graceful cancel requested. Sending SIGINT and waiting for Terraform to clean up state lock...",
+                    );
+                    self.push_output("Press `c` again to force kill if absolutely necessary.");
+                    self.set_status("cancelling (graceful)...");
+                }
+                CancelStage::GracefulRequested => {
+                    let _ = op.cancel_tx.send(CancelSignal::Force);
+                    op.cancel_stage = CancelStage::ForceRequested;
+                    self.push_output(
+                        "Force kill requested. This may leave Terraform state locked.",
+                    );
+                    self.set_status("cancelling (forced)...");
+                }
+                CancelStage::ForceRequested => {
+                    self.push_output(
+                        "Force kill already requested. Waiting for process to exit...",
+                    );
+                }
+            }
+        }
+    }
+
+    fn is_output_only(&self) -> bool {
+        self.layout_mode == LayoutMode::OutputOnly
+    }
+
+    fn enter_output_only(&mut self) {
+        if self.layout_mode == LayoutMode::Split {
+            self.previous_focus_panel = self.focused_panel;
+        }
+        self.layout_mode = LayoutMode::OutputOnly;
+        self.focused_panel = FocusPanel::Output;
+    }
+
+    fn exit_output_only(&mut self) {
+        if self.layout_mode == LayoutMode::Split {
+            return;
+        }
+
+        self.layout_mode = LayoutMode::Split;
+        self.focused_panel = self.previous_focus_panel;
+    }
+
+    fn toggle_output_only(&mut self) {
+        if self.is_output_only() {
+            self.exit_output_only();
+        } else {
+            self.enter_output_only();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum WorkerEvent {
+    OutputLine(String),
+    AccountAuthUpdate {
+        account_idx: usize,
+        status: AuthStatus,
+        message: String,
+    },
+    WorkspacesPrefetched {
+        account_idx: usize,
+        workspaces: Vec<String>,
+        current: Option<String>,
+    },
+    DriftChecked {
+        account_idx: usize,
+        workspace: String,
+        status: DriftStatus,
+    },
+    UpdateChecked {
+        latest: Option<String>,
+    },
+    WorkspacesLoaded {
+        account_idx: usize,
+        workspaces: Vec<String>,
+        current: Option<String>,
+    },
+    WorkspaceSelected {
+        account: String,
+        workspace: String,
+    },
+    WorkspaceMissing {
+        account: String,
+        workspace: String,
+        kind: OperationKind,
+        options: Box<RunOptions>,
+    },
+    CleanupCandidates {
+        account_idx: usize,
+        candidates: Vec<CleanupCandidate>,
+    },
+    CostUpdated {
+        account: String,
+        workspace: String,
+        estimate: CostEstimate,
+    },
+    TagCheckCompleted {
+        account: String,
+        workspace: String,
+        violations: usize,
+    },
+    EphemeralCreated {
+        account_idx: usize,
+        environment: EphemeralEnvironment,
+        planned: bool,
+    },
+    EphemeralRemoved {
+        account: String,
+        workspace: String,
+    },
+    BlameLoaded {
+        address: String,
+        blame: String,
+    },
+    FormatPending {
+        account_idx: usize,
+        files: Vec<String>,
+    },
+    WorkspaceDeleteChecked {
+        account_idx: usize,
+        workspace: String,
+        resources: std::result::Result<usize, String>,
+    },
+    LockProbeCompleted {
+        account_idx: usize,
+        workspace: String,
+        lock_id: Option<String>,
+    },
+    StateListed {
+        account_idx: usize,
+        workspace: String,
+        resources: Vec<String>,
+    },
+    PlanParsed {
+        changes: Vec<ResourceChange>,
+    },
+    BackendChecked {
+        account_idx: usize,
+        issues: usize,
+    },
+    BackendProbed {
+        account_idx: usize,
+        error: Option<String>,
+    },
+    LocksLoaded {
+        account_idx: usize,
+        table: String,
+        locks: Vec<LockItem>,
+    },
+    TerraformVersionDetected {
+        account_idx: usize,
+        version: (u64, u64, u64),
+    },
+    RequiredVersionChecked {
+        account_idx: usize,
+        mismatch: Option<String>,
+    },
+    ChangesToPropose {
+        paths: Vec<PathBuf>,
+        title: String,
+    },
+    CiRunsLoaded {
+        account_idx: usize,
+        runs: std::result::Result<Vec<CiRun>, String>,
+    },
+    CiJobsLoaded {
+        account_idx: usize,
+        run_id: u64,
+        jobs: std::result::Result<Vec<CiJob>, String>,
+    },
+    ImpactComputed {
+        account_idx: usize,
+        workspace: String,
+        address: String,
+        dependents: std::result::Result<Vec<(usize, String)>, String>,
+    },
+    StateShown {
+        account_idx: usize,
+        workspace: String,
+        address: String,
+        attributes: String,
+    },
+    Audited(AuditRecord),
+    TeamSynced(std::result::Result<Vec<TeamSnapshot>, String>),
+    TaskStarted {
+        id: u64,
+        label: String,
+    },
+    TaskFinished {
+        id: u64,
+        success: bool,
+    },
+    OperationFinished {
+        kind: OperationKind,
+        account_idx: usize,
+        success: bool,
+        cancelled: bool,
+        message: String,
+    },
+}
+
+#[derive(Debug)]
+struct RunOutcome {
+    success: bool,
+    cancelled: bool,
+    exit_code: Option<i32>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let cli_options = parse_cli_options()?;
+    if let CliCommand::Approve { request_path } = &cli_options.command {
+        return run_approve_command(request_path);
+    }
+    if let CliCommand::SsoGenerate {
+        start_url,
+        sso_region,
+        region,
+        composition_path,
+    } = &cli_options.command
+    {
+        // Not every setup has a config yet; `defaults.env` (proxies, ...) is used when it does.
+        let env = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| load_config(&cwd, cli_options.config_path.as_deref()).ok())
+            .map(|loaded| loaded.config.defaults.expanded_env())
+            .unwrap_or_default();
+        return run_sso_generate(
+            start_url,
+            sso_region,
+            region.as_deref(),
+            composition_path,
+            &env,
+        )
+        .await;
+    }
+
+    let cwd = std::env::current_dir().wrap_err("Unable to read current working directory")?;
+    if matches!(cli_options.command, CliCommand::Init) {
+        return run_init(&cwd, cli_options.config_path.as_deref());
+    }
+    if matches!(cli_options.command, CliCommand::ValidateConfig) {
+        return run_validate_config(&cwd, cli_options.config_path.as_deref());
+    }
+    let mut loaded_config = load_config(&cwd, cli_options.config_path.as_deref())?;
+    loaded_config.config.low_memory.enabled |= cli_options.low_memory;
+    let config_watch = loaded_config.watch_paths();
+    let mut app = AppState::from_config(loaded_config.config, &loaded_config.base_dir)?;
+    if let CliCommand::BenchPlan { account, workspace } = &cli_options.command {
+        let account = app
+            .accounts
+            .iter()
+            .find(|candidate| &candidate.name == account)
+            .ok_or_else(|| eyre!("Unknown account `{account}`"))?;
+        return run_bench_plan(account, workspace.as_deref()).await;
+    }
+    if let CliCommand::Report { file } = &cli_options.command {
+        return run_report(&app, file.as_deref()).await;
+    }
+    app.push_output(match loaded_config.sources.len() {
+        1 => format!("Loaded config from {}", loaded_config.path.display()),
+        count => format!(
+            "Loaded config from {} and {} included file(s)",
+            loaded_config.path.display(),
+            count - 1
+        ),
+    });
+    app.config_watch = config_watch;
+    app.config_mtime = latest_mtime(&app.config_watch);
+    app.config_path = Some(loaded_config.path);
+    app.leftover_children = scan_leftover_children();
+
+    let (worker_tx, mut worker_rx) = mpsc::unbounded_channel::<WorkerEvent>();
+    let (ctrlc_tx, mut ctrlc_rx) = mpsc::unbounded_channel::<()>();
+
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let _ = ctrlc_tx.send(());
+        }
+    });
+
+    let mouse_requested = !cli_options.no_mouse && app.mouse_enabled;
+    let (mut terminal, mouse_captured) = setup_terminal(mouse_requested)?;
+    app.mouse_enabled = mouse_captured;
+    if mouse_requested && !mouse_captured {
+        app.push_output(
+            "warning: mouse capture is not supported by this terminal; continuing keyboard-only",
+        );
+    }
+
+    for idx in 0..app.accounts.len() {
+        spawn_auth_check(idx, app.accounts[idx].clone(), worker_tx.clone());
+    }
+    for idx in 0..app.accounts.len() {
+        spawn_required_version_check(idx, &app.accounts[idx], &worker_tx);
+    }
+    if app.backend_probe.enabled {
+        for idx in 0..app.accounts.len() {
+            spawn_backend_probe(
+                idx,
+                &app.accounts[idx],
+                app.backend_probe.timeout_seconds,
+                &worker_tx,
+            );
+        }
+    }
+    if app.update_check && app.update_record.checked_at + UPDATE_CHECK_INTERVAL_SECS <= unix_now() {
+        spawn_update_check(&worker_tx);
+    }
+
+    let run_result = run_event_loop(
+        &mut terminal,
+        &mut app,
+        &worker_tx,
+        &mut worker_rx,
+        &mut ctrlc_rx,
+    );
+
+    restore_terminal(&mut terminal, mouse_captured && !app.mouse_released)?;
+    run_result
+}
+
+fn run_event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut AppState,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    worker_rx: &mut mpsc::UnboundedReceiver<WorkerEvent>,
+    ctrlc_rx: &mut mpsc::UnboundedReceiver<()>,
+) -> Result<()> {
+    loop {
+        while let Ok(()) = ctrlc_rx.try_recv() {
+            if app.is_busy() {
+                app.request_cancel();
+                app.quit_requested = true;
+            } else {
+                app.quit_requested = true;
+            }
+        }
+
+        let mut drained = 0;
+        while drained < MAX_EVENTS_PER_FRAME
+            && let Ok(event) = worker_rx.try_recv()
+        {
+            handle_worker_event(app, event);
+            drained += 1;
+        }
+        app.roll_output_rate(false);
+
+        if app.quit_requested && !app.is_busy() {
+            break;
+        }
+
+        check_environment_reminders(app, worker_tx);
+        check_scheduled_applies(app, worker_tx);
+        advance_deploy(app, worker_tx);
+        retry_after_workspace_new(app, worker_tx);
+        offer_change_request(app);
+        prefetch_hovered_account(app, worker_tx);
+        schedule_drift_checks(app, worker_tx);
+        sync_team_state(app, worker_tx);
+        watch_config_file(app, worker_tx);
+
+        terminal.draw(|frame| draw_ui(frame, app))?;
+
+        // Skip the input wait while a backlog of worker events is still queued.
+        let poll_timeout = if drained == MAX_EVENTS_PER_FRAME {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(100)
+        };
+        if event::poll(poll_timeout)? {
+            match event::read()? {
+                CEvent::Key(key) if key.kind == KeyEventKind::Press => {
+                    let was_released = app.mouse_released;
+                    handle_key_event(app, key, worker_tx);
+                    if app.mouse_released != was_released {
+                        set_mouse_capture(terminal, !app.mouse_released)?;
+                    }
+                    if let Some((path, line)) = app.pending_editor.take() {
+                        open_in_editor(terminal, app, &path, line)?;
+                        if app.ci.git_host().is_some() {
+                            let title = format!(
+                                "Edit {}",
+                                path.file_name().unwrap_or_default().to_string_lossy()
+                            );
+                            spawn_change_proposal(worker_tx, vec![path], title);
+                        }
+                    }
+                }
+                CEvent::Mouse(mouse) => {
+                    handle_mouse_event(app, mouse);
+                }
+                CEvent::Resize(_, _) => {}
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_worker_event(app: &mut AppState, event: WorkerEvent) {
+    match event {
+        WorkerEvent::UpdateChecked { latest } => {
+            app.update_record.checked_at = unix_now();
+            // Keep the last known release when GitHub is unreachable.
+            if latest.is_some() {
+                app.update_record.latest = latest;
+            }
+            if let Err(err) = save_update_check(&app.update_record) {
+                app.push_output(format!("warning: {err}"));
+            }
+        }
+        WorkerEvent::DriftChecked {
+            account_idx,
+            workspace,
+            status,
+        } => {
+            let Some(account) = app.accounts.get(account_idx) else {
+                return;
+            };
+            let key = cost_key(&account.name, &workspace);
+            let was_drifted = app
+                .drift
+                .get(&key)
+                .is_some_and(|record| record.status == DriftStatus::Drifted);
+            if status == DriftStatus::Drifted && !was_drifted {
+                app.push_output(format!(
+                    "Drift detected in `{}/{workspace}`. Select it and press `D` to inspect.",
+                    account.name
+                ));
+            }
+            app.drift.insert(
+                key,
+                DriftRecord {
+                    status,
+                    checked_at: unix_now(),
+                },
+            );
+            if let Err(err) = save_drift_cache(&app.drift) {
+                app.push_output(format!("warning: failed to save drift results: {err}"));
+            }
+        }
+        WorkerEvent::PlanParsed { changes } => {
+            // The JSON plan is authoritative; drop what was scraped from the text output.
+            if let Some(inflight) = app.inflight.as_mut()
+                && inflight.kind.indexes_resource_changes()
+            {
+                if let Some(summary) = app.plan_summary.as_mut()
+                    && summary.account_idx == inflight.account_idx
+                    && summary.workspace == inflight.workspace
+                {
+                    summary.noisy = changes
+                        .iter()
+                        .filter(|change| change.is_noise_only())
+                        .count();
+                }
+                inflight.resource_changes = changes;
+            }
+        }
+        WorkerEvent::OutputLine(line) => {
+            if let Some(inflight) = app.inflight.as_ref()
+                && matches!(
+                    inflight.kind,
+                    OperationKind::TerraformPlan
+                        | OperationKind::TerraformDestroyPlan
+                        | OperationKind::EphemeralCreate
+                )
+                && let Some((import, add, change, destroy)) = parse_plan_summary_line(&line)
+            {
+                app.plan_summary = Some(PlanSummary {
+                    account_idx: inflight.account_idx,
+                    workspace: inflight.workspace.clone(),
+                    destroy_preview: inflight.kind == OperationKind::TerraformDestroyPlan,
+                    import,
+                    add,
+                    change,
+                    destroy,
+                    noisy: 0,
+                });
+            }
+            if let Some(inflight) = app.inflight.as_mut()
+                && inflight.kind.indexes_resource_changes()
+                && let Some(change) = parse_resource_change_line(&line)
+                && !inflight.resource_changes.iter().any(|existing| {
+                    existing.address == change.address && existing.action == change.action
+                })
+            {
+                inflight.resource_changes.push(change);
+            }
+            if let Some(inflight) = app.inflight.as_mut()
+                && inflight.kind.indexes_resource_changes()
+                && let Some(attribute) = parse_forced_replacement_line(&line)
+                && let Some(change) = inflight.resource_changes.last_mut()
+                && change.action == ChangeAction::Replace
+                && !change.replace_reasons.contains(&attribute)
+            {
+                change.replace_reasons.push(attribute);
+            }
+            if let Some(inflight) = app.inflight.as_mut()
+                && let Some(name) = parse_missing_variable_line(&line)
+                && !inflight.missing_variables.contains(&name)
+            {
+                inflight.missing_variables.push(name);
+            }
+            if let Some(inflight) = app.inflight.as_mut() {
+                if line.contains("Error acquiring the state lock") {
+                    inflight.lock_error = true;
+                } else if inflight.lock_error
+                    && inflight.lock_id.is_none()
+                    && let Some(id) = line.trim().strip_prefix("ID:")
+                {
+                    inflight.lock_id = Some(id.trim().to_string());
+                }
+            }
+            if let Some(log) = app.operation_log.as_mut() {
+                let _ = io::Write::write_all(&mut log.writer, format!("{line}\n").as_bytes());
+                log.lines += 1;
+                if is_summary_line(&line) {
+                    log.summary.push(line.trim().to_string());
+                }
+            }
+            if app.admit_output_line(&line) {
+                let source = app.inflight.as_ref().map(|inflight| inflight.output_source);
+                app.push_sourced_output(source, line);
+            }
+        }
+        WorkerEvent::AccountAuthUpdate {
+            account_idx,
+            status,
+            message,
+        } => {
+            if let Some(account) = app.accounts.get_mut(account_idx) {
+                account.auth = status;
+            }
+            app.push_output(message);
+        }
+        WorkerEvent::WorkspacesPrefetched {
+            account_idx,
+            mut workspaces,
+            current,
+        } => {
+            if let Some(account) = app.accounts.get_mut(account_idx)
+                && account.workspaces.is_empty()
+            {
+                workspaces.sort();
+                account.workspaces = workspaces;
+                account.current_workspace = current;
+            }
+        }
+        WorkerEvent::WorkspaceMissing {
+            account,
+            workspace,
+            kind,
+            options,
+        } => {
+            let Some(account_idx) = app.accounts.iter().position(|a| a.name == account) else {
+                return;
+            };
+            if account_idx != app.selected_account {
+                return;
+            }
+            app.confirmation = Some(Confirmation {
+                title: "Workspace missing".to_string(),
+                lines: vec![
+                    format!("Workspace `{workspace}` doesn't exist in `{account}`."),
+                    format!(
+                        "Create it with `terraform workspace new` and re-run {}?",
+                        kind.label()
+                    ),
+                    String::new(),
+                    "Press `y` to create it, any other key leaves it.".to_string(),
+                ],
+                action: ConfirmAction::CreateMissingWorkspace {
+                    account_idx,
+                    workspace,
+                    retry: Box::new(WorkspaceRetry {
+                        account_idx,
+                        kind,
+                        options: *options,
+                    }),
+                },
+            });
+        }
+        WorkerEvent::WorkspaceSelected { account, workspace } => {
+            if let Some(account) = app.accounts.iter_mut().find(|a| a.name == account) {
+                account.current_workspace = Some(workspace);
+            }
+        }
+        WorkerEvent::WorkspacesLoaded {
+            account_idx,
+            mut workspaces,
+            current,
+        } => {
+            workspaces.sort();
+            let mut summary_message: Option<String> = None;
+
+            if let Some(account) = app.accounts.get_mut(account_idx) {
+                account.workspaces = workspaces;
+                account.current_workspace = current;
+                if account.workspaces.is_empty() {
+                    summary_message = Some(format!("No workspaces found for `{}`", account.name));
+                } else {
+                    summary_message = Some(format!(
+                        "Loaded {} workspaces for `{}`",
+                        account.workspaces.len(),
+                        account.name
+                    ));
+                }
+            }
+
+            if let Some(message) = summary_message {
+                app.push_output(message);
+            }
+
+            if account_idx == app.selected_account {
+                app.selected_workspace = 0;
+            }
+        }
+        WorkerEvent::CleanupCandidates {
+            account_idx,
+            candidates,
+        } => {
+            if candidates.is_empty() {
+                app.push_output("No empty or stale workspaces found.");
+            } else {
+                app.push_output(format!(
+                    "Found {} cleanup candidate(s). Confirm each one to destroy and delete it.",
+                    candidates.len()
+                ));
+                app.cleanup_session = Some(CleanupSession {
+                    account_idx,
+                    candidates,
+                    current: 0,
+                    removed: 0,
+                });
+            }
+        }
+        WorkerEvent::EphemeralCreated {
+            account_idx,
+            environment,
+            planned,
+        } => {
+            app.environments.retain(|env| {
+                env.account != environment.account || env.workspace != environment.workspace
+            });
+            app.environments.push(environment.clone());
+            if let Err(err) = save_environments(&app.environments) {
+                app.push_output(format!("warning: failed to save environments: {err}"));
+            }
+
+            if planned {
+                app.confirmation = Some(Confirmation {
+                    title: "Apply new environment".to_string(),
+                    lines: vec![
+                        format!(
+                            "Environment `{}` in `{}` planned successfully.",
+                            environment.workspace, environment.account
+                        ),
+                        format!(
+                            "Owner `{}`, expires {}",
+                            environment.owner,
+                            format_timestamp(environment.expires_at)
+                        ),
+                        String::new(),
+                        "Press `y` to apply it now, any other key to leave it planned only."
+                            .to_string(),
+                    ],
+                    action: ConfirmAction::ApplyEphemeral {
+                        account_idx,
+                        workspace: environment.workspace,
+                        var_file: environment.var_file,
+                    },
+                });
+            }
+        }
+        WorkerEvent::EphemeralRemoved { account, workspace } => {
+            app.environments
+                .retain(|env| env.account != account || env.workspace != workspace);
+            if let Err(err) = save_environments(&app.environments) {
+                app.push_output(format!("warning: failed to save environments: {err}"));
+            }
+        }
+        WorkerEvent::FormatPending { account_idx, files } => {
+            let mut lines = vec![format!(
+                "{} file(s) are not formatted (diff shown in the output panel):",
+                files.len()
+            )];
+            lines.extend(files.iter().take(8).map(|file| format!("  {file}")));
+            if files.len() > 8 {
+                lines.push(format!("  ... and {} more", files.len() - 8));
+            }
+            lines.push(String::new());
+            lines.push(
+                "Press `y` to rewrite them with terraform fmt, any other key cancels.".to_string(),
+            );
+            app.confirmation = Some(Confirmation {
+                title: "terraform fmt".to_string(),
+                lines,
+                action: ConfirmAction::FormatFiles { account_idx },
+            });
+        }
+        WorkerEvent::StateListed {
+            account_idx,
+            workspace,
+            resources,
+        } => {
+            if resources.is_empty() {
+                app.push_output(format!("State of `{workspace}` has no resources."));
+                return;
+            }
+            app.state_browser = Some(StateBrowser {
+                account_idx,
+                workspace,
+                resources,
+                selected: 0,
+                detail: None,
+                marked: BTreeSet::new(),
+            });
+        }
+        WorkerEvent::BackendChecked {
+            account_idx,
+            issues,
+        } => {
+            if let Some(account) = app.accounts.get_mut(account_idx) {
+                account.backend_issues = Some(issues);
+            }
+        }
+        WorkerEvent::BackendProbed { account_idx, error } => {
+            let Some(account) = app.accounts.get_mut(account_idx) else {
+                return;
+            };
+            account.backend_unreachable = error.clone();
+            if let Some(error) = error {
+                let message = format!(
+                    "warning: the backend of `{}` is unreachable ({error}); connect to the VPN before running init/plan.",
+                    account.name
+                );
+                app.push_output(message);
+            }
+        }
+        WorkerEvent::LocksLoaded {
+            account_idx,
+            table,
+            locks,
+        } => {
+            app.lock_table = Some(LockTableView {
+                account_idx,
+                table,
+                locks,
+                selected: 0,
+            });
+        }
+        WorkerEvent::TerraformVersionDetected {
+            account_idx,
+            version,
+        } => {
+            if let Some(account) = app.accounts.get_mut(account_idx) {
+                account.terraform_version = Some(version);
+            }
+        }
+        WorkerEvent::ChangesToPropose { paths, title } => {
+            if app.ci.git_host().is_none() {
+                return;
+            }
+            if app.confirmation.is_some() || app.prompt.is_some() {
+                app.push_output(format!(
+                    "\"{title}\" changed files; the change request is offered once the open dialog closes."
+                ));
+            }
+            app.proposed_changes.push((paths, title));
+        }
+        WorkerEvent::RequiredVersionChecked {
+            account_idx,
+            mismatch,
+        } => {
+            let Some(account) = app.accounts.get_mut(account_idx) else {
+                return;
+            };
+            account.version_mismatch = mismatch.clone();
+            if let Some(mismatch) = mismatch {
+                let message = format!(
+                    "warning: account `{}`: {mismatch}; init will fail until a matching version is used (see `terraform_version`).",
+                    account.name
+                );
+                app.push_output(message);
+            }
+        }
+        WorkerEvent::Audited(record) => {
+            match record.event {
+                AuditEvent::Started => app.team.running = Some(record),
+                AuditEvent::Finished => {
+                    app.team.running = None;
+                    app.team.recent.push(record);
+                    let excess = app.team.recent.len().saturating_sub(TEAM_RECENT_LIMIT);
+                    app.team.recent.drain(..excess);
+                }
+            }
+            // Let teammates see starts and finishes without waiting for the interval.
+            app.team.next_sync = 0;
+        }
+        WorkerEvent::TeamSynced(result) => {
+            app.team.syncing = false;
+            match result {
+                Ok(snapshots) => {
+                    app.team.last_error = None;
+                    apply_team_snapshots(app, snapshots);
+                }
+                Err(err) => {
+                    if app.team.last_error.as_ref() != Some(&err) {
+                        app.push_output(format!("warning: team sync failed: {err}"));
+                    }
+                    app.team.last_error = Some(err);
+                }
+            }
+        }
+        WorkerEvent::CiRunsLoaded { account_idx, runs } => {
+            let Some(view) = app
+                .ci_runs
+                .as_mut()
+                .filter(|view| view.account_idx == account_idx)
+            else {
+                return;
+            };
+            view.loading = false;
+            match runs {
+                Ok(runs) => {
+                    view.runs = runs;
+                    view.selected = 0;
+                }
+                Err(err) => app.push_output(format!("Could not load CI runs: {err}")),
+            }
+        }
+        WorkerEvent::CiJobsLoaded {
+            account_idx,
+            run_id,
+            jobs,
+        } => {
+            let Some(run) = app
+                .ci_runs
+                .as_mut()
+                .filter(|view| view.account_idx == account_idx)
+                .and_then(|view| view.runs.iter_mut().find(|run| run.id == run_id))
+            else {
+                return;
+            };
+            match jobs {
+                Ok(jobs) => {
+                    run.jobs = Some(jobs);
+                    run.expanded = true;
+                }
+                Err(err) => app.push_output(format!("Could not load CI jobs: {err}")),
+            }
+        }
+        WorkerEvent::ImpactComputed {
+            account_idx,
+            workspace,
+            address,
+            dependents,
+        } => {
+            let Some(browser) = app.state_browser.as_mut().filter(|browser| {
+                browser.account_idx == account_idx && browser.workspace == workspace
+            }) else {
+                return;
+            };
+            let dependents = match dependents {
+                Ok(dependents) => dependents,
+                Err(err) => {
+                    app.push_output(format!("terraform graph failed: {err}"));
+                    return;
+                }
+            };
+            let mut lines = vec![Line::from(Span::styled(
+                if dependents.is_empty() {
+                    "Nothing in the configuration depends on it.".to_string()
+                } else {
+                    format!(
+                        "{} resource(s)/output(s) depend on it, {} directly:",
+                        dependents.len(),
+                        dependents.iter().filter(|(depth, _)| *depth == 1).count()
+                    )
+                },
+                Style::default().add_modifier(Modifier::BOLD),
+            ))];
+            lines.push(Line::from(""));
+            lines.extend(dependents.into_iter().map(|(depth, node)| {
+                let color = if depth == 1 {
+                    Color::Yellow
+                } else {
+                    Color::DarkGray
+                };
+                Line::from(vec![
+                    Span::raw("  ".repeat(depth)),
+                    Span::styled(node, Style::default().fg(color)),
+                ])
+            }));
+            browser.detail = Some(StateDetail {
+                address: format!("Impact of {address}"),
+                lines,
+                scroll: 0,
+            });
+        }
+        WorkerEvent::StateShown {
+            account_idx,
+            workspace,
+            address,
+            attributes,
+        } => {
+            if let Some(browser) = app.state_browser.as_mut()
+                && browser.account_idx == account_idx
+                && browser.workspace == workspace
+            {
+                let mut in_block_comment = false;
+                browser.detail = Some(StateDetail {
+                    address,
+                    lines: attributes
+                        .lines()
+                        .map(|line| highlight_hcl_line(line, &mut in_block_comment))
+                        .collect(),
+                    scroll: 0,
+                });
+            }
+        }
+        WorkerEvent::TaskStarted { id, label } => {
+            app.tasks.push(BackgroundTask {
+                id,
+                label,
+                started: Instant::now(),
+                finished: None,
+            });
+            let finished = app
+                .tasks
+                .iter()
+                .filter(|task| task.finished.is_some())
+                .count();
+            if finished > ACTIVITY_HISTORY_LIMIT
+                && let Some(oldest) = app.tasks.iter().position(|task| task.finished.is_some())
+            {
+                app.tasks.remove(oldest);
+            }
+        }
+        WorkerEvent::TaskFinished { id, success } => {
+            if let Some(task) = app.tasks.iter_mut().find(|task| task.id == id) {
+                task.finished = Some((task.started.elapsed(), success));
+            }
+        }
+        WorkerEvent::BlameLoaded { address, blame } => {
+            if let Some(view) = app.plan_changes.as_mut() {
+                view.blame.insert(address, blame);
+            }
+        }
+        WorkerEvent::WorkspaceDeleteChecked {
+            account_idx,
+            workspace,
+            resources,
+        } => {
+            if account_idx != app.selected_account {
+                app.push_output("Account selection changed; workspace delete aborted.");
+                return;
+            }
+            let account_name = app.accounts[account_idx].name.clone();
+            match resources {
+                Err(err) => app.push_output(format!(
+                    "Cannot delete `{account_name}/{workspace}`: terraform state list failed: {err}"
+                )),
+                Ok(0) => {
+                    app.confirmation = Some(Confirmation {
+                        title: "Delete workspace".to_string(),
+                        lines: vec![
+                            format!("Delete workspace `{workspace}` from `{account_name}`?"),
+                            "Its state is empty, so no resources are abandoned.".to_string(),
+                            String::new(),
+                            "Press `y` to delete it, any other key cancels.".to_string(),
+                        ],
+                        action: ConfirmAction::DeleteWorkspace {
+                            account_idx,
+                            workspace,
+                        },
+                    });
+                }
+                Ok(count) => {
+                    app.prompt = Some(TextPrompt {
+                        title: format!(
+                            "Delete {account_name}/{workspace}: state still tracks {count} resource(s)"
+                        ),
+                        hint: format!(
+                            "The resources keep running but become unmanaged. Type `{workspace}` to delete anyway"
+                        ),
+                        purpose: PromptPurpose::WorkspaceDeleteName {
+                            account_idx,
+                            workspace,
+                        },
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+            }
+        }
+        WorkerEvent::LockProbeCompleted {
+            account_idx,
+            workspace,
+            lock_id,
+        } => match lock_id {
+            Some(lock_id) => {
+                app.push_output(format!(
+                    "State lock for `{workspace}` may be stuck (lock ID {lock_id}). Press `U` to force-unlock."
+                ));
+                app.stuck_lock = Some(StuckLock {
+                    account_idx,
+                    workspace,
+                    lock_id,
+                });
+            }
+            None => {
+                app.push_output(format!("State lock for `{workspace}` released."));
+                if app.stuck_lock.as_ref().is_some_and(|lock| {
+                    lock.account_idx == account_idx && lock.workspace == workspace
+                }) {
+                    app.stuck_lock = None;
+                }
+            }
+        },
+        WorkerEvent::TagCheckCompleted {
+            account,
+            workspace,
+            violations,
+        } => {
+            if let Some(account) = app.accounts.iter_mut().find(|a| a.name == account) {
+                account.tag_violations.insert(workspace, violations);
+            }
+        }
+        WorkerEvent::CostUpdated {
+            account,
+            workspace,
+            estimate,
+        } => {
+            app.push_output(format!(
+                "Current infrastructure cost for `{account}`/`{workspace}`: {}",
+                format_monthly_cost(&estimate)
+            ));
+            app.costs.insert(cost_key(&account, &workspace), estimate);
+            if let Err(err) = save_cost_cache(&app.costs) {
+                app.push_output(format!("warning: failed to save cost cache: {err}"));
+            }
+        }
+        WorkerEvent::OperationFinished {
+            kind,
+            account_idx,
+            success,
+            cancelled,
+            message,
+        } => {
+            app.roll_output_rate(true);
+            app.finish_operation_log();
+            app.push_output(message.clone());
+            app.clear_apply_confirmation();
+
+            if let Some(account) = app.accounts.get(account_idx)
+                && let Some(activity) = app.account_activity.get_mut(&account.name)
+                && *activity == AccountActivity::Running
+            {
+                *activity = if cancelled {
+                    AccountActivity::Cancelled
+                } else if success {
+                    AccountActivity::Succeeded
+                } else {
+                    AccountActivity::Failed
+                };
+            }
+
+            if !success
+                && !cancelled
+                && account_idx == app.selected_account
+                && let Some(inflight) = app.inflight.as_mut()
+                && inflight.kind == kind
+                && inflight.account_idx == account_idx
+                && !inflight.missing_variables.is_empty()
+                && let Some(options) = inflight.options.take()
+            {
+                let mut names = std::mem::take(&mut inflight.missing_variables);
+                names.reverse();
+                prompt_missing_variable(app, account_idx, kind, options, names);
+            }
+
+            if kind == OperationKind::ForceUnlock && success {
+                app.stuck_lock = None;
+            }
+
+            // A run that reached the backend proves the startup probe out of date.
+            if success
+                && (kind == OperationKind::TerraformInit || kind.takes_state_lock())
+                && let Some(account) = app.accounts.get_mut(account_idx)
+            {
+                account.backend_unreachable = None;
+            }
+
+            if kind == OperationKind::WorkspaceNew && !success {
+                app.workspace_retry = None;
+            }
+
+            if kind == OperationKind::WorkspaceNew
+                && success
+                && account_idx == app.selected_account
+                && let Some(inflight) = app.inflight.as_ref()
+                && let Some(position) = app.accounts.get(account_idx).and_then(|account| {
+                    account
+                        .workspaces
+                        .iter()
+                        .position(|workspace| *workspace == inflight.workspace)
+                })
+            {
+                app.selected_workspace = position;
+            }
+
+            // An applied or failed plan no longer describes what is pending.
+            if (kind == OperationKind::TerraformApply && success)
+                || (matches!(
+                    kind,
+                    OperationKind::TerraformPlan | OperationKind::TerraformDestroyPlan
+                ) && !success)
+            {
+                app.plan_summary = None;
+            }
+
+            if let Some(flow) = app.deploy.as_mut()
+                && flow.account_idx == account_idx
+            {
+                match (flow.step, kind) {
+                    (DeployStep::Init, OperationKind::TerraformInit) if success => {
+                        flow.step = DeployStep::Plan;
+                        app.push_output("Deploy: init done, planning...");
+                    }
+                    (DeployStep::PlanRunning, OperationKind::TerraformPlan) if success => {
+                        app.deploy = None;
+                        app.pending_apply_confirmation = true;
+                        app.set_status("deploy: review the plan, press y to apply");
+                        app.push_output(
+                            "Deploy: review the plan above, then press `y` to apply it (`T` schedules it, any nav key stops).",
+                        );
+                    }
+                    (DeployStep::Init, OperationKind::TerraformInit)
+                    | (DeployStep::PlanRunning, OperationKind::TerraformPlan) => {
+                        app.deploy = None;
+                        app.push_output(format!("Deploy stopped: {} failed.", kind.label()));
+                    }
+                    _ => {}
+                }
+            }
+
+            if kind == OperationKind::TerraformApply
+                && let Some(schedule) = app.scheduled_running.take()
+            {
+                let status = if success {
+                    "succeeded"
+                } else if cancelled {
+                    "cancelled"
+                } else {
+                    "failed"
+                };
+                app.schedule_outcomes.push((schedule, status, message));
+            }
+
+            if !success
+                && !cancelled
+                && let Some(inflight) = app.inflight.as_ref()
+                && inflight.kind == kind
+                && inflight.account_idx == account_idx
+                && !inflight.workspace.is_empty()
+                && let Some(lock_id) = inflight.lock_id.clone()
+            {
+                let lock = StuckLock {
+                    account_idx,
+                    workspace: inflight.workspace.clone(),
+                    lock_id,
+                };
+                app.push_output(format!(
+                    "{} failed because the state of `{}` is locked (lock ID {}).",
+                    kind.label(),
+                    lock.workspace,
+                    lock.lock_id
+                ));
+                app.stuck_lock = Some(lock.clone());
+                confirm_force_unlock(app, lock);
+            }
+
+            if kind == OperationKind::WorkspaceCleanup
+                && let Some(session) = app.cleanup_session.as_mut()
+            {
+                if success {
+                    session.removed += 1;
+                    session.current += 1;
+                    if session.current >= session.candidates.len() {
+                        let removed = session.removed;
+                        app.cleanup_session = None;
+                        app.push_output(format!(
+                            "Workspace cleanup finished: {removed} workspace(s) removed."
+                        ));
+                    }
+                } else {
+                    app.cleanup_session = None;
+                    app.push_output("Workspace cleanup stopped after a failed step.");
+                }
+            }
+
+            if kind == OperationKind::TerraformPlan
+                && let Some(inflight) = app
+                    .inflight
+                    .as_ref()
+                    .filter(|inflight| inflight.kind == kind && inflight.account_idx == account_idx)
+                && let Some(account) = app.accounts.get(account_idx)
+            {
+                // A failed plan removes the saved plan file, so nothing is left to apply.
+                let key = cost_key(&account.name, &inflight.workspace);
+                let stateful = if success {
+                    app.stateful_changes(&inflight.resource_changes)
+                } else {
+                    Vec::new()
+                };
+                if stateful.is_empty() {
+                    app.saved_plan_stateful.remove(&key);
+                } else {
+                    app.saved_plan_stateful.insert(key, stateful);
+                }
+            }
+
+            if app.inflight.as_ref().is_some_and(|inflight| {
+                inflight.kind == kind && inflight.account_idx == account_idx
+            }) && let Some(inflight) = app.inflight.take()
+                && !inflight.resource_changes.is_empty()
+            {
+                let outcome = if cancelled {
+                    "cancelled"
+                } else if success {
+                    "succeeded"
+                } else {
+                    "failed"
+                };
+                let account_name = app
+                    .accounts
+                    .get(account_idx)
+                    .map(|account| account.name.clone())
+                    .unwrap_or_default();
+                if let Err(err) = append_resource_index(&account_name, &inflight, outcome) {
+                    app.push_output(format!("warning: failed to update resource index: {err}"));
+                }
+                if matches!(
+                    kind,
+                    OperationKind::TerraformPlan | OperationKind::TerraformDestroyPlan
+                ) && success
+                {
+                    let noisy: Vec<&str> = inflight
+                        .resource_changes
+                        .iter()
+                        .filter(|change| change.is_noise_only())
+                        .map(|change| change.address.as_str())
+                        .collect();
+                    if !noisy.is_empty() {
+                        app.push_output(format!(
+                            "{} update(s) only touch noisy_attributes and are not counted: {}",
+                            noisy.len(),
+                            noisy.join(", ")
+                        ));
+                    }
+                    let replacements: Vec<String> = inflight
+                        .resource_changes
+                        .iter()
+                        .filter(|change| change.action == ChangeAction::Replace)
+                        .map(|change| {
+                            format!(
+                                "  ~ {}: {}",
+                                change.address,
+                                replace_reasons_label(&change.replace_reasons)
+                            )
+                        })
+                        .collect();
+                    if !replacements.is_empty() {
+                        app.push_output("Replacements and why:");
+                        for line in replacements {
+                            app.push_output(line);
+                        }
+                    }
+                    let stateful = app.stateful_changes(&inflight.resource_changes);
+                    if !stateful.is_empty() {
+                        app.push_output(format!(
+                            "DANGER: this plan deletes or replaces {} stateful resource(s):",
+                            stateful.len()
+                        ));
+                        for line in stateful {
+                            app.push_output(line);
+                        }
+                        if kind == OperationKind::TerraformPlan {
+                            app.push_output(
+                                "Applying it will ask you to type the workspace name to acknowledge.",
+                            );
+                        }
+                    }
+                    let view = PlanChangesView {
+                        account_idx,
+                        workspace: inflight.workspace,
+                        changes: inflight.resource_changes,
+                        selected: 0,
+                        blame: BTreeMap::new(),
+                        blame_requested: false,
+                    };
+                    if kind == OperationKind::TerraformDestroyPlan {
+                        app.destroy_preview = Some(view);
+                        app.destroy_preview_open = true;
+                    } else {
+                        app.plan_changes = Some(view);
+                        app.push_output("Press `C` to browse the planned resource changes.");
+                    }
+                }
+            }
+
+            if cancelled {
+                app.set_status("cancelled");
+            } else if success {
+                app.set_status("idle");
+            } else {
+                app.set_status("failed");
+            }
+        }
+    }
+}
+
+fn handle_key_event(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    if app.mouse_released {
+        app.mouse_released = false;
+        app.push_output("Mouse capture restored.");
+        return;
+    }
+
+    if app.prompt.is_some()
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        handle_prompt_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.run_options_popup.is_some()
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        handle_run_options_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.var_file_picker.is_some()
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        handle_var_file_picker_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.account_filter.is_some()
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        handle_account_filter_key(app, key);
+        return;
+    }
+
+    if app
+        .workspace_filter
+        .as_ref()
+        .is_some_and(|filter| filter.editing)
+        && !(key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+    {
+        handle_workspace_filter_key(app, key);
+        return;
+    }
+
+    if !app.leftover_children.is_empty() && key.code != KeyCode::Char('?') {
+        handle_leftover_children_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.confirmation.is_some() && key.code != KeyCode::Char('?') {
+        handle_confirmation_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.notes.is_some() && key.code != KeyCode::Char('?') {
+        handle_notes_key(app, key);
+        return;
+    }
+
+    if app.file_browser.is_some() && key.code != KeyCode::Char('?') {
+        handle_file_browser_key(app, key);
+        return;
+    }
+
+    if (app.plan_changes_open || app.destroy_preview_open) && key.code != KeyCode::Char('?') {
+        handle_plan_changes_key(app, key);
+        return;
+    }
+
+    if app.plan_review.is_some() && key.code != KeyCode::Char('?') {
+        handle_plan_review_key(app, key);
+        return;
+    }
+
+    if app.lock_table.is_some() && key.code != KeyCode::Char('?') {
+        handle_lock_table_key(app, key);
+        return;
+    }
+
+    if app.ci_runs.is_some() && key.code != KeyCode::Char('?') {
+        handle_ci_runs_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.state_browser.is_some() && key.code != KeyCode::Char('?') {
+        handle_state_browser_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.history_search.is_some() && key.code != KeyCode::Char('?') {
+        handle_history_search_key(app, key);
+        return;
+    }
+
+    if app.cleanup_session.is_some() && !app.is_busy() && key.code != KeyCode::Char('?') {
+        handle_cleanup_key(app, key, worker_tx);
+        return;
+    }
+
+    if app.state_metrics_view.is_some() && key.code != KeyCode::Char('?') {
+        if matches!(
+            key.code,
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('M')
+        ) {
+            app.state_metrics_view = None;
+        }
+        return;
+    }
+
+    if key.code == KeyCode::Char('?') {
+        app.toggle_help();
+        app.clear_apply_confirmation();
+        return;
+    }
+
+    if app.show_help {
+        if key.code == KeyCode::Esc {
+            app.close_help();
+            return;
+        }
+
+        if key.code != KeyCode::Char('q') && key.code != KeyCode::Char('c') {
+            return;
+        }
+    }
+
+    if key.code == KeyCode::Esc {
+        if app.focused_panel == FocusPanel::Workspaces && app.workspace_filter_query().is_some() {
+            app.workspace_filter = None;
+        } else {
+            app.exit_output_only();
+        }
+        app.clear_apply_confirmation();
+        return;
+    }
+
+    if key.code == KeyCode::Char('q') {
+        if app.is_busy() {
+            app.request_cancel();
+            app.quit_requested = true;
+        } else {
+            app.quit_requested = true;
+        }
+        return;
+    }
+
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        if app.is_busy() {
+            app.request_cancel();
+            app.quit_requested = true;
+        } else {
+            app.quit_requested = true;
+        }
+        return;
+    }
+
+    if key.code == KeyCode::Char('c') {
+        app.request_cancel();
+        return;
+    }
+
+    match key.code {
+        KeyCode::Char('z') => {
+            app.toggle_output_only();
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Tab => {
+            if !app.is_output_only() {
+                app.focused_panel = app.focused_panel.next();
+            }
+        }
+        KeyCode::BackTab => {
+            if !app.is_output_only() {
+                app.focused_panel = app.focused_panel.previous();
+            }
+        }
+        KeyCode::Left | KeyCode::Char('h') => {
+            if !app.is_output_only() {
+                app.focused_panel = app.focused_panel.previous();
+            }
+        }
+        KeyCode::Right | KeyCode::Char('l') => {
+            if !app.is_output_only() {
+                app.focused_panel = app.focused_panel.next();
+            }
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            move_selection_up(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            move_selection_down(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Enter if app.focused_panel == FocusPanel::Output => {
+            app.toggle_output_fold();
+        }
+        KeyCode::Enter | KeyCode::Char(' ') if app.focused_panel == FocusPanel::Accounts => {
+            toggle_account_group(app);
+        }
+        KeyCode::Char('/') if app.focused_panel == FocusPanel::Accounts => {
+            app.account_filter = Some(String::new());
+            app.account_filter_origin = Some((
+                app.selected_account,
+                app.selected_workspace,
+                app.selected_group.clone(),
+            ));
+        }
+        KeyCode::Char('/') if app.focused_panel == FocusPanel::Workspaces => {
+            let query = app.workspace_filter_query().unwrap_or_default().to_string();
+            app.workspace_filter = Some(WorkspaceFilter {
+                account_idx: app.selected_account,
+                query,
+                editing: true,
+            });
+        }
+        KeyCode::PageUp => {
+            if app.focused_panel == FocusPanel::Output {
+                app.output_scroll_from_bottom = app
+                    .output_scroll_from_bottom
+                    .saturating_add(app.scroll.page_lines);
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::PageDown => {
+            if app.focused_panel == FocusPanel::Output {
+                app.output_scroll_from_bottom = app
+                    .output_scroll_from_bottom
+                    .saturating_sub(app.scroll.page_lines);
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            reload_config(app, worker_tx);
+        }
+        // A full page, keeping one line of the previous page for context.
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            if app.focused_panel == FocusPanel::Output {
+                let page = app.output_page_rows.get().saturating_sub(1).max(1);
+                app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_add(page);
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            if app.focused_panel == FocusPanel::Output {
+                let page = app.output_page_rows.get().saturating_sub(1).max(1);
+                app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_sub(page);
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            if app.focused_panel == FocusPanel::Output {
+                app.output_scroll_from_bottom = usize::MAX;
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            if app.focused_panel == FocusPanel::Output {
+                app.output_scroll_from_bottom = 0;
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('a') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_auth_login(app, worker_tx.clone());
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('s') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_auth_check_for_selected(app, worker_tx.clone());
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('r') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_workspace_refresh(app, worker_tx.clone());
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('Z') => {
+            let Some(latest) = app.update_record.notice() else {
+                return;
+            };
+            app.update_record.dismissed = Some(latest.clone());
+            if let Err(err) = save_update_check(&app.update_record) {
+                app.push_output(format!("warning: {err}"));
+                return;
+            }
+            app.push_output(format!(
+                "Dismissed the lazytf {latest} update notice; newer releases will be announced again."
+            ));
+        }
+        KeyCode::Char('E') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            app.clear_apply_confirmation();
+            let Some(account) = app.selected_account() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            if !account.can_mutate() {
+                let message = role_refusal(account);
+                app.push_output(message);
+                return;
+            }
+            let Some(workspace) = app.selected_workspace_name() else {
+                app.push_output("No workspace selected. Press `r` to load workspaces first.");
+                return;
+            };
+            app.push_output(format!(
+                "Deploy `{}/{workspace}`: init, plan, then review before apply.",
+                account.name
+            ));
+            app.deploy = Some(DeployFlow {
+                account_idx: app.selected_account,
+                workspace,
+                step: DeployStep::Init,
+            });
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformInit,
+                RunOptions::default(),
+            );
+            if !app.is_busy() {
+                app.deploy = None;
+            }
+        }
+        KeyCode::Char('i') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformInit,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('f') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformFmtCheck,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('v') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformValidate,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('p') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformPlan,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('I') => {
+            match (app.selected_account(), app.selected_workspace_name()) {
+                (Some(account), Some(workspace)) => {
+                    app.prompt = Some(TextPrompt {
+                        purpose: PromptPurpose::ImportAddress {
+                            account_idx: app.selected_account,
+                        },
+                        title: format!("Import into {}/{workspace}", account.name),
+                        hint: "Resource address (e.g. aws_s3_bucket.logs)".to_string(),
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                (None, _) => app.push_output("No account selected."),
+                (Some(_), None) => {
+                    app.push_output("No workspace selected. Press `r` to load workspaces first.");
+                }
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('x') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformDestroyPlan,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('D') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformRefreshPlan,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('O') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::OrphanScan,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('$') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::CostBreakdown,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('n') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            let Some(account) = app.selected_account() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            app.prompt = Some(TextPrompt {
+                title: format!("New workspace in {}", account.name),
+                purpose: PromptPurpose::WorkspaceName {
+                    account_idx: app.selected_account,
+                },
+                hint: "Workspace name (terraform workspace new)".to_string(),
+                input: String::new(),
+                error: None,
+            });
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('d') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            let Some(account) = app.selected_account().cloned() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            let Some(workspace) = app.selected_workspace_name() else {
+                app.push_output("No workspace selected. Press `r` to load workspaces first.");
+                return;
+            };
+            if workspace == "default" {
+                app.push_output("The `default` workspace cannot be deleted.");
+                return;
+            }
+            if !account.can_mutate() {
+                app.push_output(role_refusal(&account));
+                return;
+            }
+            if account.auth != AuthStatus::Authenticated {
+                app.push_output("Selected account is not authenticated. Press `a` first.");
+                return;
+            }
+            app.push_output(format!(
+                "Checking the state of `{}/{workspace}` before deleting it...",
+                account.name
+            ));
+            let account_idx = app.selected_account;
+            let event_tx = worker_tx.clone();
+            let label = format!("state check before deleting {}/{workspace}", account.name);
+            spawn_tracked(worker_tx, label, async move {
+                let mut command = terraform_command(&account, &["state", "list"]);
+                command.env("TF_WORKSPACE", &workspace).stdin(Stdio::null());
+                let resources = match command.output().await {
+                    Ok(output) if output.status.success() => {
+                        Ok(String::from_utf8_lossy(&output.stdout)
+                            .lines()
+                            .filter(|line| !line.trim().is_empty())
+                            .count())
+                    }
+                    Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                let ok = resources.is_ok();
+                let _ = event_tx.send(WorkerEvent::WorkspaceDeleteChecked {
+                    account_idx,
+                    workspace,
+                    resources,
+                });
+                ok
+            });
+        }
+        KeyCode::Char('Q') => {
+            let account_idx = app.selected_account;
+            open_ci_runs(app, account_idx, worker_tx);
+        }
+        KeyCode::Char('t') => open_var_file_picker(app),
+        KeyCode::Char('J') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            app.prompt = Some(TextPrompt {
+                title: "Attach to an external log".to_string(),
+                purpose: PromptPurpose::AttachLog,
+                hint: "File to follow, or !command (e.g. !gh run view 123 --log)".to_string(),
+                input: String::new(),
+                error: None,
+            });
+        }
+        KeyCode::Char('K') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            let Some(account) = app.selected_account() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            let names = match desired_workspaces(account) {
+                Ok(names) => names,
+                Err(err) => {
+                    app.push_output(format!("Cannot bootstrap workspaces: {err}"));
+                    return;
+                }
+            };
+            let missing: Vec<&String> = names
+                .iter()
+                .filter(|name| !account.workspaces.contains(name))
+                .collect();
+            if missing.is_empty() {
+                let message = format!(
+                    "All {} desired workspace(s) already exist in `{}`.",
+                    names.len(),
+                    account.name
+                );
+                app.push_output(message);
+                return;
+            }
+            let mut lines = vec![format!(
+                "Create {} workspace(s) in `{}`:",
+                missing.len(),
+                account.name
+            )];
+            lines.extend(missing.iter().map(|name| format!("  {name}")));
+            if account.workspaces.is_empty() {
+                lines.push("(workspaces not loaded yet; existing ones are skipped)".to_string());
+            }
+            lines.push(String::new());
+            lines.push("Press `y` to create them, any other key cancels.".to_string());
+            app.confirmation = Some(Confirmation {
+                title: "Bootstrap workspaces".to_string(),
+                lines,
+                action: ConfirmAction::BootstrapWorkspaces {
+                    account_idx: app.selected_account,
+                    names,
+                },
+            });
+        }
+        KeyCode::Char('N') => {
+            match app.selected_account() {
+                Some(account) if account.ephemeral.is_some() => {
+                    app.prompt = Some(TextPrompt {
+                        purpose: PromptPurpose::EphemeralName {
+                            account_idx: app.selected_account,
+                        },
+                        title: format!("New ephemeral environment in {}", account.name),
+                        hint: "Environment name (becomes the workspace name)".to_string(),
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                Some(account) => {
+                    let message =
+                        format!("No `ephemeral` template configured for `{}`.", account.name);
+                    app.push_output(message);
+                }
+                None => app.push_output("No account selected."),
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('m') => {
+            if app.mouse_enabled {
+                app.mouse_released = true;
+                app.push_output(
+                    "Mouse released: select and copy text with your terminal, then press any key to resume.",
+                );
+            } else {
+                app.push_output("Mouse capture is not active.");
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('o') => {
+            app.cycle_output_filter();
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('Y') => {
+            export_account_context(app);
+        }
+        KeyCode::Char('u') => {
+            toggle_maintenance_lock(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('U') => {
+            match app.stuck_lock.clone() {
+                Some(lock)
+                    if lock.account_idx == app.selected_account
+                        && app.selected_workspace_name().as_deref()
+                            == Some(lock.workspace.as_str()) =>
+                {
+                    confirm_force_unlock(app, lock);
+                }
+                Some(lock) => {
+                    let account_name = app
+                        .accounts
+                        .get(lock.account_idx)
+                        .map(|account| account.name.clone())
+                        .unwrap_or_default();
+                    app.push_output(format!(
+                        "The stuck lock belongs to `{account_name}/{}`; select that workspace to force-unlock it.",
+                        lock.workspace
+                    ));
+                }
+                None => app.push_output("No stuck state lock detected."),
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('X') => {
+            request_ephemeral_teardown(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('W') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::CleanupScan,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('M') => {
+            open_state_metrics_view(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('L') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::LockTableView,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('B') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::BackendReport,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('b') => {
+            app.activity_open = !app.activity_open;
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('S') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::StateList,
+                RunOptions::default(),
+            );
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('V') => {
+            if let Some(view) = &app.plan_changes {
+                // Large plans open with every module folded so the outline fits on screen.
+                let mut review = PlanReview {
+                    account_idx: view.account_idx,
+                    workspace: view.workspace.clone(),
+                    changes: view.changes.clone(),
+                    collapsed_modules: BTreeSet::new(),
+                    expanded: BTreeSet::new(),
+                    layout: app.diff_layout,
+                    selected: 0,
+                    focus: None,
+                    line_diffs: BTreeMap::new(),
+                };
+                if review.changes.len() > 50 {
+                    review.collapsed_modules =
+                        review.modules().into_keys().map(str::to_string).collect();
+                }
+                app.plan_review = Some(review);
+            } else {
+                app.push_output("No plan with resource changes yet. Press `p` to plan.");
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('C') => {
+            if app.plan_changes.is_some() {
+                app.plan_changes_open = true;
+                request_plan_blame(app, worker_tx);
+            } else {
+                app.push_output("No plan with resource changes yet. Press `p` to plan.");
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('P') => {
+            match app.selected_account() {
+                Some(account) if account.profile_missing => {
+                    app.prompt = Some(TextPrompt {
+                        purpose: PromptPurpose::AwsProfile {
+                            account_idx: app.selected_account,
+                            answers: Vec::new(),
+                        },
+                        title: format!("Create AWS SSO profile `{}`", account.aws_profile),
+                        hint: AWS_PROFILE_FIELDS[0].1.to_string(),
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                Some(account) => {
+                    let message = format!(
+                        "AWS profile `{}` already exists in {} or {}.",
+                        account.aws_profile,
+                        aws_config_path().display(),
+                        aws_credentials_path().display()
+                    );
+                    app.push_output(message);
+                }
+                None => app.push_output("No account selected."),
+            }
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('w') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            switch_role(app, worker_tx);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('R') => {
+            open_notes(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('F') => {
+            open_file_browser(app);
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('H') => {
+            app.prompt = Some(TextPrompt {
+                purpose: PromptPurpose::HistorySearch,
+                title: "Search resource history".to_string(),
+                hint: "Resource address or substring, e.g. aws_iam_role.deployer".to_string(),
+                input: String::new(),
+                error: None,
+            });
+            app.clear_apply_confirmation();
+        }
+        KeyCode::Char('A') => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            if let Some(account) = app.selected_account()
+                && let Some(workspace) = app.selected_workspace_name()
+                && !saved_plan_path(&account.name, &workspace).exists()
+            {
+                app.push_output(format!(
+                    "No saved plan for `{}/{workspace}`. Run a plan (`p`) first; apply only applies the reviewed plan.",
+                    account.name
+                ));
+                return;
+            }
+            app.pending_apply_confirmation = true;
+            app.set_status("apply confirmation pending: press y to confirm");
+            app.push_output(
+                "Apply requested. Press `y` to confirm apply, `T` to schedule it for later, any nav key to cancel.",
+            );
+        }
+        KeyCode::Char('T') if app.pending_apply_confirmation => {
+            app.clear_apply_confirmation();
+            let Some(account) = app.selected_account() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            if !account.can_mutate() {
+                let message = role_refusal(account);
+                app.push_output(message);
+                return;
+            }
+            let Some(workspace) = app.selected_workspace_name() else {
+                app.push_output("No workspace selected. Press `r` to load workspaces first.");
+                return;
+            };
+            app.prompt = Some(TextPrompt {
+                title: format!("Schedule apply on {}/{workspace}", account.name),
+                purpose: PromptPurpose::ScheduleApply {
+                    account_idx: app.selected_account,
+                    workspace,
+                },
+                hint: "Run at HH:MM, YYYY-MM-DD HH:MM (UTC), or +30m / +2h".to_string(),
+                input: String::new(),
+                error: None,
+            });
+        }
+        KeyCode::Char('T') => {
+            let Some(account) = app.selected_account() else {
+                app.push_output("No account selected.");
+                return;
+            };
+            let account = account.name.clone();
+            let mut lines: Vec<String> = app
+                .scheduled_applies
+                .iter()
+                .filter(|schedule| schedule.account == account)
+                .map(|schedule| {
+                    format!(
+                        "  {} at {} (by {})",
+                        schedule.workspace,
+                        format_timestamp(schedule.run_at),
+                        schedule.scheduled_by
+                    )
+                })
+                .collect();
+            if lines.is_empty() {
+                app.push_output(format!(
+                    "No scheduled applies for `{account}`. Press `A` then `T` to schedule one."
+                ));
+                return;
+            }
+            lines.insert(0, format!("Scheduled applies for `{account}`:"));
+            lines.push(String::new());
+            lines.push("Press `y` to cancel them all, any other key keeps them.".to_string());
+            app.confirmation = Some(Confirmation {
+                title: "Scheduled applies".to_string(),
+                lines,
+                action: ConfirmAction::CancelScheduledApplies { account },
+            });
+        }
+        KeyCode::Char('y') if app.pending_apply_confirmation => {
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            app.clear_apply_confirmation();
+            if !app.pending_stateful_changes().is_empty()
+                && let Some(account) = app.selected_account()
+                && let Some(workspace) = app.selected_workspace_name()
+            {
+                app.prompt = Some(TextPrompt {
+                    title: format!(
+                        "Apply to {}/{workspace} deletes or replaces stateful resources",
+                        account.name
+                    ),
+                    purpose: PromptPurpose::AcknowledgeStatefulChanges {
+                        account_idx: app.selected_account,
+                        workspace: workspace.clone(),
+                        run_at: None,
+                    },
+                    hint: format!("Type `{workspace}` to acknowledge and apply"),
+                    input: String::new(),
+                    error: None,
+                });
+                return;
+            }
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformApply,
+                RunOptions::default(),
+            );
+        }
+        _ => {
+            app.clear_apply_confirmation();
+        }
+    }
+}
+
+fn switch_role(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let Some(account) = app.selected_account_mut() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    if account.roles.len() < 2 {
+        let message = format!(
+            "Account `{}` has no alternative roles configured.",
+            account.name
+        );
+        app.push_output(message);
+        return;
+    }
+
+    account.active_role = (account.active_role + 1) % account.roles.len();
+    let role = account.roles[account.active_role].clone();
+    account.aws_profile = role.aws_profile.clone();
+    account.profile_missing =
+        load_aws_profiles().is_some_and(|profiles| !profiles.contains(&role.aws_profile));
+    let message = format!(
+        "Switched `{}` to role {} (profile `{}`){}{}.",
+        account.name,
+        role.name,
+        role.aws_profile,
+        if account.can_mutate() {
+            "; apply is allowed"
+        } else {
+            "; read-only, apply is refused"
+        },
+        if account.profile_missing && role.derived_profile {
+            "; that profile, derived from the role name, does not exist: configure the role as `{ name, aws_profile }`"
+        } else {
+            ""
+        }
+    );
+    app.push_output(message);
+    start_auth_check_for_selected(app, worker_tx.clone());
+}
+
+fn request_run(
+    app: &mut AppState,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    kind: OperationKind,
+    mut options: RunOptions,
+) {
+    let account_idx = app.selected_account;
+    if options.tuning.is_none()
+        && let Some(account) = app.selected_account()
+    {
+        let tuning = app.run_tuning(account_idx);
+        let ask = account
+            .run_options
+            .as_ref()
+            .is_some_and(|config| config.ask);
+        if ask && !app.skip_run_options.contains(&account_idx) {
+            app.run_options_popup = Some(RunOptionsPopup {
+                kind,
+                account_idx,
+                options,
+                tuning,
+                selected: 0,
+                editing: None,
+                error: None,
+                dont_ask: false,
+            });
+            return;
+        }
+        options.tuning = Some(tuning);
+    }
+
+    if kind == OperationKind::TerraformApply {
+        request_apply(app, worker_tx, options);
+    } else {
+        start_terraform_operation(app, worker_tx.clone(), kind, options);
+    }
+}
+
+fn handle_run_options_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(popup) = app.run_options_popup.as_mut() else {
+        return;
+    };
+
+    if let Some(input) = popup.editing.as_mut() {
+        match key.code {
+            KeyCode::Esc => {
+                popup.editing = None;
+                popup.error = None;
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(ch) => input.push(ch),
+            KeyCode::Enter => {
+                let input = input.trim().to_string();
+                match set_run_option_field(&mut popup.tuning, popup.selected, &input) {
+                    Ok(()) => {
+                        popup.editing = None;
+                        popup.error = None;
+                    }
+                    Err(err) => popup.error = Some(err),
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.run_options_popup = None;
+            app.push_output("Run cancelled.");
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            popup.selected = popup.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if popup.selected + 1 < RUN_OPTION_FIELDS.len() => {
+            popup.selected += 1;
+        }
+        KeyCode::Enter | KeyCode::Char(' ') => match popup.selected {
+            0 => {
+                let profiles: Vec<String> = app
+                    .accounts
+                    .get(popup.account_idx)
+                    .and_then(|account| account.run_options.as_ref())
+                    .map(|config| config.profiles.keys().cloned().collect())
+                    .unwrap_or_default();
+                if profiles.is_empty() {
+                    popup.error = Some("no run profiles configured".to_string());
+                    return;
+                }
+                let next = match &popup.tuning.profile {
+                    Some(current) => profiles
+                        .iter()
+                        .position(|name| name == current)
+                        .and_then(|idx| profiles.get(idx + 1))
+                        .cloned(),
+                    None => profiles.first().cloned(),
+                };
+                let config = app
+                    .accounts
+                    .get(popup.account_idx)
+                    .and_then(|account| account.run_options.as_ref());
+                let mut tuning = RunTuning::from_config(config);
+                if let (Some(name), Some(config)) = (&next, config)
+                    && let Some(profile) = config.profiles.get(name)
+                {
+                    tuning.overlay(profile);
+                }
+                tuning.profile = next;
+                popup.tuning = tuning;
+                popup.error = None;
+            }
+            1 => popup.tuning.refresh = !popup.tuning.refresh,
+            6 => popup.dont_ask = !popup.dont_ask,
+            field => {
+                popup.editing = Some(run_option_value(&popup.tuning, field));
+            }
+        },
+        KeyCode::Char('y') => {
+            let Some(popup) = app.run_options_popup.take() else {
+                return;
+            };
+            if app.selected_account != popup.account_idx {
+                app.push_output("Account selection changed; run cancelled.");
+                return;
+            }
+            if popup.dont_ask {
+                app.skip_run_options.insert(popup.account_idx);
+            }
+            app.session_tuning
+                .insert(popup.account_idx, popup.tuning.clone());
+            let options = RunOptions {
+                tuning: Some(popup.tuning),
+                ..popup.options
+            };
+            request_run(app, worker_tx, popup.kind, options);
+        }
+        _ => {}
+    }
+}
+
+fn prompt_missing_variable(
+    app: &mut AppState,
+    account_idx: usize,
+    kind: OperationKind,
+    options: Box<RunOptions>,
+    mut names: Vec<String>,
+) {
+    let Some(name) = names.pop() else {
+        return;
+    };
+    let left = if names.is_empty() {
+        String::new()
+    } else {
+        format!(" ({} more after this)", names.len())
+    };
+    app.prompt = Some(TextPrompt {
+        title: format!("Value for required variable `{name}`{left}"),
+        hint: format!(
+            "Passed as TF_VAR_{name} to re-run {} only; not saved. Esc to cancel",
+            kind.label()
+        ),
+        purpose: PromptPurpose::MissingVariable {
+            account_idx,
+            kind,
+            options,
+            name,
+            remaining: names,
+        },
+        input: String::new(),
+        error: None,
+    });
+}
+
+fn open_var_file_picker(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let canonical =
+        |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let label = |path: &Path| {
+        path.strip_prefix(&account.composition_path)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let mut files: Vec<VarFileChoice> = account
+        .var_files
+        .iter()
+        .map(|path| VarFileChoice {
+            path: path.clone(),
+            label: label(path),
+            configured: true,
+            checked: true,
+        })
+        .collect();
+    let configured: BTreeSet<PathBuf> = account
+        .var_files
+        .iter()
+        .map(|path| canonical(path))
+        .collect();
+    for path in discover_var_files(&account.composition_path) {
+        if !configured.contains(&canonical(&path)) {
+            files.push(VarFileChoice {
+                label: label(&path),
+                path,
+                configured: false,
+                checked: false,
+            });
+        }
+    }
+    if files.is_empty() {
+        let message = format!(
+            "No var files configured or found in `{}`.",
+            account.composition_path.display()
+        );
+        app.push_output(message);
+        return;
+    }
+    app.var_file_picker = Some(VarFilePicker {
+        account_idx: app.selected_account,
+        files,
+        selected: 0,
+    });
+}
+
+fn discover_var_files(composition_path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(composition_path) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let stem = name
+                .strip_suffix(".tfvars")
+                .or_else(|| name.strip_suffix(".tfvars.json"));
+            stem.is_some_and(|stem| stem != "terraform" && !stem.ends_with(".auto"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn handle_var_file_picker_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(picker) = app.var_file_picker.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.var_file_picker = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            picker.selected = picker.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if picker.selected + 1 < picker.files.len() => {
+            picker.selected += 1;
+        }
+        KeyCode::Enter | KeyCode::Char(' ') => {
+            if let Some(file) = picker.files.get_mut(picker.selected) {
+                file.checked = !file.checked;
+            }
+        }
+        KeyCode::Char('p') => {
+            let Some(picker) = app.var_file_picker.take() else {
+                return;
+            };
+            if app.selected_account != picker.account_idx {
+                app.push_output("Account selection changed; run cancelled.");
+                return;
+            }
+            let var_files = picker
+                .files
+                .into_iter()
+                .filter(|file| file.checked)
+                .map(|file| file.path)
+                .collect();
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformPlan,
+                RunOptions {
+                    var_files: Some(var_files),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        _ => {}
+    }
+}
+
+fn run_option_value(tuning: &RunTuning, field: usize) -> String {
+    match field {
+        0 => tuning
+            .profile
+            .clone()
+            .unwrap_or_else(|| "(none)".to_string()),
+        1 => if tuning.refresh { "on" } else { "off" }.to_string(),
+        2 => tuning.targets.join(", "),
+        3 => tuning
+            .parallelism
+            .map(|parallelism| parallelism.to_string())
+            .unwrap_or_default(),
+        4 => tuning
+            .vars
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        5 => tuning.extra_args.join(" "),
+        _ => String::new(),
+    }
+}
+
+fn set_run_option_field(
+    tuning: &mut RunTuning,
+    field: usize,
+    input: &str,
+) -> std::result::Result<(), String> {
+    let list = |input: &str| -> Vec<String> {
+        input
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    match field {
+        2 => tuning.targets = list(input),
+        3 if input.is_empty() => tuning.parallelism = None,
+        3 => {
+            tuning.parallelism = Some(
+                input
+                    .parse()
+                    .ok()
+                    .filter(|parallelism| *parallelism >= 1)
+                    .ok_or_else(|| "parallelism must be a positive number".to_string())?,
+            );
+        }
+        4 => {
+            let mut vars = BTreeMap::new();
+            for pair in list(input) {
+                let (name, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("`{pair}` is not name=value"))?;
+                vars.insert(name.trim().to_string(), value.trim().to_string());
+            }
+            tuning.vars = vars;
+        }
+        5 => tuning.extra_args = input.split_whitespace().map(str::to_string).collect(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn export_account_context(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let name = account.name.clone();
+    let mut vars = vec![("AWS_PROFILE".to_string(), account.aws_profile.clone())];
+    if let Some(region) = &account.region {
+        vars.push(("AWS_REGION".to_string(), region.clone()));
+        vars.push(("AWS_DEFAULT_REGION".to_string(), region.clone()));
+    }
+    // `env` may hold tokens, and the output panel and clipboard are easy to leak from.
+    let skipped_env: Vec<String> = account.env.keys().cloned().collect();
+    let lines = vec![
+        format!(
+            "export {}",
+            vars.iter()
+                .map(|(key, value)| format!("{key}={}", shell_quote(value)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        format!(
+            "cd {}",
+            shell_quote(&account.composition_path.to_string_lossy())
+        ),
+    ];
+
+    let copied = copy_to_clipboard(&format!("{}\n", lines.join("\n"))).is_ok();
+    app.push_output(format!(
+        "Shell context for `{name}`{}:",
+        if copied {
+            " (copied to the clipboard where the terminal allows OSC 52)"
+        } else {
+            ""
+        }
+    ));
+    for line in lines {
+        app.push_output(line);
+    }
+    if !skipped_env.is_empty() {
+        app.push_output(format!(
+            "Not included from the account's env: {}",
+            skipped_env.join(", ")
+        ));
+    }
+}
+
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    io::Write::write_all(
+        &mut stdout,
+        format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes())).as_bytes(),
+    )?;
+    io::Write::flush(&mut stdout)
+}
+
+fn shell_quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || "-_./:@%+=,".contains(ch))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn toggle_maintenance_lock(app: &mut AppState) {
+    let account_idx = app.selected_account;
+    let Some(account) = app.selected_account().map(|account| account.name.clone()) else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let Some(workspace) = app.selected_workspace_name() else {
+        app.push_output("No workspace selected. Press `r` to load workspaces first.");
+        return;
+    };
+
+    // Pick up locks taken or released by other lazytf instances sharing the state dir.
+    app.maintenance_locks = load_maintenance_locks();
+    let key = cost_key(&account, &workspace);
+    if !app.maintenance_locks.contains_key(&key)
+        && let Some((operator, lock)) = app.team.maintenance_lock(&key)
+    {
+        let message = format!(
+            "`{key}` is locked for maintenance by {operator} ({}); only they can release it.",
+            lock.reason
+        );
+        app.push_output(message);
+        return;
+    }
+    let Some(lock) = app.maintenance_locks.get(&key) else {
+        app.prompt = Some(TextPrompt {
+            purpose: PromptPurpose::MaintenanceReason {
+                account_idx,
+                workspace: workspace.clone(),
+            },
+            title: format!("Lock {account}/{workspace} for maintenance"),
+            hint: "Reason, shown to anyone who tries to run against it".to_string(),
+            input: String::new(),
+            error: None,
+        });
+        return;
+    };
+    app.confirmation = Some(Confirmation {
+        title: "Release maintenance lock".to_string(),
+        lines: vec![
+            format!(
+                "`{account}/{workspace}` was locked by {} at {}:",
+                lock.locked_by,
+                format_timestamp(lock.locked_at)
+            ),
+            format!("  {}", lock.reason),
+            String::new(),
+            "Press `y` to release it, any other key keeps it locked.".to_string(),
+        ],
+        action: ConfirmAction::ReleaseMaintenanceLock { account, workspace },
+    });
+}
+
+fn confirm_force_unlock(app: &mut AppState, lock: StuckLock) {
+    let account_name = app
+        .accounts
+        .get(lock.account_idx)
+        .map(|account| account.name.clone())
+        .unwrap_or_default();
+    app.confirmation = Some(Confirmation {
+        title: "Force-unlock state".to_string(),
+        lines: vec![
+            format!(
+                "Release lock {} on `{account_name}/{}`?",
+                lock.lock_id, lock.workspace
+            ),
+            "Only do this if no other Terraform run is using the state.".to_string(),
+            String::new(),
+            "Press `y` to force-unlock, any other key cancels.".to_string(),
+        ],
+        action: ConfirmAction::ForceUnlock(lock),
+    });
+}
+
+fn request_apply(
+    app: &mut AppState,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    options: RunOptions,
+) {
+    if let Some(account) = app.selected_account()
+        && !account.can_mutate()
+    {
+        let message = role_refusal(account);
+        app.push_output(message);
+        app.clear_apply_confirmation();
+        return;
+    }
+
+    let workspace = options
+        .workspace
+        .clone()
+        .or_else(|| app.selected_workspace_name());
+    if let Some(account) = app.selected_account()
+        && let Some(policy) = &account.change_ticket
+        && let Some(workspace) = workspace
+    {
+        let hint = if policy.required {
+            "Enter the change ticket ID, Esc to cancel"
+        } else {
+            "Enter a change ticket ID (optional), Esc to cancel"
+        };
+        app.prompt = Some(TextPrompt {
+            title: format!("Change ticket for apply on {}/{workspace}", account.name),
+            purpose: PromptPurpose::ChangeTicket {
+                account_idx: app.selected_account,
+                workspace,
+                kind: OperationKind::TerraformApply,
+                options: Box::new(options),
+            },
+            hint: hint.to_string(),
+            input: String::new(),
+            error: None,
+        });
+        app.clear_apply_confirmation();
+        return;
+    }
+
+    launch_apply(app, worker_tx, options);
+}
+
+fn launch_apply(
+    app: &mut AppState,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    options: RunOptions,
+) {
+    let Some(run_at) = options.run_at else {
+        let kind = if options.ci_job.is_some() {
+            OperationKind::CiJobPlay
+        } else {
+            OperationKind::TerraformApply
+        };
+        start_terraform_operation(app, worker_tx.clone(), kind, options);
+        return;
+    };
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let Some(workspace) = options
+        .workspace
+        .clone()
+        .or_else(|| app.selected_workspace_name())
+    else {
+        app.push_output("No workspace selected. Press `r` to load workspaces first.");
+        return;
+    };
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    // Pin the reviewed plan so a later `p` on the same workspace can't change what runs.
+    let saved_plan = saved_plan_path(&account.name, &workspace);
+    let plan_file = saved_plan.with_file_name(format!("{workspace}.scheduled-{id}.tfplan"));
+    if let Err(err) = fs::copy(&saved_plan, &plan_file) {
+        app.push_output(format!(
+            "Cannot schedule apply: failed to keep the saved plan {}: {err}",
+            saved_plan.display()
+        ));
+        return;
+    }
+    restrict_plan_file(&plan_file);
+    // Nobody is around to approve at run time, so the approval has to exist now.
+    let approval = match account
+        .approval
+        .as_ref()
+        .filter(|approval| approval.mode == ApprovalMode::Hmac)
+        .map(|approval| take_schedule_approval(account, &workspace, approval, &plan_file))
+        .transpose()
+    {
+        Ok(approval) => approval,
+        Err(err) => {
+            let _ = fs::remove_file(&plan_file);
+            app.push_output(format!("Cannot schedule apply: {err}"));
+            return;
+        }
+    };
+    let schedule = ScheduledApply {
+        id,
+        account: account.name.clone(),
+        workspace,
+        run_at,
+        scheduled_at: unix_now(),
+        scheduled_by: current_operator(),
+        change_ticket: options.change_ticket,
+        plan_file,
+        approval,
+    };
+    app.push_output(format!(
+        "Apply on `{}/{}` scheduled for {}. lazytf must stay running (e.g. in tmux) to execute it; press `T` to review or cancel.",
+        schedule.account,
+        schedule.workspace,
+        format_timestamp(schedule.run_at)
+    ));
+    app.scheduled_applies.push(schedule);
+    app.scheduled_applies
+        .sort_by_key(|schedule| schedule.run_at);
+    if let Err(err) = save_scheduled_applies(&app.scheduled_applies) {
+        app.push_output(format!("warning: failed to save scheduled applies: {err}"));
+    }
+}
+
+fn take_schedule_approval(
+    account: &AccountState,
+    workspace: &str,
+    approval: &ApprovalConfig,
+    plan_file: &Path,
+) -> Result<(ApprovalRequest, ApprovalSignature)> {
+    let target = ApprovalTarget::plan(plan_file)?;
+    let Some((request_path, request)) =
+        find_pending_approval(&account.name, workspace, &target.digest)?
+    else {
+        let (request_path, _) = create_approval_request(account, workspace, approval, &target)?;
+        return Err(eyre!(
+            "it needs a second operator's approval first; ask them to run `lazytf approve {}`, then schedule again",
+            request_path.display()
+        ));
+    };
+    let signature_path = request_path.with_extension("sig");
+    if !signature_path.exists() {
+        return Err(eyre!(
+            "approval request {} is not signed yet; schedule again after `lazytf approve {}`",
+            request.id,
+            request_path.display()
+        ));
+    }
+    let signature = verify_approval_signature(&signature_path, &request, approval)?;
+    let _ = fs::remove_file(&signature_path);
+    let _ = fs::remove_file(&request_path);
+    Ok((request, signature))
+}
+
+fn handle_prompt_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(prompt) = app.prompt.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc => {
+            app.prompt = None;
+            app.push_output("Prompt cancelled.");
+        }
+        KeyCode::Backspace => {
+            prompt.input.pop();
+            prompt.error = None;
+        }
+        KeyCode::Char(ch) => {
+            prompt.input.push(ch);
+            prompt.error = None;
+        }
+        KeyCode::Enter => {
+            if let Some(prompt) = app.prompt.take() {
+                submit_prompt(app, prompt, worker_tx);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn submit_prompt(
+    app: &mut AppState,
+    mut prompt: TextPrompt,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    match &prompt.purpose {
+        PromptPurpose::ChangeTicket {
+            account_idx,
+            workspace,
+            kind,
+            options,
+        } => {
+            let (account_idx, workspace, kind, options) =
+                (*account_idx, workspace.clone(), *kind, *options.clone());
+            if app.selected_account != account_idx
+                || (options.workspace.is_none()
+                    && app.selected_workspace_name().as_deref() != Some(workspace.as_str()))
+            {
+                app.push_output(
+                    "Selection changed while entering the change ticket; apply aborted.",
+                );
+                return;
+            }
+
+            let Some(policy) = app
+                .accounts
+                .get(account_idx)
+                .and_then(|account| account.change_ticket.as_ref())
+            else {
+                return;
+            };
+
+            match policy.check(&prompt.input) {
+                Ok(change_ticket) => {
+                    if let Some(ticket) = &change_ticket {
+                        app.push_output(format!(
+                            "Change ticket `{ticket}` attached to {}.",
+                            kind.label()
+                        ));
+                    }
+                    let options = RunOptions {
+                        change_ticket,
+                        ..options
+                    };
+                    if kind == OperationKind::WorkspaceCleanup {
+                        start_terraform_operation(app, worker_tx.clone(), kind, options);
+                    } else {
+                        launch_apply(app, worker_tx, options);
+                    }
+                }
+                Err(err) => {
+                    prompt.error = Some(err);
+                    app.prompt = Some(prompt);
+                }
+            }
+        }
+        PromptPurpose::WorkspaceDeleteName {
+            account_idx,
+            workspace,
+        } => {
+            if prompt.input.trim() != workspace {
+                prompt.error = Some(format!("type `{workspace}` exactly, or Esc to cancel"));
+                app.prompt = Some(prompt);
+                return;
+            }
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; workspace delete aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceDelete,
+                RunOptions {
+                    workspace: Some(workspace.clone()),
+                    force: true,
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::AttachLog => {
+            let source = prompt.input.trim().to_string();
+            if source.is_empty() || source == "!" {
+                prompt.error = Some("enter a file path or `!command`".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            if !source.starts_with('!') && !Path::new(&source).is_file() {
+                prompt.error = Some(format!("`{source}` is not a file"));
+                app.prompt = Some(prompt);
+                return;
+            }
+            let command = match source.strip_prefix('!') {
+                Some(script) => {
+                    let mut command = Command::new("sh");
+                    command.args(["-c", script.trim()]);
+                    command
+                }
+                None => {
+                    let mut command = Command::new("tail");
+                    command.args(["-n", "+1", "-F", &source]);
+                    command
+                }
+            };
+            start_log_attach(app, worker_tx.clone(), source, command);
+        }
+        PromptPurpose::WorkspaceName { account_idx } => {
+            let name = prompt.input.trim().to_string();
+            let exists = app
+                .accounts
+                .get(*account_idx)
+                .is_some_and(|account| account.workspaces.contains(&name));
+            if let Err(err) = validate_workspace_name(&name) {
+                prompt.error = Some(err);
+                app.prompt = Some(prompt);
+                return;
+            }
+            if exists {
+                prompt.error = Some(format!("workspace `{name}` already exists"));
+                app.prompt = Some(prompt);
+                return;
+            }
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; workspace creation aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceNew,
+                RunOptions {
+                    workspace: Some(name),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::EphemeralName { account_idx } => {
+            let account_idx = *account_idx;
+            let name = prompt.input.trim().to_string();
+            let exists = app
+                .accounts
+                .get(account_idx)
+                .is_some_and(|account| account.workspaces.contains(&name));
+            if let Err(err) = validate_workspace_name(&name) {
+                prompt.error = Some(err);
+                app.prompt = Some(prompt);
+            } else if exists {
+                prompt.error = Some(format!("workspace `{name}` already exists"));
+                app.prompt = Some(prompt);
+            } else {
+                app.prompt = Some(TextPrompt {
+                    purpose: PromptPurpose::EphemeralOwner { account_idx, name },
+                    title: prompt.title,
+                    hint: "Owner of the environment".to_string(),
+                    input: current_operator(),
+                    error: None,
+                });
+            }
+        }
+        PromptPurpose::EphemeralOwner { account_idx, name } => {
+            let owner = prompt.input.trim().to_string();
+            if owner.is_empty() {
+                prompt.error = Some("owner is required".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            let default_ttl = app
+                .accounts
+                .get(*account_idx)
+                .and_then(|account| account.ephemeral.as_ref())
+                .map_or(DEFAULT_EPHEMERAL_TTL_HOURS, |cfg| cfg.default_ttl_hours);
+            app.prompt = Some(TextPrompt {
+                purpose: PromptPurpose::EphemeralTtl {
+                    account_idx: *account_idx,
+                    name: name.clone(),
+                    owner,
+                },
+                title: prompt.title,
+                hint: "Time to live in hours".to_string(),
+                input: default_ttl.to_string(),
+                error: None,
+            });
+        }
+        PromptPurpose::ScheduleApply {
+            account_idx,
+            workspace,
+        } => {
+            let run_at = match parse_schedule_time(&prompt.input, unix_now()) {
+                Ok(run_at) => run_at,
+                Err(err) => {
+                    prompt.error = Some(err);
+                    app.prompt = Some(prompt);
+                    return;
+                }
+            };
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; scheduling aborted.");
+                return;
+            }
+            if !app
+                .saved_plan_stateful_changes(*account_idx, workspace)
+                .is_empty()
+            {
+                app.prompt = Some(TextPrompt {
+                    title: format!(
+                        "Scheduled apply to {}/{workspace} deletes or replaces stateful resources",
+                        app.accounts[*account_idx].name
+                    ),
+                    purpose: PromptPurpose::AcknowledgeStatefulChanges {
+                        account_idx: *account_idx,
+                        workspace: workspace.clone(),
+                        run_at: Some(run_at),
+                    },
+                    hint: format!("Type `{workspace}` to acknowledge and schedule"),
+                    input: String::new(),
+                    error: None,
+                });
+                return;
+            }
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformApply,
+                RunOptions {
+                    workspace: Some(workspace.clone()),
+                    run_at: Some(run_at),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::AcknowledgeStatefulChanges {
+            account_idx,
+            workspace,
+            run_at,
+        } => {
+            if prompt.input.trim() != workspace {
+                prompt.error = Some(format!("type `{workspace}` exactly, or Esc to cancel"));
+                app.prompt = Some(prompt);
+                return;
+            }
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; apply aborted.");
+                return;
+            }
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformApply,
+                RunOptions {
+                    workspace: Some(workspace.clone()),
+                    run_at: *run_at,
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::MaintenanceReason {
+            account_idx,
+            workspace,
+        } => {
+            let reason = prompt.input.trim().to_string();
+            if reason.is_empty() {
+                prompt.error = Some("reason is required".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            let Some(account) = app
+                .accounts
+                .get(*account_idx)
+                .map(|account| account.name.clone())
+            else {
+                return;
+            };
+            let lock = MaintenanceLock {
+                reason: reason.clone(),
+                locked_by: current_operator(),
+                locked_at: unix_now(),
+            };
+            match update_maintenance_locks(|locks| {
+                locks.insert(cost_key(&account, workspace), lock);
+            }) {
+                Ok(locks) => app.maintenance_locks = locks,
+                Err(err) => {
+                    app.push_output(format!("Failed to save the maintenance lock: {err}"));
+                    return;
+                }
+            }
+            app.push_output(format!(
+                "Locked `{account}/{workspace}` for maintenance ({reason}). Operations on it are refused until `u` releases it."
+            ));
+        }
+        PromptPurpose::MissingVariable {
+            account_idx,
+            kind,
+            options,
+            name,
+            remaining,
+        } => {
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; re-run aborted.");
+                return;
+            }
+            let mut options = options.clone();
+            options.vars.insert(name.clone(), prompt.input.clone());
+            if remaining.is_empty() {
+                start_terraform_operation(app, worker_tx.clone(), *kind, *options);
+            } else {
+                prompt_missing_variable(app, *account_idx, *kind, options, remaining.clone());
+            }
+        }
+        PromptPurpose::EphemeralTtl {
+            account_idx,
+            name,
+            owner,
+        } => {
+            let ttl_hours = match prompt.input.trim().parse::<u64>() {
+                Ok(hours) if hours > 0 => hours,
+                _ => {
+                    prompt.error = Some("enter a whole number of hours".to_string());
+                    app.prompt = Some(prompt);
+                    return;
+                }
+            };
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; environment creation aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::EphemeralCreate,
+                RunOptions {
+                    workspace: Some(name.clone()),
+                    ephemeral: Some(EphemeralSpec {
+                        owner: owner.clone(),
+                        ttl_hours,
+                    }),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::AwsProfile {
+            account_idx,
+            answers,
+        } => {
+            let value = prompt.input.trim().to_string();
+            let (key, _) = AWS_PROFILE_FIELDS[answers.len()];
+            let invalid = match key {
+                "sso_account_id" => (value.len() != 12
+                    || !value.chars().all(|ch| ch.is_ascii_digit()))
+                .then_some("account ID must be 12 digits"),
+                _ => value.is_empty().then_some("a value is required"),
+            };
+            if let Some(error) = invalid {
+                prompt.error = Some(error.to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+
+            let account_idx = *account_idx;
+            let mut answers = answers.clone();
+            answers.push(value);
+            if let Some((_, hint)) = AWS_PROFILE_FIELDS.get(answers.len()) {
+                app.prompt = Some(TextPrompt {
+                    purpose: PromptPurpose::AwsProfile {
+                        account_idx,
+                        answers,
+                    },
+                    title: prompt.title,
+                    hint: hint.to_string(),
+                    input: String::new(),
+                    error: None,
+                });
+                return;
+            }
+
+            let Some(account) = app.accounts.get_mut(account_idx) else {
+                return;
+            };
+            let mut stanza = format!("\n[profile {}]\n", account.aws_profile);
+            for ((key, _), value) in AWS_PROFILE_FIELDS.iter().zip(&answers) {
+                stanza.push_str(&format!("{key} = {value}\n"));
+            }
+            stanza.push_str(&format!(
+                "region = {}\n",
+                account.region.as_deref().unwrap_or(&answers[1])
+            ));
+            let path = aws_config_path();
+            let result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::OpenOptions::new().create(true).append(true).open(&path))
+                .and_then(|mut file| io::Write::write_all(&mut file, stanza.as_bytes()));
+            match result {
+                Ok(()) => {
+                    account.profile_missing = false;
+                    let message = format!(
+                        "Wrote profile `{}` to {}. Press `a` to log in.",
+                        account.aws_profile,
+                        path.display()
+                    );
+                    app.push_output(message);
+                }
+                Err(err) => app.push_output(format!("Failed to write {}: {err}", path.display())),
+            }
+        }
+        PromptPurpose::ImportAddress { account_idx } => {
+            let address = prompt.input.trim().to_string();
+            if !is_resource_address(&address) {
+                prompt.error = Some("enter a resource address like aws_s3_bucket.logs".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            app.prompt = Some(TextPrompt {
+                purpose: PromptPurpose::ImportId {
+                    account_idx: *account_idx,
+                    address,
+                },
+                title: prompt.title,
+                hint: "ID of the existing object (as the provider expects it)".to_string(),
+                input: String::new(),
+                error: None,
+            });
+        }
+        PromptPurpose::ImportId {
+            account_idx,
+            address,
+        } => {
+            let id = prompt.input.trim().to_string();
+            if id.is_empty() {
+                prompt.error = Some("an ID is required".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            app.prompt = Some(TextPrompt {
+                purpose: PromptPurpose::ImportGenerate {
+                    account_idx: *account_idx,
+                    address: address.clone(),
+                    id,
+                },
+                title: prompt.title,
+                hint: "Generate HCL for it with -generate-config-out? (y/N)".to_string(),
+                input: String::new(),
+                error: None,
+            });
+        }
+        PromptPurpose::ImportGenerate {
+            account_idx,
+            address,
+            id,
+        } => {
+            let generate_config = matches!(
+                prompt.input.trim().to_ascii_lowercase().as_str(),
+                "y" | "yes"
+            );
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; import aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformImport,
+                RunOptions {
+                    import: Some(ImportSpec {
+                        address: address.clone(),
+                        id: id.clone(),
+                        generate_config,
+                    }),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::StateMoveDestination {
+            account_idx,
+            workspace,
+            source,
+        } => {
+            let destination = prompt.input.trim().to_string();
+            if destination.is_empty() || !strip_address_keys(&destination).contains('.') {
+                prompt.error = Some("enter a resource address like aws_vpc.main".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            if destination == *source {
+                prompt.error = Some("destination is the same as the source".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            if app.selected_account != *account_idx {
+                app.push_output("Account selection changed; terraform state mv aborted.");
+                return;
+            }
+            app.state_browser = None;
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::StateMove,
+                RunOptions {
+                    workspace: Some(workspace.clone()),
+                    state_address: Some(source.clone()),
+                    state_destination: Some(destination),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        PromptPurpose::HistorySearch => {
+            let query = prompt.input.trim().to_string();
+            if query.is_empty() {
+                prompt.error = Some("enter part of a resource address".to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+
+            match search_resource_index(&query) {
+                Ok(results) => {
+                    app.history_search = Some(HistorySearch {
+                        query,
+                        results,
+                        scroll: 0,
+                    });
+                }
+                Err(err) => {
+                    app.push_output(format!("Resource history search failed: {err}"));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvironmentStatus {
+    Active,
+    Expiring,
+    Expired,
+}
+
+impl EnvironmentStatus {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Expiring => "expiring",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+fn environment_status(
+    environment: &EphemeralEnvironment,
+    now: u64,
+    warn_before_hours: u64,
+) -> EnvironmentStatus {
+    if environment.expires_at <= now {
+        EnvironmentStatus::Expired
+    } else if environment.expires_at - now <= warn_before_hours * 3_600 {
+        EnvironmentStatus::Expiring
+    } else {
+        EnvironmentStatus::Active
+    }
+}
+
+fn environment_badge(
+    environment: &EphemeralEnvironment,
+    now: u64,
+    warn_before_hours: u64,
+    icons: IconSet,
+) -> Span<'static> {
+    match environment_status(environment, now, warn_before_hours) {
+        EnvironmentStatus::Expired => Span::styled(
+            format!(
+                "  [{} expired {} ago]",
+                icons.warning(),
+                format_duration_short(now - environment.expires_at)
+            ),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        EnvironmentStatus::Expiring => Span::styled(
+            format!(
+                "  [{} expires in {}]",
+                icons.warning(),
+                format_duration_short(environment.expires_at - now)
+            ),
+            Style::default().fg(Color::Yellow),
+        ),
+        EnvironmentStatus::Active => Span::styled(
+            format!(
+                "  [ttl {}]",
+                format_duration_short(environment.expires_at - now)
+            ),
+            Style::default().fg(Color::DarkGray),
+        ),
+    }
+}
+
+fn format_duration_short(seconds: u64) -> String {
+    if seconds >= 86_400 {
+        format!("{}d", seconds / 86_400)
+    } else if seconds >= 3_600 {
+        format!("{}h", seconds / 3_600)
+    } else {
+        format!("{}m", (seconds / 60).max(1))
+    }
+}
+
+fn check_environment_reminders(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let now = unix_now();
+    if now < app.next_reminder_at {
+        return;
+    }
+    app.next_reminder_at = now + app.reminders.interval_minutes.max(1) * 60;
+
+    let due: Vec<(EphemeralEnvironment, EnvironmentStatus)> = app
+        .environments
+        .iter()
+        .map(|env| {
+            (
+                env.clone(),
+                environment_status(env, now, app.reminders.warn_before_hours),
+            )
+        })
+        .filter(|(_, status)| *status != EnvironmentStatus::Active)
+        .collect();
+
+    for (environment, status) in due {
+        let when = if status == EnvironmentStatus::Expired {
+            format!(
+                "expired {} ago",
+                format_duration_short(now - environment.expires_at)
+            )
+        } else {
+            format!(
+                "expires in {}",
+                format_duration_short(environment.expires_at - now)
+            )
+        };
+        app.push_output(format!(
+            "reminder: ephemeral environment `{}/{}` (owner `{}`) {when}; select it and press `X` to tear it down.",
+            environment.account, environment.workspace, environment.owner
+        ));
+
+        if let Some(command) = app.reminders.notify_command.clone() {
+            let event_tx = worker_tx.clone();
+            let label = format!("notify {}/{}", environment.account, environment.workspace);
+            spawn_tracked(worker_tx, label, async move {
+                let result = Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .env("LAZYTF_ENV_ACCOUNT", &environment.account)
+                    .env("LAZYTF_ENV_WORKSPACE", &environment.workspace)
+                    .env("LAZYTF_ENV_OWNER", &environment.owner)
+                    .env(
+                        "LAZYTF_ENV_EXPIRES_AT",
+                        format_timestamp(environment.expires_at),
+                    )
+                    .env("LAZYTF_ENV_STATUS", status.label())
+                    .stdin(Stdio::null())
+                    .output()
+                    .await;
+                let failure = match result {
+                    Ok(output) if output.status.success() => None,
+                    Ok(output) => Some(format!("exited with {}", output.status)),
+                    Err(err) => Some(err.to_string()),
+                };
+                let Some(failure) = failure else {
+                    return true;
+                };
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "warning: reminder notify_command failed: {failure}"
+                )));
+                false
+            });
+        }
+    }
+}
+
+fn parse_schedule_time(input: &str, now: u64) -> Result<u64, String> {
+    let input = input.trim();
+    if let Some(relative) = input.strip_prefix('+') {
+        let (amount, unit) = match (relative.strip_suffix('m'), relative.strip_suffix('h')) {
+            (Some(minutes), _) => (minutes, 60),
+            (_, Some(hours)) => (hours, 3_600),
+            _ => return Err("use +<minutes>m or +<hours>h".to_string()),
+        };
+        return amount
+            .parse::<u64>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(unit))
+            .and_then(|seconds| now.checked_add(seconds))
+            .ok_or_else(|| "use +<minutes>m or +<hours>h".to_string());
+    }
+
+    let parse_clock = |clock: &str| -> Option<u64> {
+        let (hours, minutes) = clock.split_once(':')?;
+        let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+        (hours < 24 && minutes < 60).then_some(hours * 3_600 + minutes * 60)
+    };
+
+    let run_at = if let Some((date, clock)) = input.split_once([' ', 'T']) {
+        let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err("date must look like YYYY-MM-DD".to_string());
+        };
+        if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day)
+        {
+            return Err("date must look like YYYY-MM-DD".to_string());
+        }
+        let Some(clock) = parse_clock(clock) else {
+            return Err("time must look like HH:MM".to_string());
+        };
+        let days = u64::try_from(days_from_civil(year, month, day))
+            .map_err(|_| "date must look like YYYY-MM-DD".to_string())?;
+        days * 86_400 + clock
+    } else {
+        let Some(clock) = parse_clock(input) else {
+            return Err("time must look like HH:MM".to_string());
+        };
+        let today = now - now % 86_400 + clock;
+        if today > now { today } else { today + 86_400 }
+    };
+
+    if run_at <= now {
+        return Err(format!("{} is in the past", format_timestamp(run_at)));
+    }
+    Ok(run_at)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Inverse of the conversion in `format_timestamp`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn advance_deploy(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let Some(flow) = app.deploy.clone() else {
+        return;
+    };
+    if app.selected_account != flow.account_idx {
+        app.deploy = None;
+        app.push_output("Deploy stopped: account selection changed.");
+        return;
+    }
+    if app.is_busy() || app.run_options_popup.is_some() {
+        return;
+    }
+    match flow.step {
+        DeployStep::Init => {}
+        DeployStep::Plan => {
+            if let Some(flow) = app.deploy.as_mut() {
+                flow.step = DeployStep::PlanRunning;
+            }
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformPlan,
+                RunOptions {
+                    workspace: Some(flow.workspace),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        DeployStep::PlanRunning => {
+            // The plan never started (run options cancelled or preflight refused it).
+            app.deploy = None;
+            app.push_output("Deploy stopped: the plan did not start.");
+        }
+    }
+}
+
+fn retry_after_workspace_new(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    if app.is_busy() {
+        return;
+    }
+    let Some(retry) = app.workspace_retry.take() else {
+        return;
+    };
+    if app.selected_account != retry.account_idx {
+        app.push_output("Account selection changed; not re-running the operation.");
+        return;
+    }
+    start_terraform_operation(app, worker_tx.clone(), retry.kind, retry.options);
+}
+
+fn offer_change_request(app: &mut AppState) {
+    if app.confirmation.is_some() || app.prompt.is_some() || app.proposed_changes.is_empty() {
+        return;
+    }
+    let (paths, title) = app.proposed_changes.remove(0);
+    let Some(host) = app.ci.git_host() else {
+        return;
+    };
+    let request = match host {
+        CiProvider::Github(_) => "pull request",
+        CiProvider::Gitlab(_) => "merge request",
+    };
+    let mut lines = vec![format!("\"{title}\" changed:")];
+    lines.extend(paths.iter().map(|path| format!("  {}", path.display())));
+    lines.push(format!(
+        "Create a lazytf/ branch, commit these files, push it to origin, and open a {request} on {}?",
+        host.source()
+    ));
+    lines.push(
+        "You are switched back afterwards; other local changes are not committed.".to_string(),
+    );
+    app.confirmation = Some(Confirmation {
+        title: format!("Open a {request}"),
+        lines,
+        action: ConfirmAction::OpenChangeRequest { paths, title },
+    });
+}
+
+fn check_scheduled_applies(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    for (schedule, status, message) in std::mem::take(&mut app.schedule_outcomes) {
+        app.push_output(format!(
+            "Scheduled apply on `{}/{}` {status}.",
+            schedule.account, schedule.workspace
+        ));
+        let Some(command) = app.schedule.notify_command.clone() else {
+            continue;
+        };
+        let event_tx = worker_tx.clone();
+        let label = format!("notify {}/{}", schedule.account, schedule.workspace);
+        spawn_tracked(worker_tx, label, async move {
+            let result = Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .env("LAZYTF_SCHEDULE_ACCOUNT", &schedule.account)
+                .env("LAZYTF_SCHEDULE_WORKSPACE", &schedule.workspace)
+                .env("LAZYTF_SCHEDULE_RUN_AT", format_timestamp(schedule.run_at))
+                .env("LAZYTF_SCHEDULE_STATUS", status)
+                .env("LAZYTF_SCHEDULE_MESSAGE", &message)
+                .stdin(Stdio::null())
+                .output()
+                .await;
+            let failure = match result {
+                Ok(output) if output.status.success() => None,
+                Ok(output) => Some(format!("exited with {}", output.status)),
+                Err(err) => Some(err.to_string()),
+            };
+            let Some(failure) = failure else {
+                return true;
+            };
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "warning: schedule notify_command failed: {failure}"
+            )));
+            false
+        });
+    }
+
+    let now = unix_now();
+    if app.is_busy()
+        || app
+            .scheduled_applies
+            .first()
+            .is_none_or(|schedule| schedule.run_at > now)
+    {
+        return;
+    }
+
+    let schedule = app.scheduled_applies.remove(0);
+    // Another lazytf instance sharing the state dir may already have claimed or cancelled it.
+    if !claim_scheduled_apply(schedule.id) {
+        return;
+    }
+    let mut on_disk = load_scheduled_applies();
+    let cancelled = !on_disk.iter().any(|entry| entry.id == schedule.id);
+    on_disk.retain(|entry| entry.id != schedule.id);
+    if let Err(err) = save_scheduled_applies(&on_disk) {
+        app.push_output(format!("warning: failed to save scheduled applies: {err}"));
+    }
+    if cancelled {
+        return;
+    }
+
+    let Some(account_idx) = app
+        .accounts
+        .iter()
+        .position(|account| account.name == schedule.account)
+    else {
+        let message = format!("account `{}` is no longer configured", schedule.account);
+        app.schedule_outcomes.push((schedule, "skipped", message));
+        return;
+    };
+
+    app.push_output(format!(
+        "Running scheduled apply on `{}/{}` (scheduled by {} at {}).",
+        schedule.account,
+        schedule.workspace,
+        schedule.scheduled_by,
+        format_timestamp(schedule.scheduled_at)
+    ));
+    start_operation_for(
+        app,
+        worker_tx.clone(),
+        account_idx,
+        OperationKind::TerraformApply,
+        RunOptions {
+            workspace: Some(schedule.workspace.clone()),
+            change_ticket: schedule.change_ticket.clone(),
+            plan_file: Some(schedule.plan_file.clone()),
+            approval: schedule.approval.clone(),
+            ..RunOptions::default()
+        },
+    );
+
+    if app.is_busy() {
+        app.scheduled_running = Some(schedule);
+    } else {
+        let message = "the apply could not be started; see the output panel".to_string();
+        app.schedule_outcomes.push((schedule, "failed", message));
+    }
+}
+
+fn handle_leftover_children_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    match key.code {
+        KeyCode::Char('k') => {
+            for child in std::mem::take(&mut app.leftover_children) {
+                match terminate_process(child.pid) {
+                    Ok(()) => app.push_output(format!(
+                        "Sent SIGTERM to leftover process {} ({})",
+                        child.pid, child.command
+                    )),
+                    Err(err) => app.push_output(format!(
+                        "warning: failed to stop leftover process {}: {err}",
+                        child.pid
+                    )),
+                }
+                let _ = fs::remove_file(child_record_path(child.pid));
+            }
+        }
+        KeyCode::Char('a') => {
+            for child in std::mem::take(&mut app.leftover_children) {
+                app.push_output(format!(
+                    "Adopted leftover process {} ({}); watching until it exits.",
+                    child.pid, child.command
+                ));
+                let event_tx = worker_tx.clone();
+                spawn_tracked(worker_tx, format!("watch pid {}", child.pid), async move {
+                    while process_alive(child.pid) {
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                    }
+                    let _ = fs::remove_file(child_record_path(child.pid));
+                    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                        "Adopted process {} exited.",
+                        child.pid
+                    )));
+                    true
+                });
+            }
+        }
+        KeyCode::Esc => {
+            app.leftover_children.clear();
+            app.push_output("Leaving leftover processes running.");
+        }
+        _ => {}
+    }
+}
+
+fn request_ephemeral_teardown(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let Some(workspace) = app.selected_workspace_name() else {
+        app.push_output("No workspace selected.");
+        return;
+    };
+    let Some(environment) = app
+        .environments
+        .iter()
+        .find(|env| env.account == account.name && env.workspace == workspace)
+        .cloned()
+    else {
+        app.push_output(format!(
+            "`{workspace}` is not a tracked ephemeral environment."
+        ));
+        return;
+    };
+
+    let expiry = if environment.expires_at <= unix_now() {
+        format!("expired {}", format_timestamp(environment.expires_at))
+    } else {
+        format!("expires {}", format_timestamp(environment.expires_at))
+    };
+    app.confirmation = Some(Confirmation {
+        title: "Tear down ephemeral environment".to_string(),
+        lines: vec![
+            format!(
+                "Environment `{}` in `{}` (owner `{}`, {expiry})",
+                environment.workspace, environment.account, environment.owner
+            ),
+            String::new(),
+            "Press `y` to destroy its resources and delete the workspace.".to_string(),
+            "Any other key cancels.".to_string(),
+        ],
+        action: ConfirmAction::TeardownEphemeral {
+            account_idx: app.selected_account,
+            workspace: environment.workspace,
+            var_file: environment.var_file,
+        },
+    });
+}
+
+fn handle_confirmation_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(confirmation) = app.confirmation.take() else {
+        return;
+    };
+
+    if key.code != KeyCode::Char('y') {
+        app.push_output(format!("{} cancelled.", confirmation.title));
+        return;
+    }
+
+    if app.is_busy() {
+        app.push_output("Another operation is already running. Press `c` to cancel.");
+        return;
+    }
+
+    match confirmation.action {
+        ConfirmAction::ApplyEphemeral {
+            account_idx,
+            workspace,
+            var_file,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; apply aborted.");
+                return;
+            }
+            request_apply(
+                app,
+                worker_tx,
+                RunOptions {
+                    workspace: Some(workspace),
+                    extra_var_files: vec![var_file],
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::TeardownEphemeral {
+            account_idx,
+            workspace,
+            var_file,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; teardown aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::EphemeralTeardown,
+                RunOptions {
+                    workspace: Some(workspace),
+                    extra_var_files: vec![var_file],
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::FormatFiles { account_idx } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; terraform fmt aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::TerraformFmt,
+                RunOptions::default(),
+            );
+        }
+        ConfirmAction::ForceUnlock(lock) => {
+            if app.selected_account != lock.account_idx {
+                app.push_output("Account selection changed; force-unlock aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::ForceUnlock,
+                RunOptions {
+                    workspace: Some(lock.workspace),
+                    lock_id: Some(lock.lock_id),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::CreateMissingWorkspace {
+            account_idx,
+            workspace,
+            retry,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; workspace creation aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceNew,
+                RunOptions {
+                    workspace: Some(workspace),
+                    ..RunOptions::default()
+                },
+            );
+            if app.is_busy() {
+                app.workspace_retry = Some(*retry);
+            }
+        }
+        ConfirmAction::PlayCiJob {
+            account_idx,
+            run_id,
+            job_id,
+            name,
+        } => {
+            let Some(provider) = app
+                .ci_runs
+                .as_ref()
+                .filter(|view| view.account_idx == account_idx)
+                .map(|view| view.provider.clone())
+            else {
+                app.push_output("CI view closed; manual job not started.");
+                return;
+            };
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; manual job not started.");
+                return;
+            }
+            request_apply(
+                app,
+                worker_tx,
+                RunOptions {
+                    ci_job: Some(CiJobSpec {
+                        provider,
+                        run_id,
+                        job_id,
+                        name,
+                    }),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::OpenChangeRequest { paths, title } => {
+            let Some(host) = app.ci.git_host() else {
+                return;
+            };
+            app.push_output(format!("Opening a change request for \"{title}\"..."));
+            let event_tx = worker_tx.clone();
+            spawn_tracked(worker_tx, format!("change request {title}"), async move {
+                let message = match open_change_request(&host, &paths, &title).await {
+                    Ok(url) => format!("Opened {url}"),
+                    Err(err) => format!("Could not open a change request: {err:#}"),
+                };
+                let ok = message.starts_with("Opened");
+                let _ = event_tx.send(WorkerEvent::OutputLine(message));
+                ok
+            });
+        }
+        ConfirmAction::DeleteWorkspace {
+            account_idx,
+            workspace,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; workspace delete aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceDelete,
+                RunOptions {
+                    workspace: Some(workspace),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::BootstrapWorkspaces { account_idx, names } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; workspace bootstrap aborted.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceBootstrap,
+                RunOptions {
+                    workspace_names: names,
+                    ..RunOptions::default()
+                },
+            );
+        }
+        ConfirmAction::ReleaseMaintenanceLock { account, workspace } => {
+            let mut released = false;
+            match update_maintenance_locks(|locks| {
+                released = locks.remove(&cost_key(&account, &workspace)).is_some();
+            }) {
+                Ok(locks) => app.maintenance_locks = locks,
+                Err(err) => {
+                    app.push_output(format!("Failed to release the maintenance lock: {err}"));
+                    return;
+                }
+            }
+            if !released {
+                app.push_output(format!(
+                    "`{account}/{workspace}` is no longer locked for maintenance."
+                ));
+                return;
+            }
+            app.push_output(format!(
+                "Released the maintenance lock on `{account}/{workspace}`."
+            ));
+        }
+        ConfirmAction::CancelScheduledApplies { account } => {
+            let before = app.scheduled_applies.len();
+            app.scheduled_applies.retain(|schedule| {
+                if schedule.account == account {
+                    let _ = fs::remove_file(&schedule.plan_file);
+                }
+                schedule.account != account
+            });
+            if let Err(err) = save_scheduled_applies(&app.scheduled_applies) {
+                app.push_output(format!("warning: failed to save scheduled applies: {err}"));
+            }
+            app.push_output(format!(
+                "Cancelled {} scheduled apply(ies) for `{account}`.",
+                before - app.scheduled_applies.len()
+            ));
+        }
+        ConfirmAction::StateTaint {
+            account_idx,
+            workspace,
+            address,
+            mode,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output(format!(
+                    "Account selection changed; {} aborted.",
+                    mode.label()
+                ));
+                return;
+            }
+            app.state_browser = None;
+            match mode {
+                TaintMode::Replace => {
+                    let mut tuning = app.run_tuning(account_idx);
+                    tuning.extra_args.push(format!("-replace={address}"));
+                    request_run(
+                        app,
+                        worker_tx,
+                        OperationKind::TerraformPlan,
+                        RunOptions {
+                            workspace: Some(workspace),
+                            tuning: Some(tuning),
+                            ..RunOptions::default()
+                        },
+                    );
+                }
+                TaintMode::Taint | TaintMode::Untaint => {
+                    let kind = if mode == TaintMode::Taint {
+                        OperationKind::StateTaint
+                    } else {
+                        OperationKind::StateUntaint
+                    };
+                    start_terraform_operation(
+                        app,
+                        worker_tx.clone(),
+                        kind,
+                        RunOptions {
+                            workspace: Some(workspace),
+                            state_address: Some(address),
+                            ..RunOptions::default()
+                        },
+                    );
+                }
+            }
+        }
+        ConfirmAction::StateRemove {
+            account_idx,
+            workspace,
+            address,
+        } => {
+            if app.selected_account != account_idx {
+                app.push_output("Account selection changed; terraform state rm aborted.");
+                return;
+            }
+            app.state_browser = None;
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::StateRemove,
+                RunOptions {
+                    workspace: Some(workspace),
+                    state_address: Some(address),
+                    ..RunOptions::default()
+                },
+            );
+        }
+    }
+}
+
+fn handle_cleanup_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(session) = app.cleanup_session.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Char('y') => {
+            let account_idx = session.account_idx;
+            let workspace = session.candidates[session.current].workspace.clone();
+            if app.selected_account != account_idx {
+                app.cleanup_session = None;
+                app.push_output("Account selection changed; workspace cleanup stopped.");
+                return;
+            }
+            let options = RunOptions {
+                workspace: Some(workspace.clone()),
+                ..RunOptions::default()
+            };
+            if let Some(account) = app.selected_account()
+                && let Some(policy) = &account.change_ticket
+            {
+                let hint = if policy.required {
+                    "Enter the change ticket ID, Esc to cancel"
+                } else {
+                    "Enter a change ticket ID (optional), Esc to cancel"
+                };
+                app.prompt = Some(TextPrompt {
+                    title: format!("Change ticket for destroying {}/{workspace}", account.name),
+                    purpose: PromptPurpose::ChangeTicket {
+                        account_idx,
+                        workspace,
+                        kind: OperationKind::WorkspaceCleanup,
+                        options: Box::new(options),
+                    },
+                    hint: hint.to_string(),
+                    input: String::new(),
+                    error: None,
+                });
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::WorkspaceCleanup,
+                options,
+            );
+        }
+        KeyCode::Char('s') | KeyCode::Char('n') => {
+            let skipped = session.candidates[session.current].workspace.clone();
+            session.current += 1;
+            let finished = session.current >= session.candidates.len();
+            let removed = session.removed;
+            app.push_output(format!("Skipped workspace `{skipped}`."));
+            if finished {
+                app.cleanup_session = None;
+                app.push_output(format!(
+                    "Workspace cleanup finished: {removed} workspace(s) removed."
+                ));
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            let removed = session.removed;
+            app.cleanup_session = None;
+            app.push_output(format!(
+                "Workspace cleanup stopped: {removed} workspace(s) removed."
+            ));
+        }
+        _ => {}
+    }
+}
+
+fn open_state_metrics_view(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let Some(workspace) = app.selected_workspace_name() else {
+        app.push_output("No workspace selected. Press `r` to load workspaces first.");
+        return;
+    };
+
+    let account_name = account.name.clone();
+    match load_state_metrics(&account_name, &workspace) {
+        Ok(samples) => {
+            app.state_metrics_view = Some(StateMetricsView {
+                account: account_name,
+                workspace,
+                samples,
+            });
+        }
+        Err(err) => {
+            app.push_output(format!("Failed to load state metrics: {err}"));
+        }
+    }
+}
+
+fn handle_history_search_key(app: &mut AppState, key: KeyEvent) {
+    let Some(search) = app.history_search.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('H') => {
+            app.history_search = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            search.scroll = search.scroll.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if search.scroll + 1 < search.results.len() => {
+            search.scroll += 1;
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            search.scroll = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            search.scroll = search.results.len().saturating_sub(1);
+        }
+        _ => {}
+    }
+}
+
+fn handle_plan_changes_key(app: &mut AppState, key: KeyEvent) {
+    let view = if app.destroy_preview_open {
+        app.destroy_preview.as_mut()
+    } else {
+        app.plan_changes.as_mut()
+    };
+    let Some(view) = view else {
+        app.plan_changes_open = false;
+        app.destroy_preview_open = false;
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => {
+            app.plan_changes_open = false;
+            app.destroy_preview_open = false;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            view.selected = view.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if view.selected + 1 < view.changes.len() => {
+            view.selected += 1;
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            view.selected = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            view.selected = view.changes.len().saturating_sub(1);
+        }
+        KeyCode::Enter | KeyCode::Char('e') => {
+            let Some(change) = view.changes.get(view.selected).cloned() else {
+                return;
+            };
+            let Some(account) = app.accounts.get(view.account_idx) else {
+                return;
+            };
+            let root = account.composition_path.clone();
+            let location = locate_resource_source(&root, &change.address);
+            match location {
+                Ok((path, line)) if key.code == KeyCode::Char('e') => {
+                    app.pending_editor = Some((path, line));
+                }
+                Ok((path, line)) => {
+                    let mut files = Vec::new();
+                    let _ = collect_hcl_files(&root, &root, &mut files);
+                    files.sort();
+                    let selected = path
+                        .strip_prefix(&root)
+                        .ok()
+                        .and_then(|relative| files.iter().position(|file| file == relative))
+                        .unwrap_or(0);
+                    match open_file_view(&path, line) {
+                        Ok(viewer) => {
+                            app.file_browser = Some(FileBrowser {
+                                root,
+                                files,
+                                selected,
+                                viewer: Some(viewer),
+                            });
+                        }
+                        Err(err) => app.push_output(format!("{err}")),
+                    }
+                }
+                Err(err) => app.push_output(format!(
+                    "Cannot locate source for `{}`: {err}",
+                    change.address
+                )),
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_plan_review_key(app: &mut AppState, key: KeyEvent) {
+    let Some(review) = app.plan_review.as_mut() else {
+        return;
+    };
+    let rows = review.rows();
+    let current = rows.get(review.selected).cloned();
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('V') => {
+            app.plan_review = None;
+            return;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            review.selected = review.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if review.selected + 1 < rows.len() => {
+            review.selected += 1;
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            review.selected = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            review.selected = rows.len().saturating_sub(1);
+        }
+        KeyCode::Enter | KeyCode::Char(' ') => match current {
+            Some(PlanReviewRow::Module(module)) if !review.collapsed_modules.remove(&module) => {
+                review.collapsed_modules.insert(module);
+            }
+            Some(PlanReviewRow::Resource(idx)) if !review.expanded.remove(&idx) => {
+                review.expand(idx);
+            }
+            _ => {}
+        },
+        KeyCode::Left | KeyCode::Char('h') => {
+            // Fold the enclosing node and move the cursor onto it.
+            let target = match current {
+                Some(PlanReviewRow::Attribute(idx, _)) => {
+                    review.expanded.remove(&idx);
+                    Some(PlanReviewRow::Resource(idx))
+                }
+                Some(PlanReviewRow::Resource(idx)) if review.expanded.remove(&idx) => {
+                    Some(PlanReviewRow::Resource(idx))
+                }
+                Some(PlanReviewRow::Resource(idx)) => {
+                    let module = module_of_address(&review.changes[idx].address).to_string();
+                    review.collapsed_modules.insert(module.clone());
+                    Some(PlanReviewRow::Module(module))
+                }
+                Some(PlanReviewRow::Module(module)) => {
+                    review.collapsed_modules.insert(module.clone());
+                    Some(PlanReviewRow::Module(module))
+                }
+                None => None,
+            };
+            if let Some(target) = target
+                && let Some(position) = review.rows().iter().position(|row| *row == target)
+            {
+                review.selected = position;
+            }
+        }
+        KeyCode::Right | KeyCode::Char('l') => match current {
+            Some(PlanReviewRow::Module(module)) => {
+                review.collapsed_modules.remove(&module);
+            }
+            Some(PlanReviewRow::Resource(idx)) => {
+                review.expand(idx);
+            }
+            _ => {}
+        },
+        KeyCode::Char('-') => {
+            review.expanded.clear();
+            review.collapsed_modules = review.modules().into_keys().map(str::to_string).collect();
+            review.selected = 0;
+        }
+        KeyCode::Char('+') => {
+            review.collapsed_modules.clear();
+        }
+        KeyCode::Char('f') => {
+            let module = match &current {
+                Some(PlanReviewRow::Module(module)) => module.clone(),
+                Some(PlanReviewRow::Resource(idx) | PlanReviewRow::Attribute(idx, _)) => {
+                    module_of_address(&review.changes[*idx].address).to_string()
+                }
+                None => return,
+            };
+            if review.focus.take().is_none() {
+                review.focus = Some(module.clone());
+                let focused: Vec<String> =
+                    review.modules().into_keys().map(str::to_string).collect();
+                for nested in focused {
+                    review.collapsed_modules.remove(&nested);
+                }
+            }
+            // Keep the cursor on the module that was focused or left.
+            let target = PlanReviewRow::Module(module);
+            review.selected = review
+                .rows()
+                .iter()
+                .position(|row| *row == target)
+                .unwrap_or(0);
+        }
+        KeyCode::Char('s') => {
+            review.layout = review.layout.toggled();
+            app.diff_layout = review.layout;
+            // Keep the cursor on the change whose attributes just changed shape.
+            let anchor = match current {
+                Some(PlanReviewRow::Attribute(idx, _)) => Some(PlanReviewRow::Resource(idx)),
+                other => other,
+            };
+            if let Some(anchor) = anchor
+                && let Some(position) = review.rows().iter().position(|row| *row == anchor)
+            {
+                review.selected = position;
+            }
+        }
+        _ => {}
+    }
+
+    let len = review.rows().len();
+    review.selected = review.selected.min(len.saturating_sub(1));
+}
+
+fn module_contains(parent: &str, module: &str) -> bool {
+    module == parent
+        || (!parent.is_empty()
+            && module
+                .strip_prefix(parent)
+                .is_some_and(|rest| rest.starts_with('.')))
+}
+
+fn module_of_address(address: &str) -> &str {
+    let mut end = 0;
+    let mut pos = 0;
+    while let Some(after) = address[pos..].strip_prefix("module.") {
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut split = None;
+        for (idx, ch) in after.char_indices() {
+            match ch {
+                '"' if depth > 0 => in_string = !in_string,
+                '[' if !in_string => depth += 1,
+                ']' if !in_string => depth = depth.saturating_sub(1),
+                '.' if depth == 0 => {
+                    split = Some(idx);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let Some(split) = split else {
+            break;
+        };
+        end = pos + "module.".len() + split;
+        pos = end + 1;
+    }
+    &address[..end]
+}
+
+fn handle_ci_runs_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(view) = app.ci_runs.as_mut() else {
+        return;
+    };
+    let rows = view.rows();
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => {
+            app.ci_runs = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            view.selected = view.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if view.selected + 1 < rows.len() => {
+            view.selected += 1;
+        }
+        KeyCode::Char('r') => {
+            let account_idx = view.account_idx;
+            open_ci_runs(app, account_idx, worker_tx);
+        }
+        KeyCode::Char('p') => {
+            let Some((run_idx, Some(job_idx))) = rows.get(view.selected).copied() else {
+                return;
+            };
+            let run = &view.runs[run_idx];
+            let Some(job) = run.jobs.as_ref().and_then(|jobs| jobs.get(job_idx)) else {
+                return;
+            };
+            if !matches!(view.provider, CiProvider::Gitlab(_)) {
+                app.push_output("Only GitLab manual jobs can be started from lazytf.");
+                return;
+            }
+            if job.status != "manual" {
+                let message = format!(
+                    "Job {} is {}, not a manual job waiting to be started.",
+                    job.name, job.status
+                );
+                app.push_output(message);
+                return;
+            }
+            let confirmation = Confirmation {
+                title: "Start manual CI job".to_string(),
+                lines: vec![
+                    format!("Job: {}", job.name),
+                    format!("Pipeline: #{} on {} ({})", run.number, run.branch, run.sha),
+                    format!("Project: {}", view.source),
+                ],
+                action: ConfirmAction::PlayCiJob {
+                    account_idx: view.account_idx,
+                    run_id: run.id,
+                    job_id: job.id,
+                    name: job.name.clone(),
+                },
+            };
+            app.confirmation = Some(confirmation);
+        }
+        KeyCode::Enter => match rows.get(view.selected).copied() {
+            Some((run_idx, None)) => {
+                let run = &mut view.runs[run_idx];
+                if run.jobs.is_some() {
+                    run.expanded = !run.expanded;
+                    return;
+                }
+                let (account_idx, run_id) = (view.account_idx, run.id);
+                let provider = view.provider.clone();
+                spawn_ci_jobs_fetch(worker_tx, account_idx, provider, run_id);
+            }
+            Some((run_idx, Some(job_idx))) => {
+                let Some(job) = view.runs[run_idx]
+                    .jobs
+                    .as_ref()
+                    .and_then(|jobs| jobs.get(job_idx))
+                    .cloned()
+                else {
+                    return;
+                };
+                let command = ci_job_log_command(&view.provider, job.id);
+                if app.is_busy() {
+                    app.push_output("Another operation is already running. Press `c` to cancel.");
+                    return;
+                }
+                app.ci_runs = None;
+                start_log_attach(
+                    app,
+                    worker_tx.clone(),
+                    format!("CI job {} ({})", job.name, job.id),
+                    command,
+                );
+            }
+            None => {}
+        },
+        _ => {}
+    }
+}
+
+fn spawn_ci_jobs_fetch(
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    account_idx: usize,
+    provider: CiProvider,
+    run_id: u64,
+) {
+    let event_tx = worker_tx.clone();
+    spawn_tracked(worker_tx, format!("CI jobs of run {run_id}"), async move {
+        let jobs = fetch_ci_jobs(&provider, run_id).await;
+        let ok = jobs.is_ok();
+        let _ = event_tx.send(WorkerEvent::CiJobsLoaded {
+            account_idx,
+            run_id,
+            jobs: jobs.map_err(|err| err.to_string()),
+        });
+        ok
+    });
+}
+
+fn open_ci_runs(
+    app: &mut AppState,
+    account_idx: usize,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(account) = app.accounts.get(account_idx) else {
+        return;
+    };
+    let Some(ci) = app.ci.accounts.get(&account.name).cloned() else {
+        app.push_output(format!("No `ci.accounts.{}` configured.", account.name));
+        return;
+    };
+    let provider = match (&ci.workflow, &app.ci.github, &app.ci.gitlab) {
+        (Some(_), Some(github), _) => CiProvider::Github(github.clone()),
+        (Some(_), None, _) => {
+            app.push_output("`workflow` is set but no `ci.github` is configured.");
+            return;
+        }
+        (None, _, Some(gitlab)) => CiProvider::Gitlab(gitlab.clone()),
+        (None, _, None) => {
+            app.push_output(format!(
+                "`ci.accounts.{}` needs a `workflow` (GitHub) or a `ci.gitlab` project.",
+                account.name
+            ));
+            return;
+        }
+    };
+    let source = match &ci.workflow {
+        Some(workflow) => format!("{} {workflow}", provider.source()),
+        None => provider.source(),
+    };
+    let runs = app
+        .ci_runs
+        .take()
+        .filter(|view| view.account_idx == account_idx)
+        .map(|view| view.runs)
+        .unwrap_or_default();
+    app.ci_runs = Some(CiRunsView {
+        account_idx,
+        provider: provider.clone(),
+        source: source.clone(),
+        runs,
+        loading: true,
+        selected: 0,
+    });
+    let event_tx = worker_tx.clone();
+    spawn_tracked(worker_tx, format!("CI runs of {source}"), async move {
+        let runs = fetch_ci_runs(&provider, ci.workflow.as_deref(), ci.branch.as_deref()).await;
+        let ok = runs.is_ok();
+        let _ = event_tx.send(WorkerEvent::CiRunsLoaded {
+            account_idx,
+            runs: runs.map_err(|err| err.to_string()),
+        });
+        ok
+    });
+}
+
+fn handle_lock_table_key(app: &mut AppState, key: KeyEvent) {
+    let Some(view) = app.lock_table.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('L') => {
+            app.lock_table = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            view.selected = view.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if view.selected + 1 < view.locks.len() => {
+            view.selected += 1;
+        }
+        _ => {}
+    }
+}
+
+fn handle_state_browser_key(
+    app: &mut AppState,
+    key: KeyEvent,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let Some(browser) = app.state_browser.as_mut() else {
+        return;
+    };
+
+    if let Some(detail) = browser.detail.as_mut() {
+        let max_scroll = detail.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Backspace => {
+                browser.detail = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                detail.scroll = detail.scroll.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                detail.scroll = (detail.scroll + 1).min(max_scroll);
+            }
+            KeyCode::PageUp => {
+                detail.scroll = detail.scroll.saturating_sub(20);
+            }
+            KeyCode::PageDown => {
+                detail.scroll = (detail.scroll + 20).min(max_scroll);
+            }
+            KeyCode::Home | KeyCode::Char('g') => {
+                detail.scroll = 0;
+            }
+            KeyCode::End | KeyCode::Char('G') => {
+                detail.scroll = max_scroll;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('S') => {
+            app.state_browser = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            browser.selected = browser.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if browser.selected + 1 < browser.resources.len() => {
+            browser.selected += 1;
+        }
+        KeyCode::PageUp => {
+            browser.selected = browser.selected.saturating_sub(10);
+        }
+        KeyCode::PageDown => {
+            browser.selected =
+                (browser.selected + 10).min(browser.resources.len().saturating_sub(1));
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            browser.selected = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            browser.selected = browser.resources.len().saturating_sub(1);
+        }
+        KeyCode::Char('m') => {
+            let Some(source) = browser.resources.get(browser.selected).cloned() else {
+                return;
+            };
+            app.prompt = Some(TextPrompt {
+                title: format!("Move `{source}` in {} state", browser.workspace),
+                purpose: PromptPurpose::StateMoveDestination {
+                    account_idx: browser.account_idx,
+                    workspace: browser.workspace.clone(),
+                    source: source.clone(),
+                },
+                hint: "Destination address (e.g. module.network.aws_vpc.main)".to_string(),
+                input: source,
+                error: None,
+            });
+        }
+        KeyCode::Char(' ') => {
+            if let Some(address) = browser.resources.get(browser.selected).cloned()
+                && !browser.marked.remove(&address)
+            {
+                browser.marked.insert(address);
+            }
+            if browser.selected + 1 < browser.resources.len() {
+                browser.selected += 1;
+            }
+        }
+        KeyCode::Char('p') | KeyCode::Char('A') => {
+            let targets: Vec<String> = if browser.marked.is_empty() {
+                browser
+                    .resources
+                    .get(browser.selected)
+                    .cloned()
+                    .into_iter()
+                    .collect()
+            } else {
+                browser.marked.iter().cloned().collect()
+            };
+            if targets.is_empty() {
+                return;
+            }
+            let (account_idx, workspace) = (browser.account_idx, browser.workspace.clone());
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            app.state_browser = None;
+            if key.code == KeyCode::Char('A') {
+                // Targeted applies go through a saved plan like any other apply.
+                app.push_output(
+                    "Planning the targets first; review the plan and press `A` to apply it.",
+                );
+            }
+            let mut tuning = app.run_tuning(account_idx);
+            tuning.targets = targets;
+            request_run(
+                app,
+                worker_tx,
+                OperationKind::TerraformPlan,
+                RunOptions {
+                    workspace: Some(workspace),
+                    tuning: Some(tuning),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        KeyCode::Char('t') | KeyCode::Char('u') => {
+            let Some(address) = browser.resources.get(browser.selected).cloned() else {
+                return;
+            };
+            let supports_replace = app
+                .accounts
+                .get(browser.account_idx)
+                .is_some_and(AccountState::supports_replace);
+            let (mode, mut lines) = match key.code {
+                KeyCode::Char('u') => (
+                    TaintMode::Untaint,
+                    vec![
+                        format!("Clear the tainted mark on `{address}`?"),
+                        "Runs `terraform untaint`.".to_string(),
+                    ],
+                ),
+                _ if supports_replace => (
+                    TaintMode::Replace,
+                    vec![
+                        format!("Replace `{address}`?"),
+                        "Runs `terraform plan -replace` (taint is deprecated since 0.15.2);"
+                            .to_string(),
+                        "review it, then `A` `y` applies the saved plan.".to_string(),
+                    ],
+                ),
+                _ => (
+                    TaintMode::Taint,
+                    vec![
+                        format!("Mark `{address}` as tainted?"),
+                        "Runs `terraform taint`; the next apply destroys and recreates it."
+                            .to_string(),
+                    ],
+                ),
+            };
+            lines.push(String::new());
+            lines.push("Press `y` to continue, any other key cancels.".to_string());
+            app.confirmation = Some(Confirmation {
+                title: format!("{} in {}", mode.label(), browser.workspace),
+                lines,
+                action: ConfirmAction::StateTaint {
+                    account_idx: browser.account_idx,
+                    workspace: browser.workspace.clone(),
+                    address,
+                    mode,
+                },
+            });
+        }
+        KeyCode::Char('i') => {
+            let Some(address) = browser.resources.get(browser.selected).cloned() else {
+                return;
+            };
+            let Some(account) = app.accounts.get(browser.account_idx).cloned() else {
+                return;
+            };
+            let (account_idx, workspace) = (browser.account_idx, browser.workspace.clone());
+            let event_tx = worker_tx.clone();
+            spawn_tracked(
+                worker_tx,
+                format!("terraform graph impact of {address}"),
+                async move {
+                    let mut command = terraform_command(&account, &["graph"]);
+                    command.stdin(Stdio::null());
+                    let dependents = match command.output().await {
+                        Ok(output) if output.status.success() => Ok(graph_dependents(
+                            &String::from_utf8_lossy(&output.stdout),
+                            &strip_address_keys(&address),
+                        )),
+                        Ok(output) => {
+                            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                        }
+                        Err(err) => Err(err.to_string()),
+                    };
+                    let ok = dependents.is_ok();
+                    let _ = event_tx.send(WorkerEvent::ImpactComputed {
+                        account_idx,
+                        workspace,
+                        address,
+                        dependents,
+                    });
+                    ok
+                },
+            );
+        }
+        KeyCode::Char('d') => {
+            let Some(address) = browser.resources.get(browser.selected).cloned() else {
+                return;
+            };
+            app.confirmation = Some(Confirmation {
+                title: "terraform state rm".to_string(),
+                lines: vec![
+                    format!("Remove `{address}`"),
+                    format!("from the state of `{}`?", browser.workspace),
+                    String::new(),
+                    "Terraform stops managing it; the real resource is not destroyed.".to_string(),
+                    "Press `y` to remove it, any other key cancels.".to_string(),
+                ],
+                action: ConfirmAction::StateRemove {
+                    account_idx: browser.account_idx,
+                    workspace: browser.workspace.clone(),
+                    address,
+                },
+            });
+        }
+        KeyCode::Enter => {
+            let Some(address) = browser.resources.get(browser.selected).cloned() else {
+                return;
+            };
+            let (account_idx, workspace) = (browser.account_idx, browser.workspace.clone());
+            if app.is_busy() {
+                app.push_output("Another operation is already running. Press `c` to cancel.");
+                return;
+            }
+            if app.selected_account != account_idx {
+                app.state_browser = None;
+                app.push_output("Account selection changed; press `S` to reload the state.");
+                return;
+            }
+            start_terraform_operation(
+                app,
+                worker_tx.clone(),
+                OperationKind::StateShow,
+                RunOptions {
+                    workspace: Some(workspace),
+                    state_address: Some(address),
+                    ..RunOptions::default()
+                },
+            );
+        }
+        _ => {}
+    }
+}
+
+fn request_plan_blame(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let Some(view) = app.plan_changes.as_mut() else {
+        return;
+    };
+    if view.blame_requested {
+        return;
+    }
+    let Some(account) = app.accounts.get(view.account_idx) else {
+        return;
+    };
+    view.blame_requested = true;
+
+    let root = account.composition_path.clone();
+    let addresses: Vec<String> = view
+        .changes
+        .iter()
+        .map(|change| change.address.clone())
+        .collect();
+    let limiter = Arc::clone(&app.background_limiter);
+    let event_tx = worker_tx.clone();
+    let label = format!("git blame {} change(s)", addresses.len());
+    spawn_tracked(worker_tx, label, async move {
+        let Ok(_permit) = limiter.acquire_owned().await else {
+            return false;
+        };
+        for address in addresses {
+            let Ok((path, line)) = locate_resource_source(&root, &address) else {
+                continue;
+            };
+            if let Some(blame) = last_block_commit(&path, line).await {
+                let _ = event_tx.send(WorkerEvent::BlameLoaded { address, blame });
+            }
+        }
+        true
+    });
+}
+
+async fn last_block_commit(path: &Path, line: usize) -> Option<String> {
+    let dir = path.parent()?;
+    let file = path.file_name()?.to_string_lossy().to_string();
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args([
+            "log",
+            "-n",
+            "1",
+            "--no-patch",
+            "--format=%h %an, %ar: %s",
+            "-L",
+            &format!("{line},/^}}/:{file}"),
+        ])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+}
+
+async fn uncommitted_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut changed = Vec::new();
+    for path in paths {
+        let (Some(dir), Some(file)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(["status", "--porcelain", "--"])
+            .arg(file)
+            .stdin(Stdio::null())
+            .output()
+            .await;
+        if let Ok(output) = output
+            && output.status.success()
+            && !output.stdout.is_empty()
+        {
+            changed.push(path.clone());
+        }
+    }
+    changed
+}
+
+fn spawn_change_proposal(
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+    paths: Vec<PathBuf>,
+    title: String,
+) {
+    let event_tx = worker_tx.clone();
+    spawn_tracked(worker_tx, format!("git status for {title}"), async move {
+        let paths = uncommitted_paths(&paths).await;
+        if !paths.is_empty() {
+            let _ = event_tx.send(WorkerEvent::ChangesToPropose { paths, title });
+        }
+        true
+    });
+}
+
+async fn git_stdout(dir: &Path, args: &[&str], paths: &[PathBuf]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .args(paths)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .wrap_err("Failed to run git")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "git {}: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Commits `paths` on a new `lazytf/` branch, pushes it to `origin`, and opens a pull
+/// request (GitHub) or merge request (GitLab) against the branch it started from. On
+/// failure the repo is put back on that branch with the changes uncommitted.
+async fn open_change_request(host: &CiProvider, paths: &[PathBuf], title: &str) -> Result<String> {
+    let dir = paths
+        .first()
+        .and_then(|path| path.parent())
+        .ok_or_else(|| eyre!("no files to commit"))?;
+    let base = git_stdout(dir, &["rev-parse", "--abbrev-ref", "HEAD"], &[]).await?;
+    if base == "HEAD" {
+        return Err(eyre!("the repository is on a detached HEAD"));
+    }
+    let base_commit = git_stdout(dir, &["rev-parse", "HEAD"], &[]).await?;
+    let slug: String = title
+        .to_ascii_lowercase()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect();
+    let branch = format!(
+        "lazytf/{}-{}",
+        slug.split('-')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-"),
+        unix_now()
+    );
+    git_stdout(dir, &["switch", "--create", &branch], &[]).await?;
+    let err = match commit_and_request(host, dir, paths, title, &base, &branch).await {
+        // The files now live on the pushed branch; return to where the user was working.
+        Ok(url) => {
+            return Ok(match git_stdout(dir, &["switch", &base], &[]).await {
+                Ok(_) => url,
+                Err(err) => format!("{url} (still on `{branch}`: {err:#})"),
+            });
+        }
+        Err(err) => err,
+    };
+    let rollback = async {
+        git_stdout(dir, &["reset", "--soft", &base_commit], &[]).await?;
+        git_stdout(dir, &["reset", "--quiet", &base_commit, "--"], paths).await?;
+        git_stdout(dir, &["switch", &base], &[]).await?;
+        git_stdout(dir, &["branch", "--delete", "--force", &branch], &[]).await
+    };
+    Err(match rollback.await {
+        Ok(_) => err.wrap_err(format!("back on `{base}` with the changes uncommitted")),
+        Err(rollback_err) => err.wrap_err(format!(
+            "and returning to `{base}` failed, the repo is left on `{branch}`: {rollback_err}"
+        )),
+    })
+}
+
+async fn commit_and_request(
+    host: &CiProvider,
+    dir: &Path,
+    paths: &[PathBuf],
+    title: &str,
+    base: &str,
+    branch: &str,
+) -> Result<String> {
+    // New files need `add`; `--only` keeps anything else staged out of the commit.
+    git_stdout(dir, &["add", "--"], paths).await?;
+    git_stdout(dir, &["commit", "--only", "--message", title, "--"], paths).await?;
+    git_stdout(dir, &["push", "--set-upstream", "origin", branch], &[]).await?;
+
+    let body = format!("Opened from lazytf by {}.", current_operator());
+    let (url, payload, url_key) = match host {
+        CiProvider::Github(github) => (
+            format!("{}/repos/{}/pulls", github.api_url, github.repo),
+            serde_json::json!({ "title": title, "head": branch, "base": base, "body": body }),
+            "html_url",
+        ),
+        CiProvider::Gitlab(gitlab) => (
+            format!("{}/merge_requests", gitlab.project_url()),
+            serde_json::json!({
+                "title": title,
+                "source_branch": branch,
+                "target_branch": base,
+                "description": body,
+            }),
+            "web_url",
+        ),
+    };
+    let response = ci_api_json(host, "POST", &url, Some(&payload))
+        .await
+        .wrap_err_with(|| format!("pushed `{branch}`, but creating the request failed"))?;
+    Ok(response[url_key]
+        .as_str()
+        .map_or_else(|| branch.to_string(), str::to_string))
+}
+
+static RESOURCE_ADDRESS: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    const NAME: &str = r"[A-Za-z_][A-Za-z0-9_-]*";
+    const KEY: &str = r#"(\[([0-9]+|"[^"\\$%{}\n]*")\])?"#;
+    Regex::new(&format!(r"^(module\.{NAME}{KEY}\.)*{NAME}\.{NAME}{KEY}$"))
+        .expect("valid resource address regex")
+});
+
+fn is_resource_address(address: &str) -> bool {
+    RESOURCE_ADDRESS.is_match(address)
+}
+
+/// `value` as a quoted HCL string; a bare `${` or `%{` would start a template.
+fn hcl_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+        .replace("${", "$${")
+        .replace("%{", "%%{");
+    format!("\"{escaped}\"")
+}
+
+fn strip_address_keys(address: &str) -> String {
+    let mut stripped = String::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    for ch in address.chars() {
+        match ch {
+            '"' if depth > 0 => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(ch),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn resource_type_of_address(address: &str) -> String {
+    let stripped = strip_address_keys(address);
+    let mut parts = stripped.split('.');
+    while let Some(part) = parts.next() {
+        match part {
+            "module" => {
+                parts.next();
+            }
+            "data" => {}
+            _ => return part.to_string(),
+        }
+    }
+    String::new()
+}
+
+fn graph_dependents(dot: &str, address: &str) -> Vec<(usize, String)> {
+    let node_name = |raw: &str| -> String {
+        let raw = raw.trim().trim_end_matches(';').trim();
+        let raw = raw.strip_prefix('"').unwrap_or(raw);
+        let raw = raw.strip_suffix('"').unwrap_or(raw);
+        let raw = raw.strip_prefix("[root] ").unwrap_or(raw);
+        match raw.find(" (") {
+            Some(index) => raw[..index].to_string(),
+            None => raw.to_string(),
+        }
+    };
+    // `a -> b` means a depends on b.
+    let mut dependents_of: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for line in dot.lines() {
+        let Some((from, to)) = line.split_once(" -> ") else {
+            continue;
+        };
+        let to = to.split(" [").next().unwrap_or(to);
+        dependents_of
+            .entry(node_name(to))
+            .or_default()
+            .insert(node_name(from));
+    }
+
+    let is_listed = |node: &str| {
+        let mut parts: Vec<&str> = node.split('.').collect();
+        while parts.len() > 2 && parts[0] == "module" {
+            parts.drain(..2);
+        }
+        match parts.as_slice() {
+            ["output", _] => !node.starts_with("module."),
+            ["data", _, _] => true,
+            [kind, _] => !matches!(*kind, "var" | "local" | "module" | "provider" | "meta"),
+            _ => false,
+        }
+    };
+
+    // Depth counts listed nodes only, so a resource reached through a local is still direct.
+    let mut seen = BTreeSet::from([address.to_string()]);
+    let mut frontier = vec![(address.to_string(), 0)];
+    let mut result = Vec::new();
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for (node, depth) in frontier {
+            for dependent in dependents_of.get(&node).into_iter().flatten() {
+                if !seen.insert(dependent.clone()) {
+                    continue;
+                }
+                let depth = depth + usize::from(is_listed(dependent));
+                if is_listed(dependent) {
+                    result.push((depth, dependent.clone()));
+                }
+                next.push((dependent.clone(), depth));
+            }
+        }
+        frontier = next;
+    }
+    result.sort_by_key(|(depth, _)| *depth);
+    result
+}
+
+fn locate_resource_source(composition_path: &Path, address: &str) -> Result<(PathBuf, usize)> {
+    let stripped = strip_address_keys(address);
+    let mut parts: Vec<&str> = stripped.split('.').collect();
+
+    let mut module_keys = Vec::new();
+    while parts.len() > 2 && parts[0] == "module" {
+        module_keys.push(parts[1]);
+        parts.drain(0..2);
+    }
+    let (block, resource_type, name) = match parts.as_slice() {
+        ["data", resource_type, name] => ("data", *resource_type, *name),
+        [resource_type, name] => ("resource", *resource_type, *name),
+        _ => return Err(eyre!("unrecognised resource address")),
+    };
+
+    let dir = if module_keys.is_empty() {
+        composition_path.to_path_buf()
+    } else {
+        let manifest_path = composition_path.join(".terraform/modules/modules.json");
+        let manifest: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(&manifest_path)
+                .wrap_err("module manifest not found; run `terraform init` first")?,
+        )
+        .wrap_err("failed to parse module manifest")?;
+        let key = module_keys.join(".");
+        let module_dir = manifest
+            .get("Modules")
+            .and_then(serde_json::Value::as_array)
+            .and_then(|modules| {
+                modules.iter().find(|module| {
+                    module.get("Key").and_then(serde_json::Value::as_str) == Some(key.as_str())
+                })
+            })
+            .and_then(|module| module.get("Dir"))
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| eyre!("module `{key}` not found in module manifest"))?;
+        composition_path.join(module_dir)
+    };
+
+    let pattern = Regex::new(&format!(
+        r#"^\s*{block}\s+"{}"\s+"{}""#,
+        regex::escape(resource_type),
+        regex::escape(name)
+    ))?;
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .wrap_err_with(|| format!("failed to read {}", dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tf"))
+        .collect();
+    files.sort();
+    for file in files {
+        let Ok(contents) = fs::read_to_string(&file) else {
+            continue;
+        };
+        if let Some(line) = contents.lines().position(|line| pattern.is_match(line)) {
+            return Ok((file, line + 1));
+        }
+    }
+    Err(eyre!(
+        "no `{block} \"{resource_type}\" \"{name}\"` block in {}",
+        dir.display()
+    ))
+}
+
+fn open_in_editor(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut AppState,
+    path: &Path,
+    line: usize,
+) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let Some(program) = parts.next() else {
+        app.push_output("$EDITOR is empty.");
+        return Ok(());
+    };
+
+    let mouse_captured = app.mouse_enabled && !app.mouse_released;
+    restore_terminal(terminal, mouse_captured)?;
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(format!("+{line}"))
+        .arg(path)
+        .status();
+    enable_raw_mode().wrap_err("Failed to enable terminal raw mode")?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)
+        .wrap_err("Failed to enter alternate screen")?;
+    if mouse_captured {
+        set_mouse_capture(terminal, true)?;
+    }
+    terminal.clear().wrap_err("Failed to redraw terminal")?;
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => app.push_output(format!("Editor exited with {status}")),
+        Err(err) => app.push_output(format!("Failed to launch `{program}`: {err}")),
+    }
+    Ok(())
+}
+
+fn open_notes(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let path = account
+        .composition_path
+        .join(account.docs_path.as_deref().unwrap_or("README.md"));
+    match fs::read_to_string(&path) {
+        Ok(contents) => {
+            let mut in_code_block = false;
+            let lines = contents
+                .lines()
+                .map(|line| render_markdown_line(line, &mut in_code_block))
+                .collect();
+            app.notes = Some(FileView {
+                path,
+                lines,
+                scroll: 0,
+            });
+        }
+        Err(err) => app.push_output(format!(
+            "No notes for `{}` ({}: {err})",
+            account.name,
+            path.display()
+        )),
+    }
+}
+
+fn handle_notes_key(app: &mut AppState, key: KeyEvent) {
+    let Some(notes) = app.notes.as_mut() else {
+        return;
+    };
+    let max_scroll = notes.lines.len().saturating_sub(1);
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('R') => {
+            app.notes = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            notes.scroll = notes.scroll.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            notes.scroll = (notes.scroll + 1).min(max_scroll);
+        }
+        KeyCode::PageUp => {
+            notes.scroll = notes.scroll.saturating_sub(20);
+        }
+        KeyCode::PageDown => {
+            notes.scroll = (notes.scroll + 20).min(max_scroll);
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            notes.scroll = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            notes.scroll = max_scroll;
+        }
+        _ => {}
+    }
+}
+
+fn render_markdown_line(line: &str, in_code_block: &mut bool) -> Line<'static> {
+    let code = Style::default().fg(Color::Green);
+    let trimmed = line.trim_start();
+
+    if trimmed.starts_with("```") {
+        *in_code_block = !*in_code_block;
+        return Line::from(Span::styled(
+            "─".repeat(40),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if *in_code_block {
+        return Line::from(Span::styled(format!("  {line}"), code));
+    }
+
+    let heading_level = trimmed.chars().take_while(|ch| *ch == '#').count();
+    if heading_level > 0 && trimmed[heading_level..].starts_with(' ') {
+        let style = if heading_level == 1 {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        };
+        return Line::from(Span::styled(
+            trimmed[heading_level..].trim().to_string(),
+            style,
+        ));
+    }
+
+    let indent = &line[..line.len() - trimmed.len()];
+    let (prefix, body) = if let Some(rest) = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+    {
+        (format!("{indent}• "), rest)
+    } else if let Some(rest) = trimmed.strip_prefix("> ") {
+        (format!("{indent}│ "), rest)
+    } else {
+        (indent.to_string(), trimmed)
+    };
+
+    let mut spans = vec![Span::raw(prefix)];
+    let mut plain = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let (delimiter, style) = match ch {
+            '`' => ("`", Style::default().fg(Color::Yellow)),
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                ("**", Style::default().add_modifier(Modifier::BOLD))
+            }
+            _ => {
+                plain.push(ch);
+                continue;
+            }
+        };
+        let rest: String = chars.clone().collect();
+        let Some(end) = rest.find(delimiter) else {
+            plain.push_str(delimiter);
+            continue;
+        };
+        if !plain.is_empty() {
+            spans.push(Span::raw(std::mem::take(&mut plain)));
+        }
+        spans.push(Span::styled(rest[..end].to_string(), style));
+        for _ in 0..rest[..end].chars().count() + delimiter.len() {
+            chars.next();
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+    Line::from(spans)
+}
+
+fn open_file_browser(app: &mut AppState) {
+    let Some(account) = app.selected_account() else {
+        app.push_output("No account selected.");
+        return;
+    };
+    let root = account.composition_path.clone();
+    let mut files = Vec::new();
+    if let Err(err) = collect_hcl_files(&root, &root, &mut files) {
+        app.push_output(format!(
+            "Failed to read composition directory {}: {err}",
+            root.display()
+        ));
+        return;
+    }
+    files.sort();
+    app.file_browser = Some(FileBrowser {
+        root,
+        files,
+        selected: 0,
+        viewer: None,
+    });
+}
+
+fn collect_hcl_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_hcl_files(root, &path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "tf" || ext == "tfvars" || ext == "hcl")
+            && let Ok(relative) = path.strip_prefix(root)
+        {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn open_file_view(path: &Path, line: usize) -> Result<FileView> {
+    let contents =
+        fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let mut in_block_comment = false;
+    let lines = contents
+        .lines()
+        .map(|line| highlight_hcl_line(line, &mut in_block_comment))
+        .collect();
+    Ok(FileView {
+        path: path.to_path_buf(),
+        lines,
+        scroll: line.saturating_sub(1),
+    })
+}
+
+fn handle_file_browser_key(app: &mut AppState, key: KeyEvent) {
+    let Some(browser) = app.file_browser.as_mut() else {
+        return;
+    };
+
+    if let Some(view) = browser.viewer.as_mut() {
+        let max_scroll = view.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Backspace => {
+                browser.viewer = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                view.scroll = view.scroll.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                view.scroll = (view.scroll + 1).min(max_scroll);
+            }
+            KeyCode::PageUp => {
+                view.scroll = view.scroll.saturating_sub(20);
+            }
+            KeyCode::PageDown => {
+                view.scroll = (view.scroll + 20).min(max_scroll);
+            }
+            KeyCode::Home | KeyCode::Char('g') => {
+                view.scroll = 0;
+            }
+            KeyCode::End | KeyCode::Char('G') => {
+                view.scroll = max_scroll;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('F') => {
+            app.file_browser = None;
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            browser.selected = browser.selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if browser.selected + 1 < browser.files.len() => {
+            browser.selected += 1;
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            browser.selected = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            browser.selected = browser.files.len().saturating_sub(1);
+        }
+        KeyCode::Enter | KeyCode::Char('l') => {
+            let Some(relative) = browser.files.get(browser.selected) else {
+                return;
+            };
+            match open_file_view(&browser.root.join(relative), 1) {
+                Ok(view) => browser.viewer = Some(view),
+                Err(err) => app.push_output(format!("{err}")),
+            }
+        }
+        _ => {}
+    }
+}
+
+const HCL_KEYWORDS: [&str; 14] = [
+    "resource",
+    "data",
+    "module",
+    "variable",
+    "output",
+    "locals",
+    "provider",
+    "terraform",
+    "moved",
+    "import",
+    "for",
+    "in",
+    "if",
+    "dynamic",
+];
+
+fn highlight_hcl_line(line: &str, in_block_comment: &mut bool) -> Line<'static> {
+    let comment = Style::default().fg(Color::DarkGray);
+    let string = Style::default().fg(Color::Green);
+    let keyword = Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD);
+    let literal = Style::default().fg(Color::Magenta);
+    let attribute = Style::default().fg(Color::Yellow);
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut plain = String::new();
+    let mut idx = 0;
+
+    let flush = |plain: &mut String, spans: &mut Vec<Span<'static>>| {
+        if !plain.is_empty() {
+            spans.push(Span::raw(std::mem::take(plain)));
+        }
+    };
+
+    while idx < chars.len() {
+        if *in_block_comment {
+            let start = idx;
+            while idx < chars.len() && !(chars[idx] == '*' && chars.get(idx + 1) == Some(&'/')) {
+                idx += 1;
+            }
+            if idx < chars.len() {
+                idx += 2;
+                *in_block_comment = false;
+            }
+            spans.push(Span::styled(
+                chars[start..idx.min(chars.len())]
+                    .iter()
+                    .collect::<String>(),
+                comment,
+            ));
+            continue;
+        }
+
+        let ch = chars[idx];
+        let next = chars.get(idx + 1).copied();
+        if ch == '#' || (ch == '/' && next == Some('/')) {
+            flush(&mut plain, &mut spans);
+            spans.push(Span::styled(
+                chars[idx..].iter().collect::<String>(),
+                comment,
+            ));
+            break;
+        }
+        if ch == '/' && next == Some('*') {
+            flush(&mut plain, &mut spans);
+            *in_block_comment = true;
+            continue;
+        }
+        if ch == '"' {
+            flush(&mut plain, &mut spans);
+            let start = idx;
+            idx += 1;
+            while idx < chars.len() && chars[idx] != '"' {
+                if chars[idx] == '\\' {
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            idx = (idx + 1).min(chars.len());
+            spans.push(Span::styled(
+                chars[start..idx].iter().collect::<String>(),
+                string,
+            ));
+            continue;
+        }
+        if ch.is_ascii_digit() {
+            flush(&mut plain, &mut spans);
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                idx += 1;
+            }
+            spans.push(Span::styled(
+                chars[start..idx].iter().collect::<String>(),
+                literal,
+            ));
+            continue;
+        }
+        if ch.is_ascii_alphabetic() || ch == '_' {
+            let start = idx;
+            while idx < chars.len()
+                && (chars[idx].is_ascii_alphanumeric() || chars[idx] == '_' || chars[idx] == '-')
+            {
+                idx += 1;
+            }
+            let word: String = chars[start..idx].iter().collect();
+            let rest = chars[idx..].iter().collect::<String>();
+            let rest = rest.trim_start();
+            let style = if matches!(word.as_str(), "true" | "false" | "null") {
+                Some(literal)
+            } else if HCL_KEYWORDS.contains(&word.as_str()) {
+                Some(keyword)
+            } else if rest.starts_with('=') && !rest.starts_with("==") {
+                Some(attribute)
+            } else {
+                None
+            };
+            match style {
+                Some(style) => {
+                    flush(&mut plain, &mut spans);
+                    spans.push(Span::styled(word, style));
+                }
+                None => plain.push_str(&word),
+            }
+            continue;
+        }
+
+        plain.push(ch);
+        idx += 1;
+    }
+    flush(&mut plain, &mut spans);
+    Line::from(spans)
+}
+
+fn parse_plan_summary_line(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let line = line.trim();
+    if line.starts_with("No changes.") {
+        return Some((0, 0, 0, 0));
+    }
+    let counts = line.strip_prefix("Plan: ")?;
+    let (mut import, mut add, mut change, mut destroy) = (0, 0, 0, 0);
+    for part in counts.trim_end_matches('.').split(", ") {
+        let (count, verb) = part.split_once(" to ")?;
+        let count = count.parse().ok()?;
+        match verb {
+            "import" => import = count,
+            "add" => add = count,
+            "change" => change = count,
+            "destroy" => destroy = count,
+            _ => {}
+        }
+    }
+    Some((import, add, change, destroy))
+}
+
+fn is_summary_line(line: &str) -> bool {
+    let line = line.trim();
+    [
+        "Plan:",
+        "Apply complete!",
+        "Destroy complete!",
+        "No changes.",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+}
+
+fn prefetch_hovered_account(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    if !app.prefetch.enabled {
+        return;
+    }
+
+    let account_idx = app.selected_account;
+    let hovered_since = match app.hovered_account {
+        Some((idx, since)) if idx == account_idx => since,
+        _ => {
+            app.hovered_account = Some((account_idx, Instant::now()));
+            return;
+        }
+    };
+    if hovered_since.elapsed() < Duration::from_millis(app.prefetch.hover_delay_ms)
+        || app.prefetched_accounts.contains(&account_idx)
+    {
+        return;
+    }
+
+    let Some(account) = app.selected_account().cloned() else {
+        return;
+    };
+    if account.auth != AuthStatus::Authenticated
+        || account.composition_issue.is_some()
+        || !account.workspaces.is_empty()
+    {
+        return;
+    }
+
+    app.prefetched_accounts.insert(account_idx);
+    let limiter = Arc::clone(&app.background_limiter);
+    let event_tx = worker_tx.clone();
+    let label = format!("prefetch workspaces {}", account.name);
+    spawn_tracked(worker_tx, label, async move {
+        let Ok(_permit) = limiter.acquire_owned().await else {
+            return false;
+        };
+        let Ok((workspaces, current)) = fetch_workspaces(&account).await else {
+            return false;
+        };
+        let _ = event_tx.send(WorkerEvent::WorkspacesPrefetched {
+            account_idx,
+            workspaces,
+            current,
+        });
+        true
+    });
+}
+
+fn schedule_drift_checks(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let now = unix_now();
+    for account_idx in 0..app.accounts.len() {
+        let account = &app.accounts[account_idx];
+        let Some(config) = account.drift_check.filter(|config| config.enabled) else {
+            continue;
+        };
+        if account.auth != AuthStatus::Authenticated
+            || account.composition_issue.is_some()
+            || account.workspaces.is_empty()
+            || app
+                .next_drift_check
+                .get(&account_idx)
+                .is_some_and(|next| now < *next)
+            || app
+                .inflight
+                .as_ref()
+                .is_some_and(|inflight| inflight.account_idx == account_idx)
+        {
+            continue;
+        }
+
+        let interval = config.interval_minutes.max(1) * 60;
+        app.next_drift_check.insert(account_idx, now + interval);
+        // Results persist across restarts, so only re-check workspaces that are due.
+        let due: Vec<String> = account
+            .workspaces
+            .iter()
+            .filter(|workspace| {
+                app.drift
+                    .get(&cost_key(&account.name, workspace))
+                    .is_none_or(|record| record.checked_at + interval <= now)
+            })
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            continue;
+        }
+
+        let account = account.clone();
+        let limiter = Arc::clone(&app.background_limiter);
+        let event_tx = worker_tx.clone();
+        let label = format!("drift check {} ({} workspaces)", account.name, due.len());
+        spawn_tracked(worker_tx, label, async move {
+            let mut all_ok = true;
+            for workspace in due {
+                let Ok(_permit) = limiter.clone().acquire_owned().await else {
+                    return false;
+                };
+                let mut args = vec![
+                    "plan".to_string(),
+                    "-refresh-only".to_string(),
+                    "-detailed-exitcode".to_string(),
+                    "-lock=false".to_string(),
+                    "-input=false".to_string(),
+                    "-no-color".to_string(),
+                ];
+                // Saved only to tell noisy drift from real drift.
+                let drift_plan = saved_plan_path(&account.name, &format!("{workspace}.drift"));
+                if !account.noisy_attributes.is_empty() && prepare_plan_dir(&drift_plan).is_ok() {
+                    args.push(format!("-out={}", drift_plan.display()));
+                }
+                append_var_file_args(&mut args, &account.var_files);
+                let mut command = terraform_command_owned(&account, &args);
+                apply_tf_vars(&mut command, &account, &workspace);
+                command.env("TF_WORKSPACE", &workspace).stdin(Stdio::null());
+                let mut status = match command.output().await.map(|output| output.status.code()) {
+                    Ok(Some(0)) => DriftStatus::Clean,
+                    Ok(Some(2)) => DriftStatus::Drifted,
+                    _ => DriftStatus::Failed,
+                };
+                if status == DriftStatus::Drifted
+                    && drift_plan.exists()
+                    && let Ok(plan) = show_plan_json(&account, &drift_plan).await
+                {
+                    let mut drift = parse_resource_changes(&plan["resource_drift"]);
+                    mark_noisy_attributes(&mut drift, &account.noisy_attributes);
+                    if !drift.is_empty() && drift.iter().all(ResourceChange::is_noise_only) {
+                        status = DriftStatus::Clean;
+                    }
+                }
+                let _ = fs::remove_file(&drift_plan);
+                all_ok &= status != DriftStatus::Failed;
+                let _ = event_tx.send(WorkerEvent::DriftChecked {
+                    account_idx,
+                    workspace,
+                    status,
+                });
+            }
+            all_ok
+        });
+    }
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn latest_mtime(paths: &[PathBuf]) -> Option<SystemTime> {
+    paths.iter().filter_map(|path| file_mtime(path)).max()
+}
+
+fn watch_config_file(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    if !app.watch_config || app.is_busy() || app.has_running_tasks() || app.config_path.is_none() {
+        return;
+    }
+    let mtime = latest_mtime(&app.config_watch);
+    if mtime.is_some() && mtime != app.config_mtime {
+        reload_config(app, worker_tx);
+    }
+}
+
+fn reload_config(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    if app.is_busy() {
+        app.push_output("Config reload waits until the running operation finishes.");
+        return;
+    }
+    if app.has_running_tasks() {
+        app.push_output("Config reload waits until the background tasks finish (see `b`).");
+        return;
+    }
+    let Some(path) = app.config_path.clone() else {
+        return;
+    };
+    // Remember the attempt, so a half-written file is reported once, not on every tick.
+    app.config_mtime = latest_mtime(&app.config_watch);
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let low_memory = app.low_memory;
+    let fresh = load_config(&cwd, Some(&path)).and_then(|mut loaded| {
+        loaded.config.low_memory.enabled |= low_memory;
+        let watch = loaded.watch_paths();
+        AppState::from_config(loaded.config, &loaded.base_dir).map(|fresh| (fresh, watch))
+    });
+    let mut fresh = match fresh {
+        Ok((fresh, watch)) => {
+            app.config_mtime = latest_mtime(&watch);
+            app.config_watch = watch;
+            fresh
+        }
+        Err(err) => {
+            app.push_output(format!(
+                "Config reload failed, keeping the current config: {err:#}"
+            ));
+            return;
+        }
+    };
+
+    let mut remap = vec![None; app.accounts.len()];
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut auth_checks = Vec::new();
+    let mut version_checks = Vec::new();
+    for (new_idx, account) in fresh.accounts.iter_mut().enumerate() {
+        let Some(old_idx) = app.accounts.iter().position(|old| old.name == account.name) else {
+            added.push(account.name.clone());
+            auth_checks.push(new_idx);
+            version_checks.push(new_idx);
+            continue;
+        };
+        remap[old_idx] = Some(new_idx);
+        let old = &app.accounts[old_idx];
+        let same_roles = old
+            .roles
+            .iter()
+            .map(|role| (&role.name, &role.aws_profile))
+            .eq(account
+                .roles
+                .iter()
+                .map(|role| (&role.name, &role.aws_profile)));
+        // Without roles the profile comes straight from the config, so a changed
+        // `aws_profile` must win.
+        if same_roles && !account.roles.is_empty() {
+            account.active_role = old.active_role;
+            account.aws_profile = old.aws_profile.clone();
+            account.profile_missing = old.profile_missing;
+        }
+        if account.aws_profile == old.aws_profile
+            && account.region == old.region
+            && account.env == old.env
+            && account.auth_check_cmd == old.auth_check_cmd
+        {
+            account.auth = old.auth;
+        } else {
+            auth_checks.push(new_idx);
+        }
+        if account.composition_path == old.composition_path
+            && account.backend_config == old.backend_config
+        {
+            account.workspaces = old.workspaces.clone();
+            account.current_workspace = old.current_workspace.clone();
+            account.backend_issues = old.backend_issues;
+            account.backend_unreachable = old.backend_unreachable.clone();
+        }
+        if account.binary == old.binary && account.composition_path == old.composition_path {
+            account.terraform_version = old.terraform_version;
+            account.version_mismatch = old.version_mismatch.clone();
+        } else {
+            version_checks.push(new_idx);
+        }
+        if old.config_fingerprint() != account.config_fingerprint() {
+            changed.push(account.name.clone());
+        }
+    }
+    let removed: Vec<String> = app
+        .accounts
+        .iter()
+        .zip(&remap)
+        .filter(|(_, new_idx)| new_idx.is_none())
+        .map(|(account, _)| account.name.clone())
+        .collect();
+
+    app.selected_group = None;
+    app.account_filter = None;
+    app.account_filter_origin = None;
+    app.workspace_filter = None;
+    match remap.get(app.selected_account).copied().flatten() {
+        Some(idx) => app.selected_account = idx,
+        None => {
+            app.selected_account = 0;
+            app.selected_workspace = 0;
+        }
+    }
+    let remap_idx = |idx: &usize| remap.get(*idx).copied().flatten();
+    app.session_tuning = std::mem::take(&mut app.session_tuning)
+        .into_iter()
+        .filter_map(|(idx, tuning)| Some((remap_idx(&idx)?, tuning)))
+        .collect();
+    app.skip_run_options = app.skip_run_options.iter().filter_map(remap_idx).collect();
+    app.prefetched_accounts = app
+        .prefetched_accounts
+        .iter()
+        .filter_map(remap_idx)
+        .collect();
+    app.next_drift_check = std::mem::take(&mut app.next_drift_check)
+        .into_iter()
+        .filter_map(|(idx, next)| Some((remap_idx(&idx)?, next)))
+        .collect();
+    app.plan_changes = app.plan_changes.take().and_then(|mut view| {
+        view.account_idx = remap_idx(&view.account_idx)?;
+        Some(view)
+    });
+    app.destroy_preview = app.destroy_preview.take().and_then(|mut view| {
+        view.account_idx = remap_idx(&view.account_idx)?;
+        Some(view)
+    });
+    app.plan_summary = app.plan_summary.take().and_then(|mut summary| {
+        summary.account_idx = remap_idx(&summary.account_idx)?;
+        Some(summary)
+    });
+    app.stuck_lock = app.stuck_lock.take().and_then(|mut lock| {
+        lock.account_idx = remap_idx(&lock.account_idx)?;
+        Some(lock)
+    });
+    app.deploy = app.deploy.take().and_then(|mut flow| {
+        flow.account_idx = remap_idx(&flow.account_idx)?;
+        Some(flow)
+    });
+    app.workspace_retry = app.workspace_retry.take().and_then(|mut retry| {
+        retry.account_idx = remap_idx(&retry.account_idx)?;
+        Some(retry)
+    });
+    app.cleanup_session = app.cleanup_session.take().and_then(|mut session| {
+        session.account_idx = remap_idx(&session.account_idx)?;
+        Some(session)
+    });
+    app.hovered_account = None;
+    // Popups hold account indexes; close them when accounts moved (`watch_config` can
+    // reload while one is open).
+    if remap
+        .iter()
+        .enumerate()
+        .any(|(old_idx, new_idx)| *new_idx != Some(old_idx))
+    {
+        app.prompt = None;
+        app.confirmation = None;
+        app.run_options_popup = None;
+        app.var_file_picker = None;
+        app.plan_review = None;
+        app.state_browser = None;
+        app.lock_table = None;
+        app.ci_runs = None;
+        app.pending_apply_confirmation = false;
+    }
+
+    app.accounts = std::mem::take(&mut fresh.accounts);
+    if app
+        .selected_account()
+        .is_none_or(|account| app.selected_workspace >= account.workspaces.len())
+    {
+        app.selected_workspace = 0;
+    }
+    app.stateful_resource_types = fresh.stateful_resource_types;
+    app.scroll = fresh.scroll;
+    app.icons = fresh.icons;
+    app.update_check = fresh.update_check;
+    app.schedule = fresh.schedule;
+    app.audit_sinks = fresh.audit_sinks;
+    app.state_tracking = fresh.state_tracking;
+    app.cleanup = fresh.cleanup;
+    app.reminders = fresh.reminders;
+    app.ci = fresh.ci;
+    app.prefetch = fresh.prefetch;
+    app.backend_probe = fresh.backend_probe;
+    app.background_limiter = fresh.background_limiter;
+    app.watch_config = fresh.watch_config;
+    app.team.config = fresh.team.config;
+    app.team.env = fresh.team.env;
+    app.team.next_sync = 0;
+
+    // The first line is the startup greeting; the rest are config warnings.
+    for entry in fresh.output_lines.into_iter().skip(1) {
+        app.push_output(entry.text);
+    }
+    let summary = [
+        ("added", &added),
+        ("changed", &changed),
+        ("removed", &removed),
+    ]
+    .iter()
+    .filter(|(_, names)| !names.is_empty())
+    .map(|(label, names)| format!("{label} {}", names.join(", ")))
+    .collect::<Vec<_>>();
+    app.push_output(format!(
+        "Reloaded config from {}: {}",
+        path.display(),
+        if summary.is_empty() {
+            "no account changes".to_string()
+        } else {
+            summary.join("; ")
+        }
+    ));
+
+    for idx in auth_checks {
+        spawn_auth_check(idx, app.accounts[idx].clone(), worker_tx.clone());
+    }
+    for idx in version_checks {
+        spawn_required_version_check(idx, &app.accounts[idx], worker_tx);
+        if app.backend_probe.enabled {
+            spawn_backend_probe(
+                idx,
+                &app.accounts[idx],
+                app.backend_probe.timeout_seconds,
+                worker_tx,
+            );
+        }
+    }
+}
+
+fn move_account_cursor(app: &mut AppState, down: bool) {
+    let rows = app.account_rows();
+    let current = app.selected_account_row(&rows);
+    let next = if down {
+        current + 1
+    } else {
+        let Some(previous) = current.checked_sub(1) else {
+            return;
+        };
+        previous
+    };
+    match rows.get(next) {
+        Some(AccountRow::Group(name)) => app.selected_group = Some(name.clone()),
+        Some(AccountRow::Account(idx)) => {
+            app.selected_group = None;
+            app.selected_account = *idx;
+            app.selected_workspace = 0;
+        }
+        None => {}
+    }
+}
+
+fn toggle_account_group(app: &mut AppState) {
+    let rows = app.account_rows();
+    let group = match rows.get(app.selected_account_row(&rows)) {
+        Some(AccountRow::Group(name)) => name.clone(),
+        Some(AccountRow::Account(idx)) => match app.accounts[*idx].group.clone() {
+            Some(group) => group,
+            None => return,
+        },
+        None => return,
+    };
+    if !app.collapsed_groups.remove(&group) {
+        app.collapsed_groups.insert(group.clone());
+    }
+    app.selected_group = Some(group);
+}
+
+fn handle_account_filter_key(app: &mut AppState, key: KeyEvent) {
+    let Some(query) = app.account_filter.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc => {
+            app.account_filter = None;
+            if let Some((account, workspace, group)) = app.account_filter_origin.take()
+                && account < app.accounts.len()
+            {
+                app.selected_account = account;
+                app.selected_workspace = workspace;
+                app.selected_group = group;
+            }
+        }
+        KeyCode::Enter => {
+            app.account_filter = None;
+            app.account_filter_origin = None;
+            app.selected_group = None;
+            if let Some(group) = app
+                .accounts
+                .get(app.selected_account)
+                .and_then(|account| account.group.clone())
+            {
+                app.collapsed_groups.remove(&group);
+            }
+        }
+        KeyCode::Up => move_account_cursor(app, false),
+        KeyCode::Down => move_account_cursor(app, true),
+        KeyCode::Backspace => {
+            query.pop();
+            select_best_account_match(app);
+        }
+        KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            query.push(ch);
+            select_best_account_match(app);
+        }
+        _ => {}
+    }
+}
+
+fn select_best_account_match(app: &mut AppState) {
+    let Some(query) = app
+        .account_filter
+        .as_ref()
+        .filter(|query| !query.is_empty())
+    else {
+        return;
+    };
+    let best = app
+        .accounts
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, account)| Some((fuzzy_score(query, &account.name)?, idx)))
+        .max_by_key(|(score, idx)| (*score, std::cmp::Reverse(*idx)));
+    app.selected_group = None;
+    if let Some((_, idx)) = best
+        && idx != app.selected_account
+    {
+        app.selected_account = idx;
+        app.selected_workspace = 0;
+    }
+}
+
+fn handle_workspace_filter_key(app: &mut AppState, key: KeyEvent) {
+    let Some(filter) = app.workspace_filter.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc => app.workspace_filter = None,
+        KeyCode::Enter if filter.query.is_empty() => app.workspace_filter = None,
+        KeyCode::Enter => filter.editing = false,
+        KeyCode::Up => move_workspace_cursor(app, false),
+        KeyCode::Down => move_workspace_cursor(app, true),
+        KeyCode::Backspace => {
+            filter.query.pop();
+            select_best_workspace_match(app);
+        }
+        KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            filter.query.push(ch);
+            select_best_workspace_match(app);
+        }
+        _ => {}
+    }
+}
+
+fn select_best_workspace_match(app: &mut AppState) {
+    let Some(query) = app
+        .workspace_filter_query()
+        .filter(|query| !query.is_empty())
+    else {
+        return;
+    };
+    let Some(account) = app.selected_account() else {
+        return;
+    };
+    if let Some((_, idx)) = account
+        .workspaces
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, workspace)| Some((fuzzy_score(query, workspace)?, idx)))
+        .max_by_key(|(score, idx)| (*score, std::cmp::Reverse(*idx)))
+    {
+        app.selected_workspace = idx;
+    }
+}
+
+fn move_workspace_cursor(app: &mut AppState, down: bool) {
+    let visible = app.visible_workspaces();
+    let next = match visible
+        .iter()
+        .position(|idx| *idx == app.selected_workspace)
+    {
+        Some(position) if down => visible.get(position + 1),
+        Some(position) => position
+            .checked_sub(1)
+            .and_then(|previous| visible.get(previous)),
+        None => visible.first(),
+    };
+    if let Some(idx) = next {
+        app.selected_workspace = *idx;
+    }
+}
+
+/// Case-insensitive subsequence match of `query` in `candidate`; higher scores favour runs of
+/// consecutive characters and matches at the start of words (after `-`, `_`, `/`, `.` or space).
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        let found = position + candidate[position..].iter().position(|ch| *ch == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(candidate[found - 1], '-' | '_' | '/' | '.' | ' ') {
+            score += 8;
+        }
+        score -= (found - position) as i64 / 4;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+fn move_selection_up(app: &mut AppState) {
+    match app.focused_panel {
+        FocusPanel::Accounts => move_account_cursor(app, false),
+        FocusPanel::Workspaces => move_workspace_cursor(app, false),
+        FocusPanel::Output => {
+            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_add(1);
+        }
+    }
+}
+
+fn handle_mouse_event(app: &mut AppState, mouse: MouseEvent) {
+    if app.focused_panel != FocusPanel::Output {
+        return;
+    }
+
+    match mouse.kind {
+        MouseEventKind::ScrollUp => {
+            app.output_scroll_from_bottom = app
+                .output_scroll_from_bottom
+                .saturating_add(app.scroll.wheel_lines);
+        }
+        MouseEventKind::ScrollDown => {
+            app.output_scroll_from_bottom = app
+                .output_scroll_from_bottom
+                .saturating_sub(app.scroll.wheel_lines);
+        }
+        _ => {}
+    }
+}
+
+fn move_selection_down(app: &mut AppState) {
+    match app.focused_panel {
+        FocusPanel::Accounts => move_account_cursor(app, true),
+        FocusPanel::Workspaces => move_workspace_cursor(app, true),
+        FocusPanel::Output => {
+            app.output_scroll_from_bottom = app.output_scroll_from_bottom.saturating_sub(1);
+        }
+    }
+}
+
+fn start_auth_check_for_selected(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
+    if let Some(account) = app.selected_account().cloned() {
+        let idx = app.selected_account;
+        if let Some(account_mut) = app.selected_account_mut() {
+            account_mut.auth = AuthStatus::Checking;
+        }
+        spawn_auth_check(idx, account, event_tx);
+    }
+}
+
+fn start_auth_login(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
+    if app.is_busy() {
+        app.push_output("Another operation is already running.");
+        return;
+    }
+
+    let Some(account) = app.selected_account().cloned() else {
+        app.push_output("No account selected.");
+        return;
+    };
+
+    let account_idx = app.selected_account;
+    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
+    let login_label = OperationKind::AuthLogin.label_for(&account);
+    let output_source = app.register_output_source(&login_label, &account.name, "");
+    app.inflight = Some(InflightOperation {
+        kind: OperationKind::AuthLogin,
+        account_idx,
+        workspace: String::new(),
+        cancel_tx,
+        cancel_stage: CancelStage::None,
+        resource_changes: Vec::new(),
+        output_source,
+        lock_error: false,
+        lock_id: None,
+        missing_variables: Vec::new(),
+        options: None,
+    });
+    app.set_status(format!("running {login_label} for {}", account.name));
+
+    tokio::spawn(async move {
+        let login_cmd = match &account.login_cmd {
+            Some(template) => {
+                let rendered = render_auth_template(&account, template);
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Starting login for `{}`: {rendered}",
+                    account.name
+                )));
+                account_shell_command(&account, &rendered)
+            }
+            None => {
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Starting AWS SSO login for `{}` (profile `{}`)",
+                    account.name, account.aws_profile
+                )));
+                let mut command = Command::new("aws");
+                apply_account_env(&mut command, &account);
+                command.args(["sso", "login", "--profile", &account.aws_profile]);
+                command
+            }
+        };
+
+        let login_result = run_streaming_command(login_cmd, cancel_rx, event_tx.clone()).await;
+        match login_result {
+            Ok(outcome) if outcome.success => {
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Login complete for `{}`. Checking credentials...",
+                    account.name
+                )));
+
+                match check_auth(&account).await {
+                    Ok(true) => {
+                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                            account_idx,
+                            status: AuthStatus::Authenticated,
+                            message: format!("Authenticated to `{}`", account.name),
+                        });
+
+                        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                            "Loading workspaces for `{}`...",
+                            account.name
+                        )));
+
+                        match fetch_workspaces(&account).await {
+                            Ok((workspaces, current)) => {
+                                let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+                                    account_idx,
+                                    workspaces,
+                                    current,
+                                });
+                                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                                    kind: OperationKind::AuthLogin,
+                                    account_idx,
+                                    success: true,
+                                    cancelled: false,
+                                    message: format!("Auth/login complete for `{}`", account.name),
+                                });
+                            }
+                            Err(err) => {
+                                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                                    kind: OperationKind::AuthLogin,
+                                    account_idx,
+                                    success: false,
+                                    cancelled: false,
+                                    message: format!(
+                                        "Authenticated, but failed to load workspaces for `{}`: {err}",
+                                        account.name
+                                    ),
+                                });
+                            }
+                        }
+                    }
+                    Ok(false) => {
+                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                            account_idx,
+                            status: AuthStatus::Failed,
+                            message: format!(
+                                "Credentials for `{}` are not usable yet",
+                                account.name
+                            ),
+                        });
+                        let _ = event_tx.send(WorkerEvent::OperationFinished {
+                            kind: OperationKind::AuthLogin,
+                            account_idx,
+                            success: false,
+                            cancelled: false,
+                            message: format!("Auth check failed for `{}`", account.name),
+                        });
+                    }
+                    Err(err) => {
+                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                            account_idx,
+                            status: AuthStatus::Failed,
+                            message: format!("Auth check errored for `{}`: {err}", account.name),
+                        });
+                        let _ = event_tx.send(WorkerEvent::OperationFinished {
+                            kind: OperationKind::AuthLogin,
+                            account_idx,
+                            success: false,
+                            cancelled: false,
+                            message: format!("Auth check errored for `{}`", account.name),
+                        });
+                    }
+                }
+            }
+            Ok(outcome) => {
+                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                    account_idx,
+                    status: AuthStatus::Failed,
+                    message: format!("Login failed for `{}`", account.name),
+                });
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind: OperationKind::AuthLogin,
+                    account_idx,
+                    success: false,
+                    cancelled: outcome.cancelled,
+                    message: format!(
+                        "Login failed for `{}` with exit code {}",
+                        account.name,
+                        outcome.exit_code.unwrap_or(-1)
+                    ),
+                });
+            }
+            Err(err) => {
+                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                    account_idx,
+                    status: AuthStatus::Failed,
+                    message: format!("Failed to run login for `{}`: {err}", account.name),
+                });
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind: OperationKind::AuthLogin,
+                    account_idx,
+                    success: false,
+                    cancelled: false,
+                    message: format!("Login failed to start for `{}`: {err}", account.name),
+                });
+            }
+        }
+    });
+}
+
+fn start_log_attach(
+    app: &mut AppState,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+    source: String,
+    command: Command,
+) {
+    if app.is_busy() {
+        app.push_output("Another operation is already running.");
+        return;
+    }
+
+    let account_idx = app.selected_account;
+    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
+    let output_source = app.register_output_source(OperationKind::AttachLog.label(), &source, "");
+    app.inflight = Some(InflightOperation {
+        kind: OperationKind::AttachLog,
+        account_idx,
+        workspace: String::new(),
+        cancel_tx,
+        cancel_stage: CancelStage::None,
+        resource_changes: Vec::new(),
+        output_source,
+        lock_error: false,
+        lock_id: None,
+        missing_variables: Vec::new(),
+        options: None,
+    });
+    app.set_status(format!("attached to {source}"));
+    app.push_output(format!("Attached to `{source}`. Press `c` to detach."));
+
+    tokio::spawn(async move {
+        let (success, cancelled, message) =
+            match run_streaming_command(command, cancel_rx, event_tx.clone()).await {
+                Ok(outcome) if outcome.cancelled => {
+                    (true, true, format!("Detached from `{source}`"))
+                }
+                Ok(outcome) if outcome.success => (true, false, format!("`{source}` ended")),
+                Ok(outcome) => (
+                    false,
+                    false,
+                    format!(
+                        "`{source}` exited with code {}",
+                        outcome.exit_code.unwrap_or(-1)
+                    ),
+                ),
+                Err(err) => (
+                    false,
+                    false,
+                    format!("Could not attach to `{source}`: {err}"),
+                ),
+            };
+        let _ = event_tx.send(WorkerEvent::OperationFinished {
+            kind: OperationKind::AttachLog,
+            account_idx,
+            success,
+            cancelled,
+            message,
+        });
+    });
+}
+
+fn start_workspace_refresh(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
+    if app.is_busy() {
+        app.push_output("Another operation is already running.");
+        return;
+    }
+
+    let Some(account) = app.selected_account().cloned() else {
+        app.push_output("No account selected.");
+        return;
+    };
+
+    if account.auth != AuthStatus::Authenticated {
+        app.push_output("Selected account is not authenticated. Press `a` to run AWS SSO login.");
+        return;
+    }
+
+    if let Err(err) = validate_composition_for_execution(&account) {
+        app.push_output(format!(
+            "Cannot refresh workspaces for `{}`: {err}",
+            account.name
+        ));
+        app.set_status("failed");
+        return;
+    }
+
+    let account_idx = app.selected_account;
+    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
+    let output_source =
+        app.register_output_source(OperationKind::RefreshWorkspaces.label(), &account.name, "");
+    app.inflight = Some(InflightOperation {
+        kind: OperationKind::RefreshWorkspaces,
+        account_idx,
+        workspace: String::new(),
+        cancel_tx,
+        cancel_stage: CancelStage::None,
+        resource_changes: Vec::new(),
+        output_source,
+        lock_error: false,
+        lock_id: None,
+        missing_variables: Vec::new(),
+        options: None,
+    });
+    app.set_status(format!("loading workspaces for {}", account.name));
+
+    tokio::spawn(async move {
+        if let Err(err) = check_preconditions(&account, &event_tx).await {
+            let _ = event_tx.send(WorkerEvent::OperationFinished {
+                kind: OperationKind::RefreshWorkspaces,
+                account_idx,
+                success: false,
+                cancelled: false,
+                message: format!("Workspace refresh failed for `{}`: {err}", account.name),
+            });
+            return;
+        }
+        // A cloud block naming one workspace has nothing to list.
+        let result = if account.composition_mode.pinned_workspace().is_some() {
+            Ok(RunOutcome {
+                success: true,
+                cancelled: false,
+                exit_code: Some(0),
+            })
+        } else {
+            let command = terraform_command(&account, &["workspace", "list"]);
+            run_streaming_command(command, cancel_rx, event_tx.clone()).await
+        };
+
+        match result {
+            Ok(outcome) if outcome.success => match fetch_workspaces(&account).await {
+                Ok((workspaces, current)) => {
+                    let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+                        account_idx,
+                        workspaces,
+                        current,
+                    });
+                    let _ = event_tx.send(WorkerEvent::OperationFinished {
+                        kind: OperationKind::RefreshWorkspaces,
+                        account_idx,
+                        success: true,
+                        cancelled: false,
+                        message: format!("Workspace refresh completed for `{}`", account.name),
+                    });
+                }
+                Err(err) => {
+                    let _ = event_tx.send(WorkerEvent::OperationFinished {
+                        kind: OperationKind::RefreshWorkspaces,
+                        account_idx,
+                        success: false,
+                        cancelled: false,
+                        message: format!("Workspace refresh failed for `{}`: {err}", account.name),
+                    });
+                }
+            },
+            Ok(outcome) => {
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind: OperationKind::RefreshWorkspaces,
+                    account_idx,
+                    success: false,
+                    cancelled: outcome.cancelled,
+                    message: format!(
+                        "Workspace refresh command failed for `{}` with exit code {}",
+                        account.name,
+                        outcome.exit_code.unwrap_or(-1)
+                    ),
+                });
+            }
+            Err(err) => {
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind: OperationKind::RefreshWorkspaces,
+                    account_idx,
+                    success: false,
+                    cancelled: false,
+                    message: format!(
+                        "Workspace refresh command failed for `{}`: {err}",
+                        account.name
+                    ),
+                });
+            }
+        }
+    });
+}
+
+fn start_terraform_operation(
+    app: &mut AppState,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+    kind: OperationKind,
+    options: RunOptions,
+) {
+    if app.selected_account().is_none() {
+        app.push_output(
+            "No account selected. Move the cursor from the group header onto an account.",
+        );
+        app.set_status("no account selected");
+        return;
+    }
+    let account_idx = app.selected_account;
+    start_operation_for(app, event_tx, account_idx, kind, options);
+}
+
+fn start_operation_for(
+    app: &mut AppState,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+    account_idx: usize,
+    kind: OperationKind,
+    options: RunOptions,
+) {
+    if app.is_busy() {
+        app.push_output("Another operation is already running.");
+        return;
+    }
+
+    let Some(account) = app.accounts.get(account_idx).cloned() else {
+        app.push_output("No account selected.");
+        return;
+    };
+
+    if account.auth != AuthStatus::Authenticated {
+        app.push_output("Selected account is not authenticated. Press `a` first.");
+        return;
+    }
+
+    if let Err(err) = validate_operation_preflight(&account, kind) {
+        app.push_output(format!("Cannot run {}: {err}", kind.label_for(&account)));
+        app.set_status("failed");
+        return;
+    }
+
+    if kind.is_mutating() && !account.can_mutate() {
+        app.push_output(role_refusal(&account));
+        app.set_status("blocked by role");
+        return;
+    }
+
+    let workspace = if let Some(workspace) = options.workspace.clone() {
+        workspace
+    } else if kind.requires_workspace() {
+        match app
+            .selected_workspace_name()
+            .filter(|_| account_idx == app.selected_account)
+        {
+            Some(workspace) => workspace,
+            None => {
+                app.push_output("No workspace selected. Press `r` to load workspaces first.");
+                return;
+            }
+        }
+    } else {
+        String::new()
+    };
+
+    if !workspace.is_empty() {
+        app.maintenance_locks = load_maintenance_locks();
+        if let Some(lock) = app.maintenance_lock(&account.name, &workspace) {
+            let message = format!(
+                "Cannot run {}: `{}/{workspace}` is locked for maintenance by {} since {} ({}). Press `u` to release it.",
+                kind.label_for(&account),
+                account.name,
+                lock.locked_by,
+                format_timestamp(lock.locked_at),
+                lock.reason
+            );
+            app.push_output(message);
+            app.set_status("blocked by maintenance lock");
+            return;
+        }
+    }
+
+    if let Some(record) = app.team.running_on(&account.name, &workspace, unix_now()) {
+        let message = format!(
+            "warning: {} is running {} on `{}/{workspace}` (team sync).",
+            record.operator, record.operation, account.name
+        );
+        app.push_output(message);
+    }
+
+    if kind == OperationKind::TerraformApply
+        && account
+            .tag_policy
+            .as_ref()
+            .is_some_and(|policy| policy.mode == TagPolicyMode::Block)
+    {
+        let problem = match account.tag_violations.get(&workspace) {
+            Some(0) => None,
+            Some(count) => Some(format!(
+                "the last plan has {count} resource(s) missing required tags"
+            )),
+            None => Some("the last plan's tag check did not complete".to_string()),
+        };
+        if let Some(problem) = problem {
+            app.push_output(format!(
+                "Cannot run terraform apply: tag_policy is blocking for `{}` and {problem}. Run a plan with no tag violations on `{workspace}` first.",
+                account.name
+            ));
+            app.set_status("blocked by tag policy");
+            return;
+        }
+    }
+
+    if let Some(error) = &account.backend_unreachable
+        && (kind == OperationKind::TerraformInit || kind.takes_state_lock())
+    {
+        app.push_output(format!(
+            "warning: the backend was unreachable at startup ({error}); {} may hang until it times out.",
+            kind.label_for(&account)
+        ));
+    }
+
+    if kind == OperationKind::TerraformPlan {
+        // Unknown until this plan's tag check reports, so a failed check blocks apply.
+        app.accounts[account_idx].tag_violations.remove(&workspace);
+    }
+    let audit_sinks = app.audit_sinks.clone();
+    let state_tracking = app.state_tracking;
+    let cleanup = app.cleanup;
+    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
+    let output_source =
+        app.register_output_source(&kind.label_for(&account), &account.name, &workspace);
+
+    app.inflight = Some(InflightOperation {
+        kind,
+        account_idx,
+        workspace: workspace.clone(),
+        cancel_tx,
+        cancel_stage: CancelStage::None,
+        resource_changes: Vec::new(),
+        output_source,
+        lock_error: false,
+        lock_id: None,
+        missing_variables: Vec::new(),
+        options: Some(Box::new(options.clone())),
+    });
+    app.account_activity
+        .insert(account.name.clone(), AccountActivity::Running);
+    app.set_status(format!(
+        "running {} for {}",
+        kind.label_for(&account),
+        account.name
+    ));
+    app.begin_operation_log(kind, &account.name, &workspace);
+
+    tokio::spawn(async move {
+        let audit = kind.is_mutating().then(|| AuditRecord {
+            timestamp: unix_now(),
+            operator: current_operator(),
+            account: account.name.clone(),
+            workspace: workspace.clone(),
+            operation: kind.label().to_string(),
+            event: AuditEvent::Started,
+            outcome: None,
+            change_ticket: options.change_ticket.clone(),
+        });
+        if let Some(record) = &audit {
+            write_audit_record(record, &audit_sinks, &event_tx);
+        }
+
+        let run_result = match check_preconditions(&account, &event_tx).await {
+            Err(err) => Err(err),
+            Ok(()) => match kind {
+                OperationKind::CleanupScan => {
+                    run_cleanup_scan(account_idx, &account, cleanup, &cancel_rx, &event_tx).await
+                }
+                OperationKind::WorkspaceCleanup => {
+                    run_workspace_cleanup(account_idx, &account, &workspace, cancel_rx, &event_tx)
+                        .await
+                }
+                OperationKind::CostBreakdown => {
+                    run_cost_breakdown(&account, &workspace, cancel_rx, &event_tx).await
+                }
+                OperationKind::EphemeralCreate => {
+                    run_ephemeral_create(
+                        account_idx,
+                        &account,
+                        &workspace,
+                        &options,
+                        cancel_rx,
+                        &event_tx,
+                    )
+                    .await
+                }
+                OperationKind::EphemeralTeardown => {
+                    let mut teardown_account = account.clone();
+                    teardown_account
+                        .var_files
+                        .extend(options.extra_var_files.iter().cloned());
+                    let outcome = run_workspace_cleanup(
+                        account_idx,
+                        &teardown_account,
+                        &workspace,
+                        cancel_rx,
+                        &event_tx,
+                    )
+                    .await;
+                    if matches!(&outcome, Ok(outcome) if outcome.success) {
+                        for var_file in &options.extra_var_files {
+                            let _ = fs::remove_file(var_file);
+                        }
+                        let _ = event_tx.send(WorkerEvent::EphemeralRemoved {
+                            account: account.name.clone(),
+                            workspace: workspace.clone(),
+                        });
+                    }
+                    outcome
+                }
+                OperationKind::TerraformFmtCheck => {
+                    run_fmt_check(account_idx, &account, cancel_rx, &event_tx).await
+                }
+                OperationKind::ForceUnlock => {
+                    run_force_unlock(&account, &workspace, &options, cancel_rx, &event_tx).await
+                }
+                OperationKind::StateList => {
+                    run_state_list(account_idx, &account, &workspace, &event_tx).await
+                }
+                OperationKind::CiJobPlay => {
+                    run_ci_job_play(
+                        account_idx,
+                        &account,
+                        &workspace,
+                        &options,
+                        cancel_rx,
+                        &event_tx,
+                    )
+                    .await
+                }
+                OperationKind::StateShow => {
+                    run_state_show(account_idx, &account, &workspace, &options, &event_tx).await
+                }
+                OperationKind::StateRemove => {
+                    run_state_rm(&account, &workspace, &options, cancel_rx, &event_tx).await
+                }
+                OperationKind::LockTableView => {
+                    run_lock_table_view(account_idx, &account, &event_tx).await
+                }
+                OperationKind::BackendReport => {
+                    run_backend_report(account_idx, &account, &event_tx).await
+                }
+                OperationKind::StateTaint | OperationKind::StateUntaint => {
+                    run_state_taint(
+                        &account,
+                        &workspace,
+                        &options,
+                        kind == OperationKind::StateUntaint,
+                        cancel_rx,
+                        &event_tx,
+                    )
+                    .await
+                }
+                OperationKind::StateMove => {
+                    run_state_mv(&account, &workspace, &options, cancel_rx, &event_tx).await
+                }
+                OperationKind::WorkspaceNew => {
+                    run_workspace_new(
+                        account_idx,
+                        &account,
+                        std::slice::from_ref(&workspace),
+                        &cancel_rx,
+                        &event_tx,
+                    )
+                    .await
+                }
+                OperationKind::WorkspaceDelete => {
+                    run_workspace_delete(
+                        account_idx,
+                        &account,
+                        &workspace,
+                        options.force,
+                        &event_tx,
+                    )
+                    .await
+                }
+                OperationKind::WorkspaceBootstrap => {
+                    run_workspace_new(
+                        account_idx,
+                        &account,
+                        &options.workspace_names,
+                        &cancel_rx,
+                        &event_tx,
+                    )
+                    .await
+                }
+                _ => {
+                    run_terraform_operation(
+                        kind,
+                        account.clone(),
+                        workspace.clone(),
+                        options.clone(),
+                        cancel_rx,
+                        event_tx.clone(),
+                    )
+                    .await
+                }
+            },
+        };
+
+        if kind.takes_state_lock() && matches!(&run_result, Ok(outcome) if outcome.cancelled) {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Checking whether the state lock on `{workspace}` was released..."
+            )));
+            match probe_state_lock(&account, &workspace).await {
+                Ok(lock_id) => {
+                    let _ = event_tx.send(WorkerEvent::LockProbeCompleted {
+                        account_idx,
+                        workspace: workspace.clone(),
+                        lock_id,
+                    });
+                }
+                Err(err) => {
+                    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                        "warning: could not verify state lock: {err}"
+                    )));
+                }
+            }
+        }
+
+        if matches!(
+            kind,
+            OperationKind::TerraformPlan | OperationKind::TerraformApply
+        ) && matches!(&run_result, Ok(outcome) if outcome.success)
+        {
+            record_state_metrics(&account, &workspace, state_tracking, &event_tx).await;
+        }
+
+        if kind == OperationKind::TerraformApply
+            && matches!(&run_result, Ok(outcome) if outcome.success)
+        {
+            record_output_diff(&account, &workspace, &event_tx).await;
+        }
+
+        let ticket_suffix = options
+            .change_ticket
+            .as_deref()
+            .map(|ticket| format!(" (change {ticket})"))
+            .unwrap_or_default();
+
+        match run_result {
+            Ok(outcome) => {
+                let message = if outcome.success {
+                    format!(
+                        "{} succeeded for `{}`{ticket_suffix}",
+                        kind.label_for(&account),
+                        account.name
+                    )
+                } else if outcome.cancelled {
+                    format!(
+                        "{} cancelled for `{}`{ticket_suffix}",
+                        kind.label_for(&account),
+                        account.name
+                    )
+                } else {
+                    format!(
+                        "{} failed for `{}` with exit code {}{ticket_suffix}",
+                        kind.label_for(&account),
+                        account.name,
+                        outcome.exit_code.unwrap_or(-1)
+                    )
+                };
+
+                if let Some(record) = audit {
+                    let outcome_label = if outcome.success {
+                        "succeeded"
+                    } else if outcome.cancelled {
+                        "cancelled"
+                    } else {
+                        "failed"
+                    };
+                    write_audit_record(
+                        &AuditRecord {
+                            timestamp: unix_now(),
+                            event: AuditEvent::Finished,
+                            outcome: Some(outcome_label.to_string()),
+                            ..record
+                        },
+                        &audit_sinks,
+                        &event_tx,
+                    );
+                }
+
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind,
+                    account_idx,
+                    success: outcome.success,
+                    cancelled: outcome.cancelled,
+                    message,
+                });
+            }
+            Err(err) => {
+                if let Some(record) = audit {
+                    write_audit_record(
+                        &AuditRecord {
+                            timestamp: unix_now(),
+                            event: AuditEvent::Finished,
+                            outcome: Some(format!("error: {err}")),
+                            ..record
+                        },
+                        &audit_sinks,
+                        &event_tx,
+                    );
+                }
+
+                let _ = event_tx.send(WorkerEvent::OperationFinished {
+                    kind,
+                    account_idx,
+                    success: false,
+                    cancelled: false,
+                    message: format!(
+                        "{} failed for `{}`: {err}{ticket_suffix}",
+                        kind.label_for(&account),
+                        account.name
+                    ),
+                });
+            }
+        }
+    });
+}
+
+fn spawn_auth_check(
+    account_idx: usize,
+    account: AccountState,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let label = format!("auth check {}", account.name);
+    spawn_tracked(&event_tx.clone(), label, async move {
+        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+            account_idx,
+            status: AuthStatus::Checking,
+            message: format!(
+                "Checking auth for `{}` (profile `{}`)",
+                account.name, account.aws_profile
+            ),
+        });
+
+        match check_auth(&account).await {
+            Ok(true) => {
+                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                    account_idx,
+                    status: AuthStatus::Authenticated,
+                    message: format!("Credentials valid for `{}`", account.name),
+                });
+
+                match fetch_workspaces(&account).await {
+                    Ok((workspaces, current)) => {
+                        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+                            account_idx,
+                            workspaces,
+                            current,
+                        });
+                    }
+                    Err(err) => {
+                        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                            "Could not load workspaces for `{}` yet: {err}",
+                            account.name
+                        )));
+                    }
+                }
+                true
+            }
+            Ok(false) => {
+                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                    account_idx,
+                    status: AuthStatus::Failed,
+                    message: format!("No valid AWS session for `{}`", account.name),
+                });
+                false
+            }
+            Err(err) => {
+                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
+                    account_idx,
+                    status: AuthStatus::Failed,
+                    message: format!("Auth check errored for `{}`: {err}", account.name),
+                });
+                false
+            }
+        }
+    });
+}
+
+fn spawn_tracked<F>(event_tx: &mpsc::UnboundedSender<WorkerEvent>, label: String, task: F)
+where
+    F: Future<Output = bool> + Send + 'static,
+{
+    let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+    let _ = event_tx.send(WorkerEvent::TaskStarted { id, label });
+    let event_tx = event_tx.clone();
+    tokio::spawn(async move {
+        let success = task.await;
+        let _ = event_tx.send(WorkerEvent::TaskFinished { id, success });
+    });
+}
+
+async fn run_terraform_operation(
+    kind: OperationKind,
+    mut account: AccountState,
+    workspace: String,
+    options: RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    if let Some(var_files) = &options.var_files {
+        account.var_files = var_files.clone();
+    }
+    account
+        .var_files
+        .extend(options.extra_var_files.iter().cloned());
+    validate_operation_preflight(&account, kind)?;
+
+    if kind == OperationKind::OrphanScan {
+        return run_orphan_scan(&account, &cancel_rx, &event_tx).await;
+    }
+
+    if kind == OperationKind::TerraformValidate {
+        return run_terraform_validate(&account, &event_tx).await;
+    }
+
+    let plan_file = options
+        .plan_file
+        .clone()
+        .unwrap_or_else(|| saved_plan_path(&account.name, &workspace));
+    if kind == OperationKind::TerraformApply && !plan_file.exists() {
+        return Err(eyre!(
+            "No saved plan for `{}/{workspace}`; run a plan first",
+            account.name
+        ));
+    }
+
+    if let Some(pinned) = account.composition_mode.pinned_workspace()
+        && kind.requires_workspace()
+        && workspace != pinned
+    {
+        return Err(eyre!(
+            "`{}` is pinned to HCP Terraform workspace `{pinned}` by its cloud block",
+            account.name
+        ));
+    }
+
+    if kind.requires_workspace() && account.composition_mode.pinned_workspace().is_none() {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Selecting workspace `{}` in `{}`",
+            workspace, account.name
+        )));
+
+        let mut select_cmd = terraform_command(&account, &["workspace", "select", &workspace]);
+        let select_out = select_cmd
+            .output()
+            .await
+            .wrap_err("Failed to run terraform workspace select")?;
+        emit_process_output(&event_tx, &select_out.stdout);
+        emit_process_output(&event_tx, &select_out.stderr);
+        if !select_out.status.success() {
+            if String::from_utf8_lossy(&select_out.stderr).contains("doesn't exist") {
+                let _ = event_tx.send(WorkerEvent::WorkspaceMissing {
+                    account: account.name.clone(),
+                    workspace: workspace.clone(),
+                    kind,
+                    options: Box::new(options.clone()),
+                });
+            }
+            return Ok(RunOutcome {
+                success: false,
+                cancelled: false,
+                exit_code: select_out.status.code(),
+            });
+        }
+        let _ = event_tx.send(WorkerEvent::WorkspaceSelected {
+            account: account.name.clone(),
+            workspace: workspace.clone(),
+        });
+    }
+
+    if matches!(
+        kind,
+        OperationKind::TerraformPlan
+            | OperationKind::TerraformRefreshPlan
+            | OperationKind::TerraformDestroyPlan
+            | OperationKind::TerraformApply
+            | OperationKind::TerraformImport
+    ) && !account.var_files.is_empty()
+    {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Using var files: {}",
+            account
+                .var_files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    let variable_names: BTreeSet<&String> = account
+        .variables
+        .keys()
+        .chain(
+            account
+                .workspace_variables
+                .get(&workspace)
+                .into_iter()
+                .flat_map(|vars| vars.keys()),
+        )
+        .collect();
+    if matches!(
+        kind,
+        OperationKind::TerraformPlan
+            | OperationKind::TerraformRefreshPlan
+            | OperationKind::TerraformDestroyPlan
+            | OperationKind::TerraformImport
+    ) && !variable_names.is_empty()
+    {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Using variables: {}",
+            variable_names
+                .iter()
+                .map(|name| format!("TF_VAR_{name}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    if matches!(
+        kind,
+        OperationKind::TerraformApply | OperationKind::WorkspaceCleanup
+    ) && let Some(ticket) = &options.change_ticket
+        && let Some(validate_command) = account
+            .change_ticket
+            .as_ref()
+            .and_then(|policy| policy.validate_command.as_deref())
+    {
+        validate_change_ticket(
+            &account,
+            &workspace,
+            ticket,
+            validate_command,
+            cancel_rx.clone(),
+            &event_tx,
+        )
+        .await?;
+    }
+
+    if kind == OperationKind::TerraformApply
+        && let Some(approval) = &account.approval
+    {
+        let target = ApprovalTarget::plan(&plan_file)?;
+        if let Some((request, signature)) = &options.approval {
+            if request.account != account.name
+                || request.workspace != workspace
+                || request.plan_sha256 != target.digest
+            {
+                return Err(eyre!(
+                    "the approval given when the apply was scheduled is for a different plan"
+                ));
+            }
+            check_approval_signature(signature, request, approval)?;
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Apply approved by `{}` when it was scheduled (request {}).",
+                signature.approved_by, request.id
+            )));
+        } else {
+            ensure_apply_approved(
+                &account,
+                &workspace,
+                approval,
+                &target,
+                cancel_rx.clone(),
+                &event_tx,
+            )
+            .await?;
+        }
+    }
+
+    if let Some(tuning) = options
+        .tuning
+        .as_ref()
+        .filter(|tuning| !tuning.is_default())
+    {
+        let mut args = Vec::new();
+        if kind == OperationKind::TerraformApply {
+            tuning.append_apply_args(&mut args);
+        } else {
+            tuning.append_args(&mut args);
+        }
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Run options{}: {}",
+            tuning
+                .profile
+                .as_ref()
+                .map(|profile| format!(" ({profile})"))
+                .unwrap_or_default(),
+            redact_var_args(args.iter().map(String::as_str)).join(" ")
+        )));
+    }
+
+    if let Some(tuning) = &options.tuning
+        && !tuning.targets.is_empty()
+        && kind == OperationKind::TerraformPlan
+    {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Targeted {} limited to {} resource(s):",
+            kind.label_for(&account),
+            tuning.targets.len()
+        )));
+        for target in &tuning.targets {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!("  -target={target}")));
+        }
+    }
+
+    if kind == OperationKind::TerraformPlan {
+        let _ = fs::remove_file(&plan_file);
+        prepare_plan_dir(&plan_file)?;
+    }
+
+    let mut import_block_file = None;
+    let mut command = match kind {
+        OperationKind::TerraformImport => {
+            let spec = options
+                .import
+                .as_ref()
+                .ok_or_else(|| eyre!("Missing import address and ID"))?;
+            if !is_resource_address(&spec.address) {
+                return Err(eyre!("`{}` is not a resource address", spec.address));
+            }
+            if spec.generate_config {
+                let file_stem = strip_address_keys(&spec.address)
+                    .replace(|ch: char| !ch.is_ascii_alphanumeric(), "_");
+                let block_path = account
+                    .composition_path
+                    .join(format!("lazytf_import_{file_stem}.tf"));
+                fs::write(
+                    &block_path,
+                    format!(
+                        "import {{\n  to = {}\n  id = {}\n}}\n",
+                        spec.address,
+                        hcl_quote(&spec.id)
+                    ),
+                )
+                .wrap_err_with(|| format!("Failed to write {}", block_path.display()))?;
+                import_block_file = Some(block_path);
+
+                let generated = format!("generated_{file_stem}.tf");
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Generating configuration for `{}` into {generated}",
+                    spec.address
+                )));
+                let mut args = vec![
+                    "plan".to_string(),
+                    "-input=false".to_string(),
+                    "-no-color".to_string(),
+                    format!("-generate-config-out={generated}"),
+                ];
+                append_var_file_args(&mut args, &account.var_files);
+                append_extra_args(&mut args, &account, "plan");
+                terraform_command_owned(&account, &args)
+            } else {
+                let mut args = vec![
+                    "import".to_string(),
+                    "-input=false".to_string(),
+                    "-no-color".to_string(),
+                ];
+                append_var_file_args(&mut args, &account.var_files);
+                append_extra_args(&mut args, &account, "import");
+                args.push(spec.address.clone());
+                args.push(spec.id.clone());
+                terraform_command_owned(&account, &args)
+            }
+        }
+        OperationKind::TerraformInit => terraform_command_owned(&account, &init_args(&account)),
+        OperationKind::TerraformFmt => {
+            let mut args = vec![
+                "fmt".to_string(),
+                "-recursive".to_string(),
+                "-no-color".to_string(),
+            ];
+            append_extra_args(&mut args, &account, "fmt");
+            terraform_command_owned(&account, &args)
+        }
+        OperationKind::TerraformPlan | OperationKind::TerraformDestroyPlan => {
+            let mut args = vec![
+                "plan".to_string(),
+                "-input=false".to_string(),
+                "-no-color".to_string(),
+            ];
+            // The destroy preview is never saved, so `A` can't apply it by accident.
+            if kind == OperationKind::TerraformDestroyPlan {
+                args.push("-destroy".to_string());
+            } else {
+                args.push(format!("-out={}", plan_file.display()));
+            }
+            append_var_file_args(&mut args, &account.var_files);
+            if let Some(tuning) = &options.tuning {
+                tuning.append_args(&mut args);
+            }
+            append_extra_args(&mut args, &account, "plan");
+            terraform_command_owned(&account, &args)
+        }
+        OperationKind::TerraformRefreshPlan => {
+            let mut args = vec![
+                "plan".to_string(),
+                "-refresh-only".to_string(),
+                "-input=false".to_string(),
+                "-no-color".to_string(),
+            ];
+            append_var_file_args(&mut args, &account.var_files);
+            append_extra_args(&mut args, &account, "plan");
+            terraform_command_owned(&account, &args)
+        }
+        OperationKind::TerraformApply => {
+            // Var files, targets and other run options are baked into the saved plan.
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Applying saved plan {}",
+                plan_file.display()
+            )));
+            let mut args = vec![
+                "apply".to_string(),
+                "-input=false".to_string(),
+                "-no-color".to_string(),
+            ];
+            if let Some(tuning) = &options.tuning {
+                tuning.append_apply_args(&mut args);
+            }
+            append_extra_args(&mut args, &account, "apply");
+            args.push(plan_file.display().to_string());
+            terraform_command_owned(&account, &args)
+        }
+        _ => {
+            return Err(eyre!(
+                "Unsupported terraform operation for runner: {}",
+                kind.label()
+            ));
+        }
+    };
+
+    apply_tf_vars(&mut command, &account, &workspace);
+    // Prompted answers may be secrets: environment only, never argv or the child record.
+    for (name, value) in &options.vars {
+        command.env(format!("TF_VAR_{name}"), value);
+    }
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Running `{}` in {}",
+        kind.label_for(&account),
+        account.composition_path.display()
+    )));
+
+    let outcome = run_streaming_command(command, cancel_rx, event_tx.clone()).await;
+    // The import block is what makes the next apply import the object, so it stays until
+    // then; only a failed generation removes it.
+    if let Some(block_path) = import_block_file {
+        if matches!(&outcome, Ok(outcome) if outcome.success) {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Review the generated configuration, then plan and apply to complete the import. Keep {} until the apply has run, then delete it.",
+                block_path.display()
+            )));
+        } else {
+            let _ = fs::remove_file(block_path);
+        }
+    }
+    let outcome = outcome?;
+
+    if kind == OperationKind::TerraformPlan {
+        // The saved plan was replaced, so approvals given for the old one no longer apply.
+        discard_approvals(&account.name, &workspace);
+        if outcome.success {
+            restrict_plan_file(&plan_file);
+            match show_plan_json(&account, &plan_file).await {
+                Ok(plan) => {
+                    let mut changes = parse_plan_changes(&plan);
+                    mark_noisy_attributes(&mut changes, &account.noisy_attributes);
+                    let _ = event_tx.send(WorkerEvent::PlanParsed { changes });
+                    if let Some(policy) = &account.tag_policy {
+                        check_plan_tags(&account, &workspace, policy, &plan, &event_tx);
+                    }
+                }
+                Err(err) => {
+                    let skipped = if account.tag_policy.is_some() {
+                        "; tag check skipped"
+                    } else {
+                        ""
+                    };
+                    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                        "Warning: structured plan unavailable, change list is read from the plan text{skipped}: {err}"
+                    )));
+                }
+            }
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Plan saved to {}; `A` then `y` applies exactly this plan.",
+                plan_file.display()
+            )));
+        } else {
+            let _ = fs::remove_file(&plan_file);
+        }
+    }
+    if kind == OperationKind::TerraformApply {
+        // A saved plan can only be applied once; Terraform rejects it afterwards as stale.
+        let _ = fs::remove_file(&plan_file);
+    }
+
+    Ok(outcome)
+}
+
+async fn show_plan_json(account: &AccountState, plan_path: &Path) -> Result<serde_json::Value> {
+    let plan_arg = plan_path.display().to_string();
+    let output = terraform_command(account, &["show", "-json", &plan_arg])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform show")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "terraform show -json failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).wrap_err("Failed to parse plan JSON")
+}
+
+fn parse_plan_changes(plan: &serde_json::Value) -> Vec<ResourceChange> {
+    parse_resource_changes(&plan["resource_changes"])
+}
+
+fn parse_resource_changes(changes: &serde_json::Value) -> Vec<ResourceChange> {
+    changes
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|change| {
+            let actions: Vec<&str> = change["change"]["actions"]
+                .as_array()?
+                .iter()
+                .filter_map(serde_json::Value::as_str)
+                .collect();
+            let action = match actions.as_slice() {
+                ["create"] => ChangeAction::Create,
+                ["update"] => ChangeAction::Update,
+                ["delete"] => ChangeAction::Delete,
+                ["read"] => ChangeAction::Read,
+                ["delete", "create"] | ["create", "delete"] => ChangeAction::Replace,
+                _ => return None,
+            };
+            let provider = change["provider_name"].as_str().map(|name| {
+                name.strip_prefix("registry.terraform.io/")
+                    .unwrap_or(name)
+                    .to_string()
+            });
+            Some(ResourceChange {
+                address: change["address"].as_str()?.to_string(),
+                action,
+                provider,
+                attributes: plan_attribute_diff(&change["change"]),
+                replace_reasons: if action == ChangeAction::Replace {
+                    plan_replace_reasons(change)
+                } else {
+                    Vec::new()
+                },
+            })
+        })
+        .collect()
+}
+
+fn replace_reasons_label(reasons: &[String]) -> String {
+    if reasons.is_empty() {
+        "reason unknown".to_string()
+    } else {
+        format!("forced by {}", reasons.join(", "))
+    }
+}
+
+fn plan_replace_reasons(change: &serde_json::Value) -> Vec<String> {
+    let mut reasons: Vec<String> = change["change"]["replace_paths"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|path| {
+            let mut rendered = String::new();
+            for step in path.as_array()? {
+                match step {
+                    serde_json::Value::Number(index) => rendered.push_str(&format!("[{index}]")),
+                    serde_json::Value::String(key) if rendered.is_empty() => {
+                        rendered.push_str(key);
+                    }
+                    serde_json::Value::String(key) => {
+                        rendered.push('.');
+                        rendered.push_str(key);
+                    }
+                    _ => return None,
+                }
+            }
+            (!rendered.is_empty()).then_some(rendered)
+        })
+        .collect();
+    if reasons.is_empty() {
+        match change["action_reason"].as_str() {
+            Some("replace_because_tainted") => reasons.push("tainted".to_string()),
+            Some("replace_by_request") => reasons.push("-replace requested".to_string()),
+            Some("replace_by_triggers") => reasons.push("replace_triggered_by".to_string()),
+            _ => {}
+        }
+    }
+    reasons
+}
+
+/// `value` with every part that the `before_sensitive`/`after_sensitive` mirror `mask`
+/// marks `true` replaced by `"(sensitive)"`, at any depth.
+fn redact_sensitive(value: &serde_json::Value, mask: &serde_json::Value) -> serde_json::Value {
+    match (value, mask) {
+        (_, serde_json::Value::Bool(true)) => serde_json::Value::from("(sensitive)"),
+        (serde_json::Value::Object(fields), serde_json::Value::Object(masks)) => fields
+            .iter()
+            .map(|(key, field)| {
+                let field = masks
+                    .get(key)
+                    .map_or_else(|| field.clone(), |mask| redact_sensitive(field, mask));
+                (key.clone(), field)
+            })
+            .collect(),
+        (serde_json::Value::Array(items), serde_json::Value::Array(masks)) => items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                masks
+                    .get(idx)
+                    .map_or_else(|| item.clone(), |mask| redact_sensitive(item, mask))
+            })
+            .collect(),
+        _ => value.clone(),
+    }
+}
+
+fn plan_attribute_diff(change: &serde_json::Value) -> Vec<AttributeDiff> {
+    let empty = serde_json::Map::new();
+    let before = change["before"].as_object().unwrap_or(&empty);
+    let after = change["after"].as_object().unwrap_or(&empty);
+    let render = |key: &str, value: &serde_json::Value| {
+        if change["before_sensitive"] == true || change["after_sensitive"] == true {
+            return "(sensitive)".to_string();
+        }
+        let value = redact_sensitive(value, &change["before_sensitive"][key]);
+        let value = redact_sensitive(&value, &change["after_sensitive"][key]);
+        if let Some(text) = value.as_str().filter(|text| *text == "(sensitive)") {
+            return text.to_string();
+        }
+        // IAM policies, container definitions, ...: JSON documents stored as strings.
+        if let Some(document) = value
+            .as_str()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(text.trim()).ok())
+            .filter(|document| document.is_object() || document.is_array())
+            && let Ok(pretty) = serde_json::to_string_pretty(&document)
+        {
+            return format!("jsonencode({pretty})");
+        }
+        value.to_string()
+    };
+    let diff = |key: &str, old: Option<String>, new: Option<String>| AttributeDiff {
+        key: key.to_string(),
+        old,
+        new,
+        noisy: false,
+    };
+    let unknown = || Some("(known after apply)".to_string());
+
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = before.get(key).filter(|value| !value.is_null());
+            let new = after.get(key).filter(|value| !value.is_null());
+            let is_unknown = change["after_unknown"][key.as_str()] == true;
+            match (old, new) {
+                (Some(old), _) if is_unknown => Some(diff(key, Some(render(key, old)), unknown())),
+                (None, _) if is_unknown => Some(diff(key, None, unknown())),
+                (None, Some(new)) => Some(diff(key, None, Some(render(key, new)))),
+                (Some(old), None) => Some(diff(key, Some(render(key, old)), None)),
+                (Some(old), Some(new)) if old != new => {
+                    Some(diff(key, Some(render(key, old)), Some(render(key, new))))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn check_plan_tags(
+    account: &AccountState,
+    workspace: &str,
+    policy: &TagPolicyConfig,
+    plan: &serde_json::Value,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let violations = tag_violations(policy, plan);
+    for (address, missing) in &violations {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Warning: {address} is missing required tags: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let violations = violations.len();
+    let verdict = match (violations, policy.mode) {
+        (0, _) => "Tag policy: all changed resources carry the required tags.".to_string(),
+        (count, TagPolicyMode::Warn) => {
+            format!("Tag policy: {count} resource(s) missing required tags (warning only).")
+        }
+        (count, TagPolicyMode::Block) => format!(
+            "Error: tag policy: {count} resource(s) missing required tags. Apply is blocked until a clean plan."
+        ),
+    };
+    let _ = event_tx.send(WorkerEvent::OutputLine(verdict));
+    let _ = event_tx.send(WorkerEvent::TagCheckCompleted {
+        account: account.name.clone(),
+        workspace: workspace.to_string(),
+        violations,
+    });
+}
+
+/// Managed resources the plan creates or updates without every required tag, with the
+/// missing tag names. Resources whose tags are only known after apply are not counted.
+fn tag_violations(
+    policy: &TagPolicyConfig,
+    plan: &serde_json::Value,
+) -> Vec<(String, Vec<String>)> {
+    let type_patterns: Vec<glob::Pattern> = policy
+        .resource_types
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+        .collect();
+    let mut violations = Vec::new();
+    for change in plan["resource_changes"].as_array().into_iter().flatten() {
+        let actions = &change["change"]["actions"];
+        let creates_or_updates = actions
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|action| action == "create" || action == "update");
+        let resource_type = change["type"].as_str().unwrap_or_default();
+        if change["mode"] != "managed"
+            || !creates_or_updates
+            || (!type_patterns.is_empty()
+                && !type_patterns
+                    .iter()
+                    .any(|pattern| pattern.matches(resource_type)))
+        {
+            continue;
+        }
+
+        let after = &change["change"]["after"];
+        let after_unknown = &change["change"]["after_unknown"];
+        let tags_key = if after.get("tags_all").is_some() {
+            "tags_all"
+        } else if after.get("tags").is_some() {
+            "tags"
+        } else {
+            continue;
+        };
+        if after_unknown[tags_key] == true {
+            continue;
+        }
+
+        let missing: Vec<String> = policy
+            .required_tags
+            .iter()
+            .filter(|tag| after[tags_key].get(tag.as_str()).is_none())
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let address = change["address"].as_str().unwrap_or("?").to_string();
+            violations.push((address, missing));
+        }
+    }
+    violations
+}
+
+async fn run_orphan_scan(
+    account: &AccountState,
+    cancel_rx: &watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let cancelled_outcome = RunOutcome {
+        success: false,
+        cancelled: true,
+        exit_code: None,
+    };
+    let workspaces = if account.workspaces.is_empty() {
+        vec!["default".to_string()]
+    } else {
+        account.workspaces.clone()
+    };
+
+    let mut managed: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for workspace in &workspaces {
+        if *cancel_rx.borrow() != CancelSignal::None {
+            return Ok(cancelled_outcome);
+        }
+
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Reading state for workspace `{workspace}`..."
+        )));
+        let mut command = terraform_command(account, &["state", "pull"]);
+        command.env("TF_WORKSPACE", workspace);
+        let output = command
+            .output()
+            .await
+            .wrap_err("Failed to run terraform state pull")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "terraform state pull failed for workspace `{workspace}`: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let state: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .wrap_err_with(|| format!("Failed to parse state for workspace `{workspace}`"))?;
+        for check in &account.orphan_checks {
+            let ids = managed.entry(check.resource_type.as_str()).or_default();
+            for resource in state["resources"].as_array().into_iter().flatten() {
+                if resource["mode"] != "managed" || resource["type"] != check.resource_type.as_str()
+                {
+                    continue;
+                }
+                for instance in resource["instances"].as_array().into_iter().flatten() {
+                    if let Some(id) = instance["attributes"][&check.id_attribute].as_str() {
+                        ids.insert(id.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut total_unmanaged = 0;
+    for check in &account.orphan_checks {
+        if *cancel_rx.borrow() != CancelSignal::None {
+            return Ok(cancelled_outcome);
+        }
+
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Listing live `{}` resources...",
+            check.resource_type
+        )));
+        let output = account_shell_command(account, &check.list_command)
+            .output()
+            .await
+            .wrap_err_with(|| {
+                format!("Failed to run list_command for `{}`", check.resource_type)
+            })?;
+        if !output.status.success() {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Error: list_command for `{}` failed: {}",
+                check.resource_type,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+            continue;
+        }
+
+        let live: BTreeSet<String> = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let managed_ids = managed.get(check.resource_type.as_str());
+        let unmanaged: Vec<&String> = live
+            .iter()
+            .filter(|id| managed_ids.is_none_or(|ids| !ids.contains(*id)))
+            .collect();
+        total_unmanaged += unmanaged.len();
+
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "`{}`: {} live, {} managed, {} unmanaged",
+            check.resource_type,
+            live.len(),
+            managed_ids.map_or(0, BTreeSet::len),
+            unmanaged.len()
+        )));
+        for id in unmanaged {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Warning: unmanaged {} `{id}`",
+                check.resource_type
+            )));
+        }
+    }
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Orphan scan checked {} workspace(s); {total_unmanaged} unmanaged resource(s) found.",
+        workspaces.len()
+    )));
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: Some(0),
+    })
+}
+
+async fn run_cleanup_scan(
+    account_idx: usize,
+    account: &AccountState,
+    cleanup: CleanupConfig,
+    cancel_rx: &watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_composition_for_execution(account)?;
+
+    let stale_before = unix_now().saturating_sub(cleanup.stale_after_days * 86_400);
+    let mut candidates = Vec::new();
+    for workspace in account
+        .workspaces
+        .iter()
+        .filter(|ws| ws.as_str() != "default")
+    {
+        if *cancel_rx.borrow() != CancelSignal::None {
+            return Ok(RunOutcome {
+                success: false,
+                cancelled: true,
+                exit_code: None,
+            });
+        }
+
+        let mut command = terraform_command(account, &["state", "list"]);
+        command.env("TF_WORKSPACE", workspace);
+        let output = command
+            .output()
+            .await
+            .wrap_err("Failed to run terraform state list")?;
+        if !output.status.success() {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Skipping `{workspace}`: terraform state list failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+            continue;
+        }
+
+        let resources = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+        let last_activity = load_state_metrics(&account.name, workspace)?
+            .last()
+            .map(|sample| sample.timestamp);
+
+        let reason = if resources == 0 {
+            Some("state is empty".to_string())
+        } else {
+            last_activity
+                .filter(|timestamp| *timestamp < stale_before)
+                .map(|timestamp| {
+                    format!(
+                        "{resources} resources, untouched since {}",
+                        format_timestamp(timestamp)
+                    )
+                })
+        };
+
+        if let Some(reason) = reason {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Cleanup candidate `{workspace}`: {reason}"
+            )));
+            candidates.push(CleanupCandidate {
+                workspace: workspace.clone(),
+                reason,
+                resources,
+            });
+        }
+    }
+
+    let _ = event_tx.send(WorkerEvent::CleanupCandidates {
+        account_idx,
+        candidates,
+    });
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: Some(0),
+    })
+}
+
+async fn run_workspace_cleanup(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_operation_preflight(account, OperationKind::WorkspaceCleanup)?;
+    if workspace == "default" {
+        return Err(eyre!("the `default` workspace cannot be deleted"));
+    }
+
+    if let Some(approval) = &account.approval {
+        let target =
+            ApprovalTarget::described(format!("destroy and delete workspace `{workspace}`"));
+        ensure_apply_approved(
+            account,
+            workspace,
+            approval,
+            &target,
+            cancel_rx.clone(),
+            event_tx,
+        )
+        .await?;
+    }
+
+    let mut select_args = vec!["workspace", "select", workspace];
+    let select_out = terraform_command(account, &select_args)
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace select")?;
+    emit_process_output(event_tx, &select_out.stdout);
+    emit_process_output(event_tx, &select_out.stderr);
+    if !select_out.status.success() {
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: select_out.status.code(),
+        });
+    }
+
+    let mut destroy_args = vec![
+        "destroy".to_string(),
+        "-input=false".to_string(),
+        "-no-color".to_string(),
+        "-auto-approve".to_string(),
+    ];
+    append_var_file_args(&mut destroy_args, &account.var_files);
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Destroying resources in `{workspace}` before deleting it..."
+    )));
+    let mut destroy = terraform_command_owned(account, &destroy_args);
+    apply_tf_vars(&mut destroy, account, workspace);
+    let outcome = run_streaming_command(destroy, cancel_rx, event_tx.clone()).await?;
+    if !outcome.success {
+        return Ok(outcome);
+    }
+
+    select_args[2] = "default";
+    let select_out = terraform_command(account, &select_args)
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace select")?;
+    emit_process_output(event_tx, &select_out.stderr);
+    if !select_out.status.success() {
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: select_out.status.code(),
+        });
+    }
+
+    let delete_out = terraform_command(account, &["workspace", "delete", workspace])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace delete")?;
+    emit_process_output(event_tx, &delete_out.stdout);
+    emit_process_output(event_tx, &delete_out.stderr);
+
+    if let Ok((workspaces, current)) = fetch_workspaces(account).await {
+        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+            account_idx,
+            workspaces,
+            current,
+        });
+    }
+
+    Ok(RunOutcome {
+        success: delete_out.status.success(),
+        cancelled: false,
+        exit_code: delete_out.status.code(),
+    })
+}
+
+async fn run_fmt_check(
+    account_idx: usize,
+    account: &AccountState,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_composition_for_execution(account)?;
+    let list = terraform_command(account, &["fmt", "-check", "-recursive", "-no-color"])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform fmt -check")?;
+    let files: Vec<String> = String::from_utf8_lossy(&list.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if files.is_empty() {
+        emit_process_output(event_tx, &list.stderr);
+        let _ = event_tx.send(WorkerEvent::OutputLine(
+            "All files are formatted.".to_string(),
+        ));
+        return Ok(RunOutcome {
+            success: list.status.success(),
+            cancelled: false,
+            exit_code: list.status.code(),
+        });
+    }
+
+    let diff = terraform_command(
+        account,
+        &[
+            "fmt",
+            "-check",
+            "-diff",
+            "-list=false",
+            "-recursive",
+            "-no-color",
+        ],
+    );
+    let outcome = run_streaming_command(diff, cancel_rx, event_tx.clone()).await?;
+    if outcome.cancelled {
+        return Ok(outcome);
+    }
+
+    let _ = event_tx.send(WorkerEvent::FormatPending { account_idx, files });
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: outcome.exit_code,
+    })
+}
+
+async fn run_terraform_validate(
+    account: &AccountState,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let output = terraform_command(account, &["validate", "-json", "-no-color"])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform validate")?;
+
+    let Ok(report) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        emit_process_output(event_tx, &output.stdout);
+        emit_process_output(event_tx, &output.stderr);
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: output.status.code(),
+        });
+    };
+
+    let diagnostics = report
+        .get("diagnostics")
+        .and_then(serde_json::Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    for diagnostic in &diagnostics {
+        let field = |key: &str| {
+            diagnostic
+                .get(key)
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let severity = if field("severity") == "warning" {
+            "Warning"
+        } else {
+            "Error"
+        };
+        let location = diagnostic
+            .get("range")
+            .map(|range| {
+                let filename = range
+                    .get("filename")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default();
+                let line = range
+                    .pointer("/start/line")
+                    .and_then(serde_json::Value::as_u64)
+                    .unwrap_or_default();
+                let column = range
+                    .pointer("/start/column")
+                    .and_then(serde_json::Value::as_u64)
+                    .unwrap_or_default();
+                format!("{filename}:{line}:{column}: ")
+            })
+            .unwrap_or_default();
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "{severity}: {location}{}",
+            field("summary")
+        )));
+        for line in field("detail").lines() {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!("    {line}")));
+        }
+    }
+
+    let valid = report
+        .get("valid")
+        .and_then(serde_json::Value::as_bool)
+        .unwrap_or(false);
+    let count = |key: &str| {
+        report
+            .get(key)
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or_default()
+    };
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Validation {}: {} error(s), {} warning(s)",
+        if valid { "passed" } else { "failed" },
+        count("error_count"),
+        count("warning_count")
+    )));
+
+    Ok(RunOutcome {
+        success: valid,
+        cancelled: false,
+        exit_code: output.status.code(),
+    })
+}
+
+/// Takes and releases the state lock with a plan that neither refreshes nor saves anything,
+/// so the probe cannot modify state; returns the holder's lock ID when it is still taken.
+async fn probe_state_lock(account: &AccountState, workspace: &str) -> Result<Option<String>> {
+    let mut args: Vec<String> = [
+        "plan",
+        "-refresh=false",
+        "-lock-timeout=0s",
+        "-input=false",
+        "-no-color",
+        "-target=terraform_data.lazytf_lock_probe",
+    ]
+    .map(str::to_string)
+    .into();
+    append_var_file_args(&mut args, &account.var_files);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut command = terraform_command(account, &args);
+    command.env("TF_WORKSPACE", workspace);
+    let output = command
+        .output()
+        .await
+        .wrap_err("Failed to run terraform lock probe")?;
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.contains("Error acquiring the state lock") {
+        return Err(eyre!("lock probe failed: {}", stderr.trim()));
+    }
+    let lock_id = stderr
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("ID:"))
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default();
+    if lock_id.is_empty() {
+        return Err(eyre!("state is locked but the lock ID could not be read"));
+    }
+    Ok(Some(lock_id))
+}
+
+async fn run_force_unlock(
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_composition_for_execution(account)?;
+    let lock_id = options
+        .lock_id
+        .as_deref()
+        .ok_or_else(|| eyre!("No lock ID to release"))?;
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Releasing state lock {lock_id} on `{}/{workspace}`",
+        account.name
+    )));
+    let mut command = terraform_command(account, &["force-unlock", "-force", lock_id]);
+    command.env("TF_WORKSPACE", workspace);
+    run_streaming_command(command, cancel_rx, event_tx.clone()).await
+}
+
+async fn run_backend_report(
+    account_idx: usize,
+    account: &AccountState,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let (backend_type, config) = read_backend_metadata(account)?;
+    let config = &config;
+    let report = |line: String| {
+        let _ = event_tx.send(WorkerEvent::OutputLine(line));
+    };
+
+    let mut issues = Vec::new();
+    match backend_type.as_str() {
+        "s3" => {
+            let bucket = config["bucket"]
+                .as_str()
+                .ok_or_else(|| eyre!("S3 backend has no bucket configured"))?;
+            let region = config["region"].as_str().or(account.region.as_deref());
+            report(format!("Backend: s3 bucket `{bucket}`"));
+
+            if config["encrypt"].as_bool() != Some(true) {
+                issues.push("backend `encrypt` is not enabled".to_string());
+            }
+
+            match aws_cli_json(
+                account,
+                region,
+                &["s3api", "get-bucket-encryption", "--bucket", bucket],
+            )
+            .await
+            {
+                Ok(encryption) => {
+                    let algorithm = encryption["ServerSideEncryptionConfiguration"]["Rules"][0]
+                        ["ApplyServerSideEncryptionByDefault"]["SSEAlgorithm"]
+                        .as_str()
+                        .unwrap_or("unknown");
+                    report(format!("  ok: bucket default encryption ({algorithm})"));
+                }
+                Err(err) => issues.push(format!("bucket encryption could not be verified: {err}")),
+            }
+
+            match aws_cli_json(
+                account,
+                region,
+                &["s3api", "get-bucket-versioning", "--bucket", bucket],
+            )
+            .await
+            {
+                Ok(versioning) if versioning["Status"] == "Enabled" => {
+                    report("  ok: bucket versioning enabled".to_string());
+                }
+                Ok(_) => issues.push(
+                    "bucket versioning is not enabled; overwritten state cannot be recovered"
+                        .to_string(),
+                ),
+                Err(err) => issues.push(format!("bucket versioning could not be verified: {err}")),
+            }
+
+            if let Some(table) = config["dynamodb_table"].as_str().filter(|t| !t.is_empty()) {
+                match aws_cli_json(
+                    account,
+                    region,
+                    &["dynamodb", "describe-table", "--table-name", table],
+                )
+                .await
+                {
+                    Ok(description) => report(format!(
+                        "  ok: DynamoDB lock table `{table}` ({})",
+                        description["Table"]["TableStatus"]
+                            .as_str()
+                            .unwrap_or("unknown")
+                    )),
                     Err(err) => {
-                        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                            account_idx,
-                            status: AuthStatus::Failed,
-                            message: format!("Auth check errored for `{}`: {err}", account.name),
-                        });
-                        let _ = event_tx.send(WorkerEvent::OperationFinished {
-                            kind: OperationKind::AuthLogin,
-                            account_idx,
-                            success: false,
-                            cancelled: false,
-                            message: format!("Auth check errored for `{}`", account.name),
-                        });
+                        issues.push(format!("DynamoDB lock table `{table}` is missing: {err}"));
+                    }
+                }
+            } else if config["use_lockfile"].as_bool() == Some(true) {
+                report("  ok: S3 native state locking (use_lockfile)".to_string());
+            } else {
+                issues.push(
+                    "no state locking configured (set dynamodb_table or use_lockfile)".to_string(),
+                );
+            }
+        }
+        "local" => issues.push(
+            "state is stored locally; it is neither shared, locked, nor encrypted at rest"
+                .to_string(),
+        ),
+        other => report(format!(
+            "Backend `{other}` is not checked; only s3 backends are verified."
+        )),
+    }
+
+    for issue in &issues {
+        report(format!("  unsafe: {issue}"));
+    }
+    report(format!(
+        "Backend report for `{}`: {} issue(s).",
+        account.name,
+        issues.len()
+    ));
+    let _ = event_tx.send(WorkerEvent::BackendChecked {
+        account_idx,
+        issues: issues.len(),
+    });
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: None,
+    })
+}
+
+fn read_backend_metadata(account: &AccountState) -> Result<(String, serde_json::Value)> {
+    let metadata_path = account
+        .composition_path
+        .join(".terraform/terraform.tfstate");
+    let contents = fs::read_to_string(&metadata_path).wrap_err_with(|| {
+        format!(
+            "No backend metadata at {}; run `terraform init` first",
+            metadata_path.display()
+        )
+    })?;
+    let mut metadata: serde_json::Value =
+        serde_json::from_str(&contents).wrap_err("Failed to parse backend metadata")?;
+    let backend_type = metadata["backend"]["type"]
+        .as_str()
+        .unwrap_or("local")
+        .to_string();
+    Ok((backend_type, metadata["backend"]["config"].take()))
+}
+
+fn backend_probe_url(backend_type: &str, config: &serde_json::Value) -> Option<String> {
+    let string = |key: &str| config[key].as_str().filter(|value| !value.is_empty());
+    match backend_type {
+        "s3" => config["endpoints"]["s3"]
+            .as_str()
+            .or_else(|| string("endpoint"))
+            .map(str::to_string)
+            .or_else(|| {
+                Some(format!(
+                    "https://{}.s3.{}.amazonaws.com",
+                    string("bucket")?,
+                    string("region").unwrap_or("us-east-1")
+                ))
+            }),
+        "remote" | "cloud" => Some(format!(
+            "https://{}/api/v2/ping",
+            string("hostname").unwrap_or("app.terraform.io")
+        )),
+        "http" => string("address").map(str::to_string),
+        "consul" => Some(format!(
+            "{}://{}/v1/status/leader",
+            string("scheme").unwrap_or("http"),
+            string("address").unwrap_or("127.0.0.1:8500")
+        )),
+        "azurerm" => string("storage_account_name")
+            .map(|account| format!("https://{account}.blob.core.windows.net")),
+        "gcs" => Some("https://storage.googleapis.com".to_string()),
+        _ => None,
+    }
+}
+
+/// Any HTTP answer counts as reachable; only connection failures and timeouts don't.
+fn spawn_backend_probe(
+    account_idx: usize,
+    account: &AccountState,
+    timeout_seconds: u64,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    // Compositions that were never initialized have no recorded backend to probe.
+    let Some((backend_type, url)) =
+        read_backend_metadata(account)
+            .ok()
+            .and_then(|(backend_type, config)| {
+                backend_probe_url(&backend_type, &config).map(|url| (backend_type, url))
+            })
+    else {
+        return;
+    };
+    let event_tx = worker_tx.clone();
+    let label = format!("backend probe {} ({backend_type})", account.name);
+    let env = account.env.clone();
+    spawn_tracked(worker_tx, label, async move {
+        let output = Command::new("curl")
+            .envs(&env)
+            .args([
+                "--silent",
+                "--show-error",
+                "--output",
+                "/dev/null",
+                "--max-time",
+                &timeout_seconds.max(1).to_string(),
+                &url,
+            ])
+            .stdin(Stdio::null())
+            .output()
+            .await;
+        let error = match output {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some(format!(
+                "{backend_type} {url}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(err) => Some(format!("failed to run curl: {err}")),
+        };
+        let reachable = error.is_none();
+        let _ = event_tx.send(WorkerEvent::BackendProbed { account_idx, error });
+        reachable
+    });
+}
+
+async fn run_lock_table_view(
+    account_idx: usize,
+    account: &AccountState,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let (backend_type, config) = read_backend_metadata(account)?;
+    let table = config["dynamodb_table"]
+        .as_str()
+        .filter(|table| backend_type == "s3" && !table.is_empty())
+        .ok_or_else(|| {
+            eyre!(
+                "`{}` does not use an S3 backend with a DynamoDB lock table",
+                account.name
+            )
+        })?;
+    let region = config["region"].as_str().or(account.region.as_deref());
+    let scan = aws_cli_json(
+        account,
+        region,
+        &["dynamodb", "scan", "--table-name", table],
+    )
+    .await?;
+
+    // Items without `Info` are the state digests Terraform keeps next to the locks.
+    let locks: Vec<LockItem> = scan["Items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let info: serde_json::Value = serde_json::from_str(item["Info"]["S"].as_str()?).ok()?;
+            let field = |key: &str| info[key].as_str().unwrap_or_default().to_string();
+            Some(LockItem {
+                path: item["LockID"]["S"].as_str().unwrap_or_default().to_string(),
+                id: field("ID"),
+                who: field("Who"),
+                operation: field("Operation")
+                    .trim_start_matches("OperationType")
+                    .to_string(),
+                created: field("Created"),
+            })
+        })
+        .collect();
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "{} lock(s) held in `{table}`.",
+        locks.len()
+    )));
+    let _ = event_tx.send(WorkerEvent::LocksLoaded {
+        account_idx,
+        table: table.to_string(),
+        locks,
+    });
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: None,
+    })
+}
+
+async fn aws_cli_json(
+    account: &AccountState,
+    region: Option<&str>,
+    args: &[&str],
+) -> Result<serde_json::Value> {
+    let mut command = Command::new("aws");
+    apply_account_env(&mut command, account);
+    command.args(args).args(["--output", "json"]);
+    if let Some(region) = region {
+        command.args(["--region", region]);
+    }
+    let output = command.output().await.wrap_err("Failed to run aws")?;
+    if !output.status.success() {
+        return Err(eyre!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    serde_json::from_slice(&output.stdout).wrap_err("Failed to parse aws output")
+}
+
+async fn detect_terraform_version(account: &AccountState) -> Option<(u64, u64, u64)> {
+    let output = terraform_command(account, &["version", "-json"])
+        .output()
+        .await
+        .ok()?;
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let mut parts = report["terraform_version"]
+        .as_str()?
+        .split(['.', '-'])
+        .map(|part| part.parse::<u64>().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+static REQUIRED_VERSION: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r#"^\s*required_version\s*=\s*"([^"]*)""#).expect("valid required_version regex")
+});
+
+fn required_version_constraints(composition_path: &Path) -> Vec<String> {
+    let mut files: Vec<PathBuf> = fs::read_dir(composition_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tf"))
+        .collect();
+    files.sort();
+    files
+        .iter()
+        .filter_map(|file| fs::read_to_string(file).ok())
+        .flat_map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| {
+                    REQUIRED_VERSION
+                        .captures(line)
+                        .map(|caps| caps[1].to_string())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Checks a version against a Terraform constraint string such as `>= 1.5, < 2.0` or
+/// `~> 1.6.0`. Parts that can't be parsed are treated as satisfied.
+fn version_satisfies(version: (u64, u64, u64), constraint: &str) -> bool {
+    constraint
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .all(|part| {
+            let (op, rest) = ["~>", ">=", "<=", "!=", ">", "<", "="]
+                .iter()
+                .find_map(|op| part.strip_prefix(op).map(|rest| (*op, rest.trim())))
+                .unwrap_or(("=", part));
+            let Some(segments) = rest
+                .split('-')
+                .next()
+                .unwrap_or_default()
+                .split('.')
+                .map(|segment| segment.parse::<u64>().ok())
+                .collect::<Option<Vec<u64>>>()
+                .filter(|segments| !segments.is_empty())
+            else {
+                return true;
+            };
+            let target = (
+                segments[0],
+                segments.get(1).copied().unwrap_or(0),
+                segments.get(2).copied().unwrap_or(0),
+            );
+            match op {
+                // `~> 1.6` allows 1.x from 1.6 on; `~> 1.6.0` allows 1.6.x only.
+                "~>" if segments.len() >= 3 => {
+                    version >= target && (version.0, version.1) == (target.0, target.1)
+                }
+                "~>" => version >= target && version.0 == target.0,
+                ">=" => version >= target,
+                "<=" => version <= target,
+                "!=" => version != target,
+                ">" => version > target,
+                "<" => version < target,
+                _ => version == target,
+            }
+        })
+}
+
+fn spawn_required_version_check(
+    account_idx: usize,
+    account: &AccountState,
+    worker_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    if account.composition_issue.is_some() {
+        return;
+    }
+    let constraints = required_version_constraints(&account.composition_path);
+    if constraints.is_empty() {
+        return;
+    }
+    let account = account.clone();
+    let event_tx = worker_tx.clone();
+    let label = format!("version check {}", account.name);
+    spawn_tracked(worker_tx, label, async move {
+        let Some(version) = detect_terraform_version(&account).await else {
+            let _ = event_tx.send(WorkerEvent::RequiredVersionChecked {
+                account_idx,
+                mismatch: Some(format!(
+                    "`{} version` failed, so required_version {} can't be checked",
+                    account.binary,
+                    constraints.join(", ")
+                )),
+            });
+            return false;
+        };
+        let _ = event_tx.send(WorkerEvent::TerraformVersionDetected {
+            account_idx,
+            version,
+        });
+        let mismatch = constraints
+            .iter()
+            .find(|constraint| !version_satisfies(version, constraint))
+            .map(|constraint| {
+                format!(
+                    "{} {}.{}.{} does not satisfy required_version \"{constraint}\"",
+                    account.binary_name(),
+                    version.0,
+                    version.1,
+                    version.2
+                )
+            });
+        let _ = event_tx.send(WorkerEvent::RequiredVersionChecked {
+            account_idx,
+            mismatch,
+        });
+        true
+    });
+}
+
+async fn run_state_taint(
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    untaint: bool,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let address = options
+        .state_address
+        .as_deref()
+        .ok_or_else(|| eyre!("No resource address selected"))?;
+    let subcommand = if untaint { "untaint" } else { "taint" };
+    let mut command = terraform_command(account, &[subcommand, "-no-color", address]);
+    command.env("TF_WORKSPACE", workspace);
+    run_streaming_command(command, cancel_rx, event_tx.clone()).await
+}
+
+async fn run_state_list(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    if let Some(version) = detect_terraform_version(account).await {
+        let _ = event_tx.send(WorkerEvent::TerraformVersionDetected {
+            account_idx,
+            version,
+        });
+    }
+
+    let mut command = terraform_command(account, &["state", "list"]);
+    command.env("TF_WORKSPACE", workspace);
+    let output = command
+        .output()
+        .await
+        .wrap_err("Failed to run terraform state list")?;
+    if !output.status.success() {
+        emit_process_output(event_tx, &output.stderr);
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: output.status.code(),
+        });
+    }
+
+    let resources: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "{} resource(s) in `{}/{workspace}` state.",
+        resources.len(),
+        account.name
+    )));
+    let _ = event_tx.send(WorkerEvent::StateListed {
+        account_idx,
+        workspace: workspace.to_string(),
+        resources,
+    });
+    Ok(RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: output.status.code(),
+    })
+}
+
+async fn run_state_show(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let address = options
+        .state_address
+        .as_deref()
+        .ok_or_else(|| eyre!("No resource address selected"))?;
+    let mut command = terraform_command(account, &["state", "show", "-no-color", address]);
+    command.env("TF_WORKSPACE", workspace);
+    let output = command
+        .output()
+        .await
+        .wrap_err("Failed to run terraform state show")?;
+    if output.status.success() {
+        let _ = event_tx.send(WorkerEvent::StateShown {
+            account_idx,
+            workspace: workspace.to_string(),
+            address: address.to_string(),
+            attributes: String::from_utf8_lossy(&output.stdout).into_owned(),
+        });
+    } else {
+        emit_process_output(event_tx, &output.stderr);
+    }
+    Ok(RunOutcome {
+        success: output.status.success(),
+        cancelled: false,
+        exit_code: output.status.code(),
+    })
+}
+
+async fn run_state_rm(
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let address = options
+        .state_address
+        .as_deref()
+        .ok_or_else(|| eyre!("No resource address selected"))?;
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Removing `{address}` from `{}/{workspace}` state (the real resource is left untouched)",
+        account.name
+    )));
+    let mut command = terraform_command(account, &["state", "rm", "-no-color", address]);
+    command.env("TF_WORKSPACE", workspace);
+    run_streaming_command(command, cancel_rx, event_tx.clone()).await
+}
+
+async fn run_state_mv(
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let (Some(source), Some(destination)) = (
+        options.state_address.as_deref(),
+        options.state_destination.as_deref(),
+    ) else {
+        return Err(eyre!("terraform state mv needs a source and a destination"));
+    };
+    let mut command =
+        terraform_command(account, &["state", "mv", "-no-color", source, destination]);
+    command.env("TF_WORKSPACE", workspace);
+    run_streaming_command(command, cancel_rx, event_tx.clone()).await
+}
+
+async fn run_workspace_new(
+    account_idx: usize,
+    account: &AccountState,
+    names: &[String],
+    cancel_rx: &watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let (existing, _) = fetch_workspaces(account).await?;
+    let mut outcome = RunOutcome {
+        success: true,
+        cancelled: false,
+        exit_code: Some(0),
+    };
+    let mut created = 0;
+    for name in names {
+        if existing.contains(name) {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Workspace `{name}` already exists; skipping."
+            )));
+            continue;
+        }
+        if *cancel_rx.borrow() != CancelSignal::None {
+            outcome = RunOutcome {
+                success: false,
+                cancelled: true,
+                exit_code: None,
+            };
+            break;
+        }
+        let new_out = terraform_command(account, &["workspace", "new", name])
+            .output()
+            .await
+            .wrap_err("Failed to run terraform workspace new")?;
+        emit_process_output(event_tx, &new_out.stdout);
+        emit_process_output(event_tx, &new_out.stderr);
+        if !new_out.status.success() {
+            outcome = RunOutcome {
+                success: false,
+                cancelled: false,
+                exit_code: new_out.status.code(),
+            };
+            break;
+        }
+        created += 1;
+    }
+    if names.len() > 1 {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Created {created} of {} listed workspace(s) in `{}`.",
+            names.len(),
+            account.name
+        )));
+    }
+
+    if let Ok((workspaces, current)) = fetch_workspaces(account).await {
+        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+            account_idx,
+            workspaces,
+            current,
+        });
+    }
+
+    Ok(outcome)
+}
+
+async fn run_workspace_delete(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    force: bool,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    if workspace == "default" {
+        return Err(eyre!("the `default` workspace cannot be deleted"));
+    }
+    let select_out = terraform_command(account, &["workspace", "select", "default"])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace select")?;
+    emit_process_output(event_tx, &select_out.stderr);
+    if !select_out.status.success() {
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: select_out.status.code(),
+        });
+    }
+
+    let mut args = vec!["workspace", "delete"];
+    if force {
+        args.push("-force");
+    }
+    args.push(workspace);
+    let delete_out = terraform_command(account, &args)
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace delete")?;
+    emit_process_output(event_tx, &delete_out.stdout);
+    emit_process_output(event_tx, &delete_out.stderr);
+
+    if let Ok((workspaces, current)) = fetch_workspaces(account).await {
+        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+            account_idx,
+            workspaces,
+            current,
+        });
+    }
+
+    Ok(RunOutcome {
+        success: delete_out.status.success(),
+        cancelled: false,
+        exit_code: delete_out.status.code(),
+    })
+}
+
+fn desired_workspaces(account: &AccountState) -> Result<Vec<String>> {
+    let names = match &account.desired_workspaces {
+        None => {
+            return Err(eyre!(
+                "No `desired_workspaces` configured for `{}`",
+                account.name
+            ));
+        }
+        Some(DesiredWorkspacesConfig::List(names)) => names.clone(),
+        Some(DesiredWorkspacesConfig::File { file }) => {
+            let path = account.composition_path.join(file);
+            fs::read_to_string(&path)
+                .wrap_err_with(|| format!("Failed to read {}", path.display()))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect()
+        }
+    };
+    let mut unique = Vec::new();
+    for name in names {
+        validate_workspace_name(&name).map_err(|err| eyre!("workspace `{name}`: {err}"))?;
+        if !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    Ok(unique)
+}
+
+async fn run_ephemeral_create(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_composition_for_execution(account)?;
+    let config = account
+        .ephemeral
+        .as_ref()
+        .ok_or_else(|| eyre!("No `ephemeral` template configured for `{}`", account.name))?;
+    let spec = options
+        .ephemeral
+        .as_ref()
+        .ok_or_else(|| eyre!("Missing ephemeral environment details"))?;
+
+    let template_path = account.composition_path.join(&config.template);
+    let template = fs::read_to_string(&template_path).wrap_err_with(|| {
+        format!(
+            "Failed to read ephemeral template {}",
+            template_path.display()
+        )
+    })?;
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Creating workspace `{workspace}` in `{}`",
+        account.name
+    )));
+    let new_out = terraform_command(account, &["workspace", "new", workspace])
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace new")?;
+    emit_process_output(event_tx, &new_out.stdout);
+    emit_process_output(event_tx, &new_out.stderr);
+    if !new_out.status.success() {
+        return Ok(RunOutcome {
+            success: false,
+            cancelled: false,
+            exit_code: new_out.status.code(),
+        });
+    }
+
+    let created_at = unix_now();
+    let expires_at = created_at + spec.ttl_hours * 3_600;
+    let rendered = render_template(
+        &template,
+        &[
+            ("name", workspace.to_string()),
+            ("owner", spec.owner.clone()),
+            ("account", account.name.clone()),
+            ("ttl_hours", spec.ttl_hours.to_string()),
+            ("expires_at", format_timestamp(expires_at)),
+        ],
+    );
+    let output_dir = account.composition_path.join(&config.output_dir);
+    fs::create_dir_all(&output_dir)
+        .wrap_err_with(|| format!("Failed to create {}", output_dir.display()))?;
+    let var_file = output_dir.join(format!("{workspace}.tfvars"));
+    fs::write(&var_file, rendered)
+        .wrap_err_with(|| format!("Failed to write {}", var_file.display()))?;
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Wrote environment var file {}",
+        var_file.display()
+    )));
+    let uncommitted = uncommitted_paths(std::slice::from_ref(&var_file)).await;
+    if !uncommitted.is_empty() {
+        let _ = event_tx.send(WorkerEvent::ChangesToPropose {
+            paths: uncommitted,
+            title: format!("Add ephemeral environment {workspace} to {}", account.name),
+        });
+    }
+
+    let environment = EphemeralEnvironment {
+        account: account.name.clone(),
+        workspace: workspace.to_string(),
+        owner: spec.owner.clone(),
+        created_at,
+        expires_at,
+        var_file: var_file.clone(),
+    };
+
+    let outcome = run_terraform_operation(
+        OperationKind::TerraformPlan,
+        account.clone(),
+        workspace.to_string(),
+        RunOptions {
+            extra_var_files: vec![var_file],
+            ..RunOptions::default()
+        },
+        cancel_rx,
+        event_tx.clone(),
+    )
+    .await;
+
+    if let Ok((workspaces, current)) = fetch_workspaces(account).await {
+        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
+            account_idx,
+            workspaces,
+            current,
+        });
+    }
+    let _ = event_tx.send(WorkerEvent::EphemeralCreated {
+        account_idx,
+        environment,
+        planned: matches!(&outcome, Ok(outcome) if outcome.success),
+    });
+
+    outcome
+}
+
+fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |rendered, (key, value)| {
+            rendered.replace(&format!("{{{{{key}}}}}"), value)
+        })
+}
+
+fn validate_workspace_name(name: &str) -> std::result::Result<(), String> {
+    if name.is_empty() {
+        return Err("name is required".to_string());
+    }
+    if !name
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    {
+        return Err("use only letters, digits, `-`, and `_`".to_string());
+    }
+    Ok(())
+}
+
+fn environments_path() -> PathBuf {
+    lazytf_state_dir().join("environments.json")
+}
+
+fn load_environments() -> Vec<EphemeralEnvironment> {
+    fs::read_to_string(environments_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn saved_plan_path(account: &str, workspace: &str) -> PathBuf {
+    lazytf_state_dir()
+        .join("plans")
+        .join(account)
+        .join(format!("{workspace}.tfplan"))
+}
+
+/// Creates the directory of `plan_file` as `0700`; plans hold secrets in clear, so a
+/// directory owned by someone else (or a symlink) is refused.
+fn prepare_plan_dir(plan_file: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    let Some(dir) = plan_file.parent() else {
+        return Ok(());
+    };
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    let uid = nix::unistd::getuid().as_raw();
+    for dir in [lazytf_state_dir().join("plans").as_path(), dir] {
+        let meta = fs::symlink_metadata(dir)
+            .wrap_err_with(|| format!("Failed to inspect {}", dir.display()))?;
+        if !meta.is_dir() || meta.uid() != uid {
+            return Err(eyre!(
+                "{} is not a directory owned by you; refusing to keep plans there",
+                dir.display()
+            ));
+        }
+        if meta.mode() & 0o077 != 0 {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+                .wrap_err_with(|| format!("Failed to restrict {}", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn restrict_plan_file(plan_file: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(plan_file, fs::Permissions::from_mode(0o600));
+}
+
+fn scheduled_applies_path() -> PathBuf {
+    lazytf_state_dir().join("scheduled_applies.json")
+}
+
+fn load_scheduled_applies() -> Vec<ScheduledApply> {
+    fs::read_to_string(scheduled_applies_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_scheduled_applies(schedules: &[ScheduledApply]) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    fs::write(
+        scheduled_applies_path(),
+        serde_json::to_string_pretty(schedules)?,
+    )
+    .wrap_err("Failed to write scheduled applies file")?;
+    Ok(())
+}
+
+/// Creates the schedule's marker with `create_new`, which only one instance can win.
+fn claim_scheduled_apply(id: u64) -> bool {
+    const CLAIM_RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+    let dir = lazytf_state_dir().join("schedule_claims");
+    if fs::create_dir_all(&dir).is_err() {
+        return false;
+    }
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        if entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > CLAIM_RETENTION))
+        {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dir.join(id.to_string()))
+        .is_ok()
+}
+
+fn maintenance_locks_path() -> PathBuf {
+    lazytf_state_dir().join("maintenance_locks.json")
+}
+
+fn load_maintenance_locks() -> BTreeMap<String, MaintenanceLock> {
+    fs::read_to_string(maintenance_locks_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Read-modify-writes the locks file under an exclusive `flock`, so two lazytf instances
+/// cannot drop each other's locks, and replaces it by rename so readers never see half a
+/// file. Returns the locks as written.
+fn update_maintenance_locks(
+    update: impl FnOnce(&mut BTreeMap<String, MaintenanceLock>),
+) -> Result<BTreeMap<String, MaintenanceLock>> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    let path = maintenance_locks_path();
+    let guard = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.with_extension("json.lock"))
+        .wrap_err("Failed to open maintenance locks lock file")?;
+    let _guard = nix::fcntl::Flock::lock(guard, nix::fcntl::FlockArg::LockExclusive)
+        .map_err(|(_, err)| eyre!("Failed to lock maintenance locks file: {err}"))?;
+
+    let mut locks = load_maintenance_locks();
+    update(&mut locks);
+    let temp = path.with_extension(format!("json.tmp-{}", std::process::id()));
+    fs::write(&temp, serde_json::to_string_pretty(&locks)?)
+        .wrap_err("Failed to write maintenance locks file")?;
+    fs::rename(&temp, &path).wrap_err("Failed to replace maintenance locks file")?;
+    Ok(locks)
+}
+
+fn save_environments(environments: &[EphemeralEnvironment]) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    fs::write(
+        environments_path(),
+        serde_json::to_string_pretty(environments)?,
+    )
+    .wrap_err("Failed to write environments file")?;
+    Ok(())
+}
+
+async fn run_cost_breakdown(
+    account: &AccountState,
+    workspace: &str,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    validate_composition_for_execution(account)?;
+
+    // Costing the saved plan prices exactly what `A` would apply, with its var files and
+    // workspace, instead of having infracost re-evaluate the directory on its own.
+    let plan_file = saved_plan_path(&account.name, workspace);
+    if !plan_file.exists() {
+        return Err(eyre!(
+            "no saved plan for `{}`/`{workspace}`; press `p` to plan first",
+            account.name
+        ));
+    }
+    let plan = show_plan_json(account, &plan_file).await?;
+    // The JSON plan holds the same secrets as the plan file, so it gets the same mode.
+    let plan_json_path = plan_file.with_extension("cost.json");
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&plan_json_path)
+            .wrap_err_with(|| format!("Failed to create {}", plan_json_path.display()))?;
+        serde_json::to_writer(&mut file, &plan)
+            .wrap_err_with(|| format!("Failed to write {}", plan_json_path.display()))?;
+    }
+
+    let output_path = std::env::temp_dir().join(format!(
+        "lazytf-infracost-{}-{workspace}-{}.json",
+        account.name,
+        std::process::id()
+    ));
+    let mut command = Command::new("infracost");
+    apply_account_env(&mut command, account);
+    command
+        .args(["breakdown", "--path"])
+        .arg(&plan_json_path)
+        .args(["--format", "json", "--no-color", "--out-file"])
+        .arg(&output_path);
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Running `infracost breakdown` on the saved plan of `{}`/`{workspace}`",
+        account.name
+    )));
+    let outcome = run_streaming_command(command, cancel_rx, event_tx.clone()).await;
+    let _ = fs::remove_file(&plan_json_path);
+    let outcome = outcome?;
+    if !outcome.success {
+        let _ = fs::remove_file(&output_path);
+        return Ok(outcome);
+    }
+
+    let contents = fs::read_to_string(&output_path)
+        .wrap_err_with(|| format!("Failed to read infracost output {}", output_path.display()));
+    let _ = fs::remove_file(&output_path);
+    let report: serde_json::Value =
+        serde_json::from_str(&contents?).wrap_err("Failed to parse infracost JSON output")?;
+    let monthly_cost = report["totalMonthlyCost"]
+        .as_str()
+        .and_then(|raw| raw.parse::<f64>().ok())
+        .unwrap_or_default();
+
+    let _ = event_tx.send(WorkerEvent::CostUpdated {
+        account: account.name.clone(),
+        workspace: workspace.to_string(),
+        estimate: CostEstimate {
+            monthly_cost,
+            currency: report["currency"].as_str().unwrap_or("USD").to_string(),
+            timestamp: unix_now(),
+        },
+    });
+    Ok(outcome)
+}
+
+fn cost_key(account_name: &str, workspace: &str) -> String {
+    format!("{account_name}/{workspace}")
+}
+
+fn cost_cache_path() -> PathBuf {
+    lazytf_state_dir().join("cost_cache.json")
+}
+
+fn drift_cache_path() -> PathBuf {
+    lazytf_state_dir().join("drift.json")
+}
+
+fn load_drift_cache() -> BTreeMap<String, DriftRecord> {
+    fs::read_to_string(drift_cache_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_drift_cache(drift: &BTreeMap<String, DriftRecord>) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    fs::write(drift_cache_path(), serde_json::to_string_pretty(drift)?)
+        .wrap_err("Failed to write drift results")?;
+    Ok(())
+}
+
+fn update_check_path() -> PathBuf {
+    lazytf_state_dir().join("update_check.json")
+}
+
+fn load_update_check() -> UpdateCheckRecord {
+    fs::read_to_string(update_check_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_update_check(record: &UpdateCheckRecord) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    fs::write(update_check_path(), serde_json::to_string_pretty(record)?)
+        .wrap_err("Failed to write update check state")?;
+    Ok(())
+}
+
+fn load_cost_cache() -> BTreeMap<String, CostEstimate> {
+    fs::read_to_string(cost_cache_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_cost_cache(costs: &BTreeMap<String, CostEstimate>) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    fs::write(cost_cache_path(), serde_json::to_string_pretty(costs)?)
+        .wrap_err("Failed to write cost cache")?;
+    Ok(())
+}
+
+fn format_monthly_cost(estimate: &CostEstimate) -> String {
+    let symbol = match estimate.currency.as_str() {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        _ => "",
+    };
+    if symbol.is_empty() {
+        format!("{:.2} {}/mo", estimate.monthly_cost, estimate.currency)
+    } else {
+        format!("{symbol}{:.2}/mo", estimate.monthly_cost)
+    }
+}
+
+async fn ensure_apply_approved(
+    account: &AccountState,
+    workspace: &str,
+    approval: &ApprovalConfig,
+    target: &ApprovalTarget,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<()> {
+    match approval.mode {
+        ApprovalMode::Hmac => {
+            let Some((request_path, request)) =
+                find_pending_approval(&account.name, workspace, &target.digest)?
+            else {
+                let (request_path, request) =
+                    create_approval_request(account, workspace, approval, target)?;
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Two-person approval required to apply `{}` in `{}` (request {}, {}).",
+                    workspace, account.name, request.id, request.subject
+                )));
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "A second operator must run `lazytf approve {}` within {} minutes, then retry apply.",
+                    request_path.display(),
+                    approval.ttl_minutes
+                )));
+                return Err(eyre!("apply is waiting for second-operator approval"));
+            };
+
+            let signature_path = request_path.with_extension("sig");
+            if !signature_path.exists() {
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Approval request {} is not signed yet. Waiting for `lazytf approve {}`.",
+                    request.id,
+                    request_path.display()
+                )));
+                return Err(eyre!("apply is waiting for second-operator approval"));
+            }
+
+            let approver =
+                verify_approval_signature(&signature_path, &request, approval)?.approved_by;
+            let _ = fs::remove_file(&signature_path);
+            let _ = fs::remove_file(&request_path);
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Apply approved by `{approver}` (request {}).",
+                request.id
+            )));
+            Ok(())
+        }
+        ApprovalMode::Command => {
+            let approval_command = approval.command.as_deref().ok_or_else(|| {
+                eyre!("approval.command is required when approval.mode is `command`")
+            })?;
+            let (request_path, request) =
+                create_approval_request(account, workspace, approval, target)?;
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Running approval command for request {}...",
+                request.id
+            )));
+
+            let mut command = account_shell_command(account, approval_command);
+            command
+                .env("LAZYTF_APPROVAL_REQUEST", &request_path)
+                .env("LAZYTF_ACCOUNT", &request.account)
+                .env("LAZYTF_WORKSPACE", &request.workspace)
+                .env("LAZYTF_REQUESTED_BY", &request.requested_by)
+                .env("LAZYTF_APPROVAL_SUBJECT", &request.subject)
+                .env("LAZYTF_PLAN_SHA256", &request.plan_sha256);
+            let outcome = run_streaming_command(command, cancel_rx, event_tx.clone()).await;
+            let _ = fs::remove_file(&request_path);
+            let outcome = outcome?;
+
+            if outcome.cancelled {
+                return Err(eyre!("approval was cancelled"));
+            }
+            if !outcome.success {
+                return Err(eyre!(
+                    "approval command denied apply (exit code {})",
+                    outcome.exit_code.unwrap_or(-1)
+                ));
+            }
+
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Apply approved by approval command (request {}).",
+                request.id
+            )));
+            Ok(())
+        }
+    }
+}
+
+async fn validate_change_ticket(
+    account: &AccountState,
+    workspace: &str,
+    ticket: &str,
+    validate_command: &str,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<()> {
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Validating change ticket `{ticket}`..."
+    )));
+
+    let mut command = account_shell_command(account, validate_command);
+    command
+        .env("LAZYTF_CHANGE_TICKET", ticket)
+        .env("LAZYTF_ACCOUNT", &account.name)
+        .env("LAZYTF_WORKSPACE", workspace);
+    let outcome = run_streaming_command(command, cancel_rx, event_tx.clone()).await?;
+
+    if outcome.cancelled {
+        return Err(eyre!("change ticket validation was cancelled"));
+    }
+    if !outcome.success {
+        return Err(eyre!(
+            "change ticket `{ticket}` was rejected by change_ticket.validate_command"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_preconditions(
+    account: &AccountState,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<()> {
+    for check in &account.preconditions {
+        let (what, failure) = if let Some(host) = &check.dns {
+            let lookup = tokio::time::timeout(
+                PRECONDITION_TIMEOUT,
+                tokio::net::lookup_host((host.as_str(), 0)),
+            )
+            .await;
+            let failure = match lookup {
+                Ok(Ok(mut addresses)) => addresses
+                    .next()
+                    .is_none()
+                    .then(|| "no addresses".to_string()),
+                Ok(Err(err)) => Some(err.to_string()),
+                Err(_) => Some("lookup timed out".to_string()),
+            };
+            (format!("`{host}` resolves"), failure)
+        } else if let Some(script) = &check.command {
+            let mut command = account_shell_command(account, script);
+            command
+                .env("LAZYTF_ACCOUNT", &account.name)
+                .stdin(Stdio::null())
+                .kill_on_drop(true);
+            let failure = match tokio::time::timeout(PRECONDITION_TIMEOUT, command.output()).await {
+                Ok(Ok(output)) if output.status.success() => None,
+                Ok(Ok(output)) => Some(format!("exit code {}", output.status.code().unwrap_or(-1))),
+                Ok(Err(err)) => Some(err.to_string()),
+                Err(_) => Some("timed out".to_string()),
+            };
+            (format!("`{script}` succeeds"), failure)
+        } else {
+            continue;
+        };
+        if let Some(failure) = failure {
+            let hint = check
+                .message
+                .clone()
+                .unwrap_or_else(|| "check your VPN/network connection".to_string());
+            return Err(eyre!(
+                "precondition failed: {hint} (expected {what}: {failure})"
+            ));
+        }
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!("Precondition ok: {what}")));
+    }
+    Ok(())
+}
+
+fn write_audit_record(
+    record: &AuditRecord,
+    sinks: &[AuditSink],
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    if let Err(err) = append_audit_record(record) {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "warning: failed to write audit log: {err}"
+        )));
+    }
+    let _ = event_tx.send(WorkerEvent::Audited(record.clone()));
+
+    let Ok(payload) = serde_json::to_string(record) else {
+        return;
+    };
+    for sink in sinks {
+        let sink = sink.clone();
+        let payload = payload.clone();
+        let tracker = event_tx.clone();
+        let event_tx = event_tx.clone();
+        spawn_tracked(&tracker, format!("audit {}", sink.label()), async move {
+            // Records a sink missed earlier go first, so an outage only delays them.
+            let label = sink.label();
+            let mut pending = take_queued_audit_records(&label);
+            pending.push(payload);
+            for (idx, payload) in pending.iter().enumerate() {
+                let Err(err) = sink.emit(payload).await else {
+                    continue;
+                };
+                let unsent = &pending[idx..];
+                let kept = match queue_audit_records(&label, unsent) {
+                    Ok(()) => format!(
+                        "{} record(s) queued in {} and retried with the next one",
+                        unsent.len(),
+                        audit_outbox_path().display()
+                    ),
+                    Err(queue_err) => format!("queueing them failed too: {queue_err}"),
+                };
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "warning: failed to ship audit record to {label}: {err}; {kept}"
+                )));
+                return false;
+            }
+            true
+        });
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueuedAuditRecord {
+    sink: String,
+    payload: String,
+}
+
+static AUDIT_OUTBOX_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn audit_outbox_path() -> PathBuf {
+    lazytf_state_dir().join("audit_outbox.jsonl")
+}
+
+fn take_queued_audit_records(sink: &str) -> Vec<String> {
+    let _guard = AUDIT_OUTBOX_LOCK.lock();
+    let Ok(contents) = fs::read_to_string(audit_outbox_path()) else {
+        return Vec::new();
+    };
+    let (taken, kept): (Vec<QueuedAuditRecord>, Vec<QueuedAuditRecord>) = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .partition(|record: &QueuedAuditRecord| record.sink == sink);
+    if !taken.is_empty() {
+        let rest: String = kept
+            .iter()
+            .filter_map(|record| serde_json::to_string(record).ok())
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let _ = fs::write(audit_outbox_path(), rest);
+    }
+    taken.into_iter().map(|record| record.payload).collect()
+}
+
+fn queue_audit_records(sink: &str, payloads: &[String]) -> Result<()> {
+    let _guard = AUDIT_OUTBOX_LOCK.lock();
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_outbox_path())
+        .wrap_err("Failed to open audit outbox")?;
+    for payload in payloads {
+        let line = serde_json::to_string(&QueuedAuditRecord {
+            sink: sink.to_string(),
+            payload: payload.clone(),
+        })?;
+        io::Write::write_all(&mut file, format!("{line}\n").as_bytes())
+            .wrap_err("Failed to write audit outbox")?;
+    }
+    Ok(())
+}
+
+impl AuditSink {
+    fn label(&self) -> String {
+        match self {
+            Self::Syslog { address, .. } => format!("syslog {address}"),
+            Self::Http { url, .. } => format!("http {url}"),
+        }
+    }
+
+    async fn emit(&self, payload: &str) -> Result<()> {
+        match self {
+            Self::Syslog { address, facility } => send_syslog(address, facility, payload).await,
+            Self::Http { url, headers } => post_audit_http(url, headers, payload).await,
+        }
+    }
+}
+
+async fn send_syslog(address: &str, facility: &str, payload: &str) -> Result<()> {
+    const SEVERITY_NOTICE: u8 = 5;
+    let facility_code = match facility {
+        "user" => 1,
+        "auth" => 4,
+        "authpriv" => 10,
+        "local0" => 16,
+        "local1" => 17,
+        "local2" => 18,
+        "local3" => 19,
+        "local4" => 20,
+        "local5" => 21,
+        "local6" => 22,
+        "local7" => 23,
+        other => return Err(eyre!("unsupported syslog facility `{other}`")),
+    };
+    let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| "-".to_string());
+    let message = format!(
+        "<{}>1 - {hostname} lazytf {} audit - {payload}",
+        facility_code * 8 + SEVERITY_NOTICE,
+        std::process::id()
+    );
+
+    if let Some(target) = address.strip_prefix("udp://") {
+        let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+        socket.send_to(message.as_bytes(), target).await?;
+    } else if let Some(target) = address.strip_prefix("tcp://") {
+        let mut stream = tokio::net::TcpStream::connect(target).await?;
+        let framed = format!("{} {message}", message.len());
+        tokio::io::AsyncWriteExt::write_all(&mut stream, framed.as_bytes()).await?;
+    } else if let Some(path) = address.strip_prefix("unix://") {
+        #[cfg(unix)]
+        {
+            let socket = tokio::net::UnixDatagram::unbound()?;
+            socket.send_to(message.as_bytes(), path).await?;
+        }
+        #[cfg(not(unix))]
+        {
+            return Err(eyre!("unix syslog sockets are not supported here: {path}"));
+        }
+    } else {
+        return Err(eyre!(
+            "syslog address must start with udp://, tcp://, or unix://"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn post_audit_http(
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    payload: &str,
+) -> Result<()> {
+    curl_json("POST", url, headers, Some(payload)).await?;
+    Ok(())
+}
+
+async fn curl_json(
+    method: &str,
+    url: &str,
+    headers: &BTreeMap<String, String>,
+    payload: Option<&str>,
+) -> Result<String> {
+    // Headers often carry tokens, so they go through `--config -` rather than argv.
+    let mut config = String::new();
+    for (name, value) in headers {
+        let header = format!("{name}: {}", expand_env_vars(value));
+        config.push_str(&format!("header = {}\n", curl_config_quote(&header)));
+    }
+    if let Some(payload) = payload {
+        config.push_str(&format!("data-raw = {}\n", curl_config_quote(payload)));
+    }
+    let mut command = Command::new("curl");
+    command.args([
+        "--config",
+        "-",
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--max-time",
+        "10",
+        "-X",
+        method,
+        "-H",
+        "Content-Type: application/json",
+        url,
+    ]);
+    run_with_stdin(command, &config)
+        .await
+        .wrap_err("curl failed")
+}
+
+fn curl_config_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+async fn run_with_stdin(mut command: Command, input: &str) -> Result<String> {
+    command.stdin(Stdio::piped());
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        tokio::io::AsyncWriteExt::write_all(&mut stdin, input.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn sync_team_state(app: &mut AppState, worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let now = unix_now();
+    let Some(config) = app.team.config.clone() else {
+        return;
+    };
+    if app.team.syncing || now < app.team.next_sync {
+        return;
+    }
+    app.team.syncing = true;
+    app.team.next_sync = now + app.team.interval();
+    let env = app.team.env.clone();
+
+    app.maintenance_locks = load_maintenance_locks();
+    let (approval_requests, approval_signatures) = local_approvals();
+    let snapshot = TeamSnapshot {
+        id: team_operator_id(&config),
+        operator: current_operator(),
+        updated_at: now,
+        running: app.team.running.clone(),
+        recent: app.team.recent.clone(),
+        maintenance_locks: app.maintenance_locks.clone(),
+        approval_requests,
+        approval_signatures,
+    };
+    let event_tx = worker_tx.clone();
+    spawn_tracked(worker_tx, "team sync".to_string(), async move {
+        let result = exchange_team_snapshots(&config.backend, &env, &snapshot).await;
+        let ok = result.is_ok();
+        let _ = event_tx.send(WorkerEvent::TeamSynced(
+            result.map_err(|err| format!("{err:#}")),
+        ));
+        ok
+    });
+}
+
+fn local_approvals() -> (Vec<ApprovalRequest>, BTreeMap<String, ApprovalSignature>) {
+    let now = unix_now();
+    let read_dir = |dir: PathBuf, extension: &str| -> Vec<(String, String)> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_string_lossy().to_string();
+                Some((stem, fs::read_to_string(&path).ok()?))
+            })
+            .collect()
+    };
+    let requests = read_dir(approval_dir(), "json")
+        .into_iter()
+        .filter_map(|(_, contents)| serde_json::from_str::<ApprovalRequest>(&contents).ok())
+        .filter(|request| request.expires_at > now)
+        .collect();
+    let signatures = read_dir(approval_dir().join("team"), "sig")
+        .into_iter()
+        .filter_map(|(id, contents)| Some((id, serde_json::from_str(&contents).ok()?)))
+        .collect();
+    (requests, signatures)
+}
+
+/// Our `team_sync` key. A random ID kept in the state dir tells apart people who share a
+/// `$USER`, so they don't overwrite each other's snapshot.
+fn team_operator_id(config: &TeamSyncConfig) -> String {
+    if let Some(id) = &config.operator_id {
+        return id.clone();
+    }
+    let path = lazytf_state_dir().join("team_id");
+    let instance = fs::read_to_string(&path)
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| {
+            let mut bytes = [0u8; 8];
+            if fs::File::open("/dev/urandom")
+                .and_then(|mut file| io::Read::read_exact(&mut file, &mut bytes))
+                .is_err()
+            {
+                bytes = (unix_now() ^ u64::from(std::process::id()).rotate_left(32)).to_be_bytes();
+            }
+            let id: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+            let _ = fs::create_dir_all(lazytf_state_dir());
+            let _ = fs::write(&path, &id);
+            id
+        });
+    let host = nix::unistd::gethostname()
+        .map(|host| host.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string());
+    format!("{}@{host}-{instance}", current_operator())
+}
+
+async fn exchange_team_snapshots(
+    backend: &TeamSyncBackend,
+    env: &BTreeMap<String, String>,
+    snapshot: &TeamSnapshot,
+) -> Result<Vec<TeamSnapshot>> {
+    let payload = serde_json::to_string(snapshot)?;
+    let snapshots: Vec<TeamSnapshot> = match backend {
+        TeamSyncBackend::S3 {
+            prefix,
+            aws_profile,
+            region,
+        } => {
+            let prefix = prefix.trim_end_matches('/');
+            let aws_s3 = |args: &[&str]| {
+                let mut command = Command::new("aws");
+                command
+                    .envs(env)
+                    .arg("s3")
+                    .args(args)
+                    .arg("--only-show-errors");
+                if let Some(profile) = aws_profile {
+                    command.args(["--profile", profile]);
+                }
+                if let Some(region) = region {
+                    command.args(["--region", region]);
+                }
+                command
+            };
+            let key = format!("{prefix}/{}.json", percent_encode(&snapshot.id));
+            run_with_stdin(aws_s3(&["cp", "-", &key]), &payload)
+                .await
+                .wrap_err_with(|| format!("Failed to upload {key}"))?;
+
+            use std::os::unix::fs::DirBuilderExt;
+            let dir = lazytf_state_dir()
+                .join("team_sync")
+                .join(std::process::id().to_string());
+            let _ = fs::remove_dir_all(&dir);
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&dir)
+                .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+            let download = aws_s3(&[
+                "cp",
+                &format!("{prefix}/"),
+                &dir.to_string_lossy(),
+                "--recursive",
+                "--exclude",
+                "*",
+                "--include",
+                "*.json",
+            ]);
+            run_with_stdin(download, "")
+                .await
+                .wrap_err_with(|| format!("Failed to download {prefix}/"))?;
+            let snapshots = fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+                .filter_map(|contents| serde_json::from_str(&contents).ok())
+                .collect();
+            let _ = fs::remove_dir_all(&dir);
+            snapshots
+        }
+        TeamSyncBackend::Http { url, headers } => {
+            let url = url.trim_end_matches('/');
+            curl_json(
+                "PUT",
+                &format!("{url}/{}", percent_encode(&snapshot.id)),
+                headers,
+                Some(&payload),
+            )
+            .await?;
+            let body = curl_json("GET", url, headers, None).await?;
+            serde_json::from_str(&body).wrap_err("Failed to parse the team snapshot list")?
+        }
+    };
+    Ok(snapshots
+        .into_iter()
+        .filter(|other| other.id != snapshot.id)
+        .collect())
+}
+
+fn apply_team_snapshots(app: &mut AppState, snapshots: Vec<TeamSnapshot>) {
+    let now = unix_now();
+    let accounts: BTreeSet<String> = app
+        .accounts
+        .iter()
+        .map(|account| account.name.clone())
+        .collect();
+    let team_dir = approval_dir().join("team");
+    let mut messages = Vec::new();
+    let mut live_requests = BTreeSet::new();
+    for snapshot in &snapshots {
+        let operator = &snapshot.operator;
+        if let Some(record) = &snapshot.running
+            && snapshot.updated_at + app.team.interval() * 3 >= now
+            && accounts.contains(&record.account)
+            && app
+                .team
+                .seen
+                .insert(format!("{operator}/started/{}", record.timestamp))
+        {
+            messages.push(format!(
+                "Team: {operator} is running {} on `{}/{}`.",
+                record.operation, record.account, record.workspace
+            ));
+        }
+        for record in snapshot
+            .recent
+            .iter()
+            .filter(|record| accounts.contains(&record.account))
+        {
+            let key = format!(
+                "{operator}/finished/{}/{}/{}",
+                record.timestamp, record.account, record.workspace
+            );
+            if app.team.seen.insert(key) && app.team.seeded {
+                messages.push(format!(
+                    "Team: {operator} finished {} on `{}/{}` ({}).",
+                    record.operation,
+                    record.account,
+                    record.workspace,
+                    record.outcome.as_deref().unwrap_or("done")
+                ));
+            }
+        }
+        for (key, lock) in &snapshot.maintenance_locks {
+            if app
+                .team
+                .seen
+                .insert(format!("{operator}/lock/{key}/{}", lock.locked_at))
+            {
+                messages.push(format!(
+                    "Team: {operator} locked `{key}` for maintenance ({}).",
+                    lock.reason
+                ));
+            }
+        }
+
+        for request in &snapshot.approval_requests {
+            if request.expires_at <= now {
+                continue;
+            }
+            live_requests.insert(request.id.clone());
+            let path = team_dir.join(format!("{}.json", request.id));
+            if path.exists() || !accounts.contains(&request.account) {
+                continue;
+            }
+            let written = fs::create_dir_all(&team_dir).is_ok()
+                && serde_json::to_string_pretty(request)
+                    .is_ok_and(|contents| fs::write(&path, contents).is_ok());
+            if written {
+                messages.push(format!(
+                    "Team: {} needs a second approver to apply `{}/{}`. Run `lazytf approve {}`.",
+                    request.requested_by,
+                    request.account,
+                    request.workspace,
+                    path.display()
+                ));
+            }
+        }
+        for (id, signature) in &snapshot.approval_signatures {
+            let request_path = approval_dir().join(format!("{id}.json"));
+            let signature_path = request_path.with_extension("sig");
+            if !request_path.exists() || signature_path.exists() {
+                continue;
+            }
+            let written = serde_json::to_string_pretty(signature)
+                .is_ok_and(|contents| fs::write(&signature_path, contents).is_ok());
+            if written {
+                messages.push(format!(
+                    "Team: {} approved request {id}. Retry the apply.",
+                    signature.approved_by
+                ));
+            }
+        }
+    }
+
+    // Drop copies of teammates' requests once they were used or expired.
+    for path in fs::read_dir(&team_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+    {
+        let id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !live_requests.contains(&id) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    app.team.snapshots = snapshots;
+    app.team.seeded = true;
+    for message in messages {
+        app.push_output(message);
+    }
+}
+
+fn ci_api_command(
+    provider: &CiProvider,
+    method: &str,
+    url: &str,
+    body: Option<&serde_json::Value>,
+) -> Command {
+    // The token reaches curl through `--config -` from the `printf` builtin, never argv.
+    let (headers, token_env) = match provider {
+        CiProvider::Github(github) => (
+            "printf 'header = \"Authorization: Bearer %s\"\\n' \
+             \"${LAZYTF_CI_TOKEN:-$(gh auth token 2>/dev/null)}\" | \
+             curl -H 'Accept: application/vnd.github+json'",
+            &github.token_env,
+        ),
+        CiProvider::Gitlab(gitlab) => (
+            "printf 'header = \"PRIVATE-TOKEN: %s\"\\n' \"$LAZYTF_CI_TOKEN\" | curl",
+            &gitlab.token_env,
+        ),
+    };
+    let mut command = Command::new("sh");
+    command
+        .args([
+            "-c",
+            &format!(
+                "{headers} --config - --silent --show-error --fail --location --max-time 30 \
+                 -X {method} {} \"$LAZYTF_CI_URL\"",
+                if body.is_some() {
+                    "-H 'Content-Type: application/json' --data \"$LAZYTF_CI_BODY\""
+                } else {
+                    ""
+                }
+            ),
+        ])
+        .env("LAZYTF_CI_URL", url)
+        .stdin(Stdio::null());
+    if let Some(body) = body {
+        command.env("LAZYTF_CI_BODY", body.to_string());
+    }
+    if let Ok(token) = std::env::var(token_env) {
+        command.env("LAZYTF_CI_TOKEN", token);
+    }
+    command
+}
+
+async fn ci_api_json(
+    provider: &CiProvider,
+    method: &str,
+    url: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value> {
+    if let CiProvider::Gitlab(gitlab) = provider
+        && std::env::var_os(&gitlab.token_env).is_none()
+    {
+        return Err(eyre!("set {} to a GitLab access token", gitlab.token_env));
+    }
+    let output = ci_api_command(provider, method, url, body)
+        .output()
+        .await
+        .wrap_err("Failed to run curl")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).wrap_err("Failed to parse CI API response")
+}
+
+fn ci_status(item: &serde_json::Value) -> String {
+    match item["status"].as_str() {
+        Some("completed") => item["conclusion"].as_str().unwrap_or("completed"),
+        Some(status) => status,
+        None => "unknown",
+    }
+    .to_string()
+}
+
+fn ci_timestamp(value: &serde_json::Value) -> String {
+    value
+        .as_str()
+        .unwrap_or_default()
+        .replace('T', " ")
+        .chars()
+        .take(16)
+        .collect()
+}
+
+async fn fetch_ci_runs(
+    provider: &CiProvider,
+    workflow: Option<&str>,
+    branch: Option<&str>,
+) -> Result<Vec<CiRun>> {
+    let (url, key) = match provider {
+        CiProvider::Github(github) => {
+            let workflow = workflow.ok_or_else(|| eyre!("no GitHub `workflow` configured"))?;
+            let mut url = format!(
+                "{}/repos/{}/actions/workflows/{workflow}/runs?per_page=20",
+                github.api_url, github.repo
+            );
+            if let Some(branch) = branch {
+                url.push_str(&format!("&branch={}", percent_encode(branch)));
+            }
+            (url, "workflow_runs")
+        }
+        CiProvider::Gitlab(gitlab) => {
+            let mut url = format!("{}/pipelines?per_page=20", gitlab.project_url());
+            if let Some(branch) = branch {
+                url.push_str(&format!("&ref={}", percent_encode(branch)));
+            }
+            (url, "")
+        }
+    };
+    let response = ci_api_json(provider, "GET", &url, None).await?;
+    let runs = if key.is_empty() {
+        &response
+    } else {
+        &response[key]
+    };
+    Ok(runs
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|run| CiRun {
+            id: run["id"].as_u64().unwrap_or_default(),
+            number: run["run_number"]
+                .as_u64()
+                .or_else(|| run["iid"].as_u64())
+                .unwrap_or_default(),
+            status: ci_status(run),
+            branch: run["head_branch"]
+                .as_str()
+                .or_else(|| run["ref"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            sha: run["head_sha"]
+                .as_str()
+                .or_else(|| run["sha"].as_str())
+                .unwrap_or_default()
+                .chars()
+                .take(7)
+                .collect(),
+            title: run["display_title"]
+                .as_str()
+                .or_else(|| run["source"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            created_at: ci_timestamp(&run["created_at"]),
+            jobs: None,
+            expanded: false,
+        })
+        .collect())
+}
+
+async fn fetch_ci_jobs(provider: &CiProvider, run_id: u64) -> Result<Vec<CiJob>> {
+    let url = match provider {
+        CiProvider::Github(github) => format!(
+            "{}/repos/{}/actions/runs/{run_id}/jobs",
+            github.api_url, github.repo
+        ),
+        CiProvider::Gitlab(gitlab) => format!(
+            "{}/pipelines/{run_id}/jobs?per_page=100",
+            gitlab.project_url()
+        ),
+    };
+    let response = ci_api_json(provider, "GET", &url, None).await?;
+    let jobs = match provider {
+        CiProvider::Github(_) => &response["jobs"],
+        CiProvider::Gitlab(_) => &response,
+    };
+    let mut jobs: Vec<CiJob> = jobs
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|job| {
+            let name = job["name"].as_str().unwrap_or_default();
+            CiJob {
+                id: job["id"].as_u64().unwrap_or_default(),
+                name: match job["stage"].as_str() {
+                    Some(stage) => format!("{stage}: {name}"),
+                    None => name.to_string(),
+                },
+                status: ci_status(job),
+            }
+        })
+        .collect();
+    // GitLab lists jobs newest first; pipeline order reads better.
+    jobs.sort_by_key(|job| job.id);
+    Ok(jobs)
+}
+
+fn ci_job_log_command(provider: &CiProvider, job_id: u64) -> Command {
+    let url = match provider {
+        CiProvider::Github(github) => format!(
+            "{}/repos/{}/actions/jobs/{job_id}/logs",
+            github.api_url, github.repo
+        ),
+        CiProvider::Gitlab(gitlab) => format!("{}/jobs/{job_id}/trace", gitlab.project_url()),
+    };
+    ci_api_command(provider, "GET", &url, None)
+}
+
+async fn run_ci_job_play(
+    account_idx: usize,
+    account: &AccountState,
+    workspace: &str,
+    options: &RunOptions,
+    cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    let job = options
+        .ci_job
+        .as_ref()
+        .ok_or_else(|| eyre!("Missing CI job"))?;
+    if let Some(ticket) = &options.change_ticket
+        && let Some(validate_command) = account
+            .change_ticket
+            .as_ref()
+            .and_then(|policy| policy.validate_command.as_deref())
+    {
+        validate_change_ticket(
+            account,
+            workspace,
+            ticket,
+            validate_command,
+            cancel_rx.clone(),
+            event_tx,
+        )
+        .await?;
+    }
+    if let Some(approval) = &account.approval {
+        let target = ApprovalTarget::described(format!(
+            "CI job {} (run {}, job {})",
+            job.name, job.run_id, job.job_id
+        ));
+        ensure_apply_approved(account, workspace, approval, &target, cancel_rx, event_tx).await?;
+    }
+
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Starting manual CI job {}...",
+        job.name
+    )));
+    let result = play_gitlab_job(&job.provider, job.job_id).await;
+    let success = result.is_ok();
+    let jobs = match result {
+        Ok(()) => fetch_ci_jobs(&job.provider, job.run_id).await,
+        Err(err) => Err(err.wrap_err(format!("Could not start {}", job.name))),
+    };
+    let _ = event_tx.send(WorkerEvent::CiJobsLoaded {
+        account_idx,
+        run_id: job.run_id,
+        jobs: jobs.map_err(|err| format!("{err:#}")),
+    });
+    Ok(RunOutcome {
+        success,
+        cancelled: false,
+        exit_code: None,
+    })
+}
+
+async fn play_gitlab_job(provider: &CiProvider, job_id: u64) -> Result<()> {
+    let CiProvider::Gitlab(gitlab) = provider else {
+        return Err(eyre!("only GitLab manual jobs can be started"));
+    };
+    let url = format!("{}/jobs/{job_id}/play", gitlab.project_url());
+    ci_api_json(provider, "POST", &url, None).await.map(|_| ())
+}
+
+async fn fetch_latest_release() -> Result<String> {
+    let output = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "10",
+            "-H",
+            "Accept: application/vnd.github+json",
+            RELEASES_API_URL,
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .wrap_err("Failed to run curl")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+    let release: serde_json::Value =
+        serde_json::from_slice(&output.stdout).wrap_err("Failed to parse release metadata")?;
+    release
+        .get("tag_name")
+        .and_then(serde_json::Value::as_str)
+        .map(|tag| tag.trim_start_matches('v').to_string())
+        .ok_or_else(|| eyre!("release metadata has no tag_name"))
+}
+
+fn spawn_update_check(worker_tx: &mpsc::UnboundedSender<WorkerEvent>) {
+    let event_tx = worker_tx.clone();
+    spawn_tracked(worker_tx, "lazytf release check".to_string(), async move {
+        let latest = fetch_latest_release().await.ok();
+        let found = latest.is_some();
+        let _ = event_tx.send(WorkerEvent::UpdateChecked { latest });
+        found
+    });
+}
+
+fn is_newer_version(candidate: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parts(candidate) > parts(current)
+}
+
+fn expand_env_vars(raw: &str) -> String {
+    let mut expanded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                expanded.push_str(&std::env::var(&after[..end]).unwrap_or_default());
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn append_audit_record(record: &AuditRecord) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+
+    let path = audit_log_path();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("Failed to open audit log {}", path.display()))?;
+    let line = serde_json::to_string(record)?;
+    io::Write::write_all(&mut file, format!("{line}\n").as_bytes())
+        .wrap_err_with(|| format!("Failed to append to audit log {}", path.display()))?;
+    Ok(())
+}
+
+fn audit_log_path() -> PathBuf {
+    lazytf_state_dir().join("audit.jsonl")
+}
+
+fn approval_dir() -> PathBuf {
+    lazytf_state_dir().join("approvals")
+}
+
+fn find_pending_approval(
+    account_name: &str,
+    workspace: &str,
+    digest: &str,
+) -> Result<Option<(PathBuf, ApprovalRequest)>> {
+    let dir = approval_dir();
+    if !dir.exists() {
+        return Ok(None);
+    }
+
+    let now = unix_now();
+    let entries = fs::read_dir(&dir)
+        .wrap_err_with(|| format!("Failed to read approval directory {}", dir.display()))?;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(request) = serde_json::from_str::<ApprovalRequest>(&contents) else {
+            continue;
+        };
+
+        if request.expires_at <= now {
+            let _ = fs::remove_file(path.with_extension("sig"));
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+
+        if request.account == account_name && request.workspace == workspace {
+            if request.plan_sha256 != digest {
+                // Requested (and maybe signed) for a different plan.
+                let _ = fs::remove_file(path.with_extension("sig"));
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            return Ok(Some((path, request)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn discard_approvals(account_name: &str, workspace: &str) {
+    let Ok(entries) = fs::read_dir(approval_dir()) else {
+        return;
+    };
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let matches = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<ApprovalRequest>(&contents).ok())
+            .is_some_and(|request| {
+                request.account == account_name && request.workspace == workspace
+            });
+        if matches {
+            let _ = fs::remove_file(path.with_extension("sig"));
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+fn create_approval_request(
+    account: &AccountState,
+    workspace: &str,
+    approval: &ApprovalConfig,
+    target: &ApprovalTarget,
+) -> Result<(PathBuf, ApprovalRequest)> {
+    let dir = approval_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create approval directory {}", dir.display()))?;
+
+    let requested_at = unix_now();
+    let request = ApprovalRequest {
+        id: format!("{}-{}-{requested_at}", account.name, workspace),
+        account: account.name.clone(),
+        workspace: workspace.to_string(),
+        composition_path: account.composition_path.display().to_string(),
+        requested_by: approval_identity(),
+        requested_at,
+        expires_at: requested_at + approval.ttl_minutes * 60,
+        key_env: approval.key_env.clone(),
+        approvers: approval.approvers.clone(),
+        subject: target.subject.clone(),
+        plan_sha256: target.digest.clone(),
+    };
+
+    let path = dir.join(format!("{}.json", request.id));
+    let contents = serde_json::to_string_pretty(&request)?;
+    fs::write(&path, contents)
+        .wrap_err_with(|| format!("Failed to write approval request {}", path.display()))?;
+    Ok((path, request))
+}
+
+fn approval_payload(request: &ApprovalRequest, approved_by: &str) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{approved_by}",
+        request.id,
+        request.account,
+        request.workspace,
+        request.requested_by,
+        request.expires_at,
+        request.plan_sha256
+    )
+}
+
+/// With `approvers` configured each approver signs with their own key; otherwise everyone
+/// shares `key_env`.
+fn approval_key_env<'a>(
+    approvers: &'a BTreeMap<String, String>,
+    shared_key_env: &'a str,
+    approved_by: &str,
+) -> Result<&'a str> {
+    if approvers.is_empty() {
+        return Ok(shared_key_env);
+    }
+    approvers
+        .get(approved_by)
+        .map(String::as_str)
+        .ok_or_else(|| eyre!("`{approved_by}` is not listed in approval.approvers"))
+}
+
+fn approval_mac(
+    request: &ApprovalRequest,
+    approved_by: &str,
+    key_env: &str,
+) -> Result<Hmac<Sha256>> {
+    let key = std::env::var(key_env)
+        .wrap_err_with(|| format!("Approval key env var `{key_env}` is not set"))?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .map_err(|err| eyre!("Invalid approval key: {err}"))?;
+    mac.update(approval_payload(request, approved_by).as_bytes());
+    Ok(mac)
+}
+
+fn verify_approval_signature(
+    signature_path: &Path,
+    request: &ApprovalRequest,
+    approval: &ApprovalConfig,
+) -> Result<ApprovalSignature> {
+    let contents = fs::read_to_string(signature_path).wrap_err_with(|| {
+        format!(
+            "Failed to read approval signature {}",
+            signature_path.display()
+        )
+    })?;
+    let signature: ApprovalSignature = serde_json::from_str(&contents).wrap_err_with(|| {
+        format!(
+            "Failed to parse approval signature {}",
+            signature_path.display()
+        )
+    })?;
+    check_approval_signature(&signature, request, approval)?;
+    Ok(signature)
+}
+
+fn check_approval_signature(
+    signature: &ApprovalSignature,
+    request: &ApprovalRequest,
+    approval: &ApprovalConfig,
+) -> Result<()> {
+    if signature.approved_by == request.requested_by {
+        return Err(eyre!(
+            "approval must come from a different operator than `{}`",
+            request.requested_by
+        ));
+    }
+
+    let expected = hex_decode(&signature.signature)
+        .ok_or_else(|| eyre!("approval signature is not valid hex"))?;
+    // The key comes from our config, not the request file, which the requester could edit.
+    let key_env = approval_key_env(
+        &approval.approvers,
+        &approval.key_env,
+        &signature.approved_by,
+    )?;
+    approval_mac(request, &signature.approved_by, key_env)?
+        .verify_slice(&expected)
+        .map_err(|_| {
+            eyre!(
+                "approval signature does not match the key of `{}`",
+                signature.approved_by
+            )
+        })?;
+    Ok(())
+}
+
+fn run_validate_config(cwd: &Path, explicit_config: Option<&Path>) -> Result<()> {
+    let loaded = match load_config(cwd, explicit_config) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            println!("error: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    println!("Config: {}", loaded.path.display());
+    for include in loaded.sources.iter().skip(1) {
+        println!("  includes {}", include.display());
+    }
+    let app = match AppState::from_config(loaded.config, &loaded.base_dir) {
+        Ok(app) => app,
+        Err(err) => {
+            println!("error: {err:#}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    // Invalid compositions are reported as errors below instead of startup warnings.
+    let issues: Vec<&String> = app
+        .accounts
+        .iter()
+        .filter_map(|account| account.composition_issue.as_ref())
+        .collect();
+    for entry in app.output_lines.iter().skip(1) {
+        if issues
+            .iter()
+            .any(|issue| entry.text.contains(issue.as_str()))
+            || entry.text.contains("using fallback path")
+        {
+            continue;
+        }
+        warnings += 1;
+        println!("{}", entry.text);
+    }
+
+    for account in &app.accounts {
+        let mut problems = Vec::new();
+        if let Err(err) = validate_composition_for_execution(account) {
+            problems.push(format!("error: {err}"));
+        }
+        for var_file in account.var_files.iter().filter(|path| !path.is_file()) {
+            problems.push(format!(
+                "error: var_file {} does not exist",
+                var_file.display()
+            ));
+        }
+        if let Some(docs_path) = &account.docs_path
+            && !account.composition_path.join(docs_path).is_file()
+        {
+            problems.push(format!("warning: docs_path `{docs_path}` does not exist"));
+        }
+        if problems.is_empty() {
+            println!(
+                "ok       {} ({})",
+                account.name,
+                account.composition_path.display()
+            );
+            continue;
+        }
+        println!(
+            "problems {} ({})",
+            account.name,
+            account.composition_path.display()
+        );
+        for problem in problems {
+            if problem.starts_with("error") {
+                errors += 1;
+            } else {
+                warnings += 1;
+            }
+            println!("  {problem}");
+        }
+    }
+
+    println!(
+        "{} account(s), {errors} error(s), {warnings} warning(s)",
+        app.accounts.len()
+    );
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_report(app: &AppState, file: Option<&Path>) -> Result<()> {
+    let checks: Vec<_> = app
+        .accounts
+        .iter()
+        .cloned()
+        .map(|account| tokio::spawn(async move { check_auth(&account).await }))
+        .collect();
+    let mut auth = Vec::new();
+    for check in checks {
+        auth.push(match check.await {
+            Ok(Ok(true)) => "authenticated",
+            Ok(Ok(false)) => "not authenticated",
+            _ => "check failed",
+        });
+    }
+
+    // The latest finished apply per account/workspace, from the audit log.
+    let mut last_applies: BTreeMap<(String, String), AuditRecord> = BTreeMap::new();
+    for line in fs::read_to_string(audit_log_path())
+        .unwrap_or_default()
+        .lines()
+    {
+        let Ok(record) = serde_json::from_str::<AuditRecord>(line) else {
+            continue;
+        };
+        if record.event == AuditEvent::Finished
+            && record.operation == OperationKind::TerraformApply.label()
+        {
+            last_applies.insert((record.account.clone(), record.workspace.clone()), record);
+        }
+    }
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>lazytf report</title>\n<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}th,td{border:1px solid #ccc;padding:4px 10px;text-align:left}th{background:#eee}.ok{color:#1a7f37}.bad{color:#cf222e}</style>\n</head><body>\n",
+    );
+    html.push_str(&format!(
+        "<h1>lazytf fleet report</h1>\n<p>Generated {} by {}.</p>\n",
+        format_timestamp(unix_now()),
+        html_escape(&current_operator())
+    ));
+
+    html.push_str("<h2>Accounts</h2>\n<table><tr><th>Account</th><th>Profile</th><th>Region</th><th>Role</th><th>Auth</th><th>Ephemeral environments</th></tr>\n");
+    for (account, auth) in app.accounts.iter().zip(&auth) {
+        let class = if *auth == "authenticated" {
+            "ok"
+        } else {
+            "bad"
+        };
+        let environments = app
+            .environments
+            .iter()
+            .filter(|env| env.account == account.name)
+            .count();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{class}\">{auth}</td><td>{environments}</td></tr>\n",
+            html_escape(&account.name),
+            html_escape(&account.aws_profile),
+            html_escape(account.region.as_deref().unwrap_or("-")),
+            html_escape(account.role().map_or("-", |role| role.name.as_str())),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    let mut workspaces: BTreeSet<(String, String)> = last_applies.keys().cloned().collect();
+    for key in app.costs.keys().chain(app.drift.keys()) {
+        if let Some((account, workspace)) = key.split_once('/') {
+            workspaces.insert((account.to_string(), workspace.to_string()));
+        }
+    }
+    html.push_str("<h2>Workspaces</h2>\n<table><tr><th>Account</th><th>Workspace</th><th>Last apply</th><th>By</th><th>Outcome</th><th>Change</th><th>Drift</th><th>Monthly cost</th></tr>\n");
+    for (account, workspace) in &workspaces {
+        let apply = last_applies.get(&(account.clone(), workspace.clone()));
+        let cost = app.costs.get(&cost_key(account, workspace));
+        let drift = match app.drift.get(&cost_key(account, workspace)) {
+            Some(record) if record.status == DriftStatus::Drifted => format!(
+                "<span class=\"bad\">drifted</span> ({})",
+                format_timestamp(record.checked_at)
+            ),
+            Some(record) if record.status == DriftStatus::Clean => {
+                format!("clean ({})", format_timestamp(record.checked_at))
+            }
+            Some(_) => "check failed".to_string(),
+            None => "-".to_string(),
+        };
+        let outcome = apply
+            .and_then(|record| record.outcome.as_deref())
+            .unwrap_or("-");
+        let class = if outcome == "succeeded" { "ok" } else { "bad" };
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{class}\">{}</td><td>{}</td><td>{drift}</td><td>{}</td></tr>\n",
+            html_escape(account),
+            html_escape(workspace),
+            apply.map_or_else(|| "-".to_string(), |record| format_timestamp(record.timestamp)),
+            html_escape(apply.map_or("-", |record| record.operator.as_str())),
+            html_escape(outcome),
+            html_escape(
+                apply
+                    .and_then(|record| record.change_ticket.as_deref())
+                    .unwrap_or("-")
+            ),
+            cost.map_or_else(
+                || "-".to_string(),
+                |cost| format!("{:.2} {}", cost.monthly_cost, html_escape(&cost.currency))
+            ),
+        ));
+    }
+    html.push_str("</table>\n</body></html>\n");
+
+    match file {
+        Some(path) => fs::write(path, html)
+            .wrap_err_with(|| format!("Failed to write report {}", path.display()))?,
+        None => print!("{html}"),
+    }
+    Ok(())
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn run_bench_plan(account: &AccountState, workspace: Option<&str>) -> Result<()> {
+    validate_composition_for_execution(account)?;
+    let total_started = Instant::now();
+    let mut phases: Vec<(String, Duration)> = Vec::new();
+
+    if let Some(workspace) =
+        workspace.filter(|_| account.composition_mode.pinned_workspace().is_none())
+    {
+        let started = Instant::now();
+        let output = terraform_command(account, &["workspace", "select", workspace])
+            .output()
+            .await
+            .wrap_err("Failed to run terraform workspace select")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "terraform workspace select failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        phases.push(("workspace select".to_string(), started.elapsed()));
+    }
+
+    eprintln!("Running terraform init...");
+    let started = Instant::now();
+    let output = terraform_command_owned(account, &init_args(account))
+        .output()
+        .await
+        .wrap_err("Failed to run terraform init")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "terraform init failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    phases.push(("init (modules, providers)".to_string(), started.elapsed()));
+
+    eprintln!("Running terraform plan -json...");
+    let mut args = vec![
+        "plan".to_string(),
+        "-input=false".to_string(),
+        "-json".to_string(),
+        "-lock=false".to_string(),
+    ];
+    append_var_file_args(&mut args, &account.var_files);
+    let mut command = terraform_command_owned(account, &args);
+    apply_tf_vars(&mut command, account, workspace.unwrap_or("default"));
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::inherit());
+    command.kill_on_drop(true);
+
+    let plan_started = Instant::now();
+    let mut child = command.spawn().wrap_err("Failed to spawn terraform plan")?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| eyre!("Command stdout was not piped"))?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut first_refresh: Option<Instant> = None;
+    let mut last_refresh: Option<Instant> = None;
+    let mut summary_at: Option<Instant> = None;
+    let mut refresh_started: BTreeMap<String, Instant> = BTreeMap::new();
+    let mut refresh_times: Vec<(Duration, String)> = Vec::new();
+    let mut summary = String::new();
+
+    while let Some(line) = lines.next_line().await? {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        let now = Instant::now();
+        let address = event
+            .pointer("/hook/resource/addr")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        match event.get("type").and_then(serde_json::Value::as_str) {
+            Some("refresh_start") => {
+                first_refresh.get_or_insert(now);
+                refresh_started.insert(address, now);
+            }
+            Some("refresh_complete") => {
+                last_refresh = Some(now);
+                if let Some(started) = refresh_started.remove(&address) {
+                    refresh_times.push((now - started, address));
+                }
+            }
+            Some("change_summary") => {
+                summary_at = Some(now);
+                summary = event
+                    .get("@message")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+            }
+            _ => {}
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .wrap_err("Failed while waiting for terraform plan")?;
+    let plan_finished = Instant::now();
+    if !status.success() {
+        return Err(eyre!("terraform plan failed with {status}"));
+    }
+
+    let diff_started = last_refresh.or(first_refresh).unwrap_or(plan_started);
+    let summary_at = summary_at.unwrap_or(plan_finished);
+    match first_refresh {
+        Some(first_refresh) => {
+            phases.push((
+                "plan startup (config, providers)".to_string(),
+                first_refresh - plan_started,
+            ));
+            phases.push((
+                format!("refresh ({} resources)", refresh_times.len()),
+                diff_started - first_refresh,
+            ));
+        }
+        None => phases.push((
+            "plan startup (config, providers)".to_string(),
+            diff_started - plan_started,
+        )),
+    }
+    phases.push((
+        "plan (diff)".to_string(),
+        summary_at.saturating_duration_since(diff_started),
+    ));
+    phases.push((
+        "shutdown".to_string(),
+        plan_finished.saturating_duration_since(summary_at),
+    ));
+
+    println!(
+        "Plan timing for `{}`{}",
+        account.name,
+        workspace
+            .map(|workspace| format!(" / `{workspace}`"))
+            .unwrap_or_default()
+    );
+    for (phase, elapsed) in &phases {
+        println!("  {phase:<36} {:>8.2}s", elapsed.as_secs_f64());
+    }
+    println!(
+        "  {:<36} {:>8.2}s",
+        "total",
+        total_started.elapsed().as_secs_f64()
+    );
+    if !summary.is_empty() {
+        println!();
+        println!("{summary}");
+    }
+
+    if !refresh_times.is_empty() {
+        refresh_times.sort_by_key(|(elapsed, _)| std::cmp::Reverse(*elapsed));
+        println!();
+        println!("Slowest refreshes:");
+        for (elapsed, address) in refresh_times.iter().take(10) {
+            println!("  {:>8.2}s  {address}", elapsed.as_secs_f64());
+        }
+    }
+
+    Ok(())
+}
+
+fn run_approve_command(request_path: &Path) -> Result<()> {
+    let contents = fs::read_to_string(request_path)
+        .wrap_err_with(|| format!("Failed to read approval request {}", request_path.display()))?;
+    let request: ApprovalRequest = serde_json::from_str(&contents).wrap_err_with(|| {
+        format!(
+            "Failed to parse approval request {}",
+            request_path.display()
+        )
+    })?;
+
+    if request.expires_at <= unix_now() {
+        return Err(eyre!("Approval request {} has expired", request.id));
+    }
+
+    let approver = approval_identity();
+    if approver == request.requested_by {
+        return Err(eyre!(
+            "Approval must come from a different operator than `{}`",
+            request.requested_by
+        ));
+    }
+    let key_env = approval_key_env(&request.approvers, &request.key_env, &approver)?;
+
+    println!("Apply approval request {}", request.id);
+    println!("  account:      {}", request.account);
+    println!("  workspace:    {}", request.workspace);
+    println!("  composition:  {}", request.composition_path);
+    println!("  requested by: {}", request.requested_by);
+    println!("  approving:    {}", request.subject);
+    println!("  plan sha256:  {}", request.plan_sha256);
+    print!("Approve this apply as `{approver}`? [y/N] ");
+    io::Write::flush(&mut io::stdout())?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        println!("Approval not granted.");
+        return Ok(());
+    }
+
+    let signature = ApprovalSignature {
+        signature: hex_encode(
+            &approval_mac(&request, &approver, key_env)?
+                .finalize()
+                .into_bytes(),
+        ),
+        approved_by: approver,
+        approved_at: unix_now(),
+    };
+    let signature_path = request_path.with_extension("sig");
+    fs::write(&signature_path, serde_json::to_string_pretty(&signature)?).wrap_err_with(|| {
+        format!(
+            "Failed to write approval signature {}",
+            signature_path.display()
+        )
+    })?;
+
+    println!("Approval written to {}", signature_path.display());
+    println!(
+        "The requester can now retry apply (copy the .sig file next to the request if needed)."
+    );
+    Ok(())
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |acc, (idx, byte)| {
+            acc | u32::from(*byte) << (16 - idx * 8)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(char::from(
+                    ALPHABET[(triple >> (18 - idx * 6)) as usize & 63],
+                ));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn hex_decode(raw: &str) -> Option<Vec<u8>> {
+    if !raw.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..raw.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(raw.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn lazytf_state_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("lazytf");
+    }
+
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(".local/state/lazytf")
+}
+
+fn current_operator() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// The OS account running lazytf, for approvals; `$USER` is only the fallback since
+/// anyone can set it to a colleague's name.
+fn approval_identity() -> String {
+    nix::unistd::User::from_uid(nix::unistd::getuid())
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .unwrap_or_else(current_operator)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn emit_process_output(event_tx: &mpsc::UnboundedSender<WorkerEvent>, bytes: &[u8]) {
+    for line in bytes.split_inclusive(|byte| *byte == b'\n') {
+        let _ = event_tx.send(WorkerEvent::OutputLine(decode_output_line(line)));
+    }
+}
+
+fn aws_config_path() -> PathBuf {
+    if let Ok(path) = std::env::var("AWS_CONFIG_FILE") {
+        return PathBuf::from(path);
+    }
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".aws/config")
+}
+
+fn aws_credentials_path() -> PathBuf {
+    if let Ok(path) = std::env::var("AWS_SHARED_CREDENTIALS_FILE") {
+        return PathBuf::from(path);
+    }
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".aws/credentials")
+}
+
+fn load_aws_profiles() -> Option<BTreeSet<String>> {
+    let config = fs::read_to_string(aws_config_path()).ok();
+    let credentials = fs::read_to_string(aws_credentials_path()).ok();
+    if config.is_none() && credentials.is_none() {
+        return None;
+    }
+    let mut profiles = parse_aws_profiles(config.as_deref().unwrap_or_default());
+    profiles.extend(parse_aws_credentials_profiles(
+        credentials.as_deref().unwrap_or_default(),
+    ));
+    Some(profiles)
+}
+
+fn parse_aws_profiles(contents: &str) -> BTreeSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let section = line.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+            match section.strip_prefix("profile ") {
+                Some(name) => Some(name.trim().to_string()),
+                None if section == "default" => Some(section.to_string()),
+                None => None,
+            }
+        })
+        .collect()
+}
+
+fn parse_aws_credentials_profiles(contents: &str) -> BTreeSet<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let section = line.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+            (!section.is_empty()).then(|| section.to_string())
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct GeneratedAccount {
+    aws_profile: String,
+    composition_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    var_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeneratedConfig {
+    accounts: BTreeMap<String, GeneratedAccount>,
+}
+
+fn find_sso_access_token(start_url: &str) -> Result<String> {
+    let cache_dir = aws_config_path()
+        .parent()
+        .map(|dir| dir.join("sso/cache"))
+        .unwrap_or_default();
+    let entries = fs::read_dir(&cache_dir)
+        .wrap_err_with(|| format!("Failed to read SSO cache {}", cache_dir.display()))?;
+
+    let now = format_timestamp(unix_now());
+    let now = now.get(..16).unwrap_or_default();
+    for entry in entries.flatten() {
+        let Ok(contents) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let Ok(token) = serde_json::from_str::<serde_json::Value>(&contents) else {
+            continue;
+        };
+        let field = |key: &str| token.get(key).and_then(serde_json::Value::as_str);
+        if field("startUrl").map(|url| url.trim_end_matches('/'))
+            != Some(start_url.trim_end_matches('/'))
+        {
+            continue;
+        }
+        let expires_at = field("expiresAt").unwrap_or_default().replace('T', " ");
+        if let Some(access_token) = field("accessToken")
+            && expires_at.get(..16).unwrap_or_default() > now
+        {
+            return Ok(access_token.to_string());
+        }
+    }
+
+    Err(eyre!(
+        "No valid SSO session for {start_url}. Run `aws sso login` for a profile using this start URL first."
+    ))
+}
+
+async fn aws_sso_json(
+    args: &[&str],
+    access_token: &str,
+    sso_region: &str,
+    env: &BTreeMap<String, String>,
+) -> Result<serde_json::Value> {
+    let output = Command::new("aws")
+        .envs(env)
+        .arg("sso")
+        .args(args)
+        .args([
+            "--access-token",
+            access_token,
+            "--region",
+            sso_region,
+            "--output",
+            "json",
+        ])
+        .output()
+        .await
+        .wrap_err("Failed to run aws sso")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "aws sso {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).wrap_err("Failed to parse aws sso output")
+}
+
+async fn run_sso_generate(
+    start_url: &str,
+    sso_region: &str,
+    region: Option<&str>,
+    composition_template: &str,
+    env: &BTreeMap<String, String>,
+) -> Result<()> {
+    let access_token = find_sso_access_token(start_url)?;
+    let accounts = aws_sso_json(&["list-accounts"], &access_token, sso_region, env).await?;
+
+    let existing = load_aws_profiles().unwrap_or_default();
+    let mut stanzas = String::new();
+    let mut generated = GeneratedConfig {
+        accounts: BTreeMap::new(),
+    };
+    let mut skipped = 0usize;
+
+    for account in accounts
+        .get("accountList")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let field = |key: &str| {
+            account
+                .get(key)
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let account_id = field("accountId");
+        let account_name = field("accountName")
+            .to_ascii_lowercase()
+            .replace(|ch: char| !ch.is_ascii_alphanumeric(), "-");
+
+        let roles = aws_sso_json(
+            &["list-account-roles", "--account-id", &account_id],
+            &access_token,
+            sso_region,
+            env,
+        )
+        .await?;
+        for role in roles
+            .get("roleList")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let Some(role_name) = role.get("roleName").and_then(serde_json::Value::as_str) else {
+                continue;
+            };
+            let profile = format!("{account_name}-{}", role_name.to_ascii_lowercase());
+            if existing.contains(&profile) {
+                skipped += 1;
+            } else {
+                stanzas.push_str(&format!(
+                    "\n[profile {profile}]\nsso_start_url = {start_url}\nsso_region = {sso_region}\nsso_account_id = {account_id}\nsso_role_name = {role_name}\nregion = {}\n",
+                    region.unwrap_or(sso_region)
+                ));
+            }
+            generated.accounts.insert(
+                profile.clone(),
+                GeneratedAccount {
+                    aws_profile: profile,
+                    composition_path: composition_template.replace("{account}", &account_name),
+                    region: region.map(str::to_string),
+                    var_files: Vec::new(),
+                },
+            );
+        }
+    }
+
+    if generated.accounts.is_empty() {
+        return Err(eyre!("The SSO session has no accessible accounts or roles"));
+    }
+
+    if !stanzas.is_empty() {
+        let path = aws_config_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+        io::Write::write_all(&mut file, stanzas.as_bytes())
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+        eprintln!(
+            "Wrote {} profile(s) to {} ({skipped} already existed).",
+            generated.accounts.len() - skipped,
+            path.display()
+        );
+    } else {
+        eprintln!("All {skipped} profile(s) already exist.");
+    }
+
+    eprintln!("lazytf account entries (adjust composition_path as needed):");
+    print!("{}", serde_yaml::to_string(&generated)?);
+    Ok(())
+}
+
+fn find_terraform_roots(root: &Path) -> Vec<PathBuf> {
+    const MAX_DEPTH: usize = 6;
+
+    let mut roots = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut is_root = dir.join(".terraform.lock.hcl").is_file();
+        let mut has_tf = false;
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if path.is_dir() {
+                if depth < MAX_DEPTH && !name.starts_with('.') && name != "modules" {
+                    pending.push((path, depth + 1));
+                }
+            } else if name.ends_with(".tf") {
+                has_tf = true;
+                if !is_root
+                    && fs::read_to_string(&path).is_ok_and(|contents| {
+                        contents.contains("backend \"") || contents.contains("provider \"")
+                    })
+                {
+                    is_root = true;
+                }
+            }
+        }
+        if has_tf && is_root {
+            roots.push(dir);
+        }
+    }
+    roots.sort();
+    roots
+}
+
+fn ask(question: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        print!("{question}: ");
+    } else {
+        print!("{question} [{default}]: ");
+    }
+    io::Write::flush(&mut io::stdout())?;
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer)? == 0 {
+        return Err(eyre!("Input closed; nothing was written"));
+    }
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+fn run_init(cwd: &Path, explicit_config: Option<&Path>) -> Result<()> {
+    let config_path = match explicit_config {
+        Some(path) => cwd.join(path),
+        None => cwd.join(CONFIG_CANDIDATES[0]),
+    };
+    if config_path.exists()
+        && !matches!(
+            ask(
+                &format!("{} exists. Overwrite it?", config_path.display()),
+                "y/N"
+            )?
+            .as_str(),
+            "y" | "Y" | "yes"
+        )
+    {
+        println!("Nothing was written.");
+        return Ok(());
+    }
+
+    let roots = find_terraform_roots(cwd);
+    if roots.is_empty() {
+        return Err(eyre!(
+            "No Terraform roots found under {}. Run `lazytf init` from the directory holding your compositions.",
+            cwd.display()
+        ));
+    }
+    let profiles: Vec<String> = load_aws_profiles()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let region_pattern = Regex::new(r"^[a-z]{2}(-gov)?-[a-z]+-\d$")?;
+
+    println!("Found {} Terraform root(s).", roots.len());
+    if profiles.is_empty() {
+        println!(
+            "No AWS profiles in {} or {}.",
+            aws_config_path().display(),
+            aws_credentials_path().display()
+        );
+    } else {
+        println!(
+            "AWS profiles in {} and {}:",
+            aws_config_path().display(),
+            aws_credentials_path().display()
+        );
+        for (idx, profile) in profiles.iter().enumerate() {
+            println!("  {:>2}. {profile}", idx + 1);
+        }
+    }
+    println!("Answer `-` for the account name to leave a root out.");
+
+    // Relative composition paths are resolved against the config file's directory.
+    let config_dir = config_path
+        .parent()
+        .and_then(|dir| fs::canonicalize(dir).ok())
+        .ok_or_else(|| eyre!("{} is not in an existing directory", config_path.display()))?;
+
+    let mut generated = GeneratedConfig {
+        accounts: BTreeMap::new(),
+    };
+    for (idx, root) in roots.iter().enumerate() {
+        let relative = root.strip_prefix(cwd).unwrap_or(root);
+        let components: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        println!();
+        println!("[{}/{}] {}", idx + 1, roots.len(), relative.display());
+
+        let default_name = if components.is_empty() {
+            cwd.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "default".to_string())
+        } else {
+            components.join("-")
+        };
+        let name = ask("  Account name", &default_name)?;
+        if name == "-" {
+            continue;
+        }
+
+        let default_profile = profiles
+            .iter()
+            .find(|profile| *profile == &name || components.contains(profile))
+            .cloned()
+            .unwrap_or_default();
+        // A listed name wins over a list number, and numbers outside the list are names too,
+        // so profiles named after account IDs can still be typed.
+        let profile = loop {
+            let answer = ask("  AWS profile (number or name)", &default_profile)?;
+            if answer.is_empty() {
+                println!("  A profile is required.");
+                continue;
+            }
+            let listed = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| profiles.get(number.wrapping_sub(1)));
+            match listed {
+                Some(profile) if !profiles.contains(&answer) => break profile.clone(),
+                _ => break answer,
+            }
+        };
+
+        let default_region = components
+            .iter()
+            .find(|component| region_pattern.is_match(component))
+            .cloned()
+            .unwrap_or_default();
+        let region = ask("  Region (empty for the profile's)", &default_region)?;
+
+        let found: Vec<String> = discover_var_files(root)
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+        let var_files = if found.is_empty() {
+            Vec::new()
+        } else {
+            ask(
+                &format!("  Var files, comma-separated (found: {})", found.join(", ")),
+                "",
+            )?
+            .split(',')
+            .map(str::trim)
+            .filter(|file| !file.is_empty())
+            .map(str::to_string)
+            .collect()
+        };
+
+        generated.accounts.insert(
+            name,
+            GeneratedAccount {
+                aws_profile: profile,
+                composition_path: config_relative_path(&config_dir, root),
+                region: (!region.is_empty()).then_some(region),
+                var_files,
+            },
+        );
+    }
+
+    if generated.accounts.is_empty() {
+        println!("No accounts were configured; nothing was written.");
+        return Ok(());
+    }
+    let contents = format!(
+        "# Generated by `lazytf init`. See the Config Reference in the README for more options.\n{}",
+        serde_yaml::to_string(&generated)?
+    );
+    fs::write(&config_path, contents)
+        .wrap_err_with(|| format!("Failed to write {}", config_path.display()))?;
+    println!();
+    println!(
+        "Wrote {} with {} account(s). Run `lazytf validate-config` to check it.",
+        config_path.display(),
+        generated.accounts.len()
+    );
+    Ok(())
+}
+
+fn config_relative_path(config_dir: &Path, root: &Path) -> String {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    match root.strip_prefix(config_dir) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => format!("./{}", relative.display()),
+        Err(_) => root.display().to_string(),
+    }
+}
+
+fn role_refusal(account: &AccountState) -> String {
+    let role = account
+        .role()
+        .map(|role| role.name.as_str())
+        .unwrap_or_default();
+    match &account.apply_role {
+        Some(apply_role) => format!(
+            "Role {role} on `{}` is read-only. Press `w` to switch to {apply_role} before changing infrastructure.",
+            account.name
+        ),
+        None => format!(
+            "Account `{}` has no apply_role; changes are refused under {role}.",
+            account.name
+        ),
+    }
+}
+
+async fn check_auth(account: &AccountState) -> Result<bool> {
+    if let Some(template) = &account.auth_check_cmd {
+        let output = account_shell_command(account, &render_auth_template(account, template))
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .wrap_err("Failed to run auth_check_cmd")?;
+        return Ok(output.status.success());
+    }
+
+    let mut command = Command::new("aws");
+    apply_account_env(&mut command, account);
+    command.args([
+        "sts",
+        "get-caller-identity",
+        "--profile",
+        &account.aws_profile,
+        "--output",
+        "json",
+    ]);
+
+    let output = command
+        .output()
+        .await
+        .wrap_err("Failed to run aws sts get-caller-identity")?;
+
+    Ok(output.status.success())
+}
+
+/// `auth_check_cmd` and `login_cmd` with `{{account}}`, `{{profile}}`, and `{{region}}` filled in,
+/// shell-quoted since the result runs with `sh -c`.
+fn render_auth_template(account: &AccountState, template: &str) -> String {
+    render_template(
+        template,
+        &[
+            ("account", shell_quote(&account.name)),
+            ("profile", shell_quote(&account.aws_profile)),
+            (
+                "region",
+                shell_quote(account.region.as_deref().unwrap_or_default()),
+            ),
+        ],
+    )
+}
+
+async fn fetch_workspaces(account: &AccountState) -> Result<(Vec<String>, Option<String>)> {
+    validate_composition_for_execution(account)?;
+    if let Some(workspace) = account.composition_mode.pinned_workspace() {
+        return Ok((vec![workspace.to_string()], Some(workspace.to_string())));
+    }
+
+    let mut command = terraform_command(account, &["workspace", "list"]);
+    let output = command
+        .output()
+        .await
+        .wrap_err("Failed to run terraform workspace list")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!(
+            "terraform workspace list failed for {}: {}",
+            account.name,
+            stderr.trim()
+        ));
+    }
+
+    Ok(parse_workspace_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn parse_workspace_output(output: &str) -> (Vec<String>, Option<String>) {
+    let mut workspaces = Vec::new();
+    let mut current = None;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        let cleaned = trimmed.trim_start_matches('*').trim();
+        if cleaned.is_empty() {
+            continue;
+        }
+        if trimmed.starts_with('*') {
+            current = Some(cleaned.to_string());
+        }
+        workspaces.push(cleaned.to_string());
+    }
+    (workspaces, current)
+}
+
+fn parse_resource_change_line(line: &str) -> Option<ResourceChange> {
+    const PHRASES: [(&str, ChangeAction); 7] = [
+        (" will be created", ChangeAction::Create),
+        (" will be updated in-place", ChangeAction::Update),
+        (" will be destroyed", ChangeAction::Delete),
+        (" must be replaced", ChangeAction::Replace),
+        (" will be replaced, as requested", ChangeAction::Replace),
+        (" will be read during apply", ChangeAction::Read),
+        (" has been deleted", ChangeAction::Delete),
+    ];
+
+    let rest = line.trim().strip_prefix("# ")?;
+    PHRASES.iter().find_map(|(phrase, action)| {
+        let (address, _) = rest.split_once(phrase)?;
+        (!address.is_empty() && !address.contains(' ')).then(|| ResourceChange {
+            address: address.to_string(),
+            action: *action,
+            provider: None,
+            attributes: Vec::new(),
+            replace_reasons: Vec::new(),
+        })
+    })
+}
+
+fn parse_missing_variable_line(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("input variable \"")?;
+    let (name, rest) = rest.split_once('"')?;
+    rest.trim_start()
+        .starts_with("is not set")
+        .then(|| name.to_string())
+}
+
+fn parse_forced_replacement_line(line: &str) -> Option<String> {
+    let (attribute, _) = line
+        .trim_end()
+        .strip_suffix("# forces replacement")?
+        .split_once('=')?;
+    let attribute = attribute
+        .trim()
+        .trim_start_matches(['+', '-', '~', '/', ' ']);
+    (!attribute.is_empty() && !attribute.contains(' ')).then(|| attribute.to_string())
+}
+
+async fn record_output_diff(
+    account: &AccountState,
+    workspace: &str,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let result = async {
+        let output = terraform_command(account, &["output", "-json"])
+            .output()
+            .await
+            .wrap_err("Failed to run terraform output")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "terraform output failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let outputs: BTreeMap<String, serde_json::Value> =
+            serde_json::from_slice(&output.stdout).wrap_err("Failed to parse output JSON")?;
+        let current: BTreeMap<String, String> = outputs
+            .iter()
+            .map(|(name, output)| (name.clone(), output_fingerprint(output)))
+            .collect();
+
+        let path = outputs_path(&account.name, workspace);
+        let previous: Option<BTreeMap<String, String>> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok());
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(&current)?)
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+        Ok((current, previous))
+    }
+    .await;
+
+    let (current, previous) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Could not record outputs for `{workspace}`: {err}"
+            )));
+            return;
+        }
+    };
+    let Some(previous) = previous else {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Outputs: recorded {} output(s) for `{workspace}`; later applies are diffed against them.",
+            current.len()
+        )));
+        return;
+    };
+
+    let mut lines = Vec::new();
+    for (name, value) in &current {
+        match previous.get(name) {
+            None => lines.push(format!("  + {name} = {}", display_fingerprint(value))),
+            Some(old) if old != value => lines.push(format!(
+                "  ~ {name}: {} -> {}",
+                display_fingerprint(old),
+                display_fingerprint(value)
+            )),
+            Some(_) => {}
+        }
+    }
+    for name in previous.keys().filter(|name| !current.contains_key(*name)) {
+        lines.push(format!("  - {name}"));
+    }
+
+    if lines.is_empty() {
+        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+            "Outputs: {} output(s) unchanged since the last apply.",
+            current.len()
+        )));
+        return;
+    }
+    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+        "Outputs changed since the last apply ({} of {}):",
+        lines.len(),
+        current.len().max(previous.len())
+    )));
+    for line in lines {
+        let _ = event_tx.send(WorkerEvent::OutputLine(line));
+    }
+}
+
+/// Sensitive values are only kept as a digest so they never land on disk.
+fn output_fingerprint(output: &serde_json::Value) -> String {
+    let value = output["value"].to_string();
+    if output["sensitive"] == true {
+        let digest = <Sha256 as sha2::Digest>::digest(value.as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        format!("sensitive:{hex}")
+    } else {
+        value
+    }
+}
+
+fn display_fingerprint(fingerprint: &str) -> String {
+    if fingerprint.starts_with("sensitive:") {
+        return "(sensitive)".to_string();
+    }
+    if fingerprint.chars().count() > 120 {
+        let head: String = fingerprint.chars().take(117).collect();
+        return format!("{head}...");
+    }
+    fingerprint.to_string()
+}
+
+fn outputs_path(account: &str, workspace: &str) -> PathBuf {
+    lazytf_state_dir()
+        .join("outputs")
+        .join(account)
+        .join(format!("{workspace}.json"))
+}
+
+async fn record_state_metrics(
+    account: &AccountState,
+    workspace: &str,
+    tracking: StateTrackingConfig,
+    event_tx: &mpsc::UnboundedSender<WorkerEvent>,
+) {
+    let result = async {
+        let mut command = terraform_command(account, &["state", "pull"]);
+        let output = command
+            .output()
+            .await
+            .wrap_err("Failed to run terraform state pull")?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "terraform state pull failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let state: serde_json::Value = if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&output.stdout).wrap_err("Failed to parse pulled state")?
+        };
+        let sample = StateMetricsSample {
+            timestamp: unix_now(),
+            account: account.name.clone(),
+            workspace: workspace.to_string(),
+            serial: state["serial"].as_u64().unwrap_or_default(),
+            size_bytes: output.stdout.len() as u64,
+            resources: state["resources"].as_array().map_or(0, Vec::len),
+        };
+
+        let previous = load_state_metrics(&account.name, workspace)?.pop();
+        append_state_metrics(&sample)?;
+        Ok((sample, previous))
+    }
+    .await;
+
+    match result {
+        Ok((sample, previous)) => {
+            let growth = previous
+                .as_ref()
+                .filter(|previous| previous.size_bytes > 0)
+                .map(|previous| {
+                    (sample.size_bytes as f64 - previous.size_bytes as f64) * 100.0
+                        / previous.size_bytes as f64
+                });
+            let growth_label = growth
+                .map(|percent| format!(" ({percent:+.1}% since last sample)"))
+                .unwrap_or_default();
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "State `{workspace}`: serial {}, {} resources, {}{growth_label}",
+                sample.serial,
+                sample.resources,
+                format_bytes(sample.size_bytes)
+            )));
+
+            if let Some(percent) = growth
+                && percent >= tracking.growth_alert_percent as f64
+            {
+                let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                    "Warning: state for `{}`/`{workspace}` grew {percent:.0}% since the last sample. Check for unwanted resources or data sources bloating state.",
+                    account.name
+                )));
+            }
+        }
+        Err(err) => {
+            let _ = event_tx.send(WorkerEvent::OutputLine(format!(
+                "Could not record state metrics for `{workspace}`: {err}"
+            )));
+        }
+    }
+}
+
+fn state_metrics_path() -> PathBuf {
+    lazytf_state_dir().join("state_metrics.jsonl")
+}
+
+fn append_state_metrics(sample: &StateMetricsSample) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+
+    let path = state_metrics_path();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("Failed to open state metrics {}", path.display()))?;
+    let line = serde_json::to_string(sample)?;
+    io::Write::write_all(&mut file, format!("{line}\n").as_bytes())
+        .wrap_err_with(|| format!("Failed to append to state metrics {}", path.display()))?;
+    Ok(())
+}
+
+fn load_state_metrics(account_name: &str, workspace: &str) -> Result<Vec<StateMetricsSample>> {
+    let path = state_metrics_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(&path)
+        .wrap_err_with(|| format!("Failed to read state metrics {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<StateMetricsSample>(line).ok())
+        .filter(|sample| sample.account == account_name && sample.workspace == workspace)
+        .collect())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn resource_index_path() -> PathBuf {
+    lazytf_state_dir().join("resource_index.jsonl")
+}
+
+fn append_resource_index(
+    account_name: &str,
+    inflight: &InflightOperation,
+    outcome: &str,
+) -> Result<()> {
+    let dir = lazytf_state_dir();
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create state directory {}", dir.display()))?;
+
+    let path = resource_index_path();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("Failed to open resource index {}", path.display()))?;
+
+    let timestamp = unix_now();
+    let operator = current_operator();
+    let mut contents = String::new();
+    for change in &inflight.resource_changes {
+        let entry = ResourceIndexEntry {
+            timestamp,
+            operator: operator.clone(),
+            account: account_name.to_string(),
+            workspace: inflight.workspace.clone(),
+            operation: inflight.kind.label().to_string(),
+            outcome: outcome.to_string(),
+            address: change.address.clone(),
+            action: change.action,
+        };
+        contents.push_str(&serde_json::to_string(&entry)?);
+        contents.push('\n');
+    }
+
+    io::Write::write_all(&mut file, contents.as_bytes())
+        .wrap_err_with(|| format!("Failed to append to resource index {}", path.display()))?;
+    Ok(())
+}
+
+fn search_resource_index(query: &str) -> Result<Vec<ResourceIndexEntry>> {
+    let path = resource_index_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(&path)
+        .wrap_err_with(|| format!("Failed to read resource index {}", path.display()))?;
+    let mut results: Vec<ResourceIndexEntry> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ResourceIndexEntry>(line).ok())
+        .filter(|entry| entry.address.contains(query))
+        .collect();
+    results.reverse();
+    Ok(results)
+}
+
+fn format_timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs_of_day = unix_secs % 86_400;
+
+    // Civil-from-days conversion (proleptic Gregorian calendar, UTC).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        secs_of_day / 3_600,
+        (secs_of_day % 3_600) / 60
+    )
+}
+
+fn validate_composition_for_execution(account: &AccountState) -> Result<()> {
+    if let Some(issue) = &account.composition_issue {
+        return Err(eyre!(
+            "Account `{}` configuration is invalid: {}",
+            account.name,
+            issue
+        ));
+    }
+
+    if !account.composition_path.exists() {
+        return Err(eyre!(
+            "composition_path does not exist for `{}`: {}",
+            account.name,
+            account.composition_path.display()
+        ));
+    }
+
+    if !account.composition_path.is_dir() {
+        return Err(eyre!(
+            "composition_path is not a directory for `{}`: {}",
+            account.name,
+            account.composition_path.display()
+        ));
+    }
+
+    if account.composition_mode == CompositionMode::Stack {
+        return Err(eyre!(
+            "`{}` is a Terraform Stack (*.tfstack.hcl); stacks have no workspaces and run through `terraform stacks` or HCP Terraform, not lazytf",
+            account.name
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_var_files_for_execution(account: &AccountState) -> Result<()> {
+    let missing_files: Vec<String> = account
+        .var_files
+        .iter()
+        .filter(|path| !path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+
+    if missing_files.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "Configured var_files are missing for `{}`: {}",
+            account.name,
+            missing_files.join(", ")
+        ))
+    }
+}
+
+fn validate_operation_preflight(account: &AccountState, kind: OperationKind) -> Result<()> {
+    validate_composition_for_execution(account)?;
+
+    if kind == OperationKind::OrphanScan && account.orphan_checks.is_empty() {
+        return Err(eyre!("No orphan_checks configured for `{}`", account.name));
+    }
+
+    if let CompositionMode::Cloud { workspace, .. } = &account.composition_mode {
+        match kind {
+            OperationKind::WorkspaceNew
+            | OperationKind::WorkspaceBootstrap
+            | OperationKind::EphemeralCreate
+                if workspace.is_some() =>
+            {
+                return Err(eyre!(
+                    "the cloud block of `{}` pins it to one workspace; add workspaces in HCP Terraform and use `workspaces {{ tags = [...] }}`",
+                    account.name
+                ));
+            }
+            OperationKind::WorkspaceDelete
+            | OperationKind::WorkspaceCleanup
+            | OperationKind::EphemeralTeardown => {
+                return Err(eyre!(
+                    "`{}` keeps its workspaces in HCP Terraform; delete them there, where their state and run history live",
+                    account.name
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if matches!(
+        kind,
+        OperationKind::TerraformPlan
+            | OperationKind::TerraformRefreshPlan
+            | OperationKind::TerraformDestroyPlan
+            | OperationKind::TerraformApply
+            | OperationKind::TerraformImport
+    ) && !account.var_files.is_empty()
+    {
+        validate_var_files_for_execution(account)?;
+    }
+
+    Ok(())
+}
+
+fn append_var_file_args(args: &mut Vec<String>, var_files: &[PathBuf]) {
+    for var_file in var_files {
+        args.push(format!("-var-file={}", var_file.display()));
+    }
+}
+
+fn terraform_base_command(account: &AccountState) -> Command {
+    let mut command = Command::new(&account.binary);
+    apply_account_env(&mut command, account);
+    command.env("TF_IN_AUTOMATION", "1");
+    if let Some(version) = &account.pinned_version {
+        let vars: [&str; 3] = if account.is_opentofu() {
+            [
+                "TOFUENV_TOFU_VERSION",
+                "MISE_OPENTOFU_VERSION",
+                "ASDF_OPENTOFU_VERSION",
+            ]
+        } else {
+            [
+                "TFENV_TERRAFORM_VERSION",
+                "MISE_TERRAFORM_VERSION",
+                "ASDF_TERRAFORM_VERSION",
+            ]
+        };
+        for var in vars {
+            command.env(var, version);
+        }
+    }
+    command
+}
+
+fn resolve_pinned_binary(
+    binary: &str,
+    version: &str,
+    versions_dir: Option<&Path>,
+) -> Option<PathBuf> {
+    let tool = Path::new(binary)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("terraform");
+    let (env_manager, plugin) = if tool.starts_with("tofu") {
+        ("tofuenv", "opentofu")
+    } else {
+        ("tfenv", "terraform")
+    };
+    let env_dir = |var: &str| {
+        std::env::var_os(var)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    };
+    let home = env_dir("HOME").unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    if let Some(dir) = versions_dir {
+        candidates.push(dir.join(version).join(tool));
+        candidates.push(dir.join(format!("{tool}_{version}")));
+        candidates.push(dir.join(format!("{tool}-{version}")));
+    }
+    let env_manager_upper = env_manager.to_ascii_uppercase();
+    let env_manager_dir = env_dir(&format!("{env_manager_upper}_CONFIG_DIR"))
+        .or_else(|| env_dir(&format!("{env_manager_upper}_ROOT")))
+        .unwrap_or_else(|| home.join(format!(".{env_manager}")));
+    candidates.push(env_manager_dir.join("versions").join(version).join(tool));
+    let mise_dir = env_dir("MISE_DATA_DIR")
+        .or_else(|| env_dir("XDG_DATA_HOME").map(|dir| dir.join("mise")))
+        .unwrap_or_else(|| home.join(".local/share/mise"));
+    let asdf_dir = env_dir("ASDF_DATA_DIR").unwrap_or_else(|| home.join(".asdf"));
+    for root in [mise_dir, asdf_dir] {
+        let install = root.join("installs").join(plugin).join(version);
+        candidates.push(install.join("bin").join(tool));
+        candidates.push(install.join(tool));
+    }
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+fn account_shell_command(account: &AccountState, shell_command: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.args(["-c", shell_command]);
+    apply_account_env(&mut command, account);
+    command
+}
+
+fn apply_account_env(command: &mut Command, account: &AccountState) {
+    command.current_dir(&account.composition_path);
+    command.env("AWS_PROFILE", &account.aws_profile);
+    command.env("AWS_SDK_LOAD_CONFIG", "1");
+
+    if let Some(region) = &account.region {
+        command.env("AWS_REGION", region);
+        command.env("AWS_DEFAULT_REGION", region);
+    }
+    command.envs(&account.env);
+}
+
+fn apply_tf_vars(command: &mut Command, account: &AccountState, workspace: &str) {
+    let overrides = account.workspace_variables.get(workspace);
+    for (name, value) in account
+        .variables
+        .iter()
+        .chain(overrides.into_iter().flatten())
+    {
+        command.env(format!("TF_VAR_{name}"), value);
+    }
+}
+
+fn init_args(account: &AccountState) -> Vec<String> {
+    let mut args = vec![
+        "init".to_string(),
+        "-input=false".to_string(),
+        "-no-color".to_string(),
+    ];
+    args.extend(account.backend_config.iter().cloned());
+    append_extra_args(&mut args, account, "init");
+    args
+}
+
+fn append_extra_args(args: &mut Vec<String>, account: &AccountState, subcommand: &str) {
+    if let Some(extra) = account.extra_args.get(subcommand) {
+        args.extend(extra.iter().cloned());
+    }
+}
+
+fn terraform_command(account: &AccountState, args: &[&str]) -> Command {
+    let mut command = terraform_base_command(account);
+    command.args(args);
+    command
+}
+
+fn terraform_command_owned(account: &AccountState, args: &[String]) -> Command {
+    let mut command = terraform_base_command(account);
+    command.args(args);
+    command
+}
+
+async fn run_streaming_command(
+    mut command: Command,
+    mut cancel_rx: watch::Receiver<CancelSignal>,
+    event_tx: mpsc::UnboundedSender<WorkerEvent>,
+) -> Result<RunOutcome> {
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+    command.kill_on_drop(true);
+
+    let mut child = command.spawn().wrap_err("Failed to spawn command")?;
+    let child_record = child
+        .id()
+        .and_then(|pid| register_child(pid, command_description(&command)));
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| eyre!("Command stdout was not piped"))?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| eyre!("Command stderr was not piped"))?;
+
+    let tx_stdout = event_tx.clone();
+    let tx_stderr = event_tx.clone();
+
+    let stdout_task = tokio::spawn(async move { stream_reader(stdout, tx_stdout).await });
+    let stderr_task = tokio::spawn(async move { stream_reader(stderr, tx_stderr).await });
+
+    let mut cancelled = false;
+    let mut sigint_sent = false;
+    let mut force_kill_sent = false;
+
+    let status = loop {
+        tokio::select! {
+            child_status = child.wait() => {
+                break child_status.wrap_err("Failed while waiting for command")?;
+            }
+            changed = cancel_rx.changed() => {
+                if changed.is_ok() {
+                    match *cancel_rx.borrow() {
+                        CancelSignal::None => {}
+                        CancelSignal::Graceful => {
+                            cancelled = true;
+                            if !sigint_sent {
+                                if let Some(pid) = child.id() {
+                                    send_sigint(pid)?;
+                                    let _ = event_tx.send(WorkerEvent::OutputLine("Sent SIGINT to running command.".to_string()));
+                                }
+                                sigint_sent = true;
+                            }
+                        }
+                        CancelSignal::Force => {
+                            cancelled = true;
+                            if !force_kill_sent {
+                                let _ = event_tx.send(WorkerEvent::OutputLine("Force kill signal sent to running command.".to_string()));
+                                let _ = child.start_kill();
+                                force_kill_sent = true;
+                            }
+                        }
                     }
                 }
             }
-            Ok(outcome) => {
-                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                    account_idx,
-                    status: AuthStatus::Failed,
-                    message: format!("AWS login failed for `{}`", account.name),
-                });
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind: OperationKind::AuthLogin,
-                    account_idx,
-                    success: false,
-                    cancelled: outcome.cancelled,
-                    message: format!(
-                        "AWS login failed for `{}` with exit code {}",
-                        account.name,
-                        outcome.exit_code.unwrap_or(-1)
-                    ),
-                });
-            }
-            Err(err) => {
-                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                    account_idx,
-                    status: AuthStatus::Failed,
-                    message: format!("Failed to run AWS login for `{}`: {err}", account.name),
-                });
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind: OperationKind::AuthLogin,
-                    account_idx,
-                    success: false,
-                    cancelled: false,
-                    message: format!("AWS login execution failed for `{}`: {err}", account.name),
-                });
-            }
         }
-    });
-}
+    };
 
-fn start_workspace_refresh(app: &mut AppState, event_tx: mpsc::UnboundedSender<WorkerEvent>) {
-    if app.is_busy() {
-        app.push_output("Another operation is already running.");
-        return;
+    if let Some(path) = child_record {
+        let _ = fs::remove_file(path);
     }
 
-    let Some(account) = app.selected_account().cloned() else {
-        app.push_output("No account selected.");
-        return;
-    };
+    let _ = stdout_task.await;
+    let _ = stderr_task.await;
 
-    if account.auth != AuthStatus::Authenticated {
-        app.push_output("Selected account is not authenticated. Press `a` to run AWS SSO login.");
-        return;
+    Ok(RunOutcome {
+        success: status.success(),
+        cancelled,
+        exit_code: status.code(),
+    })
+}
+
+async fn stream_reader<R>(reader: R, event_tx: mpsc::UnboundedSender<WorkerEvent>) -> Result<()>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer).await? == 0 {
+            return Ok(());
+        }
+        let _ = event_tx.send(WorkerEvent::OutputLine(decode_output_line(&buffer)));
     }
+}
 
-    if let Err(err) = validate_composition_for_execution(&account) {
-        app.push_output(format!(
-            "Cannot refresh workspaces for `{}`: {err}",
-            account.name
-        ));
-        app.set_status("failed");
-        return;
+/// Decodes one line of process output. Lines are split on `\n` bytes, which
+/// never occur inside a multi-byte UTF-8 sequence, so characters are never cut
+/// in half; bytes that are not valid UTF-8 are kept visible as `\xNN` escapes
+/// instead of being replaced with U+FFFD.
+fn decode_output_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let mut line = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        line.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            line.push_str(&format!("\\x{byte:02X}"));
+        }
     }
+    line
+}
 
-    let account_idx = app.selected_account;
-    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
-    app.inflight = Some(InflightOperation {
-        kind: OperationKind::RefreshWorkspaces,
-        account_idx,
-        cancel_tx,
-        cancel_stage: CancelStage::None,
-    });
-    app.set_status(format!("loading workspaces for {}", account.name));
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChildRecord {
+    pid: u32,
+    owner_pid: u32,
+    command: String,
+    started_at: u64,
+}
 
-    tokio::spawn(async move {
-        let command = terraform_command(&account, &["workspace", "list"]);
-        let result = run_streaming_command(command, cancel_rx, event_tx.clone()).await;
+fn children_dir() -> PathBuf {
+    lazytf_state_dir().join("children")
+}
 
-        match result {
-            Ok(outcome) if outcome.success => match fetch_workspaces(&account).await {
-                Ok(workspaces) => {
-                    let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
-                        account_idx,
-                        workspaces,
-                    });
-                    let _ = event_tx.send(WorkerEvent::OperationFinished {
-                        kind: OperationKind::RefreshWorkspaces,
-                        account_idx,
-                        success: true,
-                        cancelled: false,
-                        message: format!("Workspace refresh completed for `{}`", account.name),
-                    });
-                }
-                Err(err) => {
-                    let _ = event_tx.send(WorkerEvent::OperationFinished {
-                        kind: OperationKind::RefreshWorkspaces,
-                        account_idx,
-                        success: false,
-                        cancelled: false,
-                        message: format!("Workspace refresh failed for `{}`: {err}", account.name),
-                    });
-                }
-            },
-            Ok(outcome) => {
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind: OperationKind::RefreshWorkspaces,
-                    account_idx,
-                    success: false,
-                    cancelled: outcome.cancelled,
-                    message: format!(
-                        "Workspace refresh command failed for `{}` with exit code {}",
-                        account.name,
-                        outcome.exit_code.unwrap_or(-1)
-                    ),
-                });
+fn child_record_path(pid: u32) -> PathBuf {
+    children_dir().join(format!("{pid}.json"))
+}
+
+/// `args` with the values of `-var name=value` and `-var=name=value` replaced by `***`, for
+/// output and records; the variable names stay visible.
+fn redact_var_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut redacted = Vec::new();
+    let mut value_follows = false;
+    for arg in args {
+        let var = if value_follows {
+            Some(arg)
+        } else {
+            arg.strip_prefix("-var=")
+        };
+        redacted.push(match var.and_then(|var| var.split_once('=')) {
+            Some((name, _)) if value_follows => format!("{name}=***"),
+            Some((name, _)) => format!("-var={name}=***"),
+            None => arg.to_string(),
+        });
+        value_follows = arg == "-var";
+    }
+    redacted
+}
+
+fn command_description(command: &Command) -> String {
+    let command = command.as_std();
+    let parts: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy().into_owned())
+        .collect();
+    redact_var_args(parts.iter().map(String::as_str)).join(" ")
+}
+
+fn register_child(pid: u32, command: String) -> Option<PathBuf> {
+    let record = ChildRecord {
+        pid,
+        owner_pid: std::process::id(),
+        command,
+        started_at: unix_now(),
+    };
+    let path = child_record_path(pid);
+    fs::create_dir_all(children_dir()).ok()?;
+    fs::write(&path, serde_json::to_string(&record).ok()?).ok()?;
+    Some(path)
+}
+
+fn scan_leftover_children() -> Vec<ChildRecord> {
+    let Ok(entries) = fs::read_dir(children_dir()) else {
+        return Vec::new();
+    };
+
+    let mut leftovers = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let record = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<ChildRecord>(&contents).ok());
+        match record {
+            Some(record) if process_alive(record.owner_pid) => {}
+            Some(record) if process_alive(record.pid) && process_matches(&record) => {
+                leftovers.push(record);
             }
-            Err(err) => {
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind: OperationKind::RefreshWorkspaces,
-                    account_idx,
-                    success: false,
-                    cancelled: false,
-                    message: format!(
-                        "Workspace refresh command failed for `{}`: {err}",
-                        account.name
-                    ),
-                });
+            _ => {
+                let _ = fs::remove_file(&path);
             }
         }
-    });
+    }
+    leftovers.sort_by_key(|record| record.started_at);
+    leftovers
 }
 
-fn start_terraform_operation(
-    app: &mut AppState,
-    event_tx: mpsc::UnboundedSender<WorkerEvent>,
-    kind: OperationKind,
-) {
-    if app.is_busy() {
-        app.push_output("Another operation is already running.");
-        return;
+/// Whether `record.pid` still runs the recorded program rather than a process that reused
+/// the PID; unverifiable processes are treated as not matching, so they are never killed.
+fn process_matches(record: &ChildRecord) -> bool {
+    let program = record.command.split_whitespace().next().unwrap_or_default();
+    if let Ok(cmdline) = fs::read(format!("/proc/{}/cmdline", record.pid)) {
+        return String::from_utf8_lossy(&cmdline).contains(program);
     }
+    // No procfs (macOS, BSD): ask `ps`.
+    std::process::Command::new("ps")
+        .args(["-p", &record.pid.to_string(), "-o", "args="])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains(program)
+        })
+}
 
-    let Some(account) = app.selected_account().cloned() else {
-        app.push_output("No account selected.");
-        return;
-    };
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
 
-    if account.auth != AuthStatus::Authenticated {
-        app.push_output("Selected account is not authenticated. Press `a` first.");
-        return;
+    i32::try_from(pid).is_ok_and(|pid| kill(Pid::from_raw(pid), None).is_ok())
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn terminate_process(pid: u32) -> Result<()> {
+    use nix::sys::signal::{Signal, kill};
+    use nix::unistd::Pid;
+
+    let pid_i32 = i32::try_from(pid).wrap_err("child PID overflowed i32")?;
+    kill(Pid::from_raw(pid_i32), Signal::SIGTERM).wrap_err("failed to send SIGTERM")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate_process(_pid: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn send_sigint(pid: u32) -> Result<()> {
+    use nix::sys::signal::{Signal, kill};
+    use nix::unistd::Pid;
+
+    let pid_i32 = i32::try_from(pid).wrap_err("child PID overflowed i32")?;
+    kill(Pid::from_raw(pid_i32), Signal::SIGINT).wrap_err("failed to send SIGINT")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_sigint(_pid: u32) -> Result<()> {
+    Ok(())
+}
+
+fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &AppState) {
+    let activity = account_activity_line(app);
+    let root = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(10),
+            Constraint::Length(if activity.is_some() { 3 } else { 2 }),
+        ])
+        .split(frame.area());
+
+    let mut title = Line::from(vec![
+        Span::styled(
+            " lazytf ",
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!(
+            "| {} | mode: {} | focus: {:?}",
+            app.current_operation_label(),
+            app.layout_mode.label(),
+            app.focused_panel
+        )),
+    ]);
+    let running_tasks = app
+        .tasks
+        .iter()
+        .filter(|task| task.finished.is_none())
+        .count();
+    if let Some(current) = app
+        .selected_account()
+        .and_then(|account| account.current_workspace.as_deref())
+    {
+        title.spans.push(Span::raw(" | terraform workspace: "));
+        title.spans.push(Span::styled(
+            current.to_string(),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(summary) = &app.plan_summary {
+        let account_name = app
+            .accounts
+            .get(summary.account_idx)
+            .map(|account| account.name.as_str())
+            .unwrap_or_default();
+        let label = if summary.destroy_preview {
+            "destroy preview"
+        } else {
+            "plan"
+        };
+        title.spans.push(Span::raw(format!(
+            " | {label} {account_name}/{}: ",
+            summary.workspace
+        )));
+        if summary.import > 0 {
+            title.spans.push(Span::styled(
+                format!("{} to import ", summary.import),
+                Style::default().fg(Color::Cyan),
+            ));
+        }
+        for (count, suffix, color) in [
+            (summary.add, "add", Color::Green),
+            (
+                summary.change.saturating_sub(summary.noisy),
+                "change",
+                Color::Yellow,
+            ),
+            (summary.destroy, "destroy", Color::Red),
+        ] {
+            let style = if count > 0 {
+                Style::default().fg(color).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            title
+                .spans
+                .push(Span::styled(format!("{count} to {suffix} "), style));
+        }
+        if summary.noisy > 0 {
+            title.spans.push(Span::styled(
+                format!("({} noisy) ", summary.noisy),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+    }
+    if let Some(next) = app.scheduled_applies.first() {
+        title.spans.push(Span::styled(
+            format!(
+                " | {} scheduled apply(ies), next {}/{} at {}",
+                app.scheduled_applies.len(),
+                next.account,
+                next.workspace,
+                format_timestamp(next.run_at)
+            ),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if running_tasks > 0 && !app.activity_open {
+        title.spans.push(Span::styled(
+            format!(" | {running_tasks} background task(s)"),
+            Style::default().fg(Color::Yellow),
+        ));
     }
+    if let Some(latest) = app.update_record.notice().filter(|_| app.update_check) {
+        title.spans.push(Span::styled(
+            format!(" | lazytf {latest} is available (Z dismisses)"),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if app.mouse_released {
+        title.spans.push(Span::styled(
+            " | MOUSE RELEASED (any key resumes)",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    frame.render_widget(title, root[0]);
 
-    if let Err(err) = validate_operation_preflight(&account, kind) {
-        app.push_output(format!("Cannot run {}: {err}", kind.label()));
-        app.set_status("failed");
-        return;
+    if app.is_output_only() {
+        draw_output_only_layout(frame, app, root[1]);
+    } else {
+        draw_split_layout(frame, app, root[1]);
     }
 
-    let workspace = if kind.requires_workspace() {
-        match app.selected_workspace_name() {
-            Some(workspace) => workspace,
-            None => {
-                app.push_output("No workspace selected. Press `r` to load workspaces first.");
-                return;
-            }
-        }
+    let mut help = if app.is_output_only() {
+        vec![
+            Line::from(
+                "z/esc:exit fullscreen  ?:help  pgup/pgdn g/G:scroll  c:cancel (again=force)  q:quit",
+            ),
+            Line::from("output-only mode for plan review"),
+        ]
     } else {
-        String::new()
+        vec![
+            Line::from(
+                "j/k or arrows: move  tab/h/l: panel  z:fullscreen output  ?:help  a:aws login  s:auth check  r:workspaces",
+            ),
+            Line::from(
+                "i:init  v:validate  p:plan  A then y:apply  H:history  M:state growth  c:cancel (again=force)  q:quit  pgup/pgdn g/G:output scroll",
+            ),
+        ]
     };
+    if let Some(activity) = activity {
+        help.insert(0, activity);
+    }
+    frame.render_widget(Paragraph::new(help), root[2]);
 
-    let account_idx = app.selected_account;
-    let (cancel_tx, cancel_rx) = watch::channel(CancelSignal::None);
-
-    app.inflight = Some(InflightOperation {
-        kind,
-        account_idx,
-        cancel_tx,
-        cancel_stage: CancelStage::None,
-    });
-    app.set_status(format!("running {} for {}", kind.label(), account.name));
+    if app.pending_apply_confirmation {
+        draw_apply_confirmation(frame, app);
+    }
 
-    tokio::spawn(async move {
-        let run_result = run_terraform_operation(
-            kind,
-            account.clone(),
-            workspace.clone(),
-            cancel_rx,
-            event_tx.clone(),
-        )
-        .await;
+    if let Some(search) = &app.history_search {
+        draw_history_search(frame, search);
+    }
 
-        match run_result {
-            Ok(outcome) => {
-                let message = if outcome.success {
-                    format!("{} succeeded for `{}`", kind.label(), account.name)
-                } else if outcome.cancelled {
-                    format!("{} cancelled for `{}`", kind.label(), account.name)
-                } else {
-                    format!(
-                        "{} failed for `{}` with exit code {}",
-                        kind.label(),
-                        account.name,
-                        outcome.exit_code.unwrap_or(-1)
-                    )
-                };
+    if app.destroy_preview_open
+        && let Some(view) = &app.destroy_preview
+    {
+        draw_plan_changes(frame, app, view);
+    } else if app.plan_changes_open
+        && let Some(view) = &app.plan_changes
+    {
+        draw_plan_changes(frame, app, view);
+    }
 
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind,
-                    account_idx,
-                    success: outcome.success,
-                    cancelled: outcome.cancelled,
-                    message,
-                });
-            }
-            Err(err) => {
-                let _ = event_tx.send(WorkerEvent::OperationFinished {
-                    kind,
-                    account_idx,
-                    success: false,
-                    cancelled: false,
-                    message: format!("{} failed for `{}`: {err}", kind.label(), account.name),
-                });
-            }
-        }
-    });
-}
+    if let Some(review) = &app.plan_review {
+        draw_plan_review(frame, app, review);
+    }
 
-fn spawn_auth_check(
-    account_idx: usize,
-    account: AccountState,
-    event_tx: mpsc::UnboundedSender<WorkerEvent>,
-) {
-    tokio::spawn(async move {
-        let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-            account_idx,
-            status: AuthStatus::Checking,
-            message: format!(
-                "Checking auth for `{}` (profile `{}`)",
-                account.name, account.aws_profile
-            ),
-        });
+    if let Some(browser) = &app.state_browser {
+        draw_state_browser(frame, app, browser);
+    }
 
-        match check_auth(&account).await {
-            Ok(true) => {
-                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                    account_idx,
-                    status: AuthStatus::Authenticated,
-                    message: format!("Credentials valid for `{}`", account.name),
-                });
+    if let Some(view) = &app.lock_table {
+        draw_lock_table(frame, app, view);
+    }
 
-                match fetch_workspaces(&account).await {
-                    Ok(workspaces) => {
-                        let _ = event_tx.send(WorkerEvent::WorkspacesLoaded {
-                            account_idx,
-                            workspaces,
-                        });
-                    }
-                    Err(err) => {
-                        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-                            "Could not load workspaces for `{}` yet: {err}",
-                            account.name
-                        )));
-                    }
-                }
-            }
-            Ok(false) => {
-                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                    account_idx,
-                    status: AuthStatus::Failed,
-                    message: format!("No valid AWS session for `{}`", account.name),
-                });
-            }
-            Err(err) => {
-                let _ = event_tx.send(WorkerEvent::AccountAuthUpdate {
-                    account_idx,
-                    status: AuthStatus::Failed,
-                    message: format!("Auth check errored for `{}`: {err}", account.name),
-                });
-            }
-        }
-    });
-}
+    if let Some(view) = &app.ci_runs {
+        draw_ci_runs(frame, app, view);
+    }
 
-async fn run_terraform_operation(
-    kind: OperationKind,
-    account: AccountState,
-    workspace: String,
-    cancel_rx: watch::Receiver<CancelSignal>,
-    event_tx: mpsc::UnboundedSender<WorkerEvent>,
-) -> Result<RunOutcome> {
-    validate_operation_preflight(&account, kind)?;
+    if let Some(browser) = &app.file_browser {
+        draw_file_browser(frame, browser);
+    }
 
-    if kind.requires_workspace() {
-        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-            "Selecting workspace `{}` in `{}`",
-            workspace, account.name
-        )));
+    if let Some(notes) = &app.notes {
+        draw_notes(frame, notes);
+    }
 
-        let mut select_cmd = terraform_command(&account, &["workspace", "select", &workspace]);
-        let select_out = select_cmd
-            .output()
-            .await
-            .wrap_err("Failed to run terraform workspace select")?;
-        emit_process_output(&event_tx, &select_out.stdout);
-        emit_process_output(&event_tx, &select_out.stderr);
-        if !select_out.status.success() {
-            return Ok(RunOutcome {
-                success: false,
-                cancelled: false,
-                exit_code: select_out.status.code(),
-            });
-        }
+    if let Some(view) = &app.state_metrics_view {
+        draw_state_metrics(frame, view);
     }
 
-    if matches!(
-        kind,
-        OperationKind::TerraformPlan | OperationKind::TerraformApply
-    ) && !account.var_files.is_empty()
+    if let Some(session) = &app.cleanup_session
+        && !app.is_busy()
     {
-        let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-            "Using var files: {}",
-            account
-                .var_files
-                .iter()
-                .map(|path| path.display().to_string())
-                .collect::<Vec<_>>()
-                .join(", ")
-        )));
+        draw_cleanup_prompt(frame, app, session);
     }
 
-    let command = match kind {
-        OperationKind::TerraformInit => {
-            terraform_command(&account, &["init", "-input=false", "-no-color"])
-        }
-        OperationKind::TerraformPlan => {
-            let mut args = vec![
-                "plan".to_string(),
-                "-input=false".to_string(),
-                "-no-color".to_string(),
-            ];
-            append_var_file_args(&mut args, &account.var_files);
-            terraform_command_owned(&account, &args)
-        }
-        OperationKind::TerraformApply => {
-            let mut args = vec![
-                "apply".to_string(),
-                "-input=false".to_string(),
-                "-no-color".to_string(),
-                "-auto-approve".to_string(),
-            ];
-            append_var_file_args(&mut args, &account.var_files);
-            terraform_command_owned(&account, &args)
-        }
-        _ => {
-            return Err(eyre!(
-                "Unsupported terraform operation for runner: {}",
-                kind.label()
-            ));
-        }
-    };
+    if let Some(confirmation) = &app.confirmation {
+        draw_confirmation(frame, confirmation);
+    }
 
-    let _ = event_tx.send(WorkerEvent::OutputLine(format!(
-        "Running `{}` in {}",
-        kind.label(),
-        account.composition_path.display()
-    )));
+    if let Some(popup) = &app.run_options_popup {
+        draw_run_options(frame, popup);
+    }
 
-    run_streaming_command(command, cancel_rx, event_tx).await
-}
+    if let Some(picker) = &app.var_file_picker {
+        draw_var_file_picker(frame, picker);
+    }
 
-fn emit_process_output(event_tx: &mpsc::UnboundedSender<WorkerEvent>, bytes: &[u8]) {
-    for line in String::from_utf8_lossy(bytes).lines() {
-        let _ = event_tx.send(WorkerEvent::OutputLine(line.to_string()));
+    if !app.leftover_children.is_empty() {
+        draw_leftover_children(frame, &app.leftover_children);
+    }
+
+    if let Some(prompt) = &app.prompt {
+        draw_text_prompt(frame, prompt);
+    }
+
+    if app.show_help {
+        draw_help_modal(frame);
     }
 }
 
-async fn check_auth(account: &AccountState) -> Result<bool> {
-    let mut command = Command::new("aws");
-    command.args([
-        "sts",
-        "get-caller-identity",
-        "--profile",
-        &account.aws_profile,
-        "--output",
-        "json",
-    ]);
+fn draw_split_layout(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(28),
+            Constraint::Percentage(28),
+            Constraint::Percentage(44),
+        ])
+        .split(area);
 
-    if let Some(region) = &account.region {
-        command.env("AWS_REGION", region);
-        command.env("AWS_DEFAULT_REGION", region);
+    draw_accounts_panel(frame, app, columns[0]);
+    draw_workspaces_panel(frame, app, columns[1]);
+    if app.activity_open {
+        let output = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(20), Constraint::Length(36)])
+            .split(columns[2]);
+        draw_output_panel(frame, app, output[0]);
+        draw_activity_panel(frame, app, output[1]);
+    } else {
+        draw_output_panel(frame, app, columns[2]);
     }
+}
 
-    let output = command
-        .output()
-        .await
-        .wrap_err("Failed to run aws sts get-caller-identity")?;
+fn draw_activity_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    let items: Vec<ListItem<'_>> = app
+        .tasks
+        .iter()
+        .rev()
+        .map(|task| {
+            let (state, color, elapsed) = match task.finished {
+                None => ("run", Color::Yellow, task.started.elapsed()),
+                Some((elapsed, true)) => ("ok", Color::Green, elapsed),
+                Some((elapsed, false)) => ("fail", Color::Red, elapsed),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{state:<4}"), Style::default().fg(color)),
+                Span::styled(
+                    format!(" {:>5.1}s ", elapsed.as_secs_f64()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(task.label.clone()),
+            ]))
+        })
+        .collect();
 
-    Ok(output.status.success())
+    let widget = List::new(items).block(
+        Block::default()
+            .title("Activity (b to hide)")
+            .borders(Borders::ALL),
+    );
+    frame.render_widget(widget, area);
 }
 
-async fn fetch_workspaces(account: &AccountState) -> Result<Vec<String>> {
-    validate_composition_for_execution(account)?;
-
-    let mut command = terraform_command(account, &["workspace", "list"]);
-    let output = command
-        .output()
-        .await
-        .wrap_err("Failed to run terraform workspace list")?;
+fn draw_output_only_layout(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    draw_output_panel(frame, app, area);
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(eyre!(
-            "terraform workspace list failed for {}: {}",
-            account.name,
-            stderr.trim()
+fn account_activity_line(app: &AppState) -> Option<Line<'static>> {
+    let entries: Vec<(&str, AccountActivity)> = app
+        .accounts
+        .iter()
+        .filter_map(|account| {
+            app.account_activity
+                .get(&account.name)
+                .map(|activity| (account.name.as_str(), *activity))
+        })
+        .collect();
+    if entries.len() < 2 {
+        return None;
+    }
+    let mut spans = Vec::new();
+    for (name, activity) in entries {
+        if !spans.is_empty() {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::raw(format!("{name}:")));
+        spans.push(Span::styled(
+            app.icons.activity(activity),
+            Style::default()
+                .fg(activity.color())
+                .add_modifier(Modifier::BOLD),
         ));
     }
-
-    Ok(parse_workspace_output(&String::from_utf8_lossy(
-        &output.stdout,
-    )))
+    Some(Line::from(spans))
 }
 
-fn parse_workspace_output(output: &str) -> Vec<String> {
-    output
-        .lines()
-        .filter_map(|line| {
-            let cleaned = line.trim().trim_start_matches('*').trim();
-            if cleaned.is_empty() {
-                None
-            } else {
-                Some(cleaned.to_string())
+fn draw_accounts_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    let border_style = if app.focused_panel == FocusPanel::Accounts {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+
+    let rows = app.account_rows();
+    let cursor = app.selected_account_row(&rows);
+    let items: Vec<ListItem<'_>> = rows
+        .iter()
+        .enumerate()
+        .map(|(row, entry)| {
+            let selected = if row == cursor { ">" } else { " " };
+            let idx = match entry {
+                AccountRow::Account(idx) => *idx,
+                AccountRow::Group(name) => {
+                    let members: Vec<&AccountState> = app
+                        .accounts
+                        .iter()
+                        .filter(|account| account.group.as_ref() == Some(name))
+                        .collect();
+                    let authenticated = members
+                        .iter()
+                        .filter(|account| account.auth == AuthStatus::Authenticated)
+                        .count();
+                    return ListItem::new(Line::from(vec![
+                        Span::raw(format!("{selected} ")),
+                        Span::styled(
+                            format!(
+                                "{} {name} ({authenticated}/{} authenticated)",
+                                app.icons.group(app.collapsed_groups.contains(name)),
+                                members.len()
+                            ),
+                            Style::default()
+                                .fg(Color::Cyan)
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                    ]));
+                }
+            };
+            let account = &app.accounts[idx];
+            let indent = if account.group.is_some() { "  " } else { "" };
+            let mut line = Line::from(vec![
+                Span::raw(format!("{selected} {indent}")),
+                Span::styled(
+                    app.icons.auth(account.auth),
+                    Style::default().fg(account.auth.color()),
+                ),
+                Span::raw(format!(" {} [{}]", account.name, account.auth.label())),
+            ]);
+            if let Some(role) = account.role() {
+                let color = if account.can_mutate() {
+                    Color::Red
+                } else {
+                    Color::Green
+                };
+                line.spans.push(Span::styled(
+                    format!(" {}", role.name),
+                    Style::default().fg(color),
+                ));
+            }
+            if let Some(issues) = account.backend_issues.filter(|issues| *issues > 0) {
+                line.spans.push(Span::styled(
+                    format!(" {} backend ({issues})", app.icons.warning()),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            if account.backend_unreachable.is_some() {
+                line.spans.push(Span::styled(
+                    format!(" {} backend unreachable", app.icons.warning()),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            if account.version_mismatch.is_some() {
+                line.spans.push(Span::styled(
+                    format!(" {} version", app.icons.warning()),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            if account.profile_missing {
+                line.spans.push(Span::styled(
+                    " (no profile)",
+                    Style::default().fg(Color::Yellow),
+                ));
             }
+            ListItem::new(line)
         })
-        .collect()
+        .collect();
+
+    let items = if items.is_empty() && app.account_filter.is_some() {
+        vec![ListItem::new("  (no matches)")]
+    } else {
+        items
+    };
+    let title = match &app.account_filter {
+        Some(query) => format!("Accounts /{query}_ ({}/{})", rows.len(), app.accounts.len()),
+        None => "Accounts".to_string(),
+    };
+    let widget = List::new(items).block(
+        Block::default()
+            .title(app.icons.panel_title(FocusPanel::Accounts, &title))
+            .borders(Borders::ALL)
+            .border_style(border_style),
+    );
+
+    let mut state = ListState::default()
+        .with_offset(app.accounts_offset.get())
+        .with_selected(Some(cursor));
+    frame.render_stateful_widget(widget, area, &mut state);
+    app.accounts_offset.set(state.offset());
 }
 
-fn validate_composition_for_execution(account: &AccountState) -> Result<()> {
-    if let Some(issue) = &account.composition_issue {
-        return Err(eyre!(
-            "Account `{}` configuration is invalid: {}",
-            account.name,
-            issue
-        ));
-    }
+fn draw_workspaces_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    let border_style = if app.focused_panel == FocusPanel::Workspaces {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
 
-    if !account.composition_path.exists() {
-        return Err(eyre!(
-            "composition_path does not exist for `{}`: {}",
-            account.name,
-            account.composition_path.display()
-        ));
-    }
+    let now = unix_now();
+    let visible = app.visible_workspaces();
+    let items: Vec<ListItem<'_>> = if let Some(account) = app.selected_account() {
+        if account.workspaces.is_empty() {
+            vec![ListItem::new("  (no workspaces loaded)")]
+        } else if visible.is_empty() {
+            vec![ListItem::new("  (no matches)")]
+        } else {
+            visible
+                .iter()
+                .map(|idx| (*idx, &account.workspaces[*idx]))
+                .map(|(idx, workspace)| {
+                    let selected = if idx == app.selected_workspace {
+                        ">"
+                    } else {
+                        " "
+                    };
+                    let mut spans = if account.current_workspace.as_ref() == Some(workspace) {
+                        vec![
+                            Span::raw(format!("{selected} ")),
+                            Span::styled(
+                                format!("{workspace} *"),
+                                Style::default()
+                                    .fg(Color::Green)
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                        ]
+                    } else {
+                        vec![Span::raw(format!("{selected} {workspace}"))]
+                    };
+                    if let Some(environment) = app
+                        .environments
+                        .iter()
+                        .find(|env| env.account == account.name && &env.workspace == workspace)
+                    {
+                        spans.push(environment_badge(
+                            environment,
+                            now,
+                            app.reminders.warn_before_hours,
+                            app.icons,
+                        ));
+                    }
+                    if let Some(record) = app.team.running_on(&account.name, workspace, now) {
+                        spans.push(Span::styled(
+                            format!("  [{}: {}]", record.operator, record.operation),
+                            Style::default()
+                                .fg(Color::Magenta)
+                                .add_modifier(Modifier::BOLD),
+                        ));
+                    }
+                    if let Some(lock) = app.maintenance_lock(&account.name, workspace) {
+                        spans.push(Span::styled(
+                            format!("  [locked by {}]", lock.locked_by),
+                            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        ));
+                    }
+                    match app
+                        .drift
+                        .get(&cost_key(&account.name, workspace))
+                        .map(|record| record.status)
+                    {
+                        Some(DriftStatus::Drifted) => spans.push(Span::styled(
+                            format!("  [{} drift]", app.icons.warning()),
+                            Style::default()
+                                .fg(Color::Yellow)
+                                .add_modifier(Modifier::BOLD),
+                        )),
+                        Some(DriftStatus::Failed) => spans.push(Span::styled(
+                            "  [drift check failed]",
+                            Style::default().fg(Color::DarkGray),
+                        )),
+                        _ => {}
+                    }
+                    if let Some(estimate) = app.costs.get(&cost_key(&account.name, workspace)) {
+                        spans.push(Span::styled(
+                            format!("  {}", format_monthly_cost(estimate)),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    ListItem::new(Line::from(spans))
+                })
+                .collect()
+        }
+    } else {
+        vec![ListItem::new("  (no account selected)")]
+    };
 
-    if !account.composition_path.is_dir() {
-        return Err(eyre!(
-            "composition_path is not a directory for `{}`: {}",
-            account.name,
-            account.composition_path.display()
+    let mut title = match app
+        .selected_account()
+        .map(|account| &account.composition_mode)
+    {
+        Some(CompositionMode::Cloud {
+            organization: Some(organization),
+            ..
+        }) => format!("Workspaces (HCP Terraform: {organization})"),
+        Some(CompositionMode::Cloud { .. }) => "Workspaces (HCP Terraform)".to_string(),
+        Some(CompositionMode::Stack) => "Workspaces (stack: none)".to_string(),
+        _ => "Workspaces".to_string(),
+    };
+    if let (Some(filter), Some(query)) = (&app.workspace_filter, app.workspace_filter_query()) {
+        let total = app
+            .selected_account()
+            .map_or(0, |account| account.workspaces.len());
+        title.push_str(&format!(
+            " /{query}{} ({}/{total})",
+            if filter.editing { "_" } else { "" },
+            visible.len()
         ));
     }
+    let widget = List::new(items).block(
+        Block::default()
+            .title(app.icons.panel_title(FocusPanel::Workspaces, &title))
+            .borders(Borders::ALL)
+            .border_style(border_style),
+    );
 
-    Ok(())
+    let mut state = ListState::default()
+        .with_offset(app.workspaces_offset.get())
+        .with_selected(
+            visible
+                .iter()
+                .position(|idx| *idx == app.selected_workspace),
+        );
+    frame.render_stateful_widget(widget, area, &mut state);
+    app.workspaces_offset.set(state.offset());
 }
 
-fn validate_var_files_for_execution(account: &AccountState) -> Result<()> {
-    let missing_files: Vec<String> = account
-        .var_files
+fn draw_output_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
+    let border_style = if app.focused_panel == FocusPanel::Output {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    app.output_page_rows.set(visible_rows);
+    let text: Vec<Line<'_>> = app
+        .output_lines
         .iter()
-        .filter(|path| !path.exists())
-        .map(|path| path.display().to_string())
+        .filter(|entry| app.output_filter.is_none() || entry.source == app.output_filter)
+        .flat_map(|entry| {
+            let mut lines = if !entry.is_foldable() {
+                vec![styled_output_line(&entry.text)]
+            } else if entry.expanded {
+                let chars: Vec<char> = entry.text.chars().collect();
+                chars
+                    .chunks(OUTPUT_WRAP_WIDTH)
+                    .map(|chunk| styled_output_line(&chunk.iter().collect::<String>()))
+                    .collect()
+            } else {
+                let preview: String = entry.text.chars().take(OUTPUT_FOLD_PREVIEW_CHARS).collect();
+                let mut line = styled_output_line(&preview);
+                line.spans.push(Span::styled(
+                    format!(
+                        " [… {}KB, press enter to expand]",
+                        entry.text.len().div_ceil(1024)
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                vec![line]
+            };
+            if let Some(source) = entry.source
+                && app.output_filter.is_none()
+                && let Some(first) = lines.first_mut()
+            {
+                first.spans.insert(
+                    0,
+                    Span::styled(
+                        format!("[{}] ", app.output_sources[source]),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                );
+            }
+            lines
+        })
         .collect();
+    let total_lines = text.len();
+    let max_scroll_from_bottom = total_lines.saturating_sub(visible_rows);
+    let from_bottom = app.output_scroll_from_bottom.min(max_scroll_from_bottom);
+    let scroll_from_top = max_scroll_from_bottom.saturating_sub(from_bottom);
 
-    if missing_files.is_empty() {
-        Ok(())
+    let view = match app.output_filter {
+        Some(source) => format!("Output: {}", app.output_sources[source]),
+        None => "Output".to_string(),
+    };
+    let view = app.icons.panel_title(FocusPanel::Output, &view);
+    let output_title = if from_bottom == 0 {
+        view
     } else {
-        Err(eyre!(
-            "Configured var_files are missing for `{}`: {}",
-            account.name,
-            missing_files.join(", ")
-        ))
-    }
+        format!("{view} (scroll +{from_bottom})")
+    };
+
+    let widget = Paragraph::new(text)
+        .scroll((scroll_from_top as u16, 0))
+        .block(
+            Block::default()
+                .title(output_title)
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+    frame.render_widget(widget, area);
 }
 
-fn validate_operation_preflight(account: &AccountState, kind: OperationKind) -> Result<()> {
-    validate_composition_for_execution(account)?;
+fn styled_output_line(line: &str) -> Line<'static> {
+    let trimmed = line.trim_start();
 
-    if matches!(
-        kind,
-        OperationKind::TerraformPlan | OperationKind::TerraformApply
-    ) && !account.var_files.is_empty()
+    let style = if trimmed.starts_with("DANGER:") {
+        Style::default()
+            .fg(Color::White)
+            .bg(Color::Red)
+            .add_modifier(Modifier::BOLD)
+    } else if trimmed.contains("Error:") {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    } else if trimmed.contains("Warning:") {
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    } else if trimmed.starts_with('+') {
+        Style::default().fg(Color::Green)
+    } else if trimmed.starts_with('~') {
+        Style::default().fg(Color::Yellow)
+    } else if trimmed.starts_with('-') {
+        Style::default().fg(Color::Red)
+    } else if trimmed.starts_with("Replacements and why:") {
+        Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD)
+    } else if trimmed.starts_with("Plan:") {
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD)
+    } else if trimmed.starts_with("Apply complete!") || trimmed.starts_with("No changes.") {
+        Style::default()
+            .fg(Color::Green)
+            .add_modifier(Modifier::BOLD)
+    } else if trimmed.starts_with("Running `")
+        || trimmed.starts_with("Using var files:")
+        || trimmed.starts_with("Using variables:")
     {
-        validate_var_files_for_execution(account)?;
-    }
+        Style::default().fg(Color::Blue)
+    } else {
+        Style::default()
+    };
 
-    Ok(())
+    Line::from(Span::styled(line.to_string(), style))
 }
 
-fn append_var_file_args(args: &mut Vec<String>, var_files: &[PathBuf]) {
-    for var_file in var_files {
-        args.push(format!("-var-file={}", var_file.display()));
-    }
-}
+fn draw_apply_confirmation(frame: &mut ratatui::Frame<'_>, app: &AppState) {
+    let stateful = app.pending_stateful_changes();
+    let height = if stateful.is_empty() { 30 } else { 50 };
+    let area = centered_rect(65, height, frame.area());
+    frame.render_widget(Clear, area);
 
-fn terraform_base_command(account: &AccountState) -> Command {
-    let mut command = Command::new("terraform");
-    command.current_dir(&account.composition_path);
-    command.env("AWS_PROFILE", &account.aws_profile);
-    command.env("AWS_SDK_LOAD_CONFIG", "1");
-    command.env("TF_IN_AUTOMATION", "1");
+    let account = app.selected_account();
+    let workspace = app.selected_workspace_name().unwrap_or_default();
+    let color = account
+        .and_then(|account| account.color)
+        .unwrap_or(Color::Yellow);
+    let bold = |color: Color| Style::default().fg(color).add_modifier(Modifier::BOLD);
 
-    if let Some(region) = &account.region {
-        command.env("AWS_REGION", region);
-        command.env("AWS_DEFAULT_REGION", region);
+    let mut lines = vec![Line::from(vec![
+        Span::raw("Apply to "),
+        Span::styled(
+            format!(
+                "{}/{workspace}",
+                account
+                    .map(|account| account.name.as_str())
+                    .unwrap_or_default()
+            ),
+            bold(color),
+        ),
+    ])];
+    let summary = app.plan_summary.as_ref().filter(|summary| {
+        summary.account_idx == app.selected_account
+            && summary.workspace == workspace
+            && !summary.destroy_preview
+    });
+    match summary {
+        Some(summary) => {
+            let mut counts = vec![Span::raw("Plan: ")];
+            if summary.import > 0 {
+                counts.push(Span::styled(
+                    format!("{} to import, ", summary.import),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            for (count, suffix, count_color) in [
+                (summary.add, "add, ", Color::Green),
+                (
+                    summary.change.saturating_sub(summary.noisy),
+                    "change, ",
+                    Color::Yellow,
+                ),
+                (summary.destroy, "destroy", Color::Red),
+            ] {
+                let style = if count > 0 {
+                    bold(count_color)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                counts.push(Span::styled(format!("{count} to {suffix}"), style));
+            }
+            if summary.noisy > 0 {
+                counts.push(Span::styled(
+                    format!(" ({} noisy)", summary.noisy),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            lines.push(Line::from(counts));
+            if summary.destroy > 0 {
+                lines.push(Line::from(Span::styled(
+                    format!("This apply destroys {} resource(s).", summary.destroy),
+                    bold(Color::Red),
+                )));
+            }
+        }
+        None => lines.push(Line::from(Span::styled(
+            "No plan counts recorded for this workspace; review the plan output first.",
+            Style::default().fg(Color::DarkGray),
+        ))),
     }
+    if !stateful.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!(
+                " DANGER: {} stateful resource(s) deleted or replaced ",
+                stateful.len()
+            ),
+            bold(Color::White).bg(Color::Red),
+        )));
+        lines.extend(
+            stateful
+                .into_iter()
+                .map(|line| Line::from(Span::styled(line, Style::default().fg(Color::Red)))),
+        );
+        lines.push(Line::from(Span::styled(
+            "After `y` you must type the workspace name to acknowledge.",
+            bold(Color::Red),
+        )));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(format!(
+            "Press `y` to run {}",
+            account
+                .map(|account| OperationKind::TerraformApply.label_for(account))
+                .unwrap_or_else(|| OperationKind::TerraformApply.label().to_string())
+        )),
+        Line::from("Press `T` to schedule it for a maintenance window"),
+        Line::from("Use any navigation key to cancel"),
+    ]);
 
-    command
-}
-
-fn terraform_command(account: &AccountState, args: &[&str]) -> Command {
-    let mut command = terraform_base_command(account);
-    command.args(args);
-    command
-}
-
-fn terraform_command_owned(account: &AccountState, args: &[String]) -> Command {
-    let mut command = terraform_base_command(account);
-    command.args(args);
-    command
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title("Confirm apply")
+            .borders(Borders::ALL)
+            .border_style(bold(color)),
+    );
+    frame.render_widget(popup, area);
 }
 
-async fn run_streaming_command(
-    mut command: Command,
-    mut cancel_rx: watch::Receiver<CancelSignal>,
-    event_tx: mpsc::UnboundedSender<WorkerEvent>,
-) -> Result<RunOutcome> {
-    command.stdout(Stdio::piped());
-    command.stderr(Stdio::piped());
-
-    let mut child = command.spawn().wrap_err("Failed to spawn command")?;
-
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| eyre!("Command stdout was not piped"))?;
-    let stderr = child
-        .stderr
-        .take()
-        .ok_or_else(|| eyre!("Command stderr was not piped"))?;
+fn draw_history_search(frame: &mut ratatui::Frame<'_>, search: &HistorySearch) {
+    let area = centered_rect(90, 75, frame.area());
+    frame.render_widget(Clear, area);
 
-    let tx_stdout = event_tx.clone();
-    let tx_stderr = event_tx.clone();
+    let lines: Vec<Line<'_>> = if search.results.is_empty() {
+        vec![Line::from(format!(
+            "No recorded plan/apply touched a resource matching `{}`.",
+            search.query
+        ))]
+    } else {
+        search
+            .results
+            .iter()
+            .skip(search.scroll)
+            .map(|entry| {
+                Line::from(vec![
+                    Span::styled(
+                        format_timestamp(entry.timestamp),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw("  "),
+                    Span::styled(
+                        format!("{:<7}", entry.action.label()),
+                        Style::default().fg(entry.action.color()),
+                    ),
+                    Span::raw(format!(
+                        " {}  {}/{}  {} ({}) by {}",
+                        entry.address,
+                        entry.account,
+                        entry.workspace,
+                        entry.operation,
+                        entry.outcome,
+                        entry.operator
+                    )),
+                ])
+            })
+            .collect()
+    };
 
-    let stdout_task = tokio::spawn(async move { stream_reader(stdout, tx_stdout).await });
-    let stderr_task = tokio::spawn(async move { stream_reader(stderr, tx_stderr).await });
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                "Resource history: `{}` ({} matches, j/k scroll, esc close)",
+                search.query,
+                search.results.len()
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
+}
 
-    let mut cancelled = false;
-    let mut sigint_sent = false;
-    let mut force_kill_sent = false;
+fn draw_plan_review(frame: &mut ratatui::Frame<'_>, app: &AppState, review: &PlanReview) {
+    let area = centered_rect(90, 85, frame.area());
+    frame.render_widget(Clear, area);
 
-    let status = loop {
-        tokio::select! {
-            child_status = child.wait() => {
-                break child_status.wrap_err("Failed while waiting for command")?;
-            }
-            changed = cancel_rx.changed() => {
-                if changed.is_ok() {
-                    match *cancel_rx.borrow() {
-                        CancelSignal::None => {}
-                        CancelSignal::Graceful => {
-                            cancelled = true;
-                            if !sigint_sent {
-                                if let Some(pid) = child.id() {
-                                    send_sigint(pid)?;
-                                    let _ = event_tx.send(WorkerEvent::OutputLine("Sent SIGINT to running command.".to_string()));
-                                }
-                                sigint_sent = true;
-                            }
-                        }
-                        CancelSignal::Force => {
-                            cancelled = true;
-                            if !force_kill_sent {
-                                let _ = event_tx.send(WorkerEvent::OutputLine("Force kill signal sent to running command.".to_string()));
-                                let _ = child.start_kill();
-                                force_kill_sent = true;
-                            }
+    let modules = review.modules();
+    let rows = review.rows();
+    let diff_lines: BTreeMap<usize, Vec<DiffLine>> = review
+        .expanded
+        .iter()
+        .map(|idx| (*idx, review.diff_lines(*idx)))
+        .collect();
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let offset = review
+        .selected
+        .saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line<'_>> = rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible_rows)
+        .map(|(idx, row)| {
+            let marker = if idx == review.selected { ">" } else { " " };
+            match row {
+                PlanReviewRow::Module(module) => {
+                    let members = modules.get(module.as_str()).cloned().unwrap_or_default();
+                    let fold = if review.collapsed_modules.contains(module) {
+                        "+"
+                    } else {
+                        "-"
+                    };
+                    let name = if module.is_empty() {
+                        "(root module)"
+                    } else {
+                        module.as_str()
+                    };
+                    let mut spans = vec![Span::styled(
+                        format!("{marker} [{fold}] {name} ({})", members.len()),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )];
+                    for action in [
+                        ChangeAction::Create,
+                        ChangeAction::Update,
+                        ChangeAction::Replace,
+                        ChangeAction::Delete,
+                        ChangeAction::Read,
+                    ] {
+                        let count = members
+                            .iter()
+                            .filter(|member| review.changes[**member].action == action)
+                            .count();
+                        if count > 0 {
+                            spans.push(Span::styled(
+                                format!("  {count} {}", action.label()),
+                                Style::default().fg(action.color()),
+                            ));
                         }
                     }
+                    Line::from(spans)
+                }
+                PlanReviewRow::Resource(change_idx) => {
+                    let change = &review.changes[*change_idx];
+                    let fold = if change.attributes.is_empty() {
+                        " "
+                    } else if review.expanded.contains(change_idx) {
+                        "-"
+                    } else {
+                        "+"
+                    };
+                    let mut spans = vec![
+                        Span::raw(format!("{marker}   {fold} ")),
+                        Span::styled(
+                            format!("{:<7}", change.action.label()),
+                            Style::default().fg(change.action.color()),
+                        ),
+                        Span::raw(format!(" {}", change.address)),
+                    ];
+                    if change.action == ChangeAction::Replace {
+                        spans.push(Span::styled(
+                            format!("  {}", replace_reasons_label(&change.replace_reasons)),
+                            Style::default()
+                                .fg(ChangeAction::Replace.color())
+                                .add_modifier(Modifier::BOLD),
+                        ));
+                    }
+                    if change.is_noise_only() {
+                        spans.push(Span::styled(
+                            "  (noise only)",
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    Line::from(spans)
+                }
+                PlanReviewRow::Attribute(change_idx, line) => {
+                    let Some(diff_line) = diff_lines
+                        .get(change_idx)
+                        .and_then(|lines| lines.get(*line))
+                    else {
+                        return Line::from(marker);
+                    };
+                    let color = |marker: char| match marker {
+                        '+' => Color::Green,
+                        '-' => Color::Red,
+                        ' ' => Color::Gray,
+                        _ => Color::Yellow,
+                    };
+                    match diff_line {
+                        DiffLine::Text(diff_marker, text) => Line::from(vec![
+                            Span::raw(format!("{marker}       ")),
+                            Span::styled(
+                                format!("{diff_marker} {text}"),
+                                Style::default().fg(color(*diff_marker)),
+                            ),
+                        ]),
+                        DiffLine::Columns(diff_marker, old, new) => Line::from(vec![
+                            Span::raw(format!("{marker}         ")),
+                            Span::styled(
+                                format!(
+                                    "{:<width$}",
+                                    old.chars().take(SIDE_BY_SIDE_COLUMN).collect::<String>(),
+                                    width = SIDE_BY_SIDE_COLUMN
+                                ),
+                                Style::default().fg(match diff_marker {
+                                    '+' => Color::DarkGray,
+                                    ' ' => Color::Gray,
+                                    _ => Color::Red,
+                                }),
+                            ),
+                            Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
+                            Span::styled(
+                                new.clone(),
+                                Style::default().fg(match diff_marker {
+                                    '-' => Color::DarkGray,
+                                    ' ' => Color::Gray,
+                                    _ => Color::Green,
+                                }),
+                            ),
+                        ]),
+                    }
                 }
             }
-        }
-    };
-
-    let _ = stdout_task.await;
-    let _ = stderr_task.await;
+        })
+        .collect();
 
-    Ok(RunOutcome {
-        success: status.success(),
-        cancelled,
-        exit_code: status.code(),
-    })
+    let account_name = app
+        .accounts
+        .get(review.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or_default();
+    let focus = match &review.focus {
+        Some(focus) => format!(
+            " focus {} ({} of {} changes, f shows all)",
+            if focus.is_empty() {
+                "(root module)"
+            } else {
+                focus.as_str()
+            },
+            modules.values().map(Vec::len).sum::<usize>(),
+            review.changes.len()
+        ),
+        None => String::new(),
+    };
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                "Plan review {account_name}/{}{focus} (enter/space fold, h/l collapse/expand, -/+ all, f focus module, s {}, esc close)",
+                review.workspace,
+                match review.layout {
+                    DiffLayout::Unified => "side-by-side",
+                    DiffLayout::SideBySide => "unified",
+                }
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
 }
 
-async fn stream_reader<R>(reader: R, event_tx: mpsc::UnboundedSender<WorkerEvent>) -> Result<()>
-where
-    R: tokio::io::AsyncRead + Unpin,
-{
-    let mut lines = BufReader::new(reader).lines();
-    while let Some(line) = lines.next_line().await? {
-        let _ = event_tx.send(WorkerEvent::OutputLine(line));
-    }
-    Ok(())
-}
+fn draw_plan_changes(frame: &mut ratatui::Frame<'_>, app: &AppState, view: &PlanChangesView) {
+    let area = centered_rect(85, 75, frame.area());
+    frame.render_widget(Clear, area);
 
-#[cfg(unix)]
-fn send_sigint(pid: u32) -> Result<()> {
-    use nix::sys::signal::{Signal, kill};
-    use nix::unistd::Pid;
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let offset = view.selected.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line<'_>> = view
+        .changes
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(idx, change)| {
+            let marker = if idx == view.selected { ">" } else { " " };
+            let mut spans = vec![
+                Span::raw(format!("{marker} ")),
+                Span::styled(
+                    format!("{:<7}", change.action.label()),
+                    Style::default().fg(change.action.color()),
+                ),
+                Span::raw(format!(" {}", change.address)),
+            ];
+            if change.action == ChangeAction::Replace {
+                spans.push(Span::styled(
+                    format!("  {}", replace_reasons_label(&change.replace_reasons)),
+                    Style::default()
+                        .fg(ChangeAction::Replace.color())
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            if change.is_noise_only() {
+                spans.push(Span::styled(
+                    "  (noise only)",
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if let Some(provider) = &change.provider {
+                spans.push(Span::styled(
+                    format!("  [{provider}]"),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if let Some(blame) = view.blame.get(&change.address) {
+                spans.push(Span::styled(
+                    format!("  {blame}"),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
 
-    let pid_i32 = i32::try_from(pid).wrap_err("child PID overflowed i32")?;
-    kill(Pid::from_raw(pid_i32), Signal::SIGINT).wrap_err("failed to send SIGINT")?;
-    Ok(())
+    let account_name = app
+        .accounts
+        .get(view.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or_default();
+    let kind = if app.destroy_preview_open {
+        "Destroy preview"
+    } else {
+        "Plan changes"
+    };
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                "{kind} {account_name}/{} (enter view source, e open in $EDITOR, esc close)",
+                view.workspace
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
 }
 
-#[cfg(not(unix))]
-fn send_sigint(_pid: u32) -> Result<()> {
-    Ok(())
+fn ci_status_color(status: &str) -> Color {
+    match status {
+        "success" | "passed" => Color::Green,
+        "failure" | "failed" | "timed_out" | "startup_failure" => Color::Red,
+        "in_progress" | "running" | "queued" | "pending" | "waiting" | "requested" => Color::Yellow,
+        "manual" | "action_required" => Color::Magenta,
+        _ => Color::DarkGray,
+    }
 }
 
-fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &AppState) {
-    let root = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(1),
-            Constraint::Min(10),
-            Constraint::Length(2),
-        ])
-        .split(frame.area());
-
-    let title = Line::from(vec![
-        Span::styled(
-            " lazytf ",
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Span::raw(format!(
-            "| {} | mode: {} | focus: {:?}",
-            app.current_operation_label(),
-            app.layout_mode.label(),
-            app.focused_panel
-        )),
-    ]);
-    frame.render_widget(title, root[0]);
+fn draw_ci_runs(frame: &mut ratatui::Frame<'_>, app: &AppState, view: &CiRunsView) {
+    let area = centered_rect(85, 70, frame.area());
+    frame.render_widget(Clear, area);
 
-    if app.is_output_only() {
-        draw_output_only_layout(frame, app, root[1]);
-    } else {
-        draw_split_layout(frame, app, root[1]);
+    let mut lines: Vec<Line<'_>> = Vec::new();
+    if view.runs.is_empty() {
+        lines.push(Line::from(if view.loading {
+            "Loading runs..."
+        } else {
+            "No runs found."
+        }));
+    }
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let offset = view.selected.saturating_sub(visible_rows.saturating_sub(1));
+    for (row_idx, (run_idx, job_idx)) in view.rows().into_iter().enumerate().skip(offset) {
+        let marker = if row_idx == view.selected { ">" } else { " " };
+        let run = &view.runs[run_idx];
+        let line = match job_idx {
+            None => Line::from(vec![
+                Span::raw(format!("{marker} #{:<5} ", run.number)),
+                Span::styled(
+                    format!("{:<12}", run.status),
+                    Style::default().fg(ci_status_color(&run.status)),
+                ),
+                Span::raw(format!(
+                    " {}  {} {}  {}",
+                    run.created_at, run.branch, run.sha, run.title
+                )),
+            ]),
+            Some(job_idx) => {
+                let job = &run.jobs.as_ref().expect("expanded runs have jobs")[job_idx];
+                Line::from(vec![
+                    Span::raw(format!("{marker}        ")),
+                    Span::styled(
+                        format!("{:<12}", job.status),
+                        Style::default().fg(ci_status_color(&job.status)),
+                    ),
+                    Span::raw(format!(" {}", job.name)),
+                ])
+            }
+        };
+        lines.push(if row_idx == view.selected {
+            line.style(Style::default().add_modifier(Modifier::BOLD))
+        } else {
+            line
+        });
     }
 
-    let help = if app.is_output_only() {
-        vec![
-            Line::from(
-                "z/esc:exit fullscreen  ?:help  pgup/pgdn g/G mouse:scroll  c:cancel (again=force)  q:quit",
-            ),
-            Line::from("output-only mode for plan review"),
-        ]
-    } else {
-        vec![
-            Line::from(
-                "j/k or arrows: move  tab/h/l: panel  z:fullscreen output  ?:help  a:aws login  s:auth check  r:workspaces",
-            ),
-            Line::from(
-                "i:init  p:plan  A then y:apply  c:cancel (again=force)  q:quit  pgup/pgdn g/G/mouse:output scroll",
-            ),
-        ]
-    };
-    frame.render_widget(Paragraph::new(help), root[2]);
+    let account_name = app
+        .accounts
+        .get(view.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or_default();
+    let loading = if view.loading { " loading..." } else { "" };
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                "CI {} for {account_name}{loading} (enter jobs/log, p start manual job, r reload, esc close)",
+                view.source
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
+}
 
-    if app.pending_apply_confirmation {
-        draw_apply_confirmation(frame);
-    }
+fn draw_lock_table(frame: &mut ratatui::Frame<'_>, app: &AppState, view: &LockTableView) {
+    let area = centered_rect(85, 60, frame.area());
+    frame.render_widget(Clear, area);
 
-    if app.show_help {
-        draw_help_modal(frame);
+    let mut lines: Vec<Line<'_>> = Vec::new();
+    if view.locks.is_empty() {
+        lines.push(Line::from("No locks are currently held."));
+    }
+    for (idx, lock) in view.locks.iter().enumerate() {
+        let marker = if idx == view.selected { ">" } else { " " };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{marker} ")),
+            Span::styled(
+                lock.who.clone(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("  {}  since {}", lock.operation, lock.created)),
+        ]));
+        if idx == view.selected {
+            lines.push(Line::from(Span::styled(
+                format!("    path: {}", lock.path),
+                Style::default().fg(Color::DarkGray),
+            )));
+            lines.push(Line::from(Span::styled(
+                format!("    lock ID: {}", lock.id),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
     }
-}
-
-fn draw_split_layout(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
-    let columns = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(28),
-            Constraint::Percentage(28),
-            Constraint::Percentage(44),
-        ])
-        .split(area);
 
-    draw_accounts_panel(frame, app, columns[0]);
-    draw_workspaces_panel(frame, app, columns[1]);
-    draw_output_panel(frame, app, columns[2]);
+    let account_name = app
+        .accounts
+        .get(view.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or_default();
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(
+                "Locks in {} for {account_name} (j/k select, esc close)",
+                view.table
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
 }
 
-fn draw_output_only_layout(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
-    draw_output_panel(frame, app, area);
-}
+fn draw_state_browser(frame: &mut ratatui::Frame<'_>, app: &AppState, browser: &StateBrowser) {
+    let area = centered_rect(80, 75, frame.area());
+    frame.render_widget(Clear, area);
 
-fn draw_accounts_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
-    let border_style = if app.focused_panel == FocusPanel::Accounts {
-        Style::default().fg(Color::Cyan)
-    } else {
-        Style::default()
-    };
+    if let Some(detail) = &browser.detail {
+        let lines: Vec<Line<'_>> = detail.lines.iter().skip(detail.scroll).cloned().collect();
+        let popup = Paragraph::new(lines).block(
+            Block::default()
+                .title(format!(
+                    "{} (j/k pgup/pgdn scroll, esc back to list)",
+                    detail.address
+                ))
+                .borders(Borders::ALL)
+                .border_style(
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+        );
+        frame.render_widget(popup, area);
+        return;
+    }
 
-    let items: Vec<ListItem<'_>> = app
-        .accounts
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let offset = browser
+        .selected
+        .saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line<'_>> = browser
+        .resources
         .iter()
         .enumerate()
-        .map(|(idx, account)| {
-            let selected = if idx == app.selected_account {
-                ">"
+        .skip(offset)
+        .map(|(idx, address)| {
+            let marker = if idx == browser.selected { ">" } else { " " };
+            let mark = if browser.marked.contains(address) {
+                "[x]"
             } else {
-                " "
+                "[ ]"
             };
-            let line = Line::from(vec![
-                Span::raw(format!("{selected} ")),
-                Span::styled(
-                    account.auth.icon(),
-                    Style::default().fg(account.auth.color()),
-                ),
-                Span::raw(format!(" {} [{}]", account.name, account.auth.label())),
-            ]);
-            ListItem::new(line)
+            let style = if idx == browser.selected {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Line::from(Span::styled(format!("{marker} {mark} {address}"), style))
         })
         .collect();
 
-    let widget = List::new(items).block(
+    let account_name = app
+        .accounts
+        .get(browser.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or_default();
+    let popup = Paragraph::new(lines).block(
         Block::default()
-            .title("Accounts")
+            .title(format!(
+                "State {account_name}/{} ({} resources; space mark, p/A targeted plan, enter details, i impact, m mv, t taint, u untaint, d rm, esc close)",
+                browser.workspace,
+                browser.resources.len()
+            ))
             .borders(Borders::ALL)
-            .border_style(border_style),
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
     );
+    frame.render_widget(popup, area);
+}
 
-    frame.render_widget(widget, area);
+fn draw_notes(frame: &mut ratatui::Frame<'_>, notes: &FileView) {
+    let area = centered_rect(80, 80, frame.area());
+    frame.render_widget(Clear, area);
+    let lines: Vec<Line<'_>> = notes.lines.iter().skip(notes.scroll).cloned().collect();
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(format!(
+                "{} (j/k pgup/pgdn scroll, esc close)",
+                notes.path.display()
+            ))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
 }
 
-fn draw_workspaces_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
-    let border_style = if app.focused_panel == FocusPanel::Workspaces {
-        Style::default().fg(Color::Cyan)
-    } else {
-        Style::default()
-    };
+fn draw_file_browser(frame: &mut ratatui::Frame<'_>, browser: &FileBrowser) {
+    let area = centered_rect(90, 85, frame.area());
+    frame.render_widget(Clear, area);
+    let border_style = Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD);
 
-    let items: Vec<ListItem<'_>> = if let Some(account) = app.selected_account() {
-        if account.workspaces.is_empty() {
-            vec![ListItem::new("  (no workspaces loaded)")]
-        } else {
-            account
-                .workspaces
-                .iter()
-                .enumerate()
-                .map(|(idx, workspace)| {
-                    let selected = if idx == app.selected_workspace {
-                        ">"
-                    } else {
-                        " "
-                    };
-                    ListItem::new(format!("{selected} {workspace}"))
-                })
-                .collect()
-        }
+    if let Some(view) = &browser.viewer {
+        let width = view.lines.len().to_string().len();
+        let lines: Vec<Line<'_>> = view
+            .lines
+            .iter()
+            .enumerate()
+            .skip(view.scroll)
+            .map(|(idx, line)| {
+                let mut numbered = line.clone();
+                numbered.spans.insert(
+                    0,
+                    Span::styled(
+                        format!("{:>width$} ", idx + 1),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                );
+                numbered
+            })
+            .collect();
+        let relative = view.path.strip_prefix(&browser.root).unwrap_or(&view.path);
+        let popup = Paragraph::new(lines).block(
+            Block::default()
+                .title(format!(
+                    "{} (read-only, j/k pgup/pgdn scroll, esc back)",
+                    relative.display()
+                ))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        frame.render_widget(popup, area);
+        return;
+    }
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let offset = browser
+        .selected
+        .saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line<'_>> = if browser.files.is_empty() {
+        vec![Line::from(
+            "No .tf/.tfvars/.hcl files in the composition directory.",
+        )]
     } else {
-        vec![ListItem::new("  (no account selected)")]
+        browser
+            .files
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .map(|(idx, file)| {
+                if idx == browser.selected {
+                    Line::from(Span::styled(
+                        format!("> {}", file.display()),
+                        Style::default()
+                            .fg(Color::Cyan)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                } else {
+                    Line::from(format!("  {}", file.display()))
+                }
+            })
+            .collect()
     };
-
-    let widget = List::new(items).block(
+    let popup = Paragraph::new(lines).block(
         Block::default()
-            .title("Workspaces")
+            .title(format!(
+                "Files in {} (enter open, esc close)",
+                browser.root.display()
+            ))
             .borders(Borders::ALL)
             .border_style(border_style),
     );
-
-    frame.render_widget(widget, area);
+    frame.render_widget(popup, area);
 }
 
-fn draw_output_panel(frame: &mut ratatui::Frame<'_>, app: &AppState, area: Rect) {
-    let border_style = if app.focused_panel == FocusPanel::Output {
-        Style::default().fg(Color::Cyan)
+fn draw_cleanup_prompt(frame: &mut ratatui::Frame<'_>, app: &AppState, session: &CleanupSession) {
+    let Some(candidate) = session.candidates.get(session.current) else {
+        return;
+    };
+    let account_name = app
+        .accounts
+        .get(session.account_idx)
+        .map(|account| account.name.as_str())
+        .unwrap_or("?");
+
+    let area = centered_rect(70, 30, frame.area());
+    frame.render_widget(Clear, area);
+    let action = if candidate.resources == 0 {
+        "delete this empty workspace".to_string()
     } else {
-        Style::default()
+        format!(
+            "destroy {} resources and delete this workspace",
+            candidate.resources
+        )
     };
+    let popup = Paragraph::new(vec![
+        Line::from(format!(
+            "Candidate {}/{}: `{}` in `{account_name}`",
+            session.current + 1,
+            session.candidates.len(),
+            candidate.workspace
+        )),
+        Line::from(format!("Reason: {}", candidate.reason)),
+        Line::from(""),
+        Line::from(format!("Press `y` to {action}")),
+        Line::from("Press `s` to skip, Esc to stop the cleanup"),
+    ])
+    .block(
+        Block::default()
+            .title("Workspace cleanup")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+    );
+    frame.render_widget(popup, area);
+}
 
-    let visible_rows = area.height.saturating_sub(2) as usize;
-    let total_lines = app.output_lines.len();
-    let max_scroll_from_bottom = total_lines.saturating_sub(visible_rows);
-    let from_bottom = app.output_scroll_from_bottom.min(max_scroll_from_bottom);
-    let scroll_from_top = max_scroll_from_bottom.saturating_sub(from_bottom);
+fn draw_state_metrics(frame: &mut ratatui::Frame<'_>, view: &StateMetricsView) {
+    let area = centered_rect(85, 70, frame.area());
+    frame.render_widget(Clear, area);
 
-    let text: Vec<Line<'_>> = app
-        .output_lines
+    let block = Block::default()
+        .title(format!(
+            "State growth: {}/{} (esc close)",
+            view.account, view.workspace
+        ))
+        .borders(Borders::ALL)
+        .border_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if view.samples.is_empty() {
+        frame.render_widget(
+            Paragraph::new(
+                "No samples yet. State size and serial are recorded after each plan/apply.",
+            ),
+            inner,
+        );
+        return;
+    }
+
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Min(1)])
+        .split(inner);
+
+    let sizes: Vec<u64> = view
+        .samples
         .iter()
-        .map(|line| styled_output_line(line))
+        .map(|sample| sample.size_bytes)
         .collect();
+    let chart = Sparkline::default()
+        .block(
+            Block::default()
+                .title("size per sample")
+                .borders(Borders::BOTTOM),
+        )
+        .data(&sizes)
+        .style(Style::default().fg(Color::Cyan));
+    frame.render_widget(chart, sections[0]);
 
-    let output_title = if from_bottom == 0 {
-        "Output".to_string()
+    let mut previous_size: Option<u64> = None;
+    let mut lines: Vec<Line<'_>> = view
+        .samples
+        .iter()
+        .map(|sample| {
+            let delta = previous_size
+                .filter(|previous| *previous > 0)
+                .map(|previous| {
+                    format!(
+                        " ({:+.1}%)",
+                        (sample.size_bytes as f64 - previous as f64) * 100.0 / previous as f64
+                    )
+                })
+                .unwrap_or_default();
+            previous_size = Some(sample.size_bytes);
+            Line::from(format!(
+                "{}  serial {:>6}  {:>5} resources  {:>9}{delta}",
+                format_timestamp(sample.timestamp),
+                sample.serial,
+                sample.resources,
+                format_bytes(sample.size_bytes)
+            ))
+        })
+        .collect();
+    lines.reverse();
+    frame.render_widget(Paragraph::new(lines), sections[1]);
+}
+
+fn draw_text_prompt(frame: &mut ratatui::Frame<'_>, prompt: &TextPrompt) {
+    let area = centered_rect(65, 25, frame.area());
+    frame.render_widget(Clear, area);
+
+    let input = if matches!(prompt.purpose, PromptPurpose::MissingVariable { .. }) {
+        "*".repeat(prompt.input.chars().count())
     } else {
-        format!("Output (scroll +{from_bottom})")
+        prompt.input.clone()
     };
+    let mut lines = vec![
+        Line::from(prompt.hint.clone()),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Cyan)),
+            Span::raw(input),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        ]),
+    ];
+    if let Some(error) = &prompt.error {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
 
-    let widget = Paragraph::new(text)
-        .scroll((scroll_from_top as u16, 0))
-        .block(
-            Block::default()
-                .title(output_title)
-                .borders(Borders::ALL)
-                .border_style(border_style),
-        );
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title(prompt.title.clone())
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
+}
 
-    frame.render_widget(widget, area);
+fn draw_leftover_children(frame: &mut ratatui::Frame<'_>, children: &[ChildRecord]) {
+    let area = centered_rect(75, 40, frame.area());
+    frame.render_widget(Clear, area);
+
+    let mut lines = vec![
+        Line::from(format!(
+            "{} process(es) started by a previous lazytf session are still running:",
+            children.len()
+        )),
+        Line::from(""),
+    ];
+    lines.extend(children.iter().map(|child| {
+        Line::from(format!(
+            "  pid {}  started {}  {}",
+            child.pid,
+            format_timestamp(child.started_at),
+            child.command
+        ))
+    }));
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "k: kill them   a: adopt (watch until they exit)   Esc: leave them alone",
+    ));
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title("Leftover processes")
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
 }
 
-fn styled_output_line(line: &str) -> Line<'static> {
-    let trimmed = line.trim_start();
+fn draw_run_options(frame: &mut ratatui::Frame<'_>, popup: &RunOptionsPopup) {
+    let area = centered_rect(65, 45, frame.area());
+    frame.render_widget(Clear, area);
 
-    let style = if trimmed.contains("Error:") {
-        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
-    } else if trimmed.contains("Warning:") {
-        Style::default()
-            .fg(Color::Yellow)
-            .add_modifier(Modifier::BOLD)
-    } else if trimmed.starts_with('+') {
-        Style::default().fg(Color::Green)
-    } else if trimmed.starts_with('~') {
-        Style::default().fg(Color::Yellow)
-    } else if trimmed.starts_with('-') {
-        Style::default().fg(Color::Red)
-    } else if trimmed.starts_with("Plan:") {
-        Style::default()
-            .fg(Color::Cyan)
-            .add_modifier(Modifier::BOLD)
-    } else if trimmed.starts_with("Apply complete!") || trimmed.starts_with("No changes.") {
-        Style::default()
-            .fg(Color::Green)
-            .add_modifier(Modifier::BOLD)
-    } else if trimmed.starts_with("Running `") || trimmed.starts_with("Using var files:") {
-        Style::default().fg(Color::Blue)
+    let mut lines: Vec<Line<'_>> = RUN_OPTION_FIELDS
+        .iter()
+        .enumerate()
+        .map(|(idx, label)| {
+            let marker = if idx == popup.selected { ">" } else { " " };
+            let value = match (&popup.editing, idx) {
+                (Some(input), idx) if idx == popup.selected => format!("{input}_"),
+                (_, 6) => if popup.dont_ask { "yes" } else { "no" }.to_string(),
+                (_, idx) => run_option_value(&popup.tuning, idx),
+            };
+            let style = if idx == popup.selected {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Line::from(vec![
+                Span::styled(format!("{marker} {label:<30}"), style),
+                Span::styled(value, Style::default().fg(Color::Cyan)),
+            ])
+        })
+        .collect();
+    lines.push(Line::from(""));
+    if popup.kind == OperationKind::TerraformApply {
+        lines.push(Line::from(Span::styled(
+            "Refresh, targets and vars are fixed by the saved plan.",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    if let Some(error) = &popup.error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    lines.push(Line::from(if popup.editing.is_some() {
+        "Targets/vars are comma-separated. Enter to keep, Esc to discard."
     } else {
-        Style::default()
-    };
+        "enter/space: edit or toggle   y: run   esc: cancel"
+    }));
 
-    Line::from(Span::styled(line.to_string(), style))
+    let popup_widget = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!("{} options", popup.kind.label()))
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup_widget, area);
 }
 
-fn draw_apply_confirmation(frame: &mut ratatui::Frame<'_>) {
-    let area = centered_rect(65, 20, frame.area());
+fn draw_var_file_picker(frame: &mut ratatui::Frame<'_>, picker: &VarFilePicker) {
+    let area = centered_rect(65, 45, frame.area());
     frame.render_widget(Clear, area);
-    let popup = Paragraph::new(vec![
-        Line::from("Apply confirmation"),
-        Line::from(""),
-        Line::from("Press `y` to run terraform apply"),
-        Line::from("Use any navigation key to cancel"),
-    ])
-    .block(
+
+    let mut lines: Vec<Line<'_>> = picker
+        .files
+        .iter()
+        .enumerate()
+        .map(|(idx, file)| {
+            let marker = if idx == picker.selected { ">" } else { " " };
+            let check = if file.checked { "[x]" } else { "[ ]" };
+            let style = if idx == picker.selected {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![Span::styled(
+                format!("{marker} {check} {}", file.label),
+                style,
+            )];
+            if file.configured {
+                spans.push(Span::styled(
+                    "  (configured)",
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "enter/space: toggle   p: plan with the checked files   esc: cancel",
+    ));
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title("Var files for this run")
+            .borders(Borders::ALL)
+            .border_style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn draw_confirmation(frame: &mut ratatui::Frame<'_>, confirmation: &Confirmation) {
+    let area = centered_rect(65, 30, frame.area());
+    frame.render_widget(Clear, area);
+    let lines: Vec<Line<'_>> = confirmation
+        .lines
+        .iter()
+        .map(|line| Line::from(line.clone()))
+        .collect();
+    let popup = Paragraph::new(lines).block(
         Block::default()
-            .title("Confirm")
+            .title(confirmation.title.clone())
             .borders(Borders::ALL)
             .border_style(
                 Style::default()
@@ -1812,6 +18164,8 @@ fn draw_help_modal(frame: &mut ratatui::Frame<'_>) {
         Line::from(""),
         Line::from("Global:"),
         Line::from("  ?: toggle help   q: quit   Ctrl+C: graceful quit"),
+        Line::from("  Ctrl+R: reload the config file (accounts keep auth and workspaces)"),
+        Line::from("  Ctrl+B/Ctrl+F: scroll the output a full page up/down"),
         Line::from("  c: cancel running command (press again to force kill)"),
         Line::from(""),
         Line::from("Layout & Focus:"),
@@ -1821,10 +18175,64 @@ fn draw_help_modal(frame: &mut ratatui::Frame<'_>) {
         Line::from("Navigation:"),
         Line::from("  j/k or arrows: move selection   g/G or Home/End: output top/bottom"),
         Line::from("  PgUp/PgDn or mouse wheel: scroll output"),
+        Line::from("  Enter in Output: expand/fold the nearest long line (over 1KB) in view"),
+        Line::from("  Enter/space in Accounts: fold/unfold the account group under the cursor"),
+        Line::from(
+            "  / in Accounts: fuzzy-filter accounts as you type (Enter selects, Esc cancels)",
+        ),
+        Line::from(
+            "  / in Workspaces: fuzzy-filter workspaces (Enter keeps the filter, Esc clears)",
+        ),
+        Line::from("  m: release mouse for terminal text selection (any key resumes)"),
+        Line::from("  o: cycle output view (all operations, then one operation at a time)"),
         Line::from(""),
         Line::from("Actions:"),
-        Line::from("  a: aws sso login   s: auth check   r: refresh workspaces"),
+        Line::from("  a: aws sso login (or login_cmd)   s: auth check   r: refresh workspaces"),
         Line::from("  i: terraform init   p: terraform plan   A then y: terraform apply"),
+        Line::from("  A then T: schedule the apply for later   T: list/cancel scheduled applies"),
+        Line::from("  V: plan review tree: changes grouped by module with attribute diffs"),
+        Line::from("     f in the review focuses on the selected module and its submodules"),
+        Line::from("  E: full deploy: init, plan, then the apply confirmation; stops on failure"),
+        Line::from("  v: terraform validate (diagnostics with file:line)"),
+        Line::from("  I: terraform import wizard (address, ID, optional generated config)"),
+        Line::from("  D: refresh-only plan to inspect drift without proposing changes"),
+        Line::from("  x: destroy preview (terraform plan -destroy, never applied)"),
+        Line::from("  f: terraform fmt (shows the diff, then asks before rewriting)"),
+        Line::from("  F: browse and view composition .tf/.tfvars files"),
+        Line::from("  P: create a missing AWS SSO profile for the selected account"),
+        Line::from("  w: switch the selected account's role (apply needs its apply_role)"),
+        Line::from("  R: show the composition README / notes for the selected account"),
+        Line::from("  C: planned changes; enter jumps to the resource source, e opens $EDITOR"),
+        Line::from(
+            "  b: toggle the activity sidebar (background auth checks, prefetches, notifications)",
+        ),
+        Line::from("  B: backend hygiene report (S3 encryption/versioning, lock table)"),
+        Line::from("  L: list locks held in the DynamoDB lock table (who, when, operation)"),
+        Line::from(
+            "  S: state browser (terraform state list); enter details, m mv, t taint/replace, u untaint, d rm",
+        ),
+        Line::from("     space marks resources; p/A plan/apply only the marked ones with -target"),
+        Line::from(
+            "     i shows what depends on the resource (terraform graph) before replacing it",
+        ),
+        Line::from("  t: pick the var files (configured and *.tfvars found) for the next plan"),
+        Line::from("  J: attach a log file (tail -F) or !command output to the Output panel"),
+        Line::from(
+            "  Q: CI runs/pipelines of the account; enter lists jobs, enter on a job streams its log",
+        ),
+        Line::from("     p on a manual GitLab job (e.g. apply) starts it after confirmation"),
+        Line::from("  H: search resource change history   M: state size/serial history"),
+        Line::from("  O: scan for live resources missing from state (orphan_checks)"),
+        Line::from("  W: find empty/stale workspaces and clean them up one by one"),
+        Line::from("  $: refresh monthly cost of the selected workspace (infracost)"),
+        Line::from("  n: create a workspace (terraform workspace new) and select it"),
+        Line::from("  K: create the account's missing desired_workspaces in one go"),
+        Line::from("  d: delete the selected workspace (non-empty state needs its name typed)"),
+        Line::from("  N: new ephemeral environment   X: tear down ephemeral environment"),
+        Line::from("  u: lock/unlock the selected workspace for maintenance (refuses operations)"),
+        Line::from("  Y: print and copy `export AWS_PROFILE=... AWS_REGION=...` for another shell"),
+        Line::from("  U: force-unlock a state lock left behind by a cancelled run"),
+        Line::from("  Z: dismiss the new lazytf release notice in the title bar"),
     ];
 
     let popup = Paragraph::new(help_lines).block(
@@ -1872,13 +18280,42 @@ fn parse_cli_options() -> Result<CliOptions> {
                 })?;
                 options.config_path = Some(PathBuf::from(value));
             }
+            "--low-memory" => {
+                options.low_memory = true;
+            }
+            "--no-mouse" => {
+                options.no_mouse = true;
+            }
+            "approve" => {
+                let value = args.next().ok_or_else(|| {
+                    eyre!("Missing approval request path. Usage: lazytf approve <request-file>")
+                })?;
+                options.command = CliCommand::Approve {
+                    request_path: PathBuf::from(value),
+                };
+            }
+            "bench" => {
+                options.command = parse_bench_args(&mut args)?;
+            }
+            "sso" => {
+                options.command = parse_sso_args(&mut args)?;
+            }
+            "report" => {
+                options.command = parse_report_args(&mut args)?;
+            }
+            "validate-config" => {
+                options.command = CliCommand::ValidateConfig;
+            }
+            "init" => {
+                options.command = CliCommand::Init;
+            }
             "-h" | "--help" => {
                 print_usage();
                 std::process::exit(0);
             }
             _ => {
                 return Err(eyre!(
-                    "Unknown argument `{arg}`. Usage: lazytf [--config <path>]"
+                    "Unknown argument `{arg}`. Usage: lazytf [--config <path>] [--low-memory] [--no-mouse] | lazytf approve <request-file>"
                 ));
             }
         }
@@ -1887,14 +18324,121 @@ fn parse_cli_options() -> Result<CliOptions> {
     Ok(options)
 }
 
+fn parse_bench_args(args: &mut impl Iterator<Item = String>) -> Result<CliCommand> {
+    const USAGE: &str = "Usage: lazytf bench plan --account <name> [--workspace <name>]";
+
+    if args.next().as_deref() != Some("plan") {
+        return Err(eyre!("Only `plan` can be benchmarked. {USAGE}"));
+    }
+
+    let mut account = None;
+    let mut workspace = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--account" => {
+                account = Some(
+                    args.next()
+                        .ok_or_else(|| eyre!("Missing value for --account. {USAGE}"))?,
+                );
+            }
+            "--workspace" => {
+                workspace = Some(
+                    args.next()
+                        .ok_or_else(|| eyre!("Missing value for --workspace. {USAGE}"))?,
+                );
+            }
+            _ => return Err(eyre!("Unknown bench argument `{arg}`. {USAGE}")),
+        }
+    }
+
+    Ok(CliCommand::BenchPlan {
+        account: account.ok_or_else(|| eyre!("Missing --account. {USAGE}"))?,
+        workspace,
+    })
+}
+
+fn parse_report_args(args: &mut impl Iterator<Item = String>) -> Result<CliCommand> {
+    const USAGE: &str = "Usage: lazytf report --output html [--file <path>]";
+
+    let mut format = None;
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => {
+                format = Some(
+                    args.next()
+                        .ok_or_else(|| eyre!("Missing value for --output. {USAGE}"))?,
+                );
+            }
+            "--file" => {
+                file = Some(PathBuf::from(
+                    args.next()
+                        .ok_or_else(|| eyre!("Missing value for --file. {USAGE}"))?,
+                ));
+            }
+            _ => return Err(eyre!("Unknown report argument `{arg}`. {USAGE}")),
+        }
+    }
+
+    match format.as_deref() {
+        Some("html") => Ok(CliCommand::Report { file }),
+        Some(other) => Err(eyre!("Unsupported report format `{other}`. {USAGE}")),
+        None => Err(eyre!("Missing --output. {USAGE}")),
+    }
+}
+
+fn parse_sso_args(args: &mut impl Iterator<Item = String>) -> Result<CliCommand> {
+    const USAGE: &str = "Usage: lazytf sso generate --start-url <url> --sso-region <region> [--region <region>] [--composition-path <template>]";
+
+    if args.next().as_deref() != Some("generate") {
+        return Err(eyre!("Unknown sso command. {USAGE}"));
+    }
+
+    let mut start_url = None;
+    let mut sso_region = None;
+    let mut region = None;
+    let mut composition_path = "./{account}".to_string();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| eyre!("Missing value for {arg}. {USAGE}"))?;
+        match arg.as_str() {
+            "--start-url" => start_url = Some(value),
+            "--sso-region" => sso_region = Some(value),
+            "--region" => region = Some(value),
+            "--composition-path" => composition_path = value,
+            _ => return Err(eyre!("Unknown sso argument `{arg}`. {USAGE}")),
+        }
+    }
+
+    Ok(CliCommand::SsoGenerate {
+        start_url: start_url.ok_or_else(|| eyre!("Missing --start-url. {USAGE}"))?,
+        sso_region: sso_region.ok_or_else(|| eyre!("Missing --sso-region. {USAGE}"))?,
+        region,
+        composition_path,
+    })
+}
+
 fn print_usage() {
     println!("lazytf - terminal UI for Terraform workflows");
     println!();
     println!("Usage:");
-    println!("  lazytf [--config <path>]");
+    println!("  lazytf [--config <path>] [--low-memory] [--no-mouse]");
+    println!("  lazytf approve <request-file>");
+    println!("  lazytf bench plan --account <name> [--workspace <name>]");
+    println!("  lazytf report --output html [--file <path>]");
+    println!("  lazytf [--config <path>] validate-config");
+    println!("  lazytf [--config <path>] init");
+    println!(
+        "  lazytf sso generate --start-url <url> --sso-region <region> [--region <region>] [--composition-path <template>]"
+    );
     println!();
     println!("Options:");
     println!("  -c, --config <path>   Path to lazytf config YAML");
+    println!(
+        "      --low-memory      Stream operation output to disk, keep a small window in memory"
+    );
+    println!("      --no-mouse        Do not capture the mouse (keyboard-only)");
     println!("  -h, --help            Show this help");
 }
 
@@ -1909,26 +18453,154 @@ fn load_config(cwd: &Path, explicit_config: Option<&Path>) -> Result<LoadedConfi
             config_path.to_string_lossy()
         )
     })?;
+    let base_dir = config_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| cwd.to_path_buf());
 
-    let config: Config = serde_yaml::from_str(&contents).wrap_err_with(|| {
+    let mut merged: serde_yaml::Value = serde_yaml::from_str(&contents).wrap_err_with(|| {
         format!(
             "Failed to parse YAML config at {}",
             config_path.to_string_lossy()
         )
     })?;
+    let mut sources = vec![config_path.clone()];
+    let includes = config_includes(&merged, &base_dir, true)?;
+    merge_config_includes(&mut merged, includes, &mut sources)?;
 
-    let base_dir = config_path
-        .parent()
-        .map(Path::to_path_buf)
-        .unwrap_or_else(|| cwd.to_path_buf());
+    // Without includes, parse the text itself so errors keep their line numbers.
+    let config: Config = if sources.len() == 1 {
+        serde_yaml::from_str(&contents)
+    } else {
+        if let serde_yaml::Value::Mapping(map) = &mut merged {
+            map.remove("include");
+        }
+        serde_yaml::from_value(merged)
+    }
+    .wrap_err_with(|| {
+        format!(
+            "Failed to parse YAML config at {}",
+            config_path.to_string_lossy()
+        )
+    })?;
 
     Ok(LoadedConfig {
         path: config_path,
         base_dir,
         config,
+        sources,
     })
 }
 
+/// Merges each of `includes` onto `merged`, followed by the files it includes itself
+/// (relative to it). Files already in `sources` are skipped, which also breaks cycles.
+fn merge_config_includes(
+    merged: &mut serde_yaml::Value,
+    includes: Vec<PathBuf>,
+    sources: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for include in includes {
+        if sources.contains(&include) {
+            continue;
+        }
+        let contents = fs::read_to_string(&include)
+            .wrap_err_with(|| format!("Failed to read included config {}", include.display()))?;
+        let mut overlay: serde_yaml::Value = serde_yaml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse included config {}", include.display()))?;
+        let include_dir = include.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let nested = config_includes(&overlay, &include_dir, false)
+            .wrap_err_with(|| format!("In included config {}", include.display()))?;
+        if let serde_yaml::Value::Mapping(map) = &mut overlay {
+            map.remove("include");
+        }
+        merge_yaml(merged, overlay);
+        sources.push(include);
+        merge_config_includes(merged, nested, sources)?;
+    }
+    Ok(())
+}
+
+fn config_includes(
+    config: &serde_yaml::Value,
+    base_dir: &Path,
+    include_dir: bool,
+) -> Result<Vec<PathBuf>> {
+    let entries = match config.get("include") {
+        None | Some(serde_yaml::Value::Null) => Vec::new(),
+        Some(serde_yaml::Value::String(entry)) => vec![entry.clone()],
+        Some(serde_yaml::Value::Sequence(entries)) => entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| eyre!("`include` entries must be file paths"))
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => return Err(eyre!("`include` must be a list of file paths")),
+    };
+
+    let mut includes = Vec::new();
+    for entry in &entries {
+        let path = base_dir.join(entry);
+        if entry.contains(['*', '?', '[']) {
+            let mut matches: Vec<PathBuf> = glob(&path.to_string_lossy())
+                .wrap_err_with(|| format!("Invalid include pattern `{entry}`"))?
+                .filter_map(|entry| entry.ok())
+                .filter(|path| path.is_file())
+                .collect();
+            matches.sort();
+            includes.extend(matches);
+        } else if path.is_file() {
+            includes.push(path);
+        } else {
+            return Err(eyre!(
+                "Included config `{entry}` does not exist ({}); use a glob to make it optional",
+                path.display()
+            ));
+        }
+    }
+
+    let mut directory: Vec<PathBuf> = fs::read_dir(base_dir.join(CONFIG_INCLUDE_DIR))
+        .into_iter()
+        .filter(|_| include_dir)
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        })
+        .collect();
+    directory.sort();
+    includes.extend(directory);
+
+    let mut seen = BTreeSet::new();
+    Ok(includes
+        .into_iter()
+        .map(|path| path.canonicalize().unwrap_or(path))
+        .filter(|path| seen.insert(path.clone()))
+        .collect())
+}
+
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 fn find_config_path(cwd: &Path, explicit_config: Option<&Path>) -> Result<PathBuf> {
     if let Some(path) = explicit_config {
         let resolved = if path.is_absolute() {
@@ -2029,24 +18701,536 @@ fn fallback_composition_path(cwd: &Path, raw_path: &str) -> PathBuf {
     }
 }
 
-fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+fn setup_terminal(mouse: bool) -> Result<(Terminal<CrosstermBackend<io::Stdout>>, bool)> {
     enable_raw_mode().wrap_err("Failed to enable terminal raw mode")?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
-        .wrap_err("Failed to enter alternate screen")?;
+    execute!(stdout, EnterAlternateScreen).wrap_err("Failed to enter alternate screen")?;
+    let mouse_captured = mouse && execute!(stdout, EnableMouseCapture).is_ok();
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend).wrap_err("Failed to initialize terminal backend")?;
-    Ok(terminal)
+    Ok((terminal, mouse_captured))
+}
+
+fn set_mouse_capture(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    enabled: bool,
+) -> Result<()> {
+    if enabled {
+        execute!(terminal.backend_mut(), EnableMouseCapture)
+            .wrap_err("Failed to enable mouse capture")?;
+    } else {
+        execute!(terminal.backend_mut(), DisableMouseCapture)
+            .wrap_err("Failed to disable mouse capture")?;
+    }
+    Ok(())
 }
 
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    mouse_captured: bool,
+) -> Result<()> {
     disable_raw_mode().wrap_err("Failed to disable terminal raw mode")?;
-    execute!(
-        terminal.backend_mut(),
-        DisableMouseCapture,
-        LeaveAlternateScreen
-    )
-    .wrap_err("Failed to leave alternate screen")?;
+    if mouse_captured {
+        execute!(terminal.backend_mut(), DisableMouseCapture)
+            .wrap_err("Failed to disable mouse capture")?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)
+        .wrap_err("Failed to leave alternate screen")?;
     terminal.show_cursor().wrap_err("Failed to show cursor")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn test_app(yaml: &str) -> AppState {
+        let config: Config = serde_yaml::from_str(yaml).expect("test config parses");
+        AppState::from_config(config, &std::env::temp_dir()).expect("test config loads")
+    }
+
+    fn change(address: &str, action: ChangeAction) -> ResourceChange {
+        ResourceChange {
+            address: address.to_string(),
+            action,
+            provider: None,
+            attributes: Vec::new(),
+            replace_reasons: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn tag_violations_count_created_and_updated_managed_resources_only() {
+        let policy = TagPolicyConfig {
+            required_tags: vec!["owner".to_string(), "env".to_string()],
+            mode: TagPolicyMode::Block,
+            resource_types: vec!["aws_*".to_string()],
+        };
+        let resource = |address: &str, mode: &str, action: &str, after: serde_json::Value| {
+            serde_json::json!({
+                "address": address,
+                "mode": mode,
+                "type": address.split('.').next().unwrap(),
+                "change": { "actions": [action], "after": after, "after_unknown": {} },
+            })
+        };
+        let plan = serde_json::json!({ "resource_changes": [
+            resource("aws_instance.web", "managed", "create", serde_json::json!({ "tags": { "owner": "a" } })),
+            resource("aws_s3_bucket.ok", "managed", "update", serde_json::json!({ "tags_all": { "owner": "a", "env": "prod" } })),
+            resource("aws_vpc.gone", "managed", "delete", serde_json::json!({ "tags": {} })),
+            resource("aws_ami.lookup", "data", "read", serde_json::json!({ "tags": {} })),
+            resource("google_bucket.other", "managed", "create", serde_json::json!({ "labels": {} })),
+            resource("aws_iam_role.untagged", "managed", "create", serde_json::json!({ "name": "x" })),
+            {
+                "address": "aws_sqs_queue.later",
+                "mode": "managed",
+                "type": "aws_sqs_queue",
+                "change": { "actions": ["create"], "after": { "tags_all": {} }, "after_unknown": { "tags_all": true } },
+            },
+        ]});
+
+        assert_eq!(
+            tag_violations(&policy, &plan),
+            vec![("aws_instance.web".to_string(), vec!["env".to_string()])]
+        );
+    }
+
+    #[test]
+    fn plan_attribute_diff_masks_sensitive_values_at_any_depth() {
+        let change = serde_json::json!({
+            "before": { "name": "db", "password": "old-secret", "settings": { "user": "admin", "token": "t1" } },
+            "after": { "name": "db2", "password": "new-secret", "settings": { "user": "admin", "token": "t2" } },
+            "before_sensitive": { "password": true },
+            "after_sensitive": { "password": true, "settings": { "token": true } },
+            "after_unknown": {},
+        });
+        let diffs = plan_attribute_diff(&change);
+        let find = |key: &str| diffs.iter().find(|diff| diff.key == key).unwrap();
+
+        assert_eq!(find("name").new.as_deref(), Some("\"db2\""));
+        assert_eq!(find("password").old.as_deref(), Some("(sensitive)"));
+        assert_eq!(find("password").new.as_deref(), Some("(sensitive)"));
+        let settings = find("settings");
+        for value in [&settings.old, &settings.new] {
+            let value = value.as_deref().unwrap();
+            assert!(value.contains("admin"), "{value}");
+            assert!(value.contains("(sensitive)"), "{value}");
+            assert!(!value.contains("t1") && !value.contains("t2"), "{value}");
+        }
+
+        let whole = serde_json::json!({
+            "before": { "key": "a" },
+            "after": { "key": "b" },
+            "after_sensitive": true,
+        });
+        assert_eq!(
+            plan_attribute_diff(&whole)[0].new.as_deref(),
+            Some("(sensitive)")
+        );
+    }
+
+    #[test]
+    fn line_diff_keeps_common_lines_and_orders_removals_first() {
+        let old = lines("a\nb\nc");
+        assert!(line_diff(&old, &old).iter().all(|(op, _)| *op == ' '));
+        assert_eq!(
+            line_diff(&old, &lines("a\nx\nc\nd")),
+            vec![
+                (' ', "a".to_string()),
+                ('-', "b".to_string()),
+                ('+', "x".to_string()),
+                (' ', "c".to_string()),
+                ('+', "d".to_string()),
+            ]
+        );
+        assert_eq!(line_diff(&[], &lines("a")), vec![('+', "a".to_string())]);
+    }
+
+    #[test]
+    fn pending_stateful_changes_reads_the_saved_plan_of_the_selected_workspace() {
+        let mut app =
+            test_app("accounts:\n  prod:\n    aws_profile: prod\n    composition_path: .\n");
+        app.accounts[0].workspaces = vec!["default".to_string(), "blue".to_string()];
+        let stateful = app.stateful_changes(&[
+            change("aws_db_instance.main", ChangeAction::Delete),
+            change("aws_s3_bucket.logs", ChangeAction::Update),
+            change("aws_instance.web", ChangeAction::Replace),
+            change(
+                "module.cache.aws_elasticache_cluster.this",
+                ChangeAction::Replace,
+            ),
+        ]);
+        assert_eq!(
+            stateful,
+            vec![
+                "  - delete aws_db_instance.main".to_string(),
+                "  - replace module.cache.aws_elasticache_cluster.this".to_string(),
+            ]
+        );
+        app.saved_plan_stateful
+            .insert(cost_key("prod", "blue"), stateful.clone());
+
+        app.selected_workspace = 1;
+        assert_eq!(app.pending_stateful_changes(), stateful);
+        app.selected_workspace = 0;
+        assert!(app.pending_stateful_changes().is_empty());
+    }
+
+    #[test]
+    fn config_includes_are_merged_once_even_when_they_form_a_cycle() {
+        let dir = std::env::temp_dir().join(format!("lazytf-include-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join("lazyterraform.yaml"),
+            "include: [sub/a.yaml]\naccounts:\n  prod:\n    aws_profile: prod\n    composition_path: .\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub/a.yaml"),
+            "include: [../lazyterraform.yaml, a.yaml, b.yaml]\naccounts:\n  prod:\n    region: eu-west-1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("sub/b.yaml"),
+            "include: [a.yaml]\nwatch_config: true\n",
+        )
+        .unwrap();
+
+        let loaded = load_config(&dir, None);
+        let _ = fs::remove_dir_all(&dir);
+        let loaded = loaded.expect("config with an include cycle loads");
+
+        let dir = loaded.base_dir.clone();
+        assert_eq!(
+            loaded.sources,
+            vec![
+                dir.join("lazyterraform.yaml"),
+                dir.join("sub/a.yaml"),
+                dir.join("sub/b.yaml"),
+            ]
+        );
+        assert_eq!(
+            loaded.config.accounts["prod"].region.as_deref(),
+            Some("eu-west-1")
+        );
+    }
+
+    #[test]
+    fn fuzzy_score_matches_subsequences_and_prefers_word_starts() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "prod"), None);
+        assert_eq!(fuzzy_score("dp", "prod"), None);
+        assert!(fuzzy_score("PR", "prod").is_some());
+        assert!(fuzzy_score("pr", "prod") > fuzzy_score("pr", "sprint"));
+        assert!(fuzzy_score("ew", "eu-west-1") > fuzzy_score("ew", "eastwind"));
+    }
+
+    #[test]
+    fn render_auth_template_shell_quotes_substituted_values() {
+        let app = test_app(
+            "accounts:\n  \"team prod\":\n    aws_profile: \"it's\"\n    region: eu-west-1\n    composition_path: .\n",
+        );
+        assert_eq!(
+            render_auth_template(
+                &app.accounts[0],
+                "check --profile {{profile}} --region {{region}} --account {{account}}"
+            ),
+            "check --profile 'it'\\''s' --region eu-west-1 --account 'team prod'"
+        );
+    }
+
+    #[test]
+    fn team_state_ignores_stale_snapshots_and_uses_the_configured_operator_id() {
+        let config: TeamSyncConfig = serde_yaml::from_str(
+            "type: http\nurl: https://sync.example.com\ninterval_seconds: 10\noperator_id: alice-laptop\n",
+        )
+        .unwrap();
+        assert_eq!(team_operator_id(&config), "alice-laptop");
+
+        let record = |account: &str| AuditRecord {
+            timestamp: 100,
+            operator: "bob".to_string(),
+            account: account.to_string(),
+            workspace: "default".to_string(),
+            operation: "apply".to_string(),
+            event: AuditEvent::Started,
+            outcome: None,
+            change_ticket: None,
+        };
+        let lock = MaintenanceLock {
+            reason: "db upgrade".to_string(),
+            locked_by: "carol".to_string(),
+            locked_at: 50,
+        };
+        let team = TeamState {
+            config: Some(config),
+            snapshots: vec![
+                TeamSnapshot {
+                    operator: "bob".to_string(),
+                    updated_at: 1_000,
+                    running: Some(record("prod")),
+                    ..TeamSnapshot::default()
+                },
+                TeamSnapshot {
+                    operator: "carol".to_string(),
+                    updated_at: 900,
+                    running: Some(record("staging")),
+                    maintenance_locks: BTreeMap::from([(cost_key("prod", "default"), lock)]),
+                    ..TeamSnapshot::default()
+                },
+            ],
+            ..TeamState::default()
+        };
+
+        assert!(team.running_on("prod", "default", 1_030).is_some());
+        assert!(team.running_on("prod", "default", 1_031).is_none());
+        assert!(team.running_on("staging", "default", 1_000).is_none());
+        assert!(team.running_on("prod", "blue", 1_000).is_none());
+        let (operator, lock) = team.maintenance_lock(&cost_key("prod", "default")).unwrap();
+        assert_eq!((operator, lock.reason.as_str()), ("carol", "db upgrade"));
+    }
+
+    #[test]
+    fn approval_signatures_verify_only_against_the_named_approvers_key() {
+        // SAFETY: these variables are only read by this test.
+        unsafe {
+            std::env::set_var("LAZYTF_TEST_APPROVAL_KEY_ALICE", "alice-secret");
+            std::env::set_var("LAZYTF_TEST_APPROVAL_KEY_BOB", "bob-secret");
+        }
+        let approval = ApprovalConfig {
+            approvers: BTreeMap::from([
+                (
+                    "alice".to_string(),
+                    "LAZYTF_TEST_APPROVAL_KEY_ALICE".to_string(),
+                ),
+                (
+                    "bob".to_string(),
+                    "LAZYTF_TEST_APPROVAL_KEY_BOB".to_string(),
+                ),
+            ]),
+            ..ApprovalConfig::default()
+        };
+        let request = ApprovalRequest {
+            id: "prod-default-1".to_string(),
+            account: "prod".to_string(),
+            workspace: "default".to_string(),
+            composition_path: ".".to_string(),
+            requested_by: "bob".to_string(),
+            requested_at: 1,
+            expires_at: 2,
+            key_env: approval.key_env.clone(),
+            approvers: approval.approvers.clone(),
+            subject: "saved plan".to_string(),
+            plan_sha256: "abc".to_string(),
+        };
+        let path =
+            std::env::temp_dir().join(format!("lazytf-approval-test-{}.sig", std::process::id()));
+        let sign = |approved_by: &str, key_env: &str| {
+            let signature = ApprovalSignature {
+                approved_by: approved_by.to_string(),
+                approved_at: 1,
+                signature: hex_encode(
+                    &approval_mac(&request, approved_by, key_env)
+                        .unwrap()
+                        .finalize()
+                        .into_bytes(),
+                ),
+            };
+            fs::write(&path, serde_json::to_string(&signature).unwrap()).unwrap();
+            verify_approval_signature(&path, &request, &approval)
+        };
+
+        assert_eq!(
+            sign("alice", "LAZYTF_TEST_APPROVAL_KEY_ALICE")
+                .unwrap()
+                .approved_by,
+            "alice"
+        );
+        assert!(sign("bob", "LAZYTF_TEST_APPROVAL_KEY_BOB").is_err());
+        assert!(sign("alice", "LAZYTF_TEST_APPROVAL_KEY_BOB").is_err());
+        assert!(sign("carol", "LAZYTF_TEST_APPROVAL_KEY_BOB").is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn parse_schedule_time_accepts_relative_clock_and_date_inputs() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(2024, 2, 29), 19_782);
+
+        // 2024-02-29 10:00 UTC
+        let now = 19_782 * 86_400 + 10 * 3_600;
+        assert_eq!(parse_schedule_time("+30m", now), Ok(now + 1_800));
+        assert_eq!(parse_schedule_time(" +2h ", now), Ok(now + 7_200));
+        assert_eq!(parse_schedule_time("11:15", now), Ok(now + 4_500));
+        assert_eq!(
+            parse_schedule_time("09:00", now),
+            Ok(now + 23 * 3_600),
+            "a clock time already past today means tomorrow"
+        );
+        assert_eq!(
+            parse_schedule_time("2024-03-01 02:00", now),
+            Ok(19_783 * 86_400 + 2 * 3_600)
+        );
+        assert_eq!(
+            parse_schedule_time("2024-03-01T02:00", now),
+            Ok(19_783 * 86_400 + 2 * 3_600)
+        );
+        assert!(
+            parse_schedule_time("2024-02-28 23:00", now)
+                .unwrap_err()
+                .contains("in the past")
+        );
+        for bad in [
+            "+5",
+            "+m",
+            "+5é",
+            "+é",
+            "+-5m",
+            "+18446744073709551615h",
+            "25:00",
+            "2024-13-01 02:00",
+            "99999999999999-01-01 02:00",
+            "soon",
+        ] {
+            assert!(parse_schedule_time(bad, now).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn version_satisfies_follows_terraform_constraint_operators() {
+        assert!(version_satisfies((1, 6, 3), ">= 1.5, < 2.0"));
+        assert!(!version_satisfies((2, 0, 0), ">= 1.5, < 2.0"));
+        assert!(version_satisfies((1, 9, 0), "~> 1.6"));
+        assert!(!version_satisfies((1, 5, 9), "~> 1.6"));
+        assert!(!version_satisfies((2, 0, 0), "~> 1.6"));
+        assert!(version_satisfies((1, 6, 7), "~> 1.6.0"));
+        assert!(!version_satisfies((1, 7, 0), "~> 1.6.0"));
+        assert!(!version_satisfies((1, 6, 1), "~> 1.6.2"));
+        assert!(version_satisfies((1, 6, 0), "1.6.0"));
+        assert!(version_satisfies((1, 6, 0), "= 1.6"));
+        assert!(!version_satisfies((1, 6, 0), "!= 1.6.0"));
+        assert!(version_satisfies((1, 8, 0), ">= 1.8.0-beta1"));
+        assert!(version_satisfies((1, 0, 0), ">= latest"));
+    }
+
+    #[test]
+    fn parse_workspace_output_marks_the_current_workspace() {
+        let (workspaces, current) = parse_workspace_output("  default\n* blue\n\n  green \n");
+        assert_eq!(workspaces, vec!["default", "blue", "green"]);
+        assert_eq!(current.as_deref(), Some("blue"));
+
+        let (workspaces, current) = parse_workspace_output("*default\n");
+        assert_eq!(workspaces, vec!["default"]);
+        assert_eq!(current.as_deref(), Some("default"));
+        assert_eq!(parse_workspace_output(""), (Vec::new(), None));
+    }
+
+    #[test]
+    fn account_defaults_fill_gaps_without_overriding_the_account() {
+        let defaults: AccountDefaults = serde_yaml::from_str(
+            "region: eu-west-1\nbinary: tofu\nvar_files: [common.tfvars]\nenv: { A: default, B: default }\nextra_args: { plan: [-lock-timeout=1m], apply: [-parallelism=5] }\n",
+        )
+        .unwrap();
+        let account: AccountConfig = serde_yaml::from_str(
+            "aws_profile: prod\ncomposition_path: .\nregion: us-east-1\nvar_files: [prod.tfvars]\nenv: { B: account }\nextra_args: { plan: [-refresh=false] }\n",
+        )
+        .unwrap();
+        let account = defaults.apply(account);
+
+        assert_eq!(account.region.as_deref(), Some("us-east-1"));
+        assert_eq!(account.binary.as_deref(), Some("tofu"));
+        assert_eq!(account.var_files, vec!["common.tfvars", "prod.tfvars"]);
+        assert_eq!(account.env["A"], "default");
+        assert_eq!(account.env["B"], "account");
+        assert_eq!(account.extra_args["plan"], vec!["-refresh=false"]);
+        assert_eq!(account.extra_args["apply"], vec!["-parallelism=5"]);
+    }
+
+    #[tokio::test]
+    async fn reload_config_remaps_indexes_and_reports_only_config_changes() {
+        let dir = std::env::temp_dir().join(format!("lazytf-reload-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lazyterraform.yaml");
+        let account = |name: &str, region: &str| {
+            format!(
+                "  {name}:\n    aws_profile: {name}\n    region: {region}\n    composition_path: missing\n"
+            )
+        };
+        fs::write(
+            &path,
+            format!(
+                "accounts:\n{}{}",
+                account("alpha", "eu-west-1"),
+                account("charlie", "eu-west-1")
+            ),
+        )
+        .unwrap();
+        let loaded = load_config(&dir, Some(&path)).unwrap();
+        let mut app = AppState::from_config(loaded.config, &loaded.base_dir).unwrap();
+        app.config_path = Some(path.clone());
+        app.accounts[0].auth = AuthStatus::Authenticated;
+        app.accounts[0].workspaces = vec!["default".to_string(), "blue".to_string()];
+        app.accounts[0].tag_violations.insert("blue".to_string(), 2);
+        app.selected_account = 1;
+        let tuning = app.run_tuning(1);
+        app.session_tuning.insert(1, tuning);
+
+        fs::write(
+            &path,
+            format!(
+                "accounts:\n{}{}{}",
+                account("alpha", "eu-west-1"),
+                account("bravo", "eu-west-1"),
+                account("charlie", "us-east-1")
+            ),
+        )
+        .unwrap();
+        let (worker_tx, _worker_rx) = mpsc::unbounded_channel();
+        reload_config(&mut app, &worker_tx);
+        let _ = fs::remove_dir_all(&dir);
+
+        let names: Vec<&str> = app
+            .accounts
+            .iter()
+            .map(|account| account.name.as_str())
+            .collect();
+        assert_eq!(names, ["alpha", "bravo", "charlie"]);
+        assert_eq!(app.selected_account, 2);
+        assert_eq!(app.session_tuning.keys().copied().collect::<Vec<_>>(), [2]);
+        assert_eq!(app.accounts[0].auth, AuthStatus::Authenticated);
+        assert_eq!(app.accounts[0].workspaces, ["default", "blue"]);
+        let summary = &app.output_lines.last().unwrap().text;
+        assert!(
+            summary.ends_with(": added bravo; changed charlie"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn change_ticket_policy_trims_and_checks_the_pattern() {
+        let config: ChangeTicketConfig =
+            serde_yaml::from_str("required: true\npattern: '^CHG-[0-9]+$'\n").unwrap();
+        let policy = ChangeTicketPolicy::from_config("prod", config).unwrap();
+        assert_eq!(policy.check("  CHG-42 "), Ok(Some("CHG-42".to_string())));
+        assert!(policy.check("   ").unwrap_err().contains("required"));
+        assert!(
+            policy
+                .check("INC-1")
+                .unwrap_err()
+                .contains("does not match")
+        );
+
+        let optional: ChangeTicketConfig = serde_yaml::from_str("required: false\n").unwrap();
+        let policy = ChangeTicketPolicy::from_config("dev", optional).unwrap();
+        assert_eq!(policy.check(""), Ok(None));
+        assert_eq!(policy.check("anything"), Ok(Some("anything".to_string())));
+
+        let invalid: ChangeTicketConfig = serde_yaml::from_str("pattern: '('\n").unwrap();
+        assert!(ChangeTicketPolicy::from_config("bad", invalid).is_err());
+    }
+}